@@ -0,0 +1,99 @@
+use crate::i18n;
+use crate::render::escape_html;
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const ACRONYMS_PLACEHOLDER: &str = "<!--ACRONYMS_PLACEHOLDER-->";
+
+/// Expands the `+API+` marks of the acronyms defined in `acronyms`: the first use reads
+/// "Application Programming Interface (API)", later ones "API" with the definition as tooltip.
+/// Marks inside code are kept, as are those of unknown acronyms. `!acronyms` lists the
+/// acronyms used, in alphabetical order.
+pub fn expand(markdown: &str, acronyms: &BTreeMap<String, String>, language: &str) -> String {
+    if acronyms.is_empty() && !markdown.contains(ACRONYMS_PLACEHOLDER) {
+        return markdown.to_string();
+    }
+    let mark_re = Regex::new(r"\+([A-Za-z][A-Za-z0-9&-]*)\+").unwrap();
+    let mut used = BTreeSet::new();
+    let mut result = String::with_capacity(markdown.len());
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        if line.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block {
+            result.push_str(line);
+        } else {
+            // Odd segments between backticks are code spans.
+            for (i, segment) in line.split('`').enumerate() {
+                if i > 0 {
+                    result.push('`');
+                }
+                if i % 2 == 1 {
+                    result.push_str(segment);
+                    continue;
+                }
+                result.push_str(&mark_re.replace_all(segment, |caps: &Captures| {
+                    let Some(definition) = acronyms.get(&caps[1]) else {
+                        return caps[0].to_string();
+                    };
+                    let abbr = format!("<abbr title=\"{}\">{}</abbr>", escape_html(definition), escape_html(&caps[1]));
+                    if used.insert(caps[1].to_string()) {
+                        format!("{} ({abbr})", escape_html(definition))
+                    } else {
+                        abbr
+                    }
+                }));
+            }
+        }
+        result.push('\n');
+    }
+
+    if result.contains(ACRONYMS_PLACEHOLDER) {
+        result = result.replace(ACRONYMS_PLACEHOLDER, &list_html(acronyms, &used, i18n::acronyms_title(language)));
+    }
+    result
+}
+
+fn list_html(acronyms: &BTreeMap<String, String>, used: &BTreeSet<String>, title: &str) -> String {
+    let mut html = format!("<section class=\"chapter acronyms unnumbered\">\n\n## {title}\n\n<dl class=\"acronyms\">\n");
+    for acronym in used {
+        html.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", escape_html(acronym), escape_html(&acronyms[acronym])));
+    }
+    html.push_str("</dl>\n\n</section>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acronyms() -> BTreeMap<String, String> {
+        let mut acronyms = BTreeMap::new();
+        acronyms.insert("API".to_string(), "Application Programming Interface".to_string());
+        acronyms.insert("TLS".to_string(), "Transport Layer Security".to_string());
+        acronyms.insert("R&D".to_string(), "Research & Development".to_string());
+        acronyms
+    }
+
+    #[test]
+    fn test_expand() {
+        let markdown = "The +API+ uses +TLS+.\nEvery +API+ call, `+TLS+` and +XYZ+.\n```\n+API+\n```\n";
+        let result = expand(markdown, &acronyms(), "en");
+        assert_eq!(
+            result,
+            "The Application Programming Interface (<abbr title=\"Application Programming Interface\">API</abbr>) uses \
+             Transport Layer Security (<abbr title=\"Transport Layer Security\">TLS</abbr>).\n\
+             Every <abbr title=\"Application Programming Interface\">API</abbr> call, `+TLS+` and +XYZ+.\n```\n+API+\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_acronyms_list() {
+        let result = expand("<!--ACRONYMS_PLACEHOLDER-->\nOur +TLS+ and +R&D+ teams.", &acronyms(), "fr");
+        assert!(result.starts_with("<section class=\"chapter acronyms unnumbered\">\n\n## Acronymes\n\n<dl class=\"acronyms\">\n\
+            <dt>R&amp;D</dt><dd>Research &amp; Development</dd>\n<dt>TLS</dt><dd>Transport Layer Security</dd>\n</dl>"));
+        assert!(!result.contains("API"));
+    }
+}
@@ -0,0 +1,96 @@
+use crate::render::escape_html;
+
+/// HTML attributes kept under their own name; other `key=value` pairs become `data-` attributes.
+const HTML_ATTRIBUTES: [&str; 4] = ["title", "lang", "dir", "style"];
+
+/// A pandoc-style attribute block: `{#id .class key=value key2="a value"}`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Attributes {
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub pairs: Vec<(String, String)>,
+}
+
+impl Attributes {
+    /// Parses the text between the braces. `None` when a word is not an attribute, so that
+    /// `{like this}` stays text.
+    pub fn parse(text: &str) -> Option<Attributes> {
+        let mut attributes = Attributes::default();
+        for token in tokens(text)? {
+            if let Some(id) = token.strip_prefix('#').filter(|id| is_name(id)) {
+                attributes.id = Some(id.to_string());
+            } else if let Some(class) = token.strip_prefix('.').filter(|class| is_name(class)) {
+                attributes.classes.push(class.to_string());
+            } else if let Some((key, value)) = token.split_once('=').filter(|(key, _)| is_name(key)) {
+                let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+                attributes.pairs.push((key.to_string(), value.to_string()));
+            } else {
+                return None;
+            }
+        }
+        Some(attributes)
+    }
+
+    /// The attributes as HTML, each preceded by a space.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        if let Some(id) = &self.id {
+            html.push_str(&format!(" id=\"{}\"", escape_html(id)));
+        }
+        if !self.classes.is_empty() {
+            html.push_str(&format!(" class=\"{}\"", escape_html(&self.classes.join(" "))));
+        }
+        for (key, value) in &self.pairs {
+            let key = if HTML_ATTRIBUTES.contains(&key.as_str()) || key.starts_with("data-") { key.clone() } else { format!("data-{key}") };
+            html.push_str(&format!(" {key}=\"{}\"", escape_html(value)));
+        }
+        html
+    }
+}
+
+/// Words separated by spaces, keeping quoted values whole. `None` for an unclosed quote.
+fn tokens(text: &str) -> Option<Vec<&str>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut in_quotes = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if let Some(start) = start.take() {
+                    tokens.push(&text[start..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if in_quotes {
+        return None;
+    }
+    if let Some(start) = start {
+        tokens.push(&text[start..]);
+    }
+    Some(tokens)
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic()) && text.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let attributes = Attributes::parse(" #intro .note .wide title=\"A & B\" level=2 ").unwrap();
+        assert_eq!(attributes.id.as_deref(), Some("intro"));
+        assert_eq!(attributes.classes, vec!["note", "wide"]);
+        assert_eq!(attributes.to_html(), " id=\"intro\" class=\"note wide\" title=\"A &amp; B\" data-level=\"2\"");
+        assert!(Attributes::parse("like this").is_none());
+        assert!(Attributes::parse(".x title=\"open").is_none());
+        assert_eq!(Attributes::parse("").unwrap(), Attributes::default());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::render::escape_html;
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Styles of the table of authorities, laid out like the table of contents.
+pub const AUTHORITIES_CSS: &str = r#"/* Table of authorities */
+.toa { page-break-after: always; }
+.toa-title { font-size: 2em; font-weight: bold; margin-bottom: 1em; text-align: center; }
+.toa-category { font-weight: bold; margin: 1.2em 0 0.5em; text-transform: uppercase; }
+.toa-entry { display: flex; align-items: baseline; margin: 0.3em 0 0.3em 1.5em; }
+.toa-entry-title { flex-shrink: 1; }
+.toa-entry-dots { flex-grow: 1; border-bottom: 1px dotted #999; margin: 0 0.5em; min-width: 2em; }
+.toa-entry-pages { flex-shrink: 0; }"#;
+
+/// Categories of the table, in the order of a legal brief, with the name used in
+/// `{cite <category>: ...}` and their heading.
+const CATEGORIES: [(&str, &str); 6] = [
+    ("case", "Cases"),
+    ("constitution", "Constitutional Provisions"),
+    ("statute", "Statutes"),
+    ("rule", "Rules"),
+    ("regulation", "Regulations"),
+    ("other", "Other Authorities"),
+];
+
+/// Authorities cited on more pages than this are listed as "passim".
+const PASSIM_THRESHOLD: usize = 5;
+
+/// Before the measuring print, puts an invisible `@@pagemap:a<authority>.<citation>@@` marker
+/// at every citation of the document body. `fill_toc_script` removes them with the others.
+pub const MARK_CITATIONS_SCRIPT: &str = r#"(() => {
+  const counts = {};
+  document.querySelectorAll('.authority').forEach(citation => {
+    if (citation.closest('.toc, .toa')) return;
+    const a = citation.dataset.authority;
+    counts[a] = (counts[a] || 0) + 1;
+    const marker = document.createElement('span');
+    marker.className = 'pagemap-marker';
+    marker.style.cssText = 'position: absolute; font-size: 1px; opacity: 0.01;';
+    marker.textContent = '@@pagemap:a' + a + '.' + counts[a] + '@@';
+    citation.prepend(marker);
+  });
+})()"#;
+
+/// An authority cited in the document.
+#[derive(Debug, PartialEq)]
+struct Authority {
+    category: usize,
+    /// The citation as written, already HTML.
+    citation: String,
+}
+
+/// Turns the `{cite: ...}` citations of the document body, outside code, into spans and
+/// replaces the `!authorities` placeholder with the table of authorities, grouped by category.
+/// The page numbers are filled in by `fill_pages_script` when printing the PDF.
+pub fn apply(body_html: &str, title: &str) -> String {
+    let code_re = Regex::new(r"(?s)<pre[\s>].*?</pre>|<code[\s>].*?</code>").unwrap();
+    let cite_re = Regex::new(r"\{cite(?:\s+([a-z]+))?:\s*([^{}]+?)\s*\}").unwrap();
+
+    let mut authorities: Vec<Authority> = Vec::new();
+    let mut mark = |text: &str| {
+        cite_re.replace_all(text, |caps: &Captures| {
+            let citation = caps[2].to_string();
+            let category = caps.get(1).and_then(|name| CATEGORIES.iter().position(|(category, _)| *category == name.as_str()))
+                .unwrap_or_else(|| infer_category(&citation));
+            let index = match authorities.iter().position(|authority| authority.citation == citation) {
+                Some(index) => index,
+                None => {
+                    authorities.push(Authority { category, citation: citation.clone() });
+                    authorities.len() - 1
+                }
+            };
+            format!(r#"<span class="authority" data-authority="{index}">{citation}</span>"#)
+        }).to_string()
+    };
+
+    let mut body = String::with_capacity(body_html.len());
+    let mut last = 0;
+    for code in code_re.find_iter(body_html) {
+        body.push_str(&mark(&body_html[last..code.start()]));
+        body.push_str(code.as_str());
+        last = code.end();
+    }
+    body.push_str(&mark(&body_html[last..]));
+    body.replace("<!--AUTHORITIES_PLACEHOLDER-->", &table_html(&authorities, title))
+}
+
+/// Category of a citation without an explicit one, from the usual citation forms.
+fn infer_category(citation: &str) -> usize {
+    let category = if Regex::new(r"\bv\.? ").unwrap().is_match(citation) || citation.starts_with("In re ") {
+        "case"
+    } else if citation.contains("Const.") {
+        "constitution"
+    } else if citation.contains("C.F.R.") || citation.contains("Fed. Reg.") {
+        "regulation"
+    } else if citation.contains("Fed. R.") || citation.starts_with("Rule ") {
+        "rule"
+    } else if citation.contains("U.S.C.") || citation.contains('§') {
+        "statute"
+    } else {
+        "other"
+    };
+    CATEGORIES.iter().position(|(name, _)| *name == category).unwrap_or(CATEGORIES.len() - 1)
+}
+
+fn table_html(authorities: &[Authority], title: &str) -> String {
+    if authorities.is_empty() {
+        return String::new();
+    }
+    let tag_re = Regex::new(r"<[^>]*>").unwrap();
+    let mut html = format!("<div class=\"toa\">\n<div class=\"toa-title\">{}</div>\n", escape_html(title));
+    for (category, (_, heading)) in CATEGORIES.iter().enumerate() {
+        let mut entries: Vec<(usize, &Authority)> = authorities.iter().enumerate().filter(|(_, authority)| authority.category == category).collect();
+        if entries.is_empty() {
+            continue;
+        }
+        entries.sort_by_key(|(_, authority)| tag_re.replace_all(&authority.citation, "").to_lowercase());
+        html.push_str(&format!("<div class=\"toa-category\">{heading}</div>\n"));
+        for (index, authority) in entries {
+            html.push_str(&format!(
+                "<div class=\"toa-entry\"><span class=\"toa-entry-title\">{}</span><span class=\"toa-entry-dots\"></span><span class=\"toa-entry-pages\" data-authority=\"{index}\"></span></div>\n",
+                authority.citation
+            ));
+        }
+    }
+    html.push_str("</div>");
+    html
+}
+
+/// Page references of every authority, from the pages of the citation markers: "3, 7" or
+/// "passim" for authorities cited throughout the document.
+pub fn authority_pages(marker_pages: &HashMap<String, usize>) -> BTreeMap<usize, String> {
+    let mut pages: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+    for (key, page) in marker_pages {
+        let Some(authority) = key.strip_prefix('a').and_then(|key| key.split_once('.')).and_then(|(authority, _)| authority.parse().ok()) else {
+            continue;
+        };
+        pages.entry(authority).or_default().insert(*page);
+    }
+    pages.into_iter().map(|(authority, pages)| {
+        let references = if pages.len() > PASSIM_THRESHOLD {
+            "passim".to_string()
+        } else {
+            pages.iter().map(|page| page.to_string()).collect::<Vec<_>>().join(", ")
+        };
+        (authority, references)
+    }).collect()
+}
+
+/// Writes the page references (by authority) into the table of authorities.
+pub fn fill_pages_script(pages: &BTreeMap<usize, String>) -> String {
+    let pages = serde_json::to_string(pages).unwrap_or_else(|_| "{}".to_string());
+    format!(
+        r#"(() => {{
+  const pages = {pages};
+  document.querySelectorAll('.toa .toa-entry-pages').forEach(span => {{
+    const references = pages[span.dataset.authority];
+    if (references !== undefined) span.textContent = references;
+  }});
+}})()"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_builds_the_table() {
+        let html = apply(
+            "<!--AUTHORITIES_PLACEHOLDER-->\n<p>Under {cite: 42 U.S.C. § 1983} and {cite: Monroe v. Pape, 365 U.S. 167 (1961)}, see {cite: 42 U.S.C. § 1983}.</p>\n<pre><code>{cite: Not a citation}</code></pre>\n<p>{cite other: Restatement (Second) of Torts § 46}</p>",
+            "Table of Authorities",
+        );
+        assert!(html.contains(r#"Under <span class="authority" data-authority="0">42 U.S.C. § 1983</span> and <span class="authority" data-authority="1">Monroe v. Pape"#));
+        assert!(html.contains(r#"see <span class="authority" data-authority="0">42 U.S.C. § 1983</span>."#));
+        assert!(html.contains("<code>{cite: Not a citation}</code>"));
+        let position = |text: &str| html.find(text).unwrap();
+        assert!(position("<div class=\"toa-category\">Cases</div>") < position("<div class=\"toa-category\">Statutes</div>"));
+        assert!(position("<div class=\"toa-category\">Statutes</div>") < position("<div class=\"toa-category\">Other Authorities</div>"));
+        assert!(html.contains("<span class=\"toa-entry-title\">Restatement (Second) of Torts § 46</span><span class=\"toa-entry-dots\"></span><span class=\"toa-entry-pages\" data-authority=\"2\"></span>"));
+        assert!(!html.contains("Not a citation</span>"));
+    }
+
+    #[test]
+    fn test_infer_category() {
+        let category = |citation| CATEGORIES[infer_category(citation)].0;
+        assert_eq!(category("Brown v. Board of Education, 347 U.S. 483 (1954)"), "case");
+        assert_eq!(category("U.S. Const. amend. XIV, § 1"), "constitution");
+        assert_eq!(category("Fed. R. Civ. P. 12(b)(6)"), "rule");
+        assert_eq!(category("29 C.F.R. § 1604.11"), "regulation");
+        assert_eq!(category("28 U.S.C. § 1331"), "statute");
+        assert_eq!(category("Black's Law Dictionary (11th ed. 2019)"), "other");
+    }
+
+    #[test]
+    fn test_authority_pages() {
+        let mut markers: HashMap<String, usize> = [("a0.1", 2), ("a0.2", 5), ("a0.3", 2), ("a1.1", 3), ("b0.1", 4)]
+            .into_iter().map(|(key, page)| (key.to_string(), page)).collect();
+        markers.extend((1..=6).map(|page| (format!("a2.{page}"), page)));
+        let pages = authority_pages(&markers);
+        assert_eq!(pages[&0], "2, 5");
+        assert_eq!(pages[&1], "3");
+        assert_eq!(pages[&2], "passim");
+        assert_eq!(pages.len(), 3);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::directives::Directive;
+use crate::error::CoreError;
+use crate::render::escape_html;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use regex::Regex;
+
+/// Printed width of the narrowest bar, the nominal EAN-13 module.
+const MODULE_MM: f64 = 0.33;
+const BAR_HEIGHT: f64 = 60.0;
+/// Start, center and end guards of EAN-13 barcodes extend below the digits.
+const GUARD_HEIGHT: f64 = 66.0;
+const FONT_SIZE: f64 = 10.0;
+
+/// EAN-13 digit patterns (1 = bar) of the L set; the R set is their complement and the G set the reversed R set.
+const EAN_L: [&str; 10] = ["0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011", "0110111", "0001011"];
+/// L/G sets of the left half, chosen by the first digit which has no bars of its own.
+const EAN_PARITY: [&str; 10] = ["LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL", "LGGLGL"];
+
+/// Code 128 symbols as alternating bar and space widths, in modules.
+const CODE128: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212", "221213",
+    "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221", "223211", "221132",
+    "221231", "213212", "223112", "312131", "311222", "321122", "321221", "312212", "322112", "322211",
+    "212123", "212321", "232121", "111323", "131123", "131321", "112313", "132113", "132311", "211313",
+    "231113", "231311", "112133", "112331", "132131", "113123", "113321", "133121", "313121", "211331",
+    "231131", "213113", "213311", "213131", "311123", "311321", "331121", "312113", "312311", "332111",
+    "314111", "221411", "431111", "111224", "111422", "121124", "121421", "141122", "141221", "112214",
+    "112412", "122114", "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111",
+    "111242", "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311", "113141",
+    "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+const CODE128_START_B: usize = 104;
+const CODE128_STOP: usize = 106;
+
+/// A barcode as runs of modules: bars with their height, and the text printed under them.
+struct Barcode {
+    /// `(start, width, height)` of every bar, in modules from the left edge.
+    bars: Vec<(usize, usize, f64)>,
+    width: usize,
+    /// `(x, text)` of the digits printed under the bars, centered on `x`.
+    labels: Vec<(f64, String)>,
+    /// Line printed above the bars, e.g. `ISBN 978-1-23456-789-7`.
+    caption: Option<String>,
+}
+
+/// Returns the barcode of `!barcode(isbn, 978-1-23456-789-7, width=4cm)` as an embedded SVG
+/// image. Kinds are `isbn` (ISBN-10 or 13, printed as EAN-13),
+/// `ean13` and `code128`.
+pub fn barcode_directive(directive: &Directive) -> Result<String, CoreError> {
+    let usage = || CoreError::BuildError("!barcode needs a kind and the data to encode, e.g. !barcode(isbn, 978-1-23456-789-7)".to_string());
+    let kind = directive.positional(0).ok_or_else(usage)?;
+    let data = directive.data(1).filter(|data| !data.is_empty()).ok_or_else(usage)?;
+    let data = data.as_str();
+
+    let barcode = match kind.trim().to_lowercase().as_str() {
+        "isbn" => isbn(data)?,
+        "ean13" | "ean" => ean13(&digits(data), None)?,
+        "code128" => code128(data)?,
+        other => return Err(CoreError::BuildError(format!("Unknown !barcode kind '{other}', expected isbn, ean13 or code128"))),
+    };
+    let width = match directive.option("width") {
+        Some(width) => width.to_string(),
+        None => format!("{:.1}mm", barcode.width as f64 * MODULE_MM),
+    };
+    let length_re = Regex::new(r"^\d+(\.\d+)?(cm|mm|in|px|pt|em|%)$").unwrap();
+    if !length_re.is_match(&width) {
+        return Err(CoreError::BuildError(format!("Invalid !barcode width '{width}', expected a CSS length such as 4cm")));
+    }
+
+    let alt = barcode.caption.clone().unwrap_or_else(|| data.to_string());
+    Ok(format!(
+        r#"<img class="barcode" src="data:image/svg+xml;base64,{}" alt="{}" style="width: {width};">"#,
+        STANDARD.encode(barcode_svg(&barcode)),
+        escape_html(&alt)
+    ))
+}
+
+fn digits(data: &str) -> String {
+    data.chars().filter(|c| !matches!(c, '-' | ' ')).collect()
+}
+
+/// EAN-13 of an ISBN. ISBN-10 are converted to their 978 form.
+fn isbn(data: &str) -> Result<Barcode, CoreError> {
+    let isbn = digits(data).to_uppercase();
+    let ean = match isbn.len() {
+        10 => {
+            let valid = isbn[..9].chars().all(|c| c.is_ascii_digit()) && (isbn.as_bytes()[9].is_ascii_digit() || isbn.ends_with('X'));
+            let sum: u32 = isbn.chars().zip((1..=10).rev()).map(|(c, weight)| c.to_digit(10).unwrap_or(10) * weight).sum();
+            if !valid || !sum.is_multiple_of(11) {
+                return Err(CoreError::BuildError(format!("Invalid ISBN '{data}'")));
+            }
+            let ean = format!("978{}", &isbn[..9]);
+            format!("{ean}{}", ean_check_digit(&ean))
+        }
+        13 if isbn.starts_with("978") || isbn.starts_with("979") => isbn,
+        _ => return Err(CoreError::BuildError(format!("Invalid ISBN '{data}', expected 10 or 13 digits"))),
+    };
+    ean13(&ean, Some(format!("ISBN {}", data.trim())))
+}
+
+fn ean_check_digit(digits: &str) -> u32 {
+    let sum: u32 = digits.chars().filter_map(|c| c.to_digit(10)).enumerate()
+        .map(|(i, digit)| if i % 2 == 0 { digit } else { digit * 3 })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+fn ean13(digits: &str, caption: Option<String>) -> Result<Barcode, CoreError> {
+    if digits.len() != 13 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(CoreError::BuildError(format!("Invalid EAN-13 '{digits}', expected 13 digits")));
+    }
+    let values: Vec<usize> = digits.bytes().map(|b| (b - b'0') as usize).collect();
+    if values[12] as u32 != ean_check_digit(&digits[..12]) {
+        return Err(CoreError::BuildError(format!("Invalid check digit in '{digits}', expected {}", ean_check_digit(&digits[..12]))));
+    }
+
+    let complement = |pattern: &str| pattern.chars().map(|c| if c == '1' { '0' } else { '1' }).collect::<String>();
+    let mut modules: Vec<(String, bool)> = vec![("101".to_string(), true)];
+    for (i, parity) in EAN_PARITY[values[0]].chars().enumerate() {
+        let l = EAN_L[values[i + 1]];
+        modules.push((if parity == 'L' { l.to_string() } else { complement(l).chars().rev().collect() }, false));
+    }
+    modules.push(("01010".to_string(), true));
+    for &value in &values[7..] {
+        modules.push((complement(EAN_L[value]), false));
+    }
+    modules.push(("101".to_string(), true));
+
+    // Quiet zones: 11 modules before, where the first digit is printed, and 7 after.
+    let mut bars = Vec::new();
+    let mut x = 11;
+    for (pattern, guard) in &modules {
+        for (i, module) in pattern.chars().enumerate() {
+            if module == '1' {
+                bars.push((x + i, 1, if *guard { GUARD_HEIGHT } else { BAR_HEIGHT }));
+            }
+        }
+        x += pattern.len();
+    }
+    let labels = vec![
+        (7.0, digits[..1].to_string()),
+        (11.0 + 3.0 + 21.0, digits[1..7].to_string()),
+        (11.0 + 50.0 + 21.0, digits[7..].to_string()),
+    ];
+    Ok(Barcode { bars: merge_bars(bars), width: x + 7, labels, caption })
+}
+
+/// Code 128 with code set B (printable ASCII).
+fn code128(data: &str) -> Result<Barcode, CoreError> {
+    let mut values = vec![CODE128_START_B];
+    for c in data.chars() {
+        if !(' '..='~').contains(&c) {
+            return Err(CoreError::BuildError(format!("!barcode(code128) only encodes printable ASCII, found '{c}'")));
+        }
+        values.push(c as usize - 32);
+    }
+    let checksum = values.iter().enumerate().map(|(i, value)| i.max(1) * value).sum::<usize>() % 103;
+    values.push(checksum);
+    values.push(CODE128_STOP);
+
+    // Quiet zones of 10 modules.
+    let mut bars = Vec::new();
+    let mut x = 10;
+    for value in values {
+        for (i, width) in CODE128[value].bytes().map(|b| (b - b'0') as usize).enumerate() {
+            if i % 2 == 0 {
+                bars.push((x, width, BAR_HEIGHT));
+            }
+            x += width;
+        }
+    }
+    let labels = vec![((x + 10) as f64 / 2.0, data.to_string())];
+    Ok(Barcode { bars, width: x + 10, labels, caption: None })
+}
+
+/// Joins adjacent single-module bars of the same height into one rectangle.
+fn merge_bars(bars: Vec<(usize, usize, f64)>) -> Vec<(usize, usize, f64)> {
+    let mut merged: Vec<(usize, usize, f64)> = Vec::new();
+    for bar in bars {
+        match merged.last_mut() {
+            Some(last) if last.0 + last.1 == bar.0 && last.2 == bar.2 => last.1 += bar.1,
+            _ => merged.push(bar),
+        }
+    }
+    merged
+}
+
+fn barcode_svg(barcode: &Barcode) -> String {
+    let top = if barcode.caption.is_some() { FONT_SIZE + 4.0 } else { 0.0 };
+    let height = top + BAR_HEIGHT + FONT_SIZE + 2.0;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {height}" font-family="OCR-B, monospace" font-size="{FONT_SIZE}"><rect width="100%" height="100%" fill="white"/>"#,
+        barcode.width
+    );
+    if let Some(caption) = &barcode.caption {
+        svg.push_str(&format!(r#"<text x="{}" y="{FONT_SIZE}" text-anchor="middle">{}</text>"#, barcode.width as f64 / 2.0, escape_html(caption)));
+    }
+    for (x, width, bar_height) in &barcode.bars {
+        svg.push_str(&format!(r#"<rect x="{x}" y="{top}" width="{width}" height="{bar_height}"/>"#));
+    }
+    for (x, text) in &barcode.labels {
+        svg.push_str(&format!(r#"<text x="{x}" y="{}" text-anchor="middle">{}</text>"#, top + BAR_HEIGHT + FONT_SIZE, escape_html(text)));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directives;
+
+    fn modules(barcode: &Barcode) -> String {
+        let mut modules = vec!['0'; barcode.width];
+        for (x, width, _) in &barcode.bars {
+            modules[*x..x + width].iter_mut().for_each(|m| *m = '1');
+        }
+        modules.into_iter().collect()
+    }
+
+    #[test]
+    fn test_ean13() {
+        let barcode = ean13("4006381333931", None).unwrap();
+        assert_eq!(barcode.width, 113);
+        assert_eq!(
+            modules(&barcode).trim_matches('0'),
+            "10100011010100111010111101111010001001011001101010100001010000101000010111010010000101100110101"
+        );
+        assert!(ean13("4006381333932", None).is_err());
+        assert!(ean13("400638133393", None).is_err());
+    }
+
+    #[test]
+    fn test_isbn() {
+        let from_isbn10 = isbn("0-306-40615-2").unwrap();
+        assert_eq!(modules(&from_isbn10), modules(&ean13("9780306406157", None).unwrap()));
+        assert_eq!(isbn("978-0-306-40615-7").unwrap().caption.as_deref(), Some("ISBN 978-0-306-40615-7"));
+        assert!(isbn("0-306-40615-3").is_err());
+        assert!(isbn("123-0-306-40615-7").is_err());
+    }
+
+    #[test]
+    fn test_code128() {
+        assert!(CODE128.iter().take(CODE128_STOP).all(|pattern| pattern.bytes().map(|b| (b - b'0') as usize).sum::<usize>() == 11));
+        let barcode = code128("PJJ123C").unwrap();
+        // Start, 7 characters and checksum of 11 modules, stop of 13 and the quiet zones.
+        assert_eq!(barcode.width, 9 * 11 + 13 + 20);
+        assert!(code128("café").is_err());
+    }
+
+    #[test]
+    fn test_barcode_directive() {
+        let barcode = |line: &str| barcode_directive(&directives::parse(line)?.unwrap());
+        let html = barcode("!barcode(isbn, 978-0-306-40615-7, width=4cm)").unwrap();
+        assert!(html.starts_with(r#"<img class="barcode" src="data:image/svg+xml;base64,"#));
+        assert!(html.contains(r#"alt="ISBN 978-0-306-40615-7" style="width: 4cm;""#));
+        let html = barcode("!barcode(code128, A-1,B-2)").unwrap();
+        assert!(html.contains(r#"alt="A-1,B-2" style="width: 43.6mm;""#));
+
+        assert!(barcode("!barcode(isbn)").is_err());
+        assert!(barcode("!barcode(upc, 123)").is_err());
+        assert!(barcode("!barcode(isbn, 978-0-306-40615-7, width=wide)").is_err());
+    }
+}
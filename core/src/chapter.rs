@@ -0,0 +1,207 @@
+use crate::error::CoreError;
+use crate::render::escape_html;
+use serde::Deserialize;
+
+/// Optional front matter at the top of a Markdown file, between two `---` lines.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChapterMeta {
+    /// Replaces the text of the chapter's first heading.
+    pub title: Option<String>,
+    /// `false` excludes the chapter's headings from automatic numbering.
+    pub numbering: Option<bool>,
+    /// `landscape` prints the chapter on landscape pages.
+    pub orientation: Option<String>,
+    /// Extra CSS class(es) added to the chapter wrapper, e.g. `appendix`.
+    pub class: Option<String>,
+    /// Stylesheet applied to this chapter only, relative to the chapter file.
+    pub css: Option<String>,
+}
+
+impl ChapterMeta {
+    pub fn css_classes(&self) -> String {
+        let mut classes = vec!["chapter".to_string()];
+        if let Some(class) = &self.class {
+            classes.extend(class.split_whitespace().map(str::to_string));
+        }
+        if self.numbering == Some(false) {
+            classes.push("unnumbered".to_string());
+        }
+        if let Some(orientation) = &self.orientation {
+            classes.push(orientation.to_lowercase());
+        }
+        classes.join(" ")
+    }
+
+}
+
+/// Closing tag of the chapter wrapper.
+pub const CLOSE_TAG: &str = "\n</section>\n";
+
+/// Opening tag of the wrapper of a chapter read from `source` (relative to the project), with
+/// the classes of its front matter and the `extra` ones. The content must follow a blank line to
+/// be parsed as Markdown.
+pub fn open_tag(meta: Option<&ChapterMeta>, extra: &[&str], source: &str) -> String {
+    let mut classes: Vec<String> = match meta {
+        Some(meta) => meta.css_classes().split(' ').map(str::to_string).collect(),
+        None => vec!["chapter".to_string()],
+    };
+    for class in extra {
+        if !classes.iter().any(|c| c == class) {
+            classes.push(class.to_string());
+        }
+    }
+    format!("<section class=\"{}\" data-src=\"{}\">", classes.join(" "), escape_html(source))
+}
+
+/// Splits the front matter from the Markdown content of `file_path`, if present.
+pub fn split_front_matter<'a>(content: &'a str, file_path: &str) -> Result<(Option<ChapterMeta>, &'a str), CoreError> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let Some(rest) = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) else {
+        return Ok((None, content));
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let yaml = &rest[..offset];
+            let meta: ChapterMeta = serde_yaml::from_str(yaml)
+                .map_err(|e| CoreError::BuildError(format!("Invalid front matter in '{file_path}': {e}")))?;
+            return Ok((Some(meta), &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    // No closing delimiter: treat the leading `---` as a regular thematic break.
+    Ok((None, content))
+}
+
+/// Index of the first ATX heading line outside code blocks.
+pub fn first_heading(content: &str) -> Option<usize> {
+    let mut in_code_block = false;
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        let hashes = line.chars().take_while(|c| *c == '#').count();
+        if !in_code_block && (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// Moves the ATX headings outside code blocks down `shift` levels (up for a negative shift),
+/// keeping them between levels 1 and 6.
+pub fn shift_headings(content: &str, shift: i32) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut in_code_block = false;
+    for line in content.split_inclusive('\n') {
+        if line.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        let hashes = line.chars().take_while(|c| *c == '#').count();
+        if !in_code_block && (1..=6).contains(&hashes) && line[hashes..].starts_with([' ', '\n']) {
+            let level = (hashes as i32 + shift).clamp(1, 6) as usize;
+            result.push_str(&"#".repeat(level));
+            result.push_str(&line[hashes..]);
+        } else {
+            result.push_str(line);
+        }
+    }
+    result
+}
+
+/// Content of the `{#id .class}` attribute block ending an ATX heading line, if any.
+pub fn heading_attributes(line: &str) -> Option<&str> {
+    let line = line.trim_end().trim_end_matches('#').trim_end();
+    let start = line.rfind('{')?;
+    line[start + 1..].strip_suffix('}').filter(|attributes| !attributes.contains('}'))
+}
+
+/// Whether a heading line is excluded from numbering by its `.unnumbered` (or `-`) attribute.
+pub fn is_unnumbered_heading(line: &str) -> bool {
+    heading_attributes(line).is_some_and(|attributes| attributes.split_whitespace().any(|a| a == ".unnumbered" || a == "-"))
+}
+
+/// Replaces the text of the first heading, or prepends a level-1 heading when the chapter has none.
+pub fn override_title(content: &str, title: &str) -> String {
+    let Some(heading_index) = first_heading(content) else {
+        return format!("# {title}\n\n{content}\n");
+    };
+
+    let mut result = String::with_capacity(content.len() + title.len());
+    for (index, line) in content.lines().enumerate() {
+        if index == heading_index {
+            let hashes = line.chars().take_while(|c| *c == '#').count();
+            result.push_str(&format!("{} {}", &line[..hashes], title));
+            if let Some(attributes) = heading_attributes(line) {
+                result.push_str(&format!(" {{{attributes}}}"));
+            }
+        } else {
+            result.push_str(line);
+        }
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_front_matter() {
+        let content = "---\ntitle: Appendix A\nnumbering: false\nclass: appendix\n---\n## Old\nText";
+        let (meta, body) = split_front_matter(content, "a.md").unwrap();
+        let meta = meta.unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Appendix A"));
+        assert_eq!(meta.css_classes(), "chapter appendix unnumbered");
+        assert_eq!(body, "## Old\nText");
+    }
+
+    #[test]
+    fn test_split_front_matter_absent() {
+        let (meta, body) = split_front_matter("# Title\n---\nText", "a.md").unwrap();
+        assert!(meta.is_none());
+        assert_eq!(body, "# Title\n---\nText");
+    }
+
+    #[test]
+    fn test_split_front_matter_invalid() {
+        let result = split_front_matter("---\nunknown_key: 1\n---\nText", "a.md");
+        assert!(matches!(result, Err(CoreError::BuildError(_))));
+    }
+
+    #[test]
+    fn test_override_title() {
+        assert_eq!(override_title("```\n# code\n```\n## Old\nText", "New"), "```\n# code\n```\n## New\nText\n");
+        assert_eq!(override_title("Text", "New"), "# New\n\nText\n");
+        assert_eq!(override_title("## Old {#old .notoc}", "New"), "## New {#old .notoc}\n");
+    }
+
+    #[test]
+    fn test_shift_headings() {
+        let content = "# Title\n```\n# comment\n```\n##### Deep\n#hashtag\n## Section";
+        assert_eq!(shift_headings(content, 2), "### Title\n```\n# comment\n```\n###### Deep\n#hashtag\n#### Section");
+        assert_eq!(shift_headings(content, -1), "# Title\n```\n# comment\n```\n#### Deep\n#hashtag\n# Section");
+    }
+
+    #[test]
+    fn test_heading_attributes() {
+        assert_eq!(heading_attributes("## Install {#install .unnumbered}"), Some("#install .unnumbered"));
+        assert_eq!(heading_attributes("## Install {#install} ##"), Some("#install"));
+        assert_eq!(heading_attributes("## Sets {a} and b"), None);
+        assert!(is_unnumbered_heading("## Preface {-}"));
+        assert!(!is_unnumbered_heading("## Preface {.notoc}"));
+    }
+
+    #[test]
+    fn test_open_tag() {
+        let meta = ChapterMeta { class: Some("appendix".to_string()), numbering: Some(false), ..Default::default() };
+        assert_eq!(
+            open_tag(Some(&meta), &["unnumbered", "scope-1"], "chapters/a&b.md"),
+            "<section class=\"chapter appendix unnumbered scope-1\" data-src=\"chapters/a&amp;b.md\">"
+        );
+        assert_eq!(open_tag(None, &[], "intro.md"), "<section class=\"chapter\" data-src=\"intro.md\">");
+    }
+}
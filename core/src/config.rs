@@ -0,0 +1,133 @@
+use crate::critic::{ChangeMode, CommentMode};
+use crate::details::DetailsMode;
+use crate::environments::SolutionMode;
+use crate::tabs::TabsConfig;
+use crate::typography::TypographyConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The settings of `config.yaml` the conversion uses. The other fields of the file are
+/// ignored, so it can be read from the whole file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RenderConfig {
+    pub title: String,
+    pub author: String,
+    pub language: String,
+    pub syntax_theme: String,
+    #[serde(default)]
+    pub html: HtmlConfig,
+    #[serde(default)]
+    pub typography: TypographyConfig,
+    #[serde(default)]
+    pub details: DetailsMode,
+    #[serde(default)]
+    pub tabs: TabsConfig,
+    #[serde(default)]
+    pub solutions: SolutionMode,
+    #[serde(default)]
+    pub environments: BTreeMap<String, String>,
+    #[serde(default)]
+    pub numbering: bool,
+    #[serde(default)]
+    pub continued_markers: bool,
+    #[serde(default)]
+    pub comments: CommentMode,
+    #[serde(default)]
+    pub changes: ChangeMode,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    Pdf,
+    /// The HTML file and its assets, without the PDF.
+    Html,
+    /// One HTML file with images, fonts, stylesheets and scripts inlined.
+    HtmlInline,
+    /// An HTML email: styles inlined into the elements, images embedded, no scripts.
+    EmailHtml,
+    /// A roff man page, `<name>.1`.
+    Man,
+    /// Wrapped plain text, `<name>.txt`.
+    Txt,
+}
+
+impl OutputFormat {
+    /// Formats read in a browser, where tabs, collapsible blocks and the sidebar work.
+    pub fn is_interactive(self) -> bool {
+        matches!(self, OutputFormat::Html | OutputFormat::HtmlInline)
+    }
+}
+
+/// Metadata added to the `<head>` of the generated HTML, for publishing it on a website.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HtmlConfig {
+    pub description: Option<String>,
+    pub canonical_url: Option<String>,
+    /// Image shown when the page is shared (`og:image`).
+    pub og_image: Option<String>,
+    /// Raw snippets (analytics scripts, extra meta tags...) inserted as is.
+    #[serde(default)]
+    pub head_extra: Vec<String>,
+    /// Layout of the table of contents in HTML exports; the PDF always uses the block.
+    #[serde(default)]
+    pub toc: HtmlToc,
+    /// With `--format html`, references assets by copies named after a hash of their content
+    /// (`logo.3f2a9c1e.png`), so they can be cached for long.
+    #[serde(default)]
+    pub fingerprint_assets: bool,
+    /// With `--format html`, also writes one page per chapter with previous/next links and an
+    /// `index.html` landing page listing the chapters.
+    #[serde(default)]
+    pub site: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HtmlToc {
+    /// Dotted-leader block at the `!toc` position, as in the PDF.
+    #[default]
+    Block,
+    /// Collapsible sticky sidebar highlighting the section being read.
+    Sidebar,
+}
+
+/// Bounds on the expansion of the sources, which abort the build when exceeded. They protect
+/// services building untrusted documents from inputs designed to blow up (e.g. a file included
+/// ten times by a file included ten times, and so on).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct LimitsConfig {
+    /// Maximum nesting of `!include` directives.
+    #[serde(default = "default_max_include_depth")]
+    pub max_include_depth: usize,
+    /// Maximum size of the expanded Markdown, in bytes.
+    #[serde(default = "default_max_expanded_size")]
+    pub max_expanded_size: usize,
+    /// Maximum number of images in the sources.
+    #[serde(default = "default_max_images")]
+    pub max_images: usize,
+}
+
+fn default_max_include_depth() -> usize {
+    32
+}
+
+fn default_max_expanded_size() -> usize {
+    100_000_000
+}
+
+fn default_max_images() -> usize {
+    10_000
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_include_depth: default_max_include_depth(),
+            max_expanded_size: default_max_expanded_size(),
+            max_images: default_max_images(),
+        }
+    }
+}
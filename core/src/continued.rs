@@ -0,0 +1,137 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Print styles used with `continued_markers`: table headers repeat on every page, rows are
+/// never cut in half and continuation parts start with a "(continued)" caption.
+pub const CONTINUED_CSS: &str = r#"/* Continued tables and code blocks */
+@media print {
+    thead { display: table-header-group; }
+    tr { break-inside: avoid; }
+    .continued-caption { break-before: page; font-style: italic; font-size: 0.9em; margin-bottom: 0.3em; }
+    pre.continued-part, table.continued-part { margin-top: 0; }
+}"#;
+
+/// Before the measuring print, marks the start of every table row and code line so the pages
+/// where each block breaks can be found in the PDF (`@@pagemap:b<block>.<row>@@` markers).
+pub const MARK_BLOCKS_SCRIPT: &str = r#"(() => {
+  const marker = key => {
+    const m = document.createElement('span');
+    m.className = 'pagemap-marker block-marker';
+    m.style.cssText = 'position: absolute; font-size: 1px; opacity: 0.01;';
+    m.textContent = '@@pagemap:' + key + '@@';
+    return m;
+  };
+  const blocks = [...document.querySelectorAll('table, pre')].filter(b => !b.closest('.toc'));
+  blocks.forEach((block, b) => {
+    block.dataset.splitBlock = b;
+    if (block.tagName === 'TABLE') {
+      block.querySelectorAll('tbody tr').forEach((row, r) => row.firstElementChild?.prepend(marker('b' + b + '.' + r)));
+      return;
+    }
+    const walker = document.createTreeWalker(block, NodeFilter.SHOW_TEXT);
+    const texts = [];
+    while (walker.nextNode()) texts.push(walker.currentNode);
+    let line = 0;
+    block.prepend(marker('b' + b + '.0'));
+    texts.forEach(text => {
+      let index;
+      while ((index = text.data.indexOf('\n')) !== -1 && index < text.data.length - 1) {
+        text = text.splitText(index + 1);
+        line += 1;
+        text.before(marker('b' + b + '.' + line));
+      }
+      if (text.data.endsWith('\n')) {
+        line += 1;
+        text.after(marker('b' + b + '.' + line));
+      }
+    });
+  });
+  return blocks.length;
+})()"#;
+
+/// Rows (or code lines) starting a new page, by block, from the pages of the block markers.
+pub fn split_points(marker_pages: &HashMap<String, usize>) -> BTreeMap<usize, Vec<usize>> {
+    let mut rows: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+    for (key, page) in marker_pages {
+        let Some((block, row)) = key.strip_prefix('b').and_then(|key| key.split_once('.')) else {
+            continue;
+        };
+        if let (Ok(block), Ok(row)) = (block.parse(), row.parse()) {
+            rows.entry(block).or_default().push((row, *page));
+        }
+    }
+
+    rows.into_iter()
+        .filter_map(|(block, mut rows)| {
+            rows.sort();
+            let points: Vec<usize> = rows.windows(2).filter(|pair| pair[1].1 > pair[0].1).map(|pair| pair[1].0).collect();
+            (!points.is_empty()).then_some((block, points))
+        })
+        .collect()
+}
+
+/// Splits the blocks at the given rows: each part after a page break is moved to its own
+/// table (with a copy of the header) or code block, preceded by a `label` caption.
+/// Also removes the block markers.
+pub fn split_blocks_script(splits: &BTreeMap<usize, Vec<usize>>, label: &str) -> String {
+    let splits = serde_json::to_string(splits).unwrap_or_else(|_| "{}".to_string());
+    let label = serde_json::to_string(label).unwrap_or_else(|_| "\"\"".to_string());
+    format!(
+        r#"(() => {{
+  const splits = {splits};
+  const label = {label};
+  Object.entries(splits).forEach(([b, rows]) => {{
+    const block = document.querySelector('[data-split-block="' + b + '"]');
+    if (!block) return;
+    // From the last break to the first, so each part is cut from the remaining block.
+    [...rows].reverse().forEach(r => {{
+      const marker = [...block.querySelectorAll('.block-marker')].find(m => m.textContent === '@@pagemap:b' + b + '.' + r + '@@');
+      if (!marker) return;
+      let part;
+      if (block.tagName === 'TABLE') {{
+        const row = marker.closest('tr');
+        part = document.createElement('table');
+        part.className = block.className;
+        const head = block.querySelector('thead');
+        if (head) part.appendChild(head.cloneNode(true));
+        const body = document.createElement('tbody');
+        part.appendChild(body);
+        const moved = [];
+        for (let next = row; next; next = next.nextElementSibling) moved.push(next);
+        moved.forEach(next => body.appendChild(next));
+      }} else {{
+        const range = document.createRange();
+        range.setStartBefore(marker);
+        range.setEndAfter(block.lastChild);
+        part = block.cloneNode(false);
+        part.removeAttribute('data-split-block');
+        part.appendChild(range.extractContents());
+      }}
+      part.classList.add('continued-part');
+      const caption = document.createElement('div');
+      caption.className = 'continued-caption';
+      caption.textContent = label;
+      block.after(caption);
+      caption.after(part);
+    }});
+  }});
+  document.querySelectorAll('.block-marker').forEach(m => m.remove());
+}})()"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_points() {
+        let pages: HashMap<String, usize> = [
+            ("h0", 1), ("b0.0", 1), ("b0.1", 1), ("b0.2", 2), ("b0.3", 2), ("b0.4", 3),
+            ("b1.0", 3), ("b1.1", 3),
+        ].into_iter().map(|(key, page)| (key.to_string(), page)).collect();
+
+        let splits = split_points(&pages);
+        assert_eq!(splits, BTreeMap::from([(0, vec![2, 4])]));
+        assert!(split_blocks_script(&splits, "(continued)").contains(r#"const splits = {"0":[2,4]};"#));
+    }
+}
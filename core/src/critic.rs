@@ -0,0 +1,151 @@
+use crate::error::CoreError;
+use crate::render::escape_html;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// Links to this prefix carry a reviewer comment, turned into a PDF annotation after printing.
+pub const COMMENT_LINK_PREFIX: &str = "https://pdfbuilder.invalid/comment/";
+
+/// How CriticMarkup comments (`{>> needs a citation <<}`) are rendered.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentMode {
+    /// Notes in the margin of the HTML and PDF.
+    #[default]
+    Margin,
+    /// PDF comments (sticky notes), listed by viewers such as Acrobat.
+    Pdf,
+    /// Removed from the output.
+    Hide,
+}
+
+/// How CriticMarkup changes (`{++insert++}`, `{--delete--}`, `{~~old~>new~~}`) are rendered.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeMode {
+    /// Insertions and deletions marked up in the output, as in a redline.
+    #[default]
+    Show,
+    /// The document as if every change was accepted.
+    Accept,
+    /// Fails the build while changes remain, for final versions.
+    Fail,
+}
+
+/// Styles of the comments in `margin` and `pdf` modes and of the changes in `show` mode.
+pub const CRITIC_CSS: &str = r#"/* Reviewer comments and changes */
+ins.critic { background-color: #e6ffed; color: #1a1a1a; text-decoration: underline; }
+del.critic { background-color: #ffeef0; color: #86181d; text-decoration: line-through; }
+mark.critic { background-color: #fff5b1; color: #1a1a1a; }
+.margin-note { float: right; clear: right; width: 12em; margin: 0 -14em 0.5em 1em; padding: 0.3em 0.5em; font-size: 0.8em; font-family: sans-serif; border-left: 3px solid #ffa500; background-color: rgba(255, 165, 0, 0.1); }
+.pdf-comment { display: inline-block; width: 0.6em; height: 1em; }
+@media print {
+    .margin-note { width: 30%; margin: 0 0 0.5em 1em; color: black; }
+}"#;
+
+/// Renders the comments of the Markdown outside code blocks according to `mode`.
+pub fn render_comments(markdown: &str, mode: CommentMode) -> String {
+    let comment_re = Regex::new(r"(?s)\{>>(.*?)<<\}").unwrap();
+    map_outside_code(markdown, |text| {
+        comment_re.replace_all(text, |caps: &Captures| {
+            let comment = caps[1].trim();
+            match mode {
+                CommentMode::Margin => format!(r#"<span class="margin-note">{}</span>"#, escape_html(comment)),
+                CommentMode::Pdf => format!(
+                    r#"<a class="pdf-comment" href="{COMMENT_LINK_PREFIX}{}"></a>"#,
+                    URL_SAFE_NO_PAD.encode(comment)
+                ),
+                CommentMode::Hide => String::new(),
+            }
+        }).to_string()
+    })
+}
+
+/// Renders the CriticMarkup changes and highlights (`{==text==}`) of the Markdown outside code blocks.
+pub fn render_changes(markdown: &str, mode: ChangeMode) -> Result<String, CoreError> {
+    let change_re = Regex::new(r"(?s)\{\+\+(.*?)\+\+\}|\{--(.*?)--\}|\{~~(.*?)~>(.*?)~~\}|\{==(.*?)==\}").unwrap();
+    let mut unresolved = Vec::new();
+
+    let rendered = map_outside_code(markdown, |text| {
+        change_re.replace_all(text, |caps: &Captures| {
+            if let Some(highlight) = caps.get(5) {
+                return match mode {
+                    ChangeMode::Show => format!(r#"<mark class="critic">{}</mark>"#, highlight.as_str()),
+                    _ => highlight.as_str().to_string(),
+                };
+            }
+            if mode == ChangeMode::Fail {
+                unresolved.push(caps[0].to_string());
+            }
+            let (deleted, inserted) = match (caps.get(1), caps.get(2)) {
+                (Some(inserted), _) => ("", inserted.as_str()),
+                (_, Some(deleted)) => (deleted.as_str(), ""),
+                _ => (caps.get(3).map_or("", |m| m.as_str()), caps.get(4).map_or("", |m| m.as_str())),
+            };
+            match mode {
+                ChangeMode::Show => {
+                    let deleted = if deleted.is_empty() { String::new() } else { format!(r#"<del class="critic">{deleted}</del>"#) };
+                    let inserted = if inserted.is_empty() { String::new() } else { format!(r#"<ins class="critic">{inserted}</ins>"#) };
+                    deleted + &inserted
+                }
+                _ => inserted.to_string(),
+            }
+        }).to_string()
+    });
+
+    match unresolved.first() {
+        Some(first) => Err(CoreError::UnresolvedChanges(unresolved.len(), first.clone())),
+        None => Ok(rendered),
+    }
+}
+
+/// Applies `transform` to the text between fenced code blocks.
+fn map_outside_code<F>(markdown: &str, mut transform: F) -> String
+where
+    F: FnMut(&str) -> String,
+{
+    let mut result = String::with_capacity(markdown.len());
+    let mut text = String::new();
+    let mut in_code_block = false;
+    for line in markdown.split_inclusive('\n') {
+        let is_fence = line.trim_start().starts_with("```");
+        if in_code_block || is_fence {
+            result.push_str(&transform(&std::mem::take(&mut text)));
+            result.push_str(line);
+            if is_fence {
+                in_code_block = !in_code_block;
+            }
+        } else {
+            text.push_str(line);
+        }
+    }
+    result.push_str(&transform(&text));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_comments() {
+        let markdown = "Text{>> needs a <citation> <<}.\n```md\n{>> kept <<}\n```\n";
+        assert_eq!(render_comments(markdown, CommentMode::Margin), "Text<span class=\"margin-note\">needs a &lt;citation&gt;</span>.\n```md\n{>> kept <<}\n```\n");
+        assert_eq!(render_comments(markdown, CommentMode::Hide), "Text.\n```md\n{>> kept <<}\n```\n");
+        assert!(render_comments("A{>>ok<<}", CommentMode::Pdf).contains(&format!("href=\"{COMMENT_LINK_PREFIX}b2s\"")));
+    }
+
+    #[test]
+    fn test_render_changes() {
+        let markdown = "A {++new++} {--old--} {~~this~>that~~} {==key==} point.\n```\n{++code++}\n```\n";
+        assert_eq!(
+            render_changes(markdown, ChangeMode::Show).unwrap(),
+            "A <ins class=\"critic\">new</ins> <del class=\"critic\">old</del> <del class=\"critic\">this</del><ins class=\"critic\">that</ins> <mark class=\"critic\">key</mark> point.\n```\n{++code++}\n```\n"
+        );
+        assert_eq!(render_changes(markdown, ChangeMode::Accept).unwrap(), "A new  that key point.\n```\n{++code++}\n```\n");
+        assert!(matches!(render_changes(markdown, ChangeMode::Fail), Err(CoreError::UnresolvedChanges(3, first)) if first == "{++new++}"));
+        assert!(render_changes("{==only highlights==}", ChangeMode::Fail).is_ok());
+    }
+}
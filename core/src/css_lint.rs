@@ -0,0 +1,129 @@
+use regex::Regex;
+
+/// A stylesheet issue likely to be ignored by Chrome's print engine or to break pagination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CssIssue {
+    pub line: usize,
+    pub message: String,
+}
+
+/// A declaration with the preludes of the blocks around it, innermost last.
+struct Declaration<'a> {
+    line: usize,
+    property: String,
+    value: &'a str,
+    blocks: Vec<&'a str>,
+}
+
+/// Checks the declarations of a stylesheet used for printing. Rules under `@media screen` only
+/// apply to the HTML export and are skipped.
+pub fn lint_css(css: &str) -> Vec<CssIssue> {
+    let viewport_re = Regex::new(r"\d(vh|vw|vmin|vmax)\b").unwrap();
+    let mut issues = Vec::new();
+    for declaration in declarations(&strip_comments(css)) {
+        let screen_only = declaration.blocks.iter().any(|prelude| {
+            let prelude = prelude.to_lowercase();
+            prelude.starts_with("@media") && prelude.contains("screen") && !prelude.contains("print")
+        });
+        if screen_only {
+            continue;
+        }
+        let value = declaration.value.to_lowercase();
+        let property = declaration.property.as_str();
+        let selectors = declaration.blocks.last().copied().unwrap_or_default();
+        let message = if property == "position" && value == "fixed" {
+            Some("'position: fixed' elements are repeated on every printed page".to_string())
+        } else if property == "position" && value == "sticky" {
+            Some("'position: sticky' has no effect in print".to_string())
+        } else if property.starts_with("animation") || property.starts_with("transition") {
+            Some(format!("'{property}' has no effect in print"))
+        } else if let Some(unit) = viewport_re.captures(&value) {
+            Some(format!("'{}' units in '{property}' are relative to the page box in print and often overflow it", &unit[1]))
+        } else if property.starts_with("overflow") && matches!(value.as_str(), "auto" | "scroll" | "hidden")
+            && selectors.split(',').any(|selector| matches!(selector.trim(), "html" | "body")) {
+            Some(format!("'{property}: {value}' on the document clips it to its first page"))
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            issues.push(CssIssue { line: declaration.line, message });
+        }
+    }
+    issues
+}
+
+/// Replaces comments with spaces, keeping their line breaks so line numbers stay right.
+pub fn strip_comments(css: &str) -> String {
+    let comment_re = Regex::new(r"(?s)/\*.*?\*/").unwrap();
+    comment_re.replace_all(css, |caps: &regex::Captures| caps[0].chars().map(|c| if c == '\n' { '\n' } else { ' ' }).collect::<String>()).to_string()
+}
+
+/// The `property: value` declarations of a stylesheet, with the line they start on.
+fn declarations(css: &str) -> Vec<Declaration<'_>> {
+    let mut result = Vec::new();
+    let mut blocks: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut line = 1;
+    let mut start_line = 1;
+    let mut quote: Option<char> = None;
+
+    for (i, c) in css.char_indices() {
+        if c == '\n' {
+            line += 1;
+        }
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '{' => {
+                blocks.push(css[start..i].trim());
+                start = i + 1;
+                start_line = line;
+            }
+            ';' | '}' => {
+                let text = &css[start..i];
+                if let Some((property, value)) = text.split_once(':').filter(|_| !blocks.is_empty()) {
+                    let leading_lines = text[..text.len() - text.trim_start().len()].matches('\n').count();
+                    result.push(Declaration {
+                        line: start_line + leading_lines,
+                        property: property.trim().to_lowercase(),
+                        value: value.trim().trim_end_matches("!important").trim(),
+                        blocks: blocks.clone(),
+                    });
+                }
+                if c == '}' {
+                    blocks.pop();
+                }
+                start = i + 1;
+                start_line = line;
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_css() {
+        let css = "/* Header\n   on every page */\n.banner { position: fixed; top: 0; }\n\
+            body { overflow: hidden; }\n.cover {\n  height: 100vh;\n  content: \"a;b{\";\n}\n\
+            @media screen { nav { position: sticky; transition: all 1s; } }\n\
+            @media print { .toc { animation: none; } }\npre { overflow: auto; width: 50%; }\n";
+        let issues = lint_css(css);
+        let found: Vec<(usize, &str)> = issues.iter().map(|issue| (issue.line, issue.message.as_str())).collect();
+        assert_eq!(found, vec![
+            (3, "'position: fixed' elements are repeated on every printed page"),
+            (4, "'overflow: hidden' on the document clips it to its first page"),
+            (6, "'vh' units in 'height' are relative to the page box in print and often overflow it"),
+            (10, "'animation' has no effect in print"),
+        ]);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::render::escape_html;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// How collapsible `<details>` blocks are printed. Browsers print closed blocks without their
+/// content, so they are expanded by default.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DetailsMode {
+    /// Printed open, with their content.
+    #[default]
+    Expanded,
+    /// Only the summary, followed by an ellipsis marking the hidden content.
+    Collapsed,
+    /// Left out of the PDF.
+    Omit,
+}
+
+/// Marker of the printed summaries in `collapsed` mode.
+pub const COLLAPSED_DETAILS_CSS: &str = r#"/* Collapsed details */
+details:not([open]) > summary::after { content: " …"; opacity: 0.7; }"#;
+
+/// Replaces `:::details Summary` blocks of the Markdown with `<details>` elements.
+pub fn render_details_blocks(markdown: &str) -> String {
+    let open_re = Regex::new(r"^:::\s*details(?:\s+(.*?))?\s*$").unwrap();
+    let mut result = String::with_capacity(markdown.len());
+    // One entry per open `:::` block: whether it is a details block.
+    let mut stack: Vec<bool> = Vec::new();
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if !in_code_block && trimmed == ":::" && stack.pop() == Some(true) {
+            result.push_str("\n</details>\n");
+            continue;
+        }
+        if !in_code_block {
+            if let Some(caps) = open_re.captures(trimmed) {
+                let summary = caps.get(1).map(|summary| summary.as_str()).unwrap_or("Details");
+                result.push_str(&format!("<details>\n<summary>{}</summary>\n\n", escape_html(summary)));
+                stack.push(true);
+                continue;
+            }
+            if trimmed.starts_with(":::") && trimmed != ":::" {
+                stack.push(false);
+            }
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    result
+}
+
+/// Prepares the `<details>` elements of the HTML body for printing according to `mode`.
+pub fn print_details(html: &str, mode: DetailsMode) -> String {
+    match mode {
+        DetailsMode::Expanded => {
+            let open_re = Regex::new(r"<details(\s[^>]*)?>").unwrap();
+            open_re.replace_all(html, |caps: &Captures| {
+                let attributes = caps.get(1).map(|a| a.as_str()).unwrap_or_default();
+                if attributes.split_whitespace().any(|attribute| attribute == "open" || attribute.starts_with("open=")) {
+                    caps[0].to_string()
+                } else {
+                    format!("<details open{attributes}>")
+                }
+            }).to_string()
+        }
+        DetailsMode::Collapsed => html.to_string(),
+        DetailsMode::Omit => remove_details(html),
+    }
+}
+
+/// Removes every `<details>` element, nested ones included.
+fn remove_details(html: &str) -> String {
+    let tag_re = Regex::new(r"<details[\s>]|</details>").unwrap();
+    let mut result = String::with_capacity(html.len());
+    let mut depth = 0usize;
+    let mut last = 0;
+    for tag in tag_re.find_iter(html) {
+        if tag.as_str().starts_with("</") {
+            if depth == 1 {
+                last = tag.end();
+            }
+            depth = depth.saturating_sub(1);
+        } else {
+            if depth == 0 {
+                result.push_str(&html[last..tag.start()]);
+            }
+            depth += 1;
+        }
+    }
+    if depth == 0 {
+        result.push_str(&html[last..]);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_details_blocks() {
+        let markdown = ":::details Show the <output>\n:::note\nText\n:::\n```\n:::\n```\n:::\n:::details\nMore\n:::\n";
+        assert_eq!(
+            render_details_blocks(markdown),
+            "<details>\n<summary>Show the &lt;output&gt;</summary>\n\n:::note\nText\n:::\n```\n:::\n```\n\n</details>\n\
+             <details>\n<summary>Details</summary>\n\nMore\n\n</details>\n"
+        );
+    }
+
+    #[test]
+    fn test_print_details() {
+        let html = "<p>A</p><details><summary>S</summary><details class=\"x\"><p>B</p></details></details><details open><p>C</p></details><p>D</p>";
+        assert_eq!(
+            print_details(html, DetailsMode::Expanded),
+            "<p>A</p><details open><summary>S</summary><details open class=\"x\"><p>B</p></details></details><details open><p>C</p></details><p>D</p>"
+        );
+        assert_eq!(print_details(html, DetailsMode::Omit), "<p>A</p><p>D</p>");
+        assert_eq!(print_details(html, DetailsMode::Collapsed), html);
+    }
+}
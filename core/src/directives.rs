@@ -0,0 +1,247 @@
+use crate::error::CoreError;
+use regex::Regex;
+use std::ops::Range;
+
+/// The directives, each with the names of its options (`name=value` arguments).
+/// Other `!word` lines are text.
+const DIRECTIVES: [(&str, &[&str]); 15] = [
+    ("include", &["file", "shift", "shift-headings"]),
+    ("style", &["file"]),
+    ("newpage", &[]),
+    ("toc", &["depth", "title"]),
+    ("authorities", &[]),
+    ("acronyms", &[]),
+    ("qrcode", &["size"]),
+    ("barcode", &["width"]),
+    ("changelog", &["since", "until", "level"]),
+    ("openapi", &["file", "tag", "level"]),
+    ("jsonschema", &["file"]),
+    ("rustdoc", &["file", "item", "level"]),
+    ("asciinema", &["file", "stills", "at"]),
+    ("screenshot", &["width", "height", "selector", "alt"]),
+    ("html", &["file"]),
+];
+
+/// A directive line: `!name`, or `!name(argument, option=value, ...)` where values may be
+/// quoted (`title="A, B"`) to hold commas or parentheses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directive<'a> {
+    pub name: &'a str,
+    /// The text between the parentheses.
+    text: &'a str,
+    arguments: Vec<Argument<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Argument<'a> {
+    /// The option name, `None` for positional arguments.
+    name: Option<&'a str>,
+    value: String,
+    /// Position of the argument in the text.
+    span: Range<usize>,
+}
+
+/// Parses a directive line. `Ok(None)` for lines that are not directives.
+pub fn parse(line: &str) -> Result<Option<Directive<'_>>, CoreError> {
+    let line_re = Regex::new(r"^\s*!([A-Za-z][\w-]*)(?:\((.*)\))?\s*$").unwrap();
+    let Some(caps) = line_re.captures(line) else {
+        return Ok(None);
+    };
+    let name = caps.get(1).unwrap().as_str();
+    let Some((_, options)) = DIRECTIVES.iter().find(|(directive, _)| *directive == name) else {
+        return Ok(None);
+    };
+    let text = caps.get(2).map_or("", |text| text.as_str());
+    let option_re = Regex::new(r"^([A-Za-z][\w-]*)\s*=\s*").unwrap();
+    let mut arguments = Vec::new();
+    for span in split_arguments(text).ok_or_else(|| CoreError::BuildError(format!("Unclosed quote in !{name}({text})")))? {
+        let argument = &text[span.clone()];
+        let option = option_re.captures(argument).filter(|caps| options.contains(&&caps[1]));
+        let (name, value) = match option {
+            Some(caps) => (Some(caps.get(1).unwrap().as_str()), &argument[caps.get(0).unwrap().end()..]),
+            None => (None, argument),
+        };
+        arguments.push(Argument { name, value: unquote(value), span });
+    }
+    Ok(Some(Directive { name, text, arguments }))
+}
+
+impl Directive<'_> {
+    /// The positional argument at `index`.
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.arguments.iter().filter(|argument| argument.name.is_none()).nth(index).map(|argument| argument.value.as_str())
+    }
+
+    /// The value of the `name=` option.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.arguments.iter().find(|argument| argument.name == Some(name)).map(|argument| argument.value.as_str())
+    }
+
+    /// The value of the `name=` option, or else the positional argument at `index`.
+    pub fn value(&self, name: &str, index: usize) -> Option<&str> {
+        self.option(name).or_else(|| self.positional(index))
+    }
+
+    /// The positional arguments from `index` up to the next option, as written, for data that
+    /// may contain commas (`!qrcode(https://example.com/?a=1,b=2)`). A single quoted argument
+    /// loses its quotes.
+    pub fn data(&self, index: usize) -> Option<String> {
+        let start = self.arguments.iter().enumerate().filter(|(_, argument)| argument.name.is_none()).nth(index)?.0;
+        let run: Vec<&Argument> = self.arguments[start..].iter().take_while(|argument| argument.name.is_none()).collect();
+        match run.as_slice() {
+            [argument] => Some(argument.value.clone()),
+            [first, .., last] => Some(self.text[first.span.start..last.span.end].to_string()),
+            [] => None,
+        }
+    }
+
+    /// Fails when the directive has more than `count` positional arguments.
+    pub fn expect_positional(&self, count: usize) -> Result<(), CoreError> {
+        match self.positional(count) {
+            Some(extra) => Err(CoreError::BuildError(format!("Unexpected argument '{extra}' in !{}({})", self.name, self.text))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Spans of the comma-separated arguments, trimmed. `None` for an unclosed quote.
+fn split_arguments(text: &str) -> Option<Vec<Range<usize>>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                spans.push(trimmed(text, start..i));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if in_quotes {
+        return None;
+    }
+    if !text.trim().is_empty() {
+        spans.push(trimmed(text, start..text.len()));
+    }
+    Some(spans)
+}
+
+fn trimmed(text: &str, span: Range<usize>) -> Range<usize> {
+    let part = &text[span.clone()];
+    let start = span.start + part.len() - part.trim_start().len();
+    start..start + part.trim().len()
+}
+
+/// A `"quoted value"` without its quotes and backslash escapes; other values as they are.
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) {
+        Some(quoted) if value.len() >= 2 => {
+            let mut result = String::with_capacity(quoted.len());
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                result.push(if c == '\\' { chars.next().unwrap_or('\\') } else { c });
+            }
+            result
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Lines opening and closing a block whose directives are shown as text.
+pub const RAW_START: &str = "!raw";
+pub const RAW_END: &str = "!endraw";
+
+/// What a source line is to the preprocessor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceLine {
+    /// A line that may hold a directive.
+    Directive,
+    /// A line of a code block or of a `!raw` block, kept as it is.
+    Literal,
+    /// The `!raw` or `!endraw` line around a raw block, which is dropped.
+    RawMarker,
+}
+
+/// Follows the code blocks and `!raw` ... `!endraw` blocks of a file, line by line.
+#[derive(Debug, Default)]
+pub struct LineScanner {
+    in_code_block: bool,
+    in_raw_block: bool,
+}
+
+impl LineScanner {
+    pub fn scan(&mut self, line: &str) -> SourceLine {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            self.in_code_block = !self.in_code_block;
+        }
+        if self.in_code_block {
+            return SourceLine::Literal;
+        }
+        if trimmed == if self.in_raw_block { RAW_END } else { RAW_START } {
+            self.in_raw_block = !self.in_raw_block;
+            return SourceLine::RawMarker;
+        }
+        if self.in_raw_block {
+            SourceLine::Literal
+        } else {
+            SourceLine::Directive
+        }
+    }
+}
+
+/// A directive line escaped with a backslash (`\!include(...)`) without its backslash.
+pub fn unescape(line: &str) -> Option<String> {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    line.trim_start().strip_prefix("\\!").map(|rest| format!("{indent}!{rest}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_scanner() {
+        let mut scanner = LineScanner::default();
+        let kinds: Vec<SourceLine> = ["!toc", "!raw", "!toc", "```", "!endraw", "```", "!endraw", "!newpage"]
+            .iter()
+            .map(|line| scanner.scan(line))
+            .collect();
+        assert_eq!(kinds, [
+            SourceLine::Directive, SourceLine::RawMarker, SourceLine::Literal, SourceLine::Literal,
+            SourceLine::Literal, SourceLine::Literal, SourceLine::RawMarker, SourceLine::Directive,
+        ]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let directive = parse("  !toc(depth = 2, title=\"Contents, \\\"short\\\"\")").unwrap().unwrap();
+        assert_eq!(directive.name, "toc");
+        assert_eq!(directive.option("depth"), Some("2"));
+        assert_eq!(directive.option("title"), Some("Contents, \"short\""));
+        assert_eq!(directive.positional(0), None);
+
+        let directive = parse("!qrcode(https://example.com/?a=1,b=2, size=4cm)").unwrap().unwrap();
+        assert_eq!(directive.data(0).as_deref(), Some("https://example.com/?a=1,b=2"));
+        assert_eq!(directive.option("size"), Some("4cm"));
+
+        let directive = parse("!include(file=\"chapters/a (old).md\")").unwrap().unwrap();
+        assert_eq!(directive.value("file", 0), Some("chapters/a (old).md"));
+        assert!(parse("!newpage(now)").unwrap().unwrap().expect_positional(0).is_err());
+
+        assert_eq!(parse("!important").unwrap(), None);
+        assert_eq!(parse("!toc trailing").unwrap(), None);
+        assert!(parse("!toc(title=\"open)").is_err());
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("  \\!include(a.md)").as_deref(), Some("  !include(a.md)"));
+        assert_eq!(unescape("\\*not a directive*"), None);
+    }
+}
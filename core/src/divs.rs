@@ -0,0 +1,95 @@
+use crate::attributes::Attributes;
+use regex::Regex;
+
+/// Styles of the containers most documents use: side-by-side columns and callouts.
+pub const DIVS_CSS: &str = r#"/* Fenced divs */
+.columns { display: flex; gap: 1.5em; }
+.columns > .column { flex: 1; min-width: 0; }
+.note, .tip, .warning, .important { border-left: 4px solid #4a90d9; padding: 0.2em 1em; margin: 1em 0; break-inside: avoid; }
+.tip { border-left-color: #4caf50; }
+.warning { border-left-color: #e0a800; }
+.important { border-left-color: #d9534f; }
+.epigraph { max-width: 70%; margin: 2em auto; text-align: center; font-style: italic; break-inside: avoid; }
+.epigraph > p:last-child:not(:first-child) { font-style: normal; font-size: 0.9em; }
+.dedication { margin: 35vh auto 0; max-width: 70%; text-align: center; font-style: italic; }"#;
+
+/// Classes styled by `DIVS_CSS`.
+const STYLED_CLASSES: [&str; 8] = ["columns", "column", "note", "tip", "warning", "important", "epigraph", "dedication"];
+
+/// Replaces pandoc-style fenced divs, `::: {#id .class key=value}` or `::: class` up to a
+/// closing `:::`, with `<div>` elements. Divs nest, fences inside code blocks are ignored and
+/// `:::` blocks handled by the earlier passes (tabs, details, environments) are already gone.
+pub fn render_divs(markdown: &str) -> String {
+    let open_re = Regex::new(r"^:::\s*([A-Za-z][\w-]*)?\s*(?:\{([^{}]*)\})?$").unwrap();
+    let mut result = String::with_capacity(markdown.len());
+    // One entry per open `:::` block: whether it is a div.
+    let mut stack: Vec<bool> = Vec::new();
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if !in_code_block && trimmed == ":::" {
+            if stack.pop() == Some(true) {
+                result.push_str("\n</div>\n");
+                continue;
+            }
+        } else if !in_code_block && trimmed.starts_with(":::") {
+            let attributes = open_re.captures(trimmed)
+                .filter(|caps| caps.get(1).is_some() || caps.get(2).is_some())
+                .and_then(|caps| {
+                    let mut attributes = Attributes::parse(caps.get(2).map_or("", |a| a.as_str()))?;
+                    if let Some(class) = caps.get(1) {
+                        attributes.classes.insert(0, class.as_str().to_string());
+                    }
+                    Some(attributes)
+                });
+            stack.push(attributes.is_some());
+            if let Some(attributes) = attributes {
+                result.push_str(&format!("<div{}>\n\n", attributes.to_html()));
+                continue;
+            }
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+
+    // Unclosed divs end with the document.
+    for _ in stack.into_iter().filter(|is_div| *is_div) {
+        result.push_str("\n</div>\n");
+    }
+    result
+}
+
+/// Whether the HTML body uses a class styled by `DIVS_CSS`.
+pub fn uses_styled_classes(html: &str) -> bool {
+    let class_re = Regex::new(r#"<div [^>]*\bclass="([^"]*)""#).unwrap();
+    let used = class_re.captures_iter(html).any(|caps| caps[1].split_whitespace().any(|class| STYLED_CLASSES.contains(&class)));
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_divs() {
+        let markdown = "::: {.columns #intro}\n::: column\nLeft\n:::\n::: {.column style=\"flex: 0 0 40%\"}\n```\n:::\n```\n:::\n:::\n\
+            :::note Title\nKept\n:::\n::: warning\nUnclosed";
+        assert_eq!(
+            render_divs(markdown),
+            "<div id=\"intro\" class=\"columns\">\n\n<div class=\"column\">\n\nLeft\n\n</div>\n\
+             <div class=\"column\" style=\"flex: 0 0 40%\">\n\n```\n:::\n```\n\n</div>\n\n</div>\n\
+             :::note Title\nKept\n:::\n<div class=\"warning\">\n\nUnclosed\n\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_uses_styled_classes() {
+        assert!(uses_styled_classes("<div id=\"a\" class=\"columns\">"));
+        assert!(!uses_styled_classes("<div class=\"tabs\"><span class=\"note\">"));
+        assert!(uses_styled_classes("<div class=\"epigraph\">"));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::render::escape_html;
+use regex::{Captures, Regex};
+
+/// Styles of `build --draft`: a watermark repeated on every page, paragraph numbers in the
+/// left margin for review comments and highlighted TODO notes.
+pub const DRAFT_CSS: &str = r#"/* Draft mode */
+.draft-watermark { position: fixed; top: 45%; left: 0; width: 100%; text-align: center; font-size: 8em; font-weight: bold; color: rgba(128, 128, 128, 0.15); transform: rotate(-30deg); pointer-events: none; z-index: 1000; }
+.draft-body { counter-reset: paragraph; }
+.draft-body p { counter-increment: paragraph; position: relative; }
+.draft-body p::before { content: counter(paragraph); position: absolute; left: -3em; width: 2.2em; text-align: right; font-size: 0.7em; font-family: sans-serif; color: #888; }
+mark.todo { background-color: #ffeb3b; color: black; padding: 0 0.2em; }
+.draft-todos { page-break-before: always; }"#;
+
+/// Highlights the `TODO:` and `FIXME:` notes of the text of the document body, outside code,
+/// and appends the list of notes linking back to them. The body is wrapped for paragraph numbering.
+pub fn apply(body_html: &str, todos_title: &str) -> String {
+    // Code blocks, inline code, styles and scripts are kept as is.
+    let code_re = Regex::new(r"(?s)<pre[\s>].*?</pre>|<code[\s>].*?</code>|<style[\s>].*?</style>|<script[\s>].*?</script>").unwrap();
+    // Tags (with their attributes) and comments, between the text nodes.
+    let tag_re = Regex::new(r"(?s)<!--.*?-->|<[^>]*>").unwrap();
+    let todo_re = Regex::new(r"\b(TODO|FIXME):([^<]*)").unwrap();
+
+    let mut todos = Vec::new();
+    let mut mark_text = |text: &str| {
+        todo_re.replace_all(text, |caps: &Captures| {
+            todos.push(format!("{}:{}", &caps[1], caps[2].trim_end()));
+            format!(r#"<mark class="todo" id="todo-{}">{}:</mark>{}"#, todos.len(), &caps[1], &caps[2])
+        }).to_string()
+    };
+    let mut mark = |html: &str| {
+        let mut marked = String::with_capacity(html.len());
+        let mut last = 0;
+        for tag in tag_re.find_iter(html) {
+            marked.push_str(&mark_text(&html[last..tag.start()]));
+            marked.push_str(tag.as_str());
+            last = tag.end();
+        }
+        marked.push_str(&mark_text(&html[last..]));
+        marked
+    };
+
+    let mut body = String::with_capacity(body_html.len());
+    let mut last = 0;
+    for code in code_re.find_iter(body_html) {
+        body.push_str(&mark(&body_html[last..code.start()]));
+        body.push_str(code.as_str());
+        last = code.end();
+    }
+    body.push_str(&mark(&body_html[last..]));
+
+    let mut html = format!("<div class=\"draft-watermark\">DRAFT</div>\n<div class=\"draft-body\">\n{body}\n</div>\n");
+    if !todos.is_empty() {
+        html.push_str(&format!("<section class=\"draft-todos\">\n<h2>{}</h2>\n<ol>\n", escape_html(todos_title)));
+        for (index, todo) in todos.iter().enumerate() {
+            // The note is already escaped HTML text.
+            html.push_str(&format!("<li><a href=\"#todo-{}\">{}</a></li>\n", index + 1, todo.trim()));
+        }
+        html.push_str("</ol>\n</section>\n");
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_marks_todos_outside_code() {
+        let html = apply("<p>Intro. TODO: add a figure &amp; a table</p>\n<pre><code>// TODO: not a note</code></pre>\n<p>Use <code>FIXME: x</code>. FIXME: check <em>this</em></p>", "Unresolved TODOs");
+        assert!(html.starts_with("<div class=\"draft-watermark\">DRAFT</div>\n<div class=\"draft-body\">"));
+        assert!(html.contains(r#"<mark class="todo" id="todo-1">TODO:</mark> add a figure"#));
+        assert!(html.contains("<pre><code>// TODO: not a note</code></pre>"));
+        assert!(html.contains("<code>FIXME: x</code>"));
+        assert!(html.contains(r#"<mark class="todo" id="todo-2">FIXME:</mark> check <em>"#));
+        assert!(html.contains("<li><a href=\"#todo-1\">TODO: add a figure &amp; a table</a></li>\n<li><a href=\"#todo-2\">FIXME: check</a></li>"));
+    }
+
+    #[test]
+    fn test_apply_leaves_markup_alone() {
+        let html = apply("<p><img alt=\"TODO: alt text\" src=\"a.png\"><!-- FIXME: hidden --><a title=\"FIXME: title\" href=\"#\">link</a> TODO: text</p>", "Unresolved TODOs");
+        assert!(html.contains("<img alt=\"TODO: alt text\" src=\"a.png\"><!-- FIXME: hidden --><a title=\"FIXME: title\" href=\"#\">link</a> <mark class=\"todo\" id=\"todo-1\">TODO:</mark> text</p>"));
+        assert!(!html.contains("todo-2"));
+    }
+
+    #[test]
+    fn test_apply_without_todos() {
+        let html = apply("<p>Done.</p>", "Unresolved TODOs");
+        assert!(!html.contains("draft-todos"));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::render::escape_html;
+use crate::i18n;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Environment labels for the document: the built-in ones in the document language,
+/// extended or overridden by the `environments:` config.
+pub fn environment_labels(language: &str, environments: &BTreeMap<String, String>) -> HashMap<String, String> {
+    let mut labels: HashMap<String, String> = i18n::environment_labels(language)
+        .iter()
+        .map(|(name, label)| (name.to_string(), label.to_string()))
+        .collect();
+    labels.extend(environments.clone());
+    labels
+}
+
+/// Where `:::solution` blocks are rendered: after their exercise, in an answers appendix
+/// at the end of the document, or not at all (student edition).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SolutionMode {
+    #[default]
+    Inline,
+    Appendix,
+    Omit,
+}
+
+const SOLUTION: &str = "solution";
+const EXERCISE: &str = "exercise";
+/// Label key of the answers appendix title; not an environment.
+const SOLUTIONS_TITLE: &str = "solutions";
+
+/// Replaces environment blocks (`:::name Optional title {#label}` ... `:::`) with numbered
+/// `<div class="environment name">` blocks and `[@label]` references with links to them.
+/// Each environment type has its own counter; with `by_chapter`, counters restart at every
+/// numbered chapter (`## 3 ...`) and numbers are prefixed with it ("Theorem 3.2").
+/// A `:::solution` block takes the number of the last exercise and links back to it.
+pub fn number_environments(markdown: &str, labels: &HashMap<String, String>, by_chapter: bool, solutions: SolutionMode) -> String {
+    let open_re = Regex::new(r"^:::\s*([A-Za-z][\w-]*)\s*(.*?)\s*(?:\{#([^}\s]+)\})?\s*$").unwrap();
+    let chapter_re = Regex::new(r"^##\s+(\d+)\s").unwrap();
+
+    let mut result = String::with_capacity(markdown.len());
+    let mut appendix = String::new();
+    let mut references: HashMap<String, String> = HashMap::new();
+    let mut counters: HashMap<String, usize> = HashMap::new();
+    let mut chapter: Option<String> = None;
+    // Number and id of the last exercise, for the solutions that follow it.
+    let mut last_exercise: Option<(String, String)> = None;
+    // One entry per open `:::` block: whether it is an environment. Other blocks are left untouched.
+    let mut stack: Vec<bool> = Vec::new();
+    // Depth of the solution being moved to the appendix or dropped.
+    let mut diverted_depth: Option<usize> = None;
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        let output = if diverted_depth.is_some() { &mut appendix } else { &mut result };
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block {
+            output.push_str(line);
+            output.push('\n');
+            continue;
+        }
+
+        if by_chapter {
+            if let Some(caps) = chapter_re.captures(line) {
+                chapter = Some(caps[1].to_string());
+                counters.clear();
+            }
+        }
+
+        if trimmed == ":::" {
+            match stack.pop() {
+                Some(true) => output.push_str("\n</div>\n"),
+                _ => output.push_str(line),
+            }
+            output.push('\n');
+            if diverted_depth.is_some_and(|depth| stack.len() < depth) {
+                diverted_depth = None;
+            }
+            continue;
+        }
+
+        let Some(caps) = open_re.captures(trimmed) else {
+            // Other `:::` blocks, such as `::: {.class}` divs, are closed by a later `:::` too.
+            if trimmed.starts_with(":::") {
+                stack.push(false);
+            }
+            output.push_str(line);
+            output.push('\n');
+            continue;
+        };
+        let name = caps[1].to_string();
+        let Some(label) = labels.get(&name).filter(|_| name != SOLUTIONS_TITLE) else {
+            stack.push(false);
+            output.push_str(line);
+            output.push('\n');
+            continue;
+        };
+
+        if name == SOLUTION {
+            let (number, exercise_id) = last_exercise.clone().unwrap_or_default();
+            let caption = format!("{label} {number}").trim_end().to_string();
+            let backlink = if exercise_id.is_empty() { caption } else { format!("<a href=\"#{}\">{}</a>", escape_html(&exercise_id), caption) };
+            stack.push(true);
+            // Omitted solutions are collected like appendix ones, then discarded.
+            let output = match solutions {
+                SolutionMode::Inline => &mut result,
+                SolutionMode::Appendix | SolutionMode::Omit => {
+                    diverted_depth = Some(stack.len());
+                    &mut appendix
+                }
+            };
+            output.push_str(&format!(
+                "<div class=\"environment solution\" id=\"solution-{}\">\n<p class=\"environment-title\"><strong>{backlink}</strong></p>\n\n",
+                escape_html(&number)
+            ));
+            continue;
+        }
+
+        let counter = counters.entry(name.clone()).or_insert(0);
+        *counter += 1;
+        let number = match &chapter {
+            Some(chapter) => format!("{chapter}.{counter}"),
+            None => counter.to_string(),
+        };
+        let caption = format!("{label} {number}");
+
+        let id = match caps.get(3) {
+            Some(id) => {
+                references.insert(id.as_str().to_string(), caption.clone());
+                Some(id.as_str().to_string())
+            }
+            // Exercises always get an id so their solutions can link back to them.
+            None if name == EXERCISE => Some(format!("exercise-{number}")),
+            None => None,
+        };
+        if name == EXERCISE {
+            last_exercise = Some((number.clone(), id.clone().unwrap_or_default()));
+        }
+        let id = id.map(|id| format!(" id=\"{}\"", escape_html(&id))).unwrap_or_default();
+        let title = match caps[2].trim() {
+            "" => String::new(),
+            title => format!(" ({})", escape_html(title)),
+        };
+        output.push_str(&format!(
+            "<div class=\"environment {name}\"{id}>\n<p class=\"environment-title\"><strong>{caption}</strong>{title}</p>\n\n"
+        ));
+        stack.push(true);
+    }
+
+    if solutions == SolutionMode::Appendix && !appendix.is_empty() {
+        let title = labels.get(SOLUTIONS_TITLE).cloned().unwrap_or_else(|| "Solutions".to_string());
+        result.push_str(&format!("\n<section class=\"chapter solutions unnumbered\">\n\n## {title}\n\n{appendix}\n</section>\n"));
+    }
+
+    resolve_references(&result, &references)
+}
+
+/// Turns `[@label]` into a link showing the number of the referenced environment.
+/// Unknown labels are left as is.
+fn resolve_references(markdown: &str, references: &HashMap<String, String>) -> String {
+    let reference_re = Regex::new(r"\[@([^\]\s]+)\]").unwrap();
+    let mut result = String::with_capacity(markdown.len());
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        if line.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block {
+            result.push_str(line);
+        } else {
+            result.push_str(&reference_re.replace_all(line, |caps: &Captures| match references.get(&caps[1]) {
+                Some(caption) => format!("[{}](#{})", caption, &caps[1]),
+                None => caps[0].to_string(),
+            }));
+        }
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> HashMap<String, String> {
+        i18n::environment_labels("en").iter().map(|(n, l)| (n.to_string(), l.to_string())).collect()
+    }
+
+    #[test]
+    fn test_number_environments_by_chapter() {
+        let markdown = "## 1 Basics\n:::theorem\nA.\n:::\n## 2 Geometry\n:::definition\nB.\n:::\n:::theorem Pythagoras {#thm:pythagoras}\nC.\n:::\nSee [@thm:pythagoras] and [@missing].";
+        let result = number_environments(markdown, &labels(), true, SolutionMode::Inline);
+
+        assert!(result.contains("<div class=\"environment theorem\">\n<p class=\"environment-title\"><strong>Theorem 1.1</strong></p>\n\nA.\n\n</div>"));
+        assert!(result.contains("<strong>Definition 2.1</strong>"));
+        assert!(result.contains("<div class=\"environment theorem\" id=\"thm:pythagoras\">\n<p class=\"environment-title\"><strong>Theorem 2.1</strong> (Pythagoras)</p>"));
+        assert!(result.contains("See [Theorem 2.1](#thm:pythagoras) and [@missing]."));
+    }
+
+    #[test]
+    fn test_number_environments_leaves_other_blocks() {
+        let markdown = ":::example\n:::note\nKept.\n:::\n::: {.aside}\nAlso kept.\n:::\n:::\n```\n:::theorem\n```\n:::example\n:::";
+        let result = number_environments(markdown, &labels(), false, SolutionMode::Inline);
+
+        assert!(result.contains("<strong>Example 1</strong>"));
+        assert!(result.contains(":::note\nKept.\n:::\n::: {.aside}\nAlso kept.\n:::\n\n</div>"));
+        assert!(result.contains("```\n:::theorem\n```"));
+        assert!(result.contains("<strong>Example 2</strong>"));
+    }
+
+    #[test]
+    fn test_solutions() {
+        let markdown = ":::exercise\nAdd 1 and 1.\n:::\n:::solution\n2\n:::\n:::exercise Hard {#ex:hard}\nProve it.\n:::\n:::solution\nEasy.\n:::\nThe end.";
+
+        let inline = number_environments(markdown, &labels(), false, SolutionMode::Inline);
+        assert!(inline.contains("<div class=\"environment exercise\" id=\"exercise-1\">"));
+        assert!(inline.contains("<div class=\"environment solution\" id=\"solution-1\">\n<p class=\"environment-title\"><strong><a href=\"#exercise-1\">Solution 1</a></strong></p>\n\n2\n\n</div>\n\n<div class=\"environment exercise\" id=\"ex:hard\">"));
+        assert!(inline.contains("<a href=\"#ex:hard\">Solution 2</a>"));
+
+        let appendix = number_environments(markdown, &labels(), false, SolutionMode::Appendix);
+        let end = appendix.find("The end.").unwrap();
+        let section = appendix.find("<section class=\"chapter solutions unnumbered\">\n\n## Solutions").unwrap();
+        assert!(end < section);
+        assert!(appendix.find("id=\"solution-1\"").unwrap() > section);
+        assert!(appendix.find("id=\"solution-2\"").unwrap() > section);
+
+        let omitted = number_environments(markdown, &labels(), false, SolutionMode::Omit);
+        assert!(!omitted.contains("solution-"));
+        assert!(!omitted.contains("Easy."));
+        assert!(omitted.contains("Prove it.\n\n</div>\n\nThe end."));
+    }
+}
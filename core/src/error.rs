@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+/// Errors of the conversion. The command line maps them onto its own errors, which carry the
+/// codes and hints shown to the user.
+#[derive(Debug, Error)]
+pub enum CoreError {
+    #[error("Error while building the document: {0}")]
+    BuildError(String),
+
+    #[error("Syntax theme '{0}' not found.")]
+    ThemeNotFound(String),
+
+    #[error("Circular include: {}", .0.join(" -> "))]
+    IncludeCycle(Vec<String>),
+
+    #[error("The document exceeds 'limits.{0}' ({1}).")]
+    LimitExceeded(&'static str, usize),
+
+    #[error("{0} unresolved CriticMarkup change(s), the first being '{1}'.")]
+    UnresolvedChanges(usize, String),
+
+    #[error("I/O Error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// An error of the `Host` of an expansion, e.g. a source file that could not be read.
+    #[error(transparent)]
+    Host(Box<dyn std::error::Error + Send + Sync>),
+}
@@ -0,0 +1,141 @@
+/// Title of the generated table of contents, in the document language.
+/// Unsupported languages fall back to English.
+pub fn toc_title(language: &str) -> &'static str {
+    match primary_language(language).as_str() {
+        "fr" => "Table des matières",
+        "de" => "Inhaltsverzeichnis",
+        "es" => "Índice",
+        "it" => "Indice",
+        "pt" => "Sumário",
+        "nl" => "Inhoudsopgave",
+        _ => "Table of Contents",
+    }
+}
+
+/// Caption of the parts of tables and code blocks continued on a new page.
+pub fn continued_label(language: &str) -> &'static str {
+    match primary_language(language).as_str() {
+        "fr" => "(suite)",
+        "de" => "(Fortsetzung)",
+        "es" => "(continuación)",
+        "it" => "(continua)",
+        "pt" => "(continuação)",
+        "nl" => "(vervolg)",
+        _ => "(continued)",
+    }
+}
+
+/// Title of the list of notes appended by `build --draft`.
+pub fn draft_todos_title(language: &str) -> &'static str {
+    match primary_language(language).as_str() {
+        "fr" => "TODO non résolus",
+        "de" => "Offene TODOs",
+        "es" => "TODO pendientes",
+        "it" => "TODO da risolvere",
+        "pt" => "TODO pendentes",
+        "nl" => "Openstaande TODO's",
+        _ => "Unresolved TODOs",
+    }
+}
+
+/// Title of the table of authorities generated at `!authorities`.
+pub fn authorities_title(language: &str) -> &'static str {
+    match primary_language(language).as_str() {
+        "fr" => "Table des autorités",
+        "de" => "Verzeichnis der Rechtsquellen",
+        "es" => "Índice de autoridades",
+        "it" => "Indice delle fonti",
+        "pt" => "Índice de autoridades",
+        "nl" => "Lijst van aangehaalde bronnen",
+        _ => "Table of Authorities",
+    }
+}
+
+/// Title of the list of acronyms generated at `!acronyms`.
+pub fn acronyms_title(language: &str) -> &'static str {
+    match primary_language(language).as_str() {
+        "fr" => "Acronymes",
+        "de" => "Abkürzungsverzeichnis",
+        "es" => "Siglas",
+        "it" => "Acronimi",
+        "pt" => "Siglas",
+        "nl" => "Afkortingen",
+        _ => "Acronyms",
+    }
+}
+
+/// Month names in the document language, January first.
+pub fn month_names(language: &str) -> [&'static str; 12] {
+    match primary_language(language).as_str() {
+        "fr" => ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+        "de" => ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+        "es" => ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+        "it" => ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+        "pt" => ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+        "nl" => ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus", "september", "oktober", "november", "december"],
+        _ => ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+    }
+}
+
+/// Weekday names in the document language, Monday first.
+pub fn weekday_names(language: &str) -> [&'static str; 7] {
+    match primary_language(language).as_str() {
+        "fr" => ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+        "de" => ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"],
+        "es" => ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+        "it" => ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"],
+        "pt" => ["segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado", "domingo"],
+        "nl" => ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"],
+        _ => ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+    }
+}
+
+/// Format of `{{ today }}` in the document language, e.g. `%B %-d, %Y` for October 16, 2026.
+pub fn long_date_format(language: &str) -> &'static str {
+    match primary_language(language).as_str() {
+        "fr" | "it" | "nl" => "%-d %B %Y",
+        "de" => "%-d. %B %Y",
+        "es" | "pt" => "%-d de %B de %Y",
+        _ => "%B %-d, %Y",
+    }
+}
+
+/// Built-in numbered environments (`:::theorem`...) and their captions in the document language.
+/// `solution` blocks are numbered after their exercise; `solutions` titles the answers appendix.
+pub fn environment_labels(language: &str) -> &'static [(&'static str, &'static str)] {
+    match primary_language(language).as_str() {
+        "fr" => &[
+            ("theorem", "Théorème"), ("lemma", "Lemme"), ("corollary", "Corollaire"), ("proposition", "Proposition"),
+            ("definition", "Définition"), ("example", "Exemple"), ("exercise", "Exercice"), ("remark", "Remarque"),
+            ("solution", "Solution"), ("solutions", "Solutions"),
+        ],
+        "de" => &[
+            ("theorem", "Satz"), ("lemma", "Lemma"), ("corollary", "Korollar"), ("proposition", "Proposition"),
+            ("definition", "Definition"), ("example", "Beispiel"), ("exercise", "Aufgabe"), ("remark", "Bemerkung"),
+            ("solution", "Lösung"), ("solutions", "Lösungen"),
+        ],
+        _ => &[
+            ("theorem", "Theorem"), ("lemma", "Lemma"), ("corollary", "Corollary"), ("proposition", "Proposition"),
+            ("definition", "Definition"), ("example", "Example"), ("exercise", "Exercise"), ("remark", "Remark"),
+            ("solution", "Solution"), ("solutions", "Solutions"),
+        ],
+    }
+}
+
+/// `fr-CA` and `fr_CA` both map to `fr`.
+pub fn primary_language(language: &str) -> String {
+    language.split(['-', '_']).next().unwrap_or(language).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toc_title() {
+        assert_eq!(toc_title("fr-CA"), "Table des matières");
+        assert_eq!(toc_title("de"), "Inhaltsverzeichnis");
+        assert_eq!(toc_title("ja"), "Table of Contents");
+        assert_eq!(continued_label("fr"), "(suite)");
+    }
+}
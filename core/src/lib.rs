@@ -0,0 +1,39 @@
+//! Markdown to HTML conversion of PdfBuilder: the expansion of includes and directives, the
+//! text passes, code highlighting, the table of contents and the page assembly. Files and
+//! downloads are reached through a `preprocess::Host` and nothing is printed: warnings are
+//! returned with the results. It builds for `wasm32-unknown-unknown`, so an editor in the
+//! browser shows the HTML the command line would print to PDF.
+
+pub mod acronyms;
+pub mod attributes;
+pub mod authorities;
+pub mod barcode;
+pub mod chapter;
+pub mod config;
+pub mod continued;
+pub mod critic;
+pub mod css_lint;
+pub mod details;
+pub mod directives;
+pub mod divs;
+pub mod draft;
+pub mod environments;
+pub mod error;
+pub mod i18n;
+pub mod numbering;
+pub mod openapi;
+pub mod preprocess;
+pub mod qr;
+pub mod render;
+pub mod scoped_css;
+pub mod sidebar;
+pub mod spans;
+pub mod tabs;
+pub mod terminal;
+pub mod typography;
+pub mod ui;
+pub mod variables;
+pub mod warnings;
+
+pub use config::RenderConfig;
+pub use error::CoreError;
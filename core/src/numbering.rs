@@ -0,0 +1,97 @@
+use crate::chapter;
+
+/// Heading levels that receive a number: `## 1`, `### 1.1`, `#### 1.1.1`.
+/// Level-1 headings are the document (or part) titles and stay unnumbered.
+const FIRST_NUMBERED_LEVEL: usize = 2;
+const LAST_NUMBERED_LEVEL: usize = 4;
+
+/// Prefixes headings of the expanded Markdown with hierarchical numbers, skipping
+/// code blocks, chapters wrapped in an `unnumbered` section and `{.unnumbered}` headings. Numbering restarts
+/// after every level-1 heading, i.e. for every part of a book.
+pub fn number_headings(markdown: &str) -> String {
+    let mut result = String::with_capacity(markdown.len());
+    let mut counters = [0usize; LAST_NUMBERED_LEVEL - FIRST_NUMBERED_LEVEL + 1];
+    let mut in_code_block = false;
+    // One entry per open chapter section: whether its content is unnumbered.
+    let mut sections: Vec<bool> = Vec::new();
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+
+        if !in_code_block {
+            if trimmed.starts_with("<section") {
+                let classes = trimmed.strip_prefix("<section class=\"").and_then(|rest| rest.split('"').next()).unwrap_or_default();
+                let unnumbered = classes.split_whitespace().any(|c| c == "chapter") && classes.split_whitespace().any(|c| c == "unnumbered");
+                sections.push(unnumbered || sections.last().copied().unwrap_or(false));
+            } else if trimmed == "</section>" {
+                sections.pop();
+            }
+        }
+
+        let hashes = line.chars().take_while(|c| *c == '#').count();
+        let is_heading = hashes > 0 && line[hashes..].starts_with(' ');
+        let unnumbered = sections.last().copied().unwrap_or(false);
+
+        // A level-1 heading starts a new part: numbering restarts at 1.
+        if !in_code_block && is_heading && hashes < FIRST_NUMBERED_LEVEL {
+            counters.iter_mut().for_each(|c| *c = 0);
+        }
+
+        if !in_code_block && is_heading && !unnumbered && !chapter::is_unnumbered_heading(line) && (FIRST_NUMBERED_LEVEL..=LAST_NUMBERED_LEVEL).contains(&hashes) {
+            let index = hashes - FIRST_NUMBERED_LEVEL;
+            counters[index] += 1;
+            counters[index + 1..].iter_mut().for_each(|c| *c = 0);
+            let number = counters[..=index].iter().map(|c| c.to_string()).collect::<Vec<_>>().join(".");
+            result.push_str(&format!("{} {} {}", &line[..hashes], number, line[hashes..].trim_start()));
+        } else {
+            result.push_str(line);
+        }
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_headings_hierarchy() {
+        let result = number_headings("# Book\n## Intro\n### Goals\n## Usage\n### Install\n#### Linux");
+        assert_eq!(result, "# Book\n## 1 Intro\n### 1.1 Goals\n## 2 Usage\n### 2.1 Install\n#### 2.1.1 Linux\n");
+    }
+
+    #[test]
+    fn test_number_headings_skips_unnumbered_sections_and_code() {
+        let markdown = "## One\n```\n## not a heading\n```\n<section class=\"chapter appendix unnumbered\">\n\n## Appendix\n\n</section>\n## Two";
+        let result = number_headings(markdown);
+        assert!(result.contains("## 1 One"));
+        assert!(result.contains("## not a heading"));
+        assert!(result.contains("## Appendix"));
+        assert!(result.contains("## 2 Two"));
+    }
+
+    #[test]
+    fn test_number_headings_skips_unnumbered_headings() {
+        let result = number_headings("## Preface {#preface .unnumbered}
+## Intro
+## Thanks {-}
+## Usage {.notoc}");
+        assert_eq!(result, "## Preface {#preface .unnumbered}
+## 1 Intro
+## Thanks {-}
+## 2 Usage {.notoc}
+");
+    }
+
+    #[test]
+    fn test_number_headings_restarts_for_each_part() {
+        let markdown = "<section class=\"part-divider\">\n\n# Part I\n\n</section>\n## A\n## B\n# Part II\n## C";
+        let result = number_headings(markdown);
+        assert!(result.contains("## 2 B"));
+        assert!(result.contains("## 1 C"));
+    }
+}
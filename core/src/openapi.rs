@@ -0,0 +1,11 @@
+//! Styles of the `!openapi` reference, added to the pages using it. The directive reads the
+//! spec from the project, so it is expanded by the command line.
+
+/// Styles of the operation and schema sections.
+pub const OPENAPI_CSS: &str = r#"/* OpenAPI reference */
+.openapi-operation, .openapi-schema { margin: 1.5em 0; break-inside: avoid-page; }
+.openapi-operation { border-left: 4px solid #4a90d9; padding-left: 1em; }
+.openapi-operation.deprecated { border-left-color: #999; }
+.openapi-operation.deprecated h1, .openapi-operation.deprecated h2, .openapi-operation.deprecated h3,
+.openapi-operation.deprecated h4, .openapi-operation.deprecated h5, .openapi-operation.deprecated h6 { text-decoration: line-through; }
+.openapi-schema { border-left: 4px solid #ccc; padding-left: 1em; }"#;
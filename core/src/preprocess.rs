@@ -0,0 +1,353 @@
+//! Expansion of the includes and directives of the sources into one Markdown document. Files,
+//! downloads and the directives reading them are left to a `Host`, so the same expansion runs
+//! on the files of a project or on files held in memory.
+
+use crate::acronyms;
+use crate::barcode;
+use crate::chapter;
+use crate::config::LimitsConfig;
+use crate::directives::{self, Directive, LineScanner, SourceLine};
+use crate::error::CoreError;
+use crate::qr;
+use crate::render;
+use crate::scoped_css;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// What an expansion reads through its caller.
+pub trait Host {
+    /// The canonical path of a source file, which include cycles are detected on. Fails when
+    /// the file is outside `project_root`.
+    fn resolve(&mut self, project_root: &Path, file_path: &Path) -> Result<PathBuf, CoreError>;
+
+    /// The Markdown of a source file.
+    fn read_source(&mut self, file_path: &Path) -> Result<String, CoreError>;
+
+    /// A stylesheet named by a source file, relative to the working directory like it.
+    fn read_stylesheet(&mut self, project_root: &Path, path: &Path) -> Result<String, CoreError>;
+
+    /// Reports the local image `image` of `file_path`, at `line`, when it cannot be found.
+    fn check_image(&mut self, project_root: &Path, file_path: &Path, line: usize, image: &str) -> Result<(), CoreError>;
+
+    /// The content of `!include(<url>)`.
+    fn include_url(&mut self, url: &str) -> Result<String, CoreError>;
+
+    /// The text replacing a directive the expansion does not know (`!openapi`, `!screenshot`...),
+    /// or `None` when the host does not know it either.
+    fn expand_directive(&mut self, project_root: &Path, file_path: &Path, directive: &Directive) -> Option<Result<String, CoreError>>;
+}
+
+/// State of an include expansion.
+#[derive(Debug, Default)]
+pub struct Expansion {
+    /// The files being expanded, from the outermost one, to report include cycles: their
+    /// canonical path, which cycles are detected on, and the path they were included with.
+    stack: Vec<(PathBuf, PathBuf)>,
+    limits: LimitsConfig,
+    /// Images found so far, for `limits.max_images`.
+    images: usize,
+    /// Files with scoped styles so far, to name their scopes.
+    scopes: usize,
+}
+
+impl Expansion {
+    pub fn new(limits: LimitsConfig) -> Self {
+        Self { limits, ..Default::default() }
+    }
+
+    /// Fails once `content` is larger than `limits.max_expanded_size`.
+    pub fn check_size(&self, content: &str) -> Result<(), CoreError> {
+        if content.len() > self.limits.max_expanded_size {
+            return Err(CoreError::LimitExceeded("max_expanded_size", self.limits.max_expanded_size));
+        }
+        Ok(())
+    }
+}
+
+/// Expands the includes and directives of a source file, which must be in the project.
+/// Included files are wrapped in a `<section class="chapter" data-src="...">` element.
+pub fn preprocess_markdown(project_root: &Path, file_path: &Path, expansion: &mut Expansion, host: &mut impl Host) -> Result<String, CoreError> {
+    let wrapper = (!expansion.stack.is_empty()).then_some("");
+    preprocess_file(project_root, file_path, expansion, host, wrapper)
+}
+
+/// Expands a file of a book (chapter, front or back matter...) wrapped in a chapter section
+/// with the extra `classes`.
+pub fn preprocess_chapter(project_root: &Path, file_path: &Path, expansion: &mut Expansion, host: &mut impl Host, classes: &str) -> Result<String, CoreError> {
+    preprocess_file(project_root, file_path, expansion, host, Some(classes))
+}
+
+/// `wrapper` holds the extra classes of the chapter section, when the file is always wrapped;
+/// files with front matter or scoped styles are wrapped anyway.
+fn preprocess_file(project_root: &Path, file_path: &Path, expansion: &mut Expansion, host: &mut impl Host, wrapper: Option<&str>) -> Result<String, CoreError> {
+    // --- Security: Path Traversal Check ---
+    let resolved = host.resolve(project_root, file_path)?;
+    if let Some(start) = expansion.stack.iter().position(|(canonical, _)| *canonical == resolved) {
+        let cycle = expansion.stack[start..].iter().map(|(_, file)| file.as_path()).chain([file_path]);
+        return Err(CoreError::IncludeCycle(cycle.map(|file| file.display().to_string()).collect()));
+    }
+    if expansion.stack.len() >= expansion.limits.max_include_depth {
+        return Err(CoreError::LimitExceeded("max_include_depth", expansion.limits.max_include_depth));
+    }
+    expansion.stack.push((resolved, file_path.to_path_buf()));
+
+    let content = host.read_source(file_path)?;
+
+    let (meta, body) = chapter::split_front_matter(&content, &file_path.display().to_string())?;
+    expansion.images += check_images(project_root, file_path, content[..content.len() - body.len()].matches('\n').count() + 1, body, host)?;
+    if expansion.images > expansion.limits.max_images {
+        return Err(CoreError::LimitExceeded("max_images", expansion.limits.max_images));
+    }
+    let body = match meta.as_ref().and_then(|m| m.title.as_deref()) {
+        Some(title) => chapter::override_title(body, title),
+        None => body.to_string(),
+    };
+
+    let mut full_content = String::new();
+    let mut scanner = LineScanner::default();
+
+    // Styles of the front matter `css` and of the `!style(...)` lines apply to this file only.
+    let base_path = file_path.parent().unwrap_or_else(|| Path::new(""));
+    let stylesheets: Vec<PathBuf> = meta.as_ref().and_then(|m| m.css.clone()).into_iter()
+        .chain(style_directives(&body))
+        .map(|css| base_path.join(css))
+        .collect();
+    let scope = (!stylesheets.is_empty()).then(|| {
+        expansion.scopes += 1;
+        format!("scope-{}", expansion.scopes)
+    });
+
+    let wrapped = wrapper.is_some() || meta.is_some() || scope.is_some();
+    if wrapped {
+        let extra_classes: Vec<&str> = wrapper.unwrap_or_default().split_whitespace().chain(scope.as_deref()).collect();
+        let source = path_clean::clean(file_path.strip_prefix(project_root).unwrap_or(file_path)).to_string_lossy().replace('\\', "/");
+        let open_tag = chapter::open_tag(meta.as_ref(), &extra_classes, &source);
+        // The styles go on the line of the wrapper, so every source line keeps one expanded line.
+        full_content.push_str(&open_tag);
+        if let Some(scope) = &scope {
+            full_content.push_str("<style>");
+            for stylesheet in &stylesheets {
+                let css = host.read_stylesheet(project_root, stylesheet)?;
+                full_content.push_str(&scoped_css::scope_css(&css, &format!(".{scope}")).replace(['\r', '\n'], " "));
+            }
+            full_content.push_str("</style>");
+        }
+        full_content.push_str("\n\n");
+    }
+
+    for line in body.lines() {
+        let kind = scanner.scan(line);
+        if kind == SourceLine::RawMarker {
+            // Dropped, keeping one expanded line per source line.
+            full_content.push('\n');
+        } else if kind == SourceLine::Directive {
+            if let Some(line) = directives::unescape(line) {
+                full_content.push_str(&line);
+                full_content.push('\n');
+            } else if let Some(directive) = directives::parse(line)? {
+                full_content.push_str(&expand_directive(project_root, file_path, &directive, expansion, host)?);
+                full_content.push('\n');
+                expansion.check_size(&full_content)?;
+            } else {
+                full_content.push_str(line);
+                full_content.push('\n');
+            }
+        } else {
+            full_content.push_str(line);
+            full_content.push('\n');
+        }
+    }
+
+    if wrapped {
+        full_content.push_str(chapter::CLOSE_TAG);
+    }
+    expansion.stack.pop();
+    Ok(full_content)
+}
+
+/// The text replacing a directive line of `file_path`, without its final line break.
+fn expand_directive(project_root: &Path, file_path: &Path, directive: &Directive, expansion: &mut Expansion, host: &mut impl Host) -> Result<String, CoreError> {
+    let no_arguments = || directive.expect_positional(0);
+    match directive.name {
+        "include" => {
+            directive.expect_positional(1)?;
+            let file = directive.value("file", 0)
+                .ok_or_else(|| CoreError::BuildError("!include needs a file, e.g. !include(chapters/intro.md)".to_string()))?;
+            let shift = directive.option("shift").or_else(|| directive.option("shift-headings"))
+                .map(|shift| shift.parse::<i32>().map_err(|_| CoreError::BuildError(format!("Invalid !include shift '{shift}', expected a number of levels"))))
+                .transpose()?;
+            let content = if is_remote(file) {
+                remote_include(file, host)?
+            } else {
+                let base_path = file_path.parent().unwrap_or_else(|| Path::new(""));
+                preprocess_markdown(project_root, &base_path.join(file), expansion, host)?
+            };
+            Ok(match shift {
+                Some(shift) => chapter::shift_headings(&content, shift),
+                None => content,
+            })
+        }
+        // Read with the front matter, before the content.
+        "style" => Ok(String::new()),
+        "newpage" => no_arguments().map(|_| "<div class=\"page-break\"></div>".to_string()),
+        "qrcode" => qr::qrcode_directive(directive),
+        "barcode" => barcode::barcode_directive(directive),
+        "toc" => {
+            no_arguments()?;
+            let depth = directive.option("depth")
+                .map(|depth| depth.parse().ok().filter(|depth| (1..=6).contains(depth))
+                    .ok_or_else(|| CoreError::BuildError(format!("Invalid !toc depth '{depth}', expected 1 to 6"))))
+                .transpose()?;
+            Ok(render::toc_placeholder(depth, directive.option("title")))
+        }
+        "authorities" => no_arguments().map(|_| "<!--AUTHORITIES_PLACEHOLDER-->".to_string()),
+        "acronyms" => no_arguments().map(|_| acronyms::ACRONYMS_PLACEHOLDER.to_string()),
+        name => host.expand_directive(project_root, file_path, directive)
+            .unwrap_or_else(|| Err(CoreError::BuildError(format!("Unknown directive !{name}")))),
+    }
+}
+
+/// Whether an `!include(...)` target is a URL rather than a file of the project.
+pub fn is_remote(target: &str) -> bool {
+    target.starts_with("https://") || target.starts_with("http://")
+}
+
+/// A downloaded include, wrapped like included files. Its directives are left as text: a
+/// remote file may not include local files.
+fn remote_include(url: &str, host: &mut impl Host) -> Result<String, CoreError> {
+    let content = host.include_url(url)?;
+    let mut full_content = chapter::open_tag(None, &[], url);
+    full_content.push_str("\n\n");
+    for line in content.lines() {
+        full_content.push_str(line);
+        full_content.push('\n');
+    }
+    full_content.push_str(chapter::CLOSE_TAG);
+    Ok(full_content)
+}
+
+/// Stylesheets of the `!style(...)` lines of a file, outside code and raw blocks.
+pub fn style_directives(body: &str) -> Vec<String> {
+    let mut scanner = LineScanner::default();
+    let mut stylesheets = Vec::new();
+    for line in body.lines() {
+        let kind = scanner.scan(line);
+        let directive = if kind == SourceLine::Directive { directives::parse(line).ok().flatten() } else { None };
+        let directive = directive.filter(|directive| directive.name == "style");
+        if let Some(stylesheet) = directive.as_ref().and_then(|directive| directive.value("file", 0)) {
+            stylesheets.push(stylesheet.to_string());
+        }
+    }
+    stylesheets
+}
+
+/// Has the host check the local images (`![alt](path)`) outside code blocks. `first_line` is
+/// the line number of `body` in the file. Returns the number of images.
+fn check_images(project_root: &Path, file_path: &Path, first_line: usize, body: &str, host: &mut impl Host) -> Result<usize, CoreError> {
+    let image_re = Regex::new(r"!\[[^\]]*\]\(\s*<?([^)\s>]+)").unwrap();
+    let mut in_code_block = false;
+    let mut count = 0;
+    for (index, line) in body.lines().enumerate() {
+        if line.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block {
+            continue;
+        }
+        for caps in image_re.captures_iter(line) {
+            count += 1;
+            let image = &caps[1];
+            if image.contains("://") || image.starts_with("data:") || image.starts_with('#') || image.contains("{{") {
+                continue;
+            }
+            host.check_image(project_root, file_path, first_line + index, image)?;
+        }
+    }
+    Ok(count)
+}
+
+/// Source files held in memory, for an editor that has no project on disk: paths are looked
+/// up as given, after removing `.` and `..` segments. Neither URLs nor the directives reading
+/// other files (`!openapi`, `!html`...) can be expanded, and images are not checked.
+#[derive(Debug, Default)]
+pub struct MemoryFiles {
+    pub files: HashMap<PathBuf, String>,
+}
+
+impl MemoryFiles {
+    fn get(&self, path: &Path) -> Result<&String, CoreError> {
+        self.files.get(&path_clean::clean(path)).ok_or_else(|| CoreError::BuildError(format!("File not found: {}", path.display())))
+    }
+}
+
+impl Host for MemoryFiles {
+    fn resolve(&mut self, project_root: &Path, file_path: &Path) -> Result<PathBuf, CoreError> {
+        let (root, path) = (path_clean::clean(project_root), path_clean::clean(file_path));
+        let inside = if root == Path::new(".") { !path.starts_with("..") } else { path.starts_with(&root) };
+        if !inside {
+            return Err(CoreError::BuildError(format!("Unauthorized file access attempt: {}", file_path.display())));
+        }
+        self.get(&path)?;
+        Ok(path)
+    }
+
+    fn read_source(&mut self, file_path: &Path) -> Result<String, CoreError> {
+        self.get(file_path).cloned()
+    }
+
+    fn read_stylesheet(&mut self, project_root: &Path, path: &Path) -> Result<String, CoreError> {
+        let path = self.resolve(project_root, path)?;
+        self.get(&path).cloned()
+    }
+
+    fn check_image(&mut self, _project_root: &Path, _file_path: &Path, _line: usize, _image: &str) -> Result<(), CoreError> {
+        Ok(())
+    }
+
+    fn include_url(&mut self, url: &str) -> Result<String, CoreError> {
+        Err(CoreError::BuildError(format!("Cannot include '{url}' in a preview")))
+    }
+
+    fn expand_directive(&mut self, _project_root: &Path, _file_path: &Path, _directive: &Directive) -> Option<Result<String, CoreError>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(files: &[(&str, &str)]) -> MemoryFiles {
+        MemoryFiles { files: files.iter().map(|(path, content)| (PathBuf::from(path), content.to_string())).collect() }
+    }
+
+    #[test]
+    fn test_preprocess_memory_files() {
+        let mut host = files(&[
+            ("book/main.md", "# Guide\n!include(chapters/intro.md, shift=1)\n```\n!newpage\n```\n!newpage"),
+            ("book/chapters/intro.md", "---\ncss: intro.css\n---\n# Intro\n![logo](logo.png)"),
+            ("book/chapters/intro.css", "p { color: red; }"),
+        ]);
+        let result = preprocess_markdown(Path::new("book"), Path::new("book/main.md"), &mut Expansion::default(), &mut host).unwrap();
+        assert!(result.starts_with("# Guide\n<section class=\"chapter scope-1\" data-src=\"chapters/intro.md\"><style>.scope-1 p { color: red; }</style>\n\n## Intro\n"));
+        assert!(result.contains("```\n!newpage\n```\n<div class=\"page-break\"></div>\n"));
+    }
+
+    #[test]
+    fn test_preprocess_memory_files_errors() {
+        let mut host = files(&[
+            ("book/a.md", "!include(b.md)"),
+            ("book/b.md", "!include(a.md)"),
+            ("book/c.md", "!include(../secret.md)"),
+            ("book/d.md", "!include(https://example.com/a.md)\n"),
+            ("book/e.md", "!openapi(spec.yaml)"),
+            ("secret.md", "secret"),
+        ]);
+        let mut expand = |file: &str| preprocess_markdown(Path::new("book"), &Path::new("book").join(file), &mut Expansion::default(), &mut host);
+        assert!(matches!(expand("a.md"), Err(CoreError::IncludeCycle(cycle)) if cycle.len() == 3));
+        assert!(matches!(expand("c.md"), Err(CoreError::BuildError(e)) if e.contains("Unauthorized")));
+        assert!(matches!(expand("d.md"), Err(CoreError::BuildError(e)) if e.contains("Cannot include")));
+        assert!(matches!(expand("e.md"), Err(CoreError::BuildError(e)) if e == "Unknown directive !openapi"));
+    }
+}
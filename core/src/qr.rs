@@ -0,0 +1,63 @@
+use crate::directives::Directive;
+use crate::render::escape_html;
+use crate::error::CoreError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use regex::Regex;
+
+const DEFAULT_SIZE: &str = "3cm";
+
+/// Returns the QR code of `!qrcode(data, size=3cm)` as an embedded SVG image. The data
+/// (usually a URL, which may contain commas) is everything before the first option.
+pub fn qrcode_directive(directive: &Directive) -> Result<String, CoreError> {
+    let data = directive.data(0).unwrap_or_default();
+    if data.is_empty() {
+        return Err(CoreError::BuildError("!qrcode needs the data to encode, e.g. !qrcode(https://example.com)".to_string()));
+    }
+
+    let size = directive.option("size").unwrap_or(DEFAULT_SIZE).to_string();
+    let length_re = Regex::new(r"^\d+(\.\d+)?(cm|mm|in|px|pt|em|%)$").unwrap();
+    if !length_re.is_match(&size) {
+        return Err(CoreError::BuildError(format!("Invalid !qrcode size '{size}', expected a CSS length such as 3cm")));
+    }
+
+    Ok(format!(
+        r#"<img class="qrcode" src="data:image/svg+xml;base64,{}" alt="{}" style="width: {size}; height: {size};">"#,
+        STANDARD.encode(qrcode_svg(&data)?),
+        escape_html(&data)
+    ))
+}
+
+fn qrcode_svg(data: &str) -> Result<String, CoreError> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| CoreError::BuildError(format!("Could not generate QR code for '{data}': {e}")))?;
+    Ok(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directives;
+
+    fn qrcode(line: &str) -> Result<String, CoreError> {
+        qrcode_directive(&directives::parse(line)?.unwrap())
+    }
+
+    #[test]
+    fn test_qrcode_directive() {
+        let html = qrcode("!qrcode(https://example.com/survey?a=1,b=2, size=4cm)").unwrap();
+        assert!(html.starts_with(r#"<img class="qrcode" src="data:image/svg+xml;base64,"#));
+        assert!(html.contains(r#"alt="https://example.com/survey?a=1,b=2""#));
+        assert!(html.contains("width: 4cm; height: 4cm;"));
+
+        let svg = String::from_utf8(STANDARD.decode(html.split("base64,").nth(1).unwrap().split('"').next().unwrap()).unwrap()).unwrap();
+        assert!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn test_qrcode_directive_errors() {
+        assert!(qrcode("!qrcode(https://example.com, size=big)").is_err());
+        assert!(qrcode("!qrcode( )").is_err());
+    }
+}
@@ -0,0 +1,950 @@
+//! Markdown to HTML without file, process or network access: the numbered environments and
+//! review marks, the Markdown conversion with code highlighting, the table of contents and the
+//! page assembly. The caller reads the theme files and renders diagrams around it, so the same
+//! functions serve an in-memory preview.
+
+use crate::authorities;
+use crate::config::{HtmlToc, OutputFormat, RenderConfig};
+use crate::critic::{self, ChangeMode, CommentMode};
+use crate::continued;
+use crate::details::{self, DetailsMode};
+use crate::divs;
+use crate::draft;
+use crate::environments;
+use crate::error::CoreError;
+use crate::i18n;
+use crate::openapi;
+use crate::sidebar;
+use crate::spans;
+use crate::tabs;
+use crate::terminal;
+use crate::typography;
+use crate::ui;
+use crate::variables;
+use crate::warnings::{self, Warning};
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd, TextMergeStream};
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{css_for_theme_with_class_style, highlighted_html_for_string, ClassStyle};
+use syntect::parsing::SyntaxSet;
+
+pub const DEFAULT_THEME_CSS: &str = r#"/* Simple Dark Theme */
+body { background-color: #1a1a1a; color: #f2f2f2; font-family: 'Georgia', 'Times New Roman', serif; line-height: 1.6; padding: 2em; max-width: 800px; margin: 0 auto; }
+h1, h2, h3 { color: #ffa500; font-family: 'Georgia', 'Times New Roman', serif; }
+h1 { font-size: 2.5em; text-align: center; margin-bottom: 1.5em; border-bottom: 3px solid #ffa500; padding-bottom: 0.5em; }
+h2 { font-size: 1.8em; margin-top: 2em; margin-bottom: 1em; }
+h3 { font-size: 1.4em; margin-top: 1.5em; margin-bottom: 0.8em; }
+code { background-color: #2a2a2a; padding: 2px 4px; border-radius: 4px; font-family: 'Monaco', 'Consolas', monospace; }
+pre { background-color: #2a2a2a; padding: 1em; border-radius: 8px; overflow-x: auto; }
+p { text-align: justify; margin-bottom: 1em; }
+
+/* Table des matières stylisée */
+.toc { 
+    background: linear-gradient(135deg, #2a2a2a 0%, #1a1a1a 100%); 
+    border: 2px solid #ffa500; 
+    border-radius: 12px; 
+    padding: 2.5em; 
+    margin: 3em 0; 
+    font-family: 'Georgia', 'Times New Roman', serif;
+    box-shadow: 0 8px 32px rgba(255, 165, 0, 0.1);
+}
+
+.toc-title { 
+    color: #ffa500; 
+    font-size: 2em; 
+    font-weight: bold; 
+    text-align: center; 
+    margin: 0 0 1.5em 0; 
+    text-transform: uppercase; 
+    letter-spacing: 2px;
+    border-bottom: 3px solid #ffa500;
+    padding-bottom: 0.5em;
+}
+
+.toc-content {
+    font-size: 1.1em;
+    line-height: 1.8;
+}
+
+.toc-entry {
+    display: flex;
+    justify-content: space-between;
+    align-items: baseline;
+    margin: 0.8em 0;
+    padding: 0.4em 0;
+    border-bottom: 1px dotted #555;
+}
+
+.toc-entry:last-child {
+    border-bottom: none;
+}
+
+.toc-entry-h1 {
+    font-weight: bold;
+    font-size: 1.2em;
+    color: #ffa500;
+    margin: 1.2em 0;
+    padding: 0.6em 0;
+    border-bottom: 2px solid #ffa500;
+}
+
+.toc-entry-h2 {
+    font-weight: 600;
+    color: #e0e0e0;
+    margin-left: 1em;
+}
+
+.toc-entry-h3 {
+    color: #c0c0c0;
+    margin-left: 2em;
+    font-style: italic;
+}
+
+.toc-entry-h4 {
+    color: #a0a0a0;
+    margin-left: 3em;
+    font-size: 0.95em;
+}
+
+.toc-entry-title {
+    flex: 1;
+    margin-right: 1em;
+}
+
+.toc-entry-dots {
+    flex-grow: 1;
+    border-bottom: 2px dotted #666;
+    margin-left: 1em;
+}
+
+.toc-entry-page {
+    margin-left: 0.5em;
+    min-width: 1.5em;
+    text-align: right;
+}
+
+@page landscape {
+    size: landscape;
+}
+
+section.landscape {
+    page: landscape;
+}
+
+.page-break { 
+    page-break-before: always !important; 
+    height: 0; 
+    overflow: hidden; 
+    line-height: 0; 
+}
+
+.part-divider {
+    page-break-before: always;
+    page-break-after: always;
+    padding-top: 30vh;
+    text-align: center;
+}
+
+.part-divider h1 {
+    border-bottom: none;
+}
+
+.environment {
+    border-left: 3px solid #ffa500;
+    padding: 0.2em 0 0.2em 1em;
+    margin: 1.5em 0;
+}
+
+.environment-title {
+    margin-bottom: 0.5em;
+}
+
+.chart {
+    margin: 1.5em 0;
+    text-align: center;
+}
+
+.chart svg {
+    background: white;
+    border-radius: 8px;
+}
+
+.diagram {
+    margin: 1.5em 0;
+    text-align: center;
+}
+
+.diagram img {
+    max-width: 100%;
+}
+
+.chart figcaption {
+    font-style: italic;
+    margin-top: 0.5em;
+}
+
+@media print {
+    body { 
+        color: black; 
+        background: white;
+        padding: 0; 
+        margin: 0; 
+        -webkit-print-color-adjust: exact; 
+        print-color-adjust: exact; 
+        max-width: none;
+    }
+    
+    .page-break { 
+        page-break-before: always !important; 
+        height: 0; 
+        overflow: hidden; 
+        line-height: 0; 
+    }
+    
+    h1 { 
+        page-break-before: always; 
+        color: #333;
+        border-bottom: 3px solid #333;
+    }
+    
+    h2, h3 { color: #333; }
+
+    .part-divider h1 {
+        page-break-before: auto;
+        border-bottom: none;
+    }
+
+    .environment {
+        border-left-color: #333;
+        page-break-inside: avoid;
+    }
+
+    .chart {
+        page-break-inside: avoid;
+    }
+    
+    p, li { 
+        orphans: 3; 
+        widows: 3; 
+    }
+    
+    pre, code { 
+        background-color: #f0f0f0; 
+        border: 1px solid #ddd; 
+        page-break-inside: avoid; 
+        color: black;
+    }
+    
+    .toc { 
+        background: white; 
+        border: 2px solid #333; 
+        box-shadow: none;
+        page-break-inside: avoid;
+    }
+    
+    .toc-title {
+        color: #333;
+        border-bottom: 3px solid #333;
+    }
+    
+    .toc-entry {
+        border-bottom: 1px dotted #333;
+    }
+    
+    .toc-entry-h1 {
+        color: #333;
+        border-bottom: 2px solid #333;
+    }
+    
+    .toc-entry-h2 {
+        color: #333;
+    }
+    
+    .toc-entry-h3 {
+        color: #666;
+    }
+    
+    .toc-entry-h4 {
+        color: #999;
+    }
+}"#;
+
+/// HTML shell of the document. Themes can replace it with `themes/<theme>/template.html`,
+/// using the `{{ language }}`, `{{ title }}`, `{{ author }}`, `{{ meta }}`, `{{ css }}`
+/// and `{{ body }}` slots as well as any other template variable.
+const DEFAULT_HTML_TEMPLATE: &str = r#"<!DOCTYPE html><html lang="{{ language }}"><head>{{ meta }}<title>{{ title }}</title><style>{{ css }}</style></head><body><main>{{ body }}</main></body></html>"#;
+
+/// Marks the document body in the page, so the site pages can reuse the rest of the template.
+pub const SITE_BODY_START: &str = "<!--SITE_BODY-->";
+pub const SITE_BODY_END: &str = "<!--/SITE_BODY-->";
+
+#[derive(Debug, Clone)]
+struct TocEntry {
+    level: u8,
+    title: String,
+    children: Vec<TocEntry>,
+}
+
+/// Theme files of the page, read by the caller. `None` falls back to the built-in ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PageStyles<'a> {
+    /// `themes/<theme>/style.css`, with `screen.css` and `print.css` under their media queries.
+    pub theme_css: Option<&'a str>,
+    /// `themes/<theme>/pdf.css`, only used for PDF builds.
+    pub pdf_css: Option<&'a str>,
+    /// The `custom_css` file.
+    pub custom_css: Option<&'a str>,
+    /// `themes/<theme>/template.html`.
+    pub template: Option<&'a str>,
+    /// Styles appended after all others.
+    pub extra_css: &'a str,
+}
+
+/// Text passes run on the expanded Markdown before its conversion: tab groups, details blocks, numbered environments,
+/// other fenced divs, inline spans, keyboard keys and UI paths, then CriticMarkup changes and comments.
+pub fn apply_text_passes(config: &RenderConfig, markdown: &str, format: OutputFormat) -> Result<String, CoreError> {
+    let markdown = tabs::render_tabs(markdown, if format.is_interactive() { config.tabs.html } else { config.tabs.pdf });
+    let markdown = details::render_details_blocks(&markdown);
+    let markdown = environments::number_environments(&markdown, &environments::environment_labels(&config.language, &config.environments), config.numbering, config.solutions);
+    let markdown = divs::render_divs(&markdown);
+    let markdown = spans::render_spans(&markdown);
+    // PDF annotations only exist in the PDF: HTML exports show the comments in the margin.
+    let comments = if format != OutputFormat::Pdf && config.comments == CommentMode::Pdf { CommentMode::Margin } else { config.comments };
+    let markdown = ui::render_ui_markup(&markdown);
+    let markdown = critic::render_changes(&markdown, config.changes)?;
+    Ok(critic::render_comments(&markdown, comments))
+}
+
+/// The HTML page of Markdown whose includes are already expanded, as an editor preview would
+/// show it, with the warnings raised. Diagram and chart blocks, rendered by the command line,
+/// stay code blocks.
+pub fn render_preview(config: &RenderConfig, markdown: &str, styles: &PageStyles, vars: &HashMap<String, String>) -> Result<(String, Vec<Warning>), CoreError> {
+    let markdown = apply_text_passes(config, markdown, OutputFormat::Html)?;
+    let mut page = Vec::new();
+    let warnings = write_page(config, markdown, styles, vars, OutputFormat::Html, &mut page)?;
+    let page = String::from_utf8(page).map_err(|e| CoreError::BuildError(e.to_string()))?;
+    Ok((page, warnings))
+}
+
+/// Renders the Markdown into the HTML page, written to `out`. Code blocks are highlighted while
+/// the Markdown is parsed, and the Markdown is dropped once parsed. The document is not
+/// streamed: the whole body is kept in memory, as the table of contents, the citations and the
+/// link checks need all of it, and the passes that change it (printed details, citations, draft
+/// notes) each build a new copy. Only the page around the body is written without being
+/// assembled in memory. `vars` are the template variables besides the title, author and
+/// language of `config`. Returns the warnings raised.
+pub fn write_page(config: &RenderConfig, markdown_content: String, styles: &PageStyles, vars: &HashMap<String, String>, format: OutputFormat, out: &mut impl std::io::Write) -> Result<Vec<Warning>, CoreError> {
+    let ss = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+    let theme = ts.themes.get(&config.syntax_theme).ok_or_else(|| CoreError::ThemeNotFound(config.syntax_theme.clone()))?;
+
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_TASKLISTS | Options::ENABLE_HEADING_ATTRIBUTES);
+    let mut body_html = String::with_capacity(markdown_content.len() * 3 / 2);
+    let mut page_warnings = {
+        let parser = Parser::new_ext(&markdown_content, options);
+        let rules = typography::rules_for(&config.typography, &config.language);
+        let events: Box<dyn Iterator<Item = Event>> = match &rules {
+            Some(rules) => Box::new(typography::apply(TextMergeStream::new(parser), rules)),
+            None => Box::new(parser),
+        };
+        let mut highlighter = CodeHighlighter { events, syntaxes: &ss, theme, error: None, warnings: Vec::new() };
+        html::push_html(&mut body_html, highlighter.by_ref());
+        if let Some(error) = highlighter.error {
+            return Err(error);
+        }
+        highlighter.warnings
+    };
+    drop(markdown_content);
+
+    // Fix relative image paths
+    let img_re = Regex::new(r#"<img src=\".\\../([^\"]+)\""#).map_err(|e| CoreError::BuildError(e.to_string()))?;
+    if let Cow::Owned(fixed) = img_re.replace_all(&body_html, r#"<img src=\"$1\""#) {
+        body_html = fixed;
+    }
+
+    if !format.is_interactive() && body_html.contains("<details") {
+        body_html = details::print_details(&body_html, config.details);
+    }
+
+    // Citations are marked before the table of contents is generated, so headings citing an
+    // authority keep the citation text.
+    let has_authorities = body_html.contains("<!--AUTHORITIES_PLACEHOLDER-->");
+    if has_authorities || body_html.contains("{cite") {
+        body_html = authorities::apply(&body_html, i18n::authorities_title(&config.language));
+    }
+
+    // Generate and insert TOC
+    let sidebar_toc = format.is_interactive() && config.html.toc == HtmlToc::Sidebar;
+    if sidebar_toc {
+        take_toc_options(&mut body_html);
+        replace_in_place(&mut body_html, TOC_PLACEHOLDER, "");
+        body_html = sidebar::with_sidebar_toc(&body_html, i18n::toc_title(&config.language));
+    } else if body_html.contains(TOC_PLACEHOLDER) {
+        let options = take_toc_options(&mut body_html);
+        let title = options.title.as_deref().map(escape_html).unwrap_or_else(|| i18n::toc_title(&config.language).to_string());
+        let mut toc_html = generate_toc_from_html(&body_html, &title);
+        if let Some(depth) = options.depth {
+            toc_html = hide_deeper_entries(&toc_html, depth);
+        }
+        replace_in_place(&mut body_html, TOC_PLACEHOLDER, &toc_html);
+    }
+
+    if config.draft {
+        body_html = draft::apply(&body_html, i18n::draft_todos_title(&config.language));
+    }
+
+    for link in unresolved_links(&body_html) {
+        warnings::push(&mut page_warnings, "Unresolved link", format!("'#{link}'"));
+    }
+
+    let syntax_theme_css = css_for_theme_with_class_style(theme, ClassStyle::Spaced).map_err(|e| CoreError::BuildError(e.to_string()))?;
+    let mut final_css = format!("{}\n{}", styles.theme_css.unwrap_or(DEFAULT_THEME_CSS), syntax_theme_css);
+    if let Some(pdf_css) = styles.pdf_css.filter(|_| format == OutputFormat::Pdf) {
+        final_css.push('\n');
+        final_css.push_str(pdf_css);
+    }
+
+    if let Some(custom_css) = styles.custom_css {
+        final_css.push_str("\n\n/* Custom CSS */\n");
+        final_css.push_str(custom_css);
+    }
+
+    if sidebar_toc {
+        final_css.push('\n');
+        final_css.push_str(sidebar::SIDEBAR_CSS);
+    }
+
+    if config.comments != CommentMode::Hide || config.changes == ChangeMode::Show {
+        final_css.push('\n');
+        final_css.push_str(critic::CRITIC_CSS);
+    }
+
+    if config.draft {
+        final_css.push('\n');
+        final_css.push_str(draft::DRAFT_CSS);
+    }
+
+    if has_authorities {
+        final_css.push('\n');
+        final_css.push_str(authorities::AUTHORITIES_CSS);
+    }
+
+    if format == OutputFormat::Pdf && config.details == DetailsMode::Collapsed {
+        final_css.push('\n');
+        final_css.push_str(details::COLLAPSED_DETAILS_CSS);
+    }
+
+    if body_html.contains("<div class=\"tabs\">") || body_html.contains("<div class=\"tab-section\">") {
+        final_css.push('\n');
+        final_css.push_str(&tabs::tabs_css());
+    }
+
+    if divs::uses_styled_classes(&body_html) {
+        final_css.push('\n');
+        final_css.push_str(divs::DIVS_CSS);
+    }
+
+    if spans::uses_styled_classes(&body_html) {
+        final_css.push('\n');
+        final_css.push_str(spans::SPANS_CSS);
+    }
+
+    if body_html.contains("<div class=\"openapi-") {
+        final_css.push('\n');
+        final_css.push_str(openapi::OPENAPI_CSS);
+    }
+
+    if ["<pre class=\"console\">", "<pre class=\"ansi\">", "<div class=\"terminal-stills\">"].iter().any(|tag| body_html.contains(tag)) {
+        final_css.push('\n');
+        final_css.push_str(terminal::TERMINAL_CSS);
+    }
+
+    if body_html.contains("<kbd class=\"keys\">") || body_html.contains("<span class=\"ui-path\">") {
+        final_css.push('\n');
+        final_css.push_str(ui::UI_CSS);
+    }
+
+    if config.continued_markers && format == OutputFormat::Pdf {
+        final_css.push('\n');
+        final_css.push_str(continued::CONTINUED_CSS);
+    }
+
+    if !styles.extra_css.is_empty() {
+        final_css.push('\n');
+        final_css.push_str(styles.extra_css);
+    }
+
+    // A site splits the body out of the page again.
+    if format == OutputFormat::Html && config.html.site {
+        body_html = format!("{SITE_BODY_START}{body_html}{SITE_BODY_END}");
+    }
+
+    // The variables are text, e.g. a title sent to the server, and the template is HTML.
+    let mut page_vars: HashMap<String, String> = vars.iter().map(|(name, value)| (name.clone(), escape_html(value))).collect();
+    for (name, value) in [("title", &config.title), ("author", &config.author), ("language", &config.language)] {
+        page_vars.insert(name.to_string(), escape_html(value));
+    }
+    page_vars.insert("meta".to_string(), head_metadata(config));
+    page_vars.insert("css".to_string(), final_css);
+    page_vars.insert("body".to_string(), body_html);
+    variables::write_template(styles.template.unwrap_or(DEFAULT_HTML_TEMPLATE), &page_vars, out)?;
+    Ok(page_warnings)
+}
+
+/// Marks where `!toc` goes in the document.
+const TOC_PLACEHOLDER: &str = "<!--TOC_PLACEHOLDER-->";
+
+/// Options of a `!toc(depth=2, title="Contents")` directive, kept in a comment before the placeholder.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+struct TocOptions {
+    depth: Option<usize>,
+    title: Option<String>,
+}
+
+/// The placeholder of a table of contents, on one line.
+pub fn toc_placeholder(depth: Option<usize>, title: Option<&str>) -> String {
+    let options = TocOptions { depth, title: title.map(str::to_string) };
+    if options == TocOptions::default() {
+        return TOC_PLACEHOLDER.to_string();
+    }
+    // `>` is escaped so the JSON cannot end the comment.
+    let json = serde_json::to_string(&options).unwrap_or_default().replace('>', "\\u003e");
+    format!("<!--TOC_OPTIONS {json}-->{TOC_PLACEHOLDER}")
+}
+
+/// Removes the `!toc` options of the HTML body and returns them.
+fn take_toc_options(html: &mut String) -> TocOptions {
+    let options_re = Regex::new(r"<!--TOC_OPTIONS (.*?)-->").unwrap();
+    let options = options_re.captures(html).and_then(|caps| serde_json::from_str(&caps[1]).ok()).unwrap_or_default();
+    if let Cow::Owned(html_without) = options_re.replace_all(html, "") {
+        *html = html_without;
+    }
+    options
+}
+
+/// Replaces every `from` of `html` without copying the rest of it.
+fn replace_in_place(html: &mut String, from: &str, to: &str) {
+    let mut start = 0;
+    while let Some(position) = html[start..].find(from) {
+        let position = start + position;
+        html.replace_range(position..position + from.len(), to);
+        start = position + to.len();
+    }
+}
+
+/// Hides the entries of the table of contents below `depth`. They are kept so that the page
+/// numbers, filled in per heading, stay in order.
+fn hide_deeper_entries(toc_html: &str, depth: usize) -> String {
+    let entry_re = Regex::new(r#"<div class="toc-entry toc-entry-h(\d)">"#).unwrap();
+    entry_re.replace_all(toc_html, |caps: &regex::Captures| match caps[1].parse::<usize>() {
+        Ok(level) if level > depth => format!("<div class=\"toc-entry toc-entry-h{level}\" style=\"display: none;\">"),
+        _ => caps[0].to_string(),
+    }).to_string()
+}
+
+fn generate_toc_from_html(html: &str, title: &str) -> String {
+    let document = Html::parse_document(html);
+    let heading_selector = Selector::parse("h1, h2, h3, h4, h5, h6").unwrap();
+    
+    let mut toc_entries = Vec::new();
+    
+    for element in document.select(&heading_selector) {
+        let tag_name = element.value().name();
+        let level = match tag_name {
+            "h1" => 1,
+            "h2" => 2,
+            "h3" => 3,
+            "h4" => 4,
+            "h5" => 5,
+            "h6" => 6,
+            _ => continue,
+        };
+        
+        let title = element.text().collect::<String>().trim().to_string();
+        if title.is_empty() || element.value().classes().any(|class| class == "notoc") {
+            continue;
+        }
+        
+        toc_entries.push(TocEntry {
+            level,
+            title,
+            children: Vec::new(),
+        });
+    }
+    
+    // Build hierarchical structure
+    let hierarchical_toc = build_toc_hierarchy(toc_entries);
+    
+    // Generate TOC HTML
+    generate_toc_html(&hierarchical_toc, title)
+}
+
+fn build_toc_hierarchy(entries: Vec<TocEntry>) -> Vec<TocEntry> {
+    let mut result = Vec::new();
+    let mut stack: Vec<TocEntry> = Vec::new();
+    
+    for entry in entries {
+        // Pop items from stack until we find a parent or the stack is empty
+        while let Some(last) = stack.last() {
+            if last.level < entry.level {
+                break;
+            }
+            if let Some(parent) = stack.pop() {
+                if let Some(grandparent) = stack.last_mut() {
+                    grandparent.children.push(parent);
+                } else {
+                    result.push(parent);
+                }
+            }
+        }
+        
+        stack.push(entry);
+    }
+    
+    // Push remaining items from stack to result
+    while let Some(entry) = stack.pop() {
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(entry);
+        } else {
+            result.push(entry);
+        }
+    }
+    
+    result
+}
+
+fn generate_toc_html(entries: &[TocEntry], title: &str) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    
+    let mut html = format!(r#"<div class="toc">
+<div class="toc-title">{}</div>
+<div class="toc-content">"#, title);
+    
+    for entry in entries {
+        generate_toc_entry_html(&mut html, entry);
+    }
+    
+    html.push_str("</div>\n</div>");
+    html
+}
+
+fn generate_toc_entry_html(html: &mut String, entry: &TocEntry) {
+    let class_name = format!("toc-entry toc-entry-h{}", entry.level);
+    
+    html.push_str(&format!(
+        r#"<div class="{}">
+    <span class="toc-entry-title">{}</span>
+    <span class="toc-entry-dots"></span>
+    <span class="toc-entry-page"></span>
+</div>"#,
+        class_name, entry.title
+    ));
+    
+    // Recursively add children
+    for child in &entry.children {
+        generate_toc_entry_html(html, child);
+    }
+}
+
+/// Fence languages meaning "no highlighting", which are not reported as unknown.
+const PLAIN_TEXT_LANGUAGES: [&str; 5] = ["text", "txt", "plain", "plaintext", "none"];
+
+/// Replaces the fenced code blocks of a Markdown event stream with their highlighted HTML.
+/// Blocks without a language are left to the HTML renderer. The first error stops the highlighting.
+struct CodeHighlighter<'a, 'b, I: Iterator<Item = Event<'a>>> {
+    events: I,
+    syntaxes: &'b SyntaxSet,
+    theme: &'b Theme,
+    error: Option<CoreError>,
+    /// Languages without a syntax.
+    warnings: Vec<Warning>,
+}
+
+impl<'a, I: Iterator<Item = Event<'a>>> Iterator for CodeHighlighter<'a, '_, I> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if self.error.is_some() {
+            return None;
+        }
+        let event = self.events.next()?;
+        let lang = match &event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => info.split(' ').next().unwrap_or_default().to_string(),
+            _ => return Some(event),
+        };
+        if lang.is_empty() {
+            return Some(event);
+        }
+
+        let mut code = String::new();
+        for event in self.events.by_ref() {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => break,
+                _ => {}
+            }
+        }
+        if terminal::CONSOLE_LANGUAGES.contains(&lang.as_str()) {
+            return Some(Event::Html(terminal::render_console(&code).into()));
+        }
+        if lang == "ansi" {
+            return Some(Event::Html(terminal::render_ansi(&code).into()));
+        }
+        let syntax = self.syntaxes.find_syntax_by_token(&lang).unwrap_or_else(|| {
+            if !PLAIN_TEXT_LANGUAGES.contains(&lang.as_str()) {
+                warnings::push(&mut self.warnings, "Unknown code language", format!("'{lang}'"));
+            }
+            self.syntaxes.find_syntax_plain_text()
+        });
+        match highlighted_html_for_string(&code, self.syntaxes, syntax, self.theme) {
+            Ok(highlighted) => Some(Event::Html(highlighted.into())),
+            Err(e) => {
+                self.error = Some(CoreError::BuildError(e.to_string()));
+                None
+            }
+        }
+    }
+}
+
+/// Fragment links (`href="#id"`) of the page without a matching `id`.
+fn unresolved_links(html: &str) -> Vec<String> {
+    let id_re = Regex::new(r#"\bid="([^"]*)""#).unwrap();
+    let link_re = Regex::new(r##"\bhref="#([^"]+)""##).unwrap();
+    let ids: HashSet<&str> = id_re.captures_iter(html).filter_map(|caps| caps.get(1)).map(|id| id.as_str()).collect();
+    let mut unresolved: Vec<String> = link_re.captures_iter(html)
+        .map(|caps| caps[1].to_string())
+        .filter(|target| !ids.contains(target.as_str()))
+        .collect();
+    unresolved.dedup();
+    unresolved
+}
+
+/// Tags for the `{{ meta }}` slot: charset, author and the optional `html:` metadata.
+fn head_metadata(config: &RenderConfig) -> String {
+    let mut meta = format!(r#"<meta charset="UTF-8"><meta name="author" content="{}">"#, escape_html(&config.author));
+    let html = &config.html;
+
+    if let Some(description) = &html.description {
+        meta.push_str(&format!(r#"<meta name="description" content="{}">"#, escape_html(description)));
+    }
+    if let Some(url) = &html.canonical_url {
+        meta.push_str(&format!(r#"<link rel="canonical" href="{}">"#, escape_html(url)));
+    }
+    if html.description.is_some() || html.canonical_url.is_some() || html.og_image.is_some() {
+        meta.push_str(&format!(r#"<meta property="og:type" content="article"><meta property="og:title" content="{}">"#, escape_html(&config.title)));
+        if let Some(description) = &html.description {
+            meta.push_str(&format!(r#"<meta property="og:description" content="{}">"#, escape_html(description)));
+        }
+        if let Some(url) = &html.canonical_url {
+            meta.push_str(&format!(r#"<meta property="og:url" content="{}">"#, escape_html(url)));
+        }
+        if let Some(image) = &html.og_image {
+            meta.push_str(&format!(r#"<meta property="og:image" content="{}"><meta name="twitter:card" content="summary_large_image">"#, escape_html(image)));
+        }
+    }
+    for snippet in &html.head_extra {
+        meta.push_str(snippet);
+    }
+    meta
+}
+
+/// Escapes text for use in HTML content and attribute values.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+  #[test]
+  fn test_build_toc_hierarchy() {
+      let entries = vec![
+          TocEntry { level: 1, title: "Main".to_string(), children: Vec::new() },
+          TocEntry { level: 2, title: "Chapter 1".to_string(), children: Vec::new() },
+          TocEntry { level: 3, title: "Section 1".to_string(), children: Vec::new() },
+          TocEntry { level: 2, title: "Chapter 2".to_string(), children: Vec::new() },
+      ];
+
+      let hierarchy = build_toc_hierarchy(entries);
+      assert_eq!(hierarchy.len(), 1);
+      assert_eq!(hierarchy[0].title, "Main");
+      assert_eq!(hierarchy[0].children.len(), 2);
+      assert_eq!(hierarchy[0].children[0].title, "Chapter 1");
+      assert_eq!(hierarchy[0].children[0].children.len(), 1);
+      assert_eq!(hierarchy[0].children[0].children[0].title, "Section 1");
+      assert_eq!(hierarchy[0].children[1].title, "Chapter 2");
+  }
+
+  #[test]
+  fn test_generate_toc_entry_html() {
+      let entry = TocEntry {
+          level: 1,
+          title: "Test Chapter".to_string(),
+          children: vec![
+              TocEntry {
+                  level: 2,
+                  title: "Sub Section".to_string(),
+                  children: Vec::new(),
+              }
+          ],
+      };
+
+      let mut html = String::new();
+      generate_toc_entry_html(&mut html, &entry);
+      
+      assert!(html.contains("Test Chapter"));
+      assert!(html.contains("Sub Section"));
+      assert!(html.contains("toc-entry-h1"));
+      assert!(html.contains("toc-entry-h2"));
+      assert!(!html.contains("5"));
+      assert!(!html.contains("6"));
+      assert!(html.contains("toc-entry-dots"));
+  }
+
+  #[test]
+  fn test_toc_options() {
+      let mut html = format!("<p>A</p>{}<h2>B</h2>", toc_placeholder(Some(1), Some("In <short>")));
+      let options = take_toc_options(&mut html);
+      assert_eq!(html, "<p>A</p><!--TOC_PLACEHOLDER--><h2>B</h2>");
+      replace_in_place(&mut html, TOC_PLACEHOLDER, "<nav>TOC</nav>");
+      assert_eq!(html, "<p>A</p><nav>TOC</nav><h2>B</h2>");
+      assert_eq!(options, TocOptions { depth: Some(1), title: Some("In <short>".to_string()) });
+      assert_eq!(toc_placeholder(None, None), TOC_PLACEHOLDER);
+
+      let toc = hide_deeper_entries("<div class=\"toc-entry toc-entry-h1\"></div><div class=\"toc-entry toc-entry-h2\"></div>", 1);
+      assert_eq!(toc, "<div class=\"toc-entry toc-entry-h1\"></div><div class=\"toc-entry toc-entry-h2\" style=\"display: none;\"></div>");
+  }
+
+  #[test]
+  fn test_generate_toc_html_empty() {
+      let entries = vec![];
+      let result = generate_toc_html(&entries, i18n::toc_title("fr"));
+      assert_eq!(result, "");
+  }
+
+  #[test]
+  fn test_code_highlighter() {
+      let ss = SyntaxSet::load_defaults_newlines();
+      let ts = ThemeSet::load_defaults();
+      let markdown = "Text\n\n```rust\nlet a = 1 < 2;\n```\n\n```\nplain <code>\n```\n";
+      let mut highlighter = CodeHighlighter { events: Parser::new(markdown), syntaxes: &ss, theme: &ts.themes["base16-ocean.dark"], error: None, warnings: Vec::new() };
+      let mut html = String::new();
+      html::push_html(&mut html, highlighter.by_ref());
+
+      assert!(highlighter.error.is_none());
+      assert!(html.starts_with("<p>Text</p>\n<pre style=\""));
+      assert!(html.contains("&lt;"));
+      assert!(!html.contains("language-rust"));
+      assert!(html.contains("<pre><code>plain &lt;code&gt;\n</code></pre>"));
+  }
+
+  #[test]
+  fn test_unresolved_links() {
+      let html = r##"<h2 id="intro">Intro</h2><a href="#intro">ok</a><a href="#missing">bad</a><a href="#missing">again</a><a href="page.html#x">other page</a>"##;
+      assert_eq!(unresolved_links(html), ["missing"]);
+  }
+
+  #[test]
+  fn test_head_metadata() {
+      let mut config: RenderConfig = serde_yaml::from_str(r#"
+title: "Guide & Tips"
+author: "Ada"
+language: "en"
+theme: "dark"
+syntax_theme: "InspiredGitHub"
+source: "main.md"
+custom_css: ""
+output:
+  filename: "guide"
+"#).unwrap();
+      assert_eq!(head_metadata(&config), r#"<meta charset="UTF-8"><meta name="author" content="Ada">"#);
+
+      config.author = "Ada \"The Countess\" Lovelace".to_string();
+      assert_eq!(head_metadata(&config), r#"<meta charset="UTF-8"><meta name="author" content="Ada &quot;The Countess&quot; Lovelace">"#);
+
+      config.html.description = Some("All about \"tips\"".to_string());
+      config.html.og_image = Some("https://example.com/cover.png".to_string());
+      config.html.head_extra = vec!["<script src=\"a.js\"></script>".to_string()];
+      let meta = head_metadata(&config);
+      assert!(meta.contains(r#"<meta name="description" content="All about &quot;tips&quot;">"#));
+      assert!(meta.contains(r#"<meta property="og:title" content="Guide &amp; Tips">"#));
+      assert!(meta.contains(r#"<meta property="og:image" content="https://example.com/cover.png">"#));
+      assert!(meta.ends_with("<script src=\"a.js\"></script>"));
+  }
+
+  #[test]
+  fn test_generate_toc_html_with_entries() {
+      let entries = vec![
+          TocEntry {
+              level: 1,
+              title: "Chapter 1".to_string(),
+              children: Vec::new(),
+          },
+          TocEntry {
+              level: 2,
+              title: "Section 1.1".to_string(),
+              children: Vec::new(),
+          }
+      ];
+
+      let result = generate_toc_html(&entries, i18n::toc_title("fr"));
+      assert!(result.contains("Table des matières"));
+      assert!(result.contains("Chapter 1"));
+      assert!(result.contains("Section 1.1"));
+      assert!(result.contains("toc-content"));
+      assert!(result.contains("toc-title"));
+  }
+
+  #[test]
+  fn test_render_preview() {
+      let config: RenderConfig = serde_yaml::from_str(r#"
+title: "Guide"
+author: "Ada"
+language: "en"
+theme: "dark"
+syntax_theme: "InspiredGitHub"
+source: "main.md"
+output:
+  filename: "guide"
+"#).unwrap();
+      let styles = PageStyles { theme_css: Some("body { color: teal; }"), extra_css: "p { margin: 0; }", ..Default::default() };
+      let (html, warnings) = render_preview(&config, "# Title\n\n<!--TOC_PLACEHOLDER-->\n\n## Install {#setup .notoc}\n\n```rust\nfn main() {}\n```\n\n```nolang\nx\n```\n\nSee {++this++} and [setup](#setup).", &styles, &HashMap::new()).unwrap();
+      assert!(html.starts_with("<!DOCTYPE html><html lang=\"en\">"));
+      assert!(html.contains("<h1>Title</h1>"));
+      assert!(html.contains("<h2 id=\"setup\" class=\"notoc\">Install</h2>"));
+      assert!(html.contains("<span class=\"toc-entry-title\">Title</span>"));
+      assert!(!html.contains("<span class=\"toc-entry-title\">Install</span>"));
+      assert!(html.contains("<pre style=\""));
+      assert!(html.contains("<ins class=\"critic\">this</ins>"));
+      assert!(html.contains("body { color: teal; }"));
+      assert!(html.contains("p { margin: 0; }"));
+      assert_eq!(warnings, [Warning { category: "Unknown code language", detail: "'nolang'".to_string() }]);
+  }
+
+  #[test]
+  fn test_render_preview_escapes_variables() {
+      let config: RenderConfig = serde_yaml::from_str(r#"
+title: "</title><script>alert(1)</script>"
+author: "Ada\" onload=\"x"
+language: "en\"><script>"
+theme: "dark"
+syntax_theme: "InspiredGitHub"
+source: "main.md"
+output:
+  filename: "guide"
+"#).unwrap();
+      let (html, _) = render_preview(&config, "Text", &PageStyles::default(), &HashMap::new()).unwrap();
+      assert!(!html.contains("<script>"));
+      assert!(html.contains("<title>&lt;/title&gt;&lt;script&gt;alert(1)&lt;/script&gt;</title>"));
+      assert!(html.contains(r#"<html lang="en&quot;&gt;&lt;script&gt;">"#));
+      assert!(html.contains(r#"content="Ada&quot; onload=&quot;x""#));
+  }
+}
@@ -0,0 +1,103 @@
+use crate::css_lint;
+
+/// At-rules whose blocks hold style rules, which are scoped like top-level ones.
+const GROUPING_RULES: [&str; 5] = ["@media", "@supports", "@layer", "@container", "@document"];
+
+enum Block {
+    /// The stylesheet itself or a grouping at-rule; `false` inside `@keyframes`, `@font-face`...
+    Group { scoped: bool },
+    /// The declarations of a rule.
+    Rule,
+}
+
+/// Restricts the style rules of `css` to the elements inside `scope` (a selector such as
+/// `.scope-1`): `table td` becomes `.scope-1 table td`, and `body` or `:root` become the scope
+/// itself. `@page`, `@font-face` and `@keyframes` rules are kept as they are.
+pub fn scope_css(css: &str, scope: &str) -> String {
+    let css = css_lint::strip_comments(css);
+    let mut result = String::with_capacity(css.len() + css.len() / 4);
+    let mut stack: Vec<Block> = Vec::new();
+    let mut start = 0;
+    let mut quote: Option<char> = None;
+
+    for (i, c) in css.char_indices() {
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        let scoped = match stack.last() {
+            None => true,
+            Some(Block::Group { scoped }) => *scoped,
+            Some(Block::Rule) => false,
+        };
+        let in_group = !matches!(stack.last(), Some(Block::Rule));
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '{' => {
+                let prelude = &css[start..i];
+                let leading = &prelude[..prelude.len() - prelude.trim_start().len()];
+                let prelude = prelude.trim();
+                result.push_str(leading);
+                if prelude.starts_with('@') {
+                    result.push_str(prelude);
+                    let grouping = GROUPING_RULES.iter().any(|rule| prelude.split_whitespace().next() == Some(rule));
+                    stack.push(Block::Group { scoped: scoped && grouping });
+                } else {
+                    result.push_str(&if scoped { scope_selectors(prelude, scope) } else { prelude.to_string() });
+                    stack.push(Block::Rule);
+                }
+                result.push_str(" {");
+                start = i + 1;
+            }
+            '}' => {
+                result.push_str(&css[start..=i]);
+                stack.pop();
+                start = i + 1;
+            }
+            // Statements such as `@import ...;` end before the next rule.
+            ';' if in_group => {
+                result.push_str(&css[start..=i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push_str(&css[start..]);
+    result
+}
+
+fn scope_selectors(selectors: &str, scope: &str) -> String {
+    let scoped: Vec<String> = selectors.split(',').map(|selector| {
+        let selector = selector.trim();
+        for root in ["html", "body", ":root"] {
+            if selector == root {
+                return scope.to_string();
+            }
+            if let Some(rest) = selector.strip_prefix(root).filter(|rest| rest.starts_with([' ', '>', '+', '~'])) {
+                return format!("{scope}{rest}");
+            }
+        }
+        format!("{scope} {selector}")
+    }).collect();
+    scoped.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_css() {
+        let css = "@import url(\"a;b.css\");\n/* tables */\ntable, body > p { border: 1px solid; }\n\
+            @media print {\n  td { content: \"}\"; }\n}\n@keyframes spin { from { opacity: 0; } to { opacity: 1; } }\n\
+            @page { margin: 1cm; }\n:root { --accent: red; }\n";
+        assert_eq!(
+            scope_css(css, ".scope-1"),
+            "@import url(\"a;b.css\");\n            \n.scope-1 table, .scope-1 > p { border: 1px solid; }\n\
+             @media print {\n  .scope-1 td { content: \"}\"; }\n}\n@keyframes spin { from { opacity: 0; } to { opacity: 1; } }\n\
+             @page { margin: 1cm; }\n.scope-1 { --accent: red; }\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use regex::{Captures, Regex};
+use std::collections::HashSet;
+
+/// Styles of the sidebar table of contents; hidden when printing.
+pub const SIDEBAR_CSS: &str = r#"/* Sidebar table of contents */
+body:has(.toc-sidebar) { padding-left: 300px; }
+.toc-sidebar { position: fixed; top: 0; left: 0; width: 260px; height: 100vh; overflow-y: auto; padding: 1.5em 1em; box-sizing: border-box; border-right: 1px solid #444; font-size: 0.9em; }
+.toc-sidebar summary { font-weight: bold; cursor: pointer; margin-bottom: 0.8em; }
+.toc-sidebar ul { list-style: none; margin: 0; padding-left: 1em; }
+.toc-sidebar > details > ul { padding-left: 0; }
+.toc-sidebar a { display: block; padding: 0.2em 0.4em; color: inherit; text-decoration: none; border-left: 2px solid transparent; }
+.toc-sidebar a.active { border-left-color: #ffa500; font-weight: bold; }
+@media (max-width: 900px) { body:has(.toc-sidebar) { padding-left: 2em; } .toc-sidebar { position: static; width: auto; height: auto; border-right: none; } }
+@media print { .toc-sidebar { display: none; } body:has(.toc-sidebar) { padding-left: 0; } }"#;
+
+/// Highlights the entry of the section being read.
+const SCROLL_SPY_SCRIPT: &str = r#"<script>
+(() => {
+  const links = new Map([...document.querySelectorAll('.toc-sidebar a')].map(a => [a.getAttribute('href').slice(1), a]));
+  const observer = new IntersectionObserver(entries => {
+    entries.filter(e => e.isIntersecting).forEach(e => {
+      links.forEach(a => a.classList.remove('active'));
+      links.get(e.target.id)?.classList.add('active');
+    });
+  }, { rootMargin: '0px 0px -70% 0px' });
+  links.forEach((_, id) => { const h = document.getElementById(id); if (h) observer.observe(h); });
+})();
+</script>"#;
+
+/// Levels listed in the sidebar.
+const MAX_LEVEL: u8 = 4;
+
+/// Gives every heading an id and prepends a collapsible navigation sidebar linking to them.
+pub fn with_sidebar_toc(body_html: &str, title: &str) -> String {
+    let (body_html, entries) = add_heading_ids(body_html);
+
+    let mut nav = format!("<nav class=\"toc-sidebar\"><details open><summary>{title}</summary>");
+    let mut depth = 0;
+    for (level, id, text) in entries.iter().filter(|(level, _, _)| *level <= MAX_LEVEL) {
+        let level = *level as usize;
+        if depth == 0 {
+            nav.push_str("<ul>");
+            depth = 1;
+        }
+        // Nested lists follow the heading levels relative to the first entry.
+        let target = level.saturating_sub(entries[0].0 as usize) + 1;
+        while depth < target {
+            nav.push_str("<ul>");
+            depth += 1;
+        }
+        while depth > target.max(1) {
+            nav.push_str("</ul>");
+            depth -= 1;
+        }
+        nav.push_str(&format!("<li><a href=\"#{id}\">{text}</a></li>"));
+    }
+    nav.push_str(&"</ul>".repeat(depth));
+    nav.push_str("</details></nav>\n");
+
+    format!("{nav}{body_html}\n{SCROLL_SPY_SCRIPT}")
+}
+
+/// Adds a slug id to headings that have none and returns them as (level, id, text), except the
+/// `notoc` ones.
+pub fn add_heading_ids(html: &str) -> (String, Vec<(u8, String, String)>) {
+    let heading_re = Regex::new(r"(?s)<h([1-6])([^>]*)>(.*?)</h[1-6]>").unwrap();
+    let id_re = Regex::new(r#"\bid="([^"]*)""#).unwrap();
+    let tag_re = Regex::new(r"<[^>]+>").unwrap();
+    let class_re = Regex::new(r#"\bclass="([^"]*)""#).unwrap();
+
+    let mut used = HashSet::new();
+    let mut entries = Vec::new();
+    let html = heading_re.replace_all(html, |caps: &Captures| {
+        let level: u8 = caps[1].parse().unwrap_or(1);
+        let text = tag_re.replace_all(&caps[3], "").trim().to_string();
+        let (attributes, id) = match id_re.captures(&caps[2]) {
+            Some(id) => (caps[2].to_string(), id[1].to_string()),
+            None => {
+                let id = unique_slug(&text, &mut used);
+                (format!(" id=\"{id}\"{}", &caps[2]), id)
+            }
+        };
+        used.insert(id.clone());
+        let notoc = class_re.captures(&caps[2]).is_some_and(|classes| classes[1].split_whitespace().any(|class| class == "notoc"));
+        if !notoc {
+            entries.push((level, id, text));
+        }
+        format!("<h{}{}>{}</h{}>", &caps[1], attributes, &caps[3], &caps[1])
+    });
+    (html.to_string(), entries)
+}
+
+pub fn unique_slug(text: &str, used: &mut HashSet<String>) -> String {
+    let base = slugify(text);
+    let base = if base.is_empty() { "section".to_string() } else { base };
+    let mut slug = base.clone();
+    let mut counter = 2;
+    while used.contains(&slug) {
+        slug = format!("{base}-{counter}");
+        counter += 1;
+    }
+    slug
+}
+
+/// Lowercase, alphanumeric words joined by dashes: "1.2 Getting Started!" becomes "1-2-getting-started".
+pub fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_heading_ids() {
+        let (html, entries) = add_heading_ids("<h1>Guide</h1><h2>Getting <em>Started</em></h2><h2 id=\"custom\">Other</h2><h2>Getting Started</h2><h3 class=\"notoc\">Aside</h3>");
+        assert_eq!(html, "<h1 id=\"guide\">Guide</h1><h2 id=\"getting-started\">Getting <em>Started</em></h2><h2 id=\"custom\">Other</h2><h2 id=\"getting-started-2\">Getting Started</h2><h3 id=\"aside\" class=\"notoc\">Aside</h3>");
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[1], (2, "getting-started".to_string(), "Getting Started".to_string()));
+    }
+
+    #[test]
+    fn test_with_sidebar_toc() {
+        let html = with_sidebar_toc("<h2>One</h2><h3>One A</h3><h2>Two</h2>", "Contents");
+        assert!(html.starts_with(
+            "<nav class=\"toc-sidebar\"><details open><summary>Contents</summary><ul><li><a href=\"#one\">One</a></li><ul><li><a href=\"#one-a\">One A</a></li></ul><li><a href=\"#two\">Two</a></li></ul></details></nav>"
+        ));
+        assert!(html.contains("<h3 id=\"one-a\">One A</h3>"));
+        assert!(html.contains("IntersectionObserver"));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::attributes::Attributes;
+use regex::{Captures, Regex};
+
+/// Styles of the built-in span classes, added when the document uses them.
+pub const SPANS_CSS: &str = r#"/* Inline spans */
+.mark { background-color: #fff3a0; color: #1a1a1a; padding: 0 0.1em; -webkit-print-color-adjust: exact; print-color-adjust: exact; }
+.smallcaps { font-variant: small-caps; }
+.underline { text-decoration: underline; }
+.nowrap { white-space: nowrap; }"#;
+
+/// Classes styled by `SPANS_CSS`.
+const STYLED_CLASSES: [&str; 4] = ["mark", "smallcaps", "underline", "nowrap"];
+
+/// Renders `[text]{#id .class key=value}` spans outside code. Brackets followed by anything
+/// else than a valid attribute block are left as they are.
+pub fn render_spans(markdown: &str) -> String {
+    let span_re = Regex::new(r"\[([^\[\]\n]+)\]\{([^{}\n]*)\}").unwrap();
+    let mut result = String::with_capacity(markdown.len());
+    let mut in_code_block = false;
+
+    for line in markdown.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block || !line.contains("]{") {
+            result.push_str(line);
+            continue;
+        }
+        // Odd segments between backticks are code spans.
+        for (i, segment) in line.split('`').enumerate() {
+            if i > 0 {
+                result.push('`');
+            }
+            if i % 2 == 1 {
+                result.push_str(segment);
+                continue;
+            }
+            result.push_str(&span_re.replace_all(segment, |caps: &Captures| {
+                // `![alt]` and `[^note]` are images and footnotes.
+                let image = segment[..caps.get(0).unwrap().start()].ends_with('!');
+                match Attributes::parse(&caps[2]).filter(|_| !image && !caps[1].starts_with('^')) {
+                    Some(attributes) => format!("<span{}>{}</span>", attributes.to_html(), &caps[1]),
+                    None => caps[0].to_string(),
+                }
+            }));
+        }
+    }
+    result
+}
+
+/// Whether the HTML body uses a class styled by `SPANS_CSS`.
+pub fn uses_styled_classes(html: &str) -> bool {
+    let class_re = Regex::new(r#"<span [^>]*\bclass="([^"]*)""#).unwrap();
+    let used = class_re.captures_iter(html).any(|caps| caps[1].split_whitespace().any(|class| STYLED_CLASSES.contains(&class)));
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_spans() {
+        let result = render_spans("A [key *point*]{.mark} and [Rome]{#rome .smallcaps lang=la}.\n`[x]{.mark}` [not]{a span} ![img]{.x}\n```\n[x]{.mark}\n```\n");
+        assert_eq!(
+            result,
+            "A <span class=\"mark\">key *point*</span> and <span id=\"rome\" class=\"smallcaps\" lang=\"la\">Rome</span>.\n\
+             `[x]{.mark}` [not]{a span} ![img]{.x}\n```\n[x]{.mark}\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_uses_styled_classes() {
+        assert!(uses_styled_classes("<span id=\"a\" class=\"smallcaps\">"));
+        assert!(!uses_styled_classes("<span class=\"ui\">"));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::render::escape_html;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Tabs supported by `TABS_CSS`; later tabs of a larger group are never shown.
+const MAX_TABS: usize = 12;
+
+/// How `:::tabs` groups are rendered.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TabsMode {
+    /// Clickable tabs, one panel visible at a time.
+    Tabs,
+    /// Every tab in turn, under its title.
+    Sections,
+    /// Only the first tab, without its title.
+    First,
+}
+
+/// Rendering of `:::tabs` groups per output format.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct TabsConfig {
+    #[serde(default = "default_html_mode")]
+    pub html: TabsMode,
+    #[serde(default = "default_pdf_mode")]
+    pub pdf: TabsMode,
+}
+
+fn default_html_mode() -> TabsMode {
+    TabsMode::Tabs
+}
+
+fn default_pdf_mode() -> TabsMode {
+    TabsMode::Sections
+}
+
+impl Default for TabsConfig {
+    fn default() -> Self {
+        TabsConfig { html: default_html_mode(), pdf: default_pdf_mode() }
+    }
+}
+
+/// Styles of the tab groups: radio buttons select the visible panel, without scripts.
+pub fn tabs_css() -> String {
+    let mut css = String::from(r#"/* Tabs */
+.tabs { display: flex; flex-wrap: wrap; margin: 1em 0; }
+.tabs > input { position: absolute; opacity: 0; }
+.tabs > label { padding: 0.4em 1em; cursor: pointer; border-bottom: 2px solid transparent; opacity: 0.7; }
+.tabs > input:checked + label { border-bottom-color: #ffa500; opacity: 1; }
+.tabs > .tab-panel { display: none; order: 1; width: 100%; padding-top: 0.5em; border-top: 1px solid #ccc; }
+.tab-title { margin-bottom: 0.3em; }
+"#);
+    for n in 1..=MAX_TABS {
+        css.push_str(&format!(".tabs > input:nth-of-type({n}):checked ~ .tab-panel:nth-of-type({n}) {{ display: block; }}\n"));
+    }
+    css
+}
+
+struct TabGroup {
+    titles: Vec<String>,
+    panels: Vec<String>,
+    in_tab: bool,
+}
+
+enum Block {
+    Group,
+    Tab,
+    Other,
+}
+
+/// Replaces `:::tabs` groups, made of `:::tab Title` blocks, according to `mode`. Text of a
+/// group outside its tabs is dropped.
+pub fn render_tabs(markdown: &str, mode: TabsMode) -> String {
+    let open_re = Regex::new(r"^:::\s*(tabs|tab)(?:\s+(.*?))?\s*$").unwrap();
+    let mut result = String::with_capacity(markdown.len());
+    let mut groups: Vec<TabGroup> = Vec::new();
+    let mut stack: Vec<Block> = Vec::new();
+    let mut group_count = 0;
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if !in_code_block && trimmed == ":::" {
+            match stack.pop() {
+                Some(Block::Tab) => {
+                    if let Some(group) = groups.last_mut() {
+                        group.in_tab = false;
+                    }
+                    continue;
+                }
+                Some(Block::Group) => {
+                    let group = groups.pop().unwrap();
+                    group_count += 1;
+                    if let Some(output) = target(&mut result, &mut groups) {
+                        output.push_str(&group_html(&group, group_count, mode));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        } else if let Some(caps) = open_re.captures(trimmed).filter(|_| !in_code_block) {
+            let title = caps.get(2).map(|title| title.as_str().to_string()).unwrap_or_default();
+            match (&caps[1], groups.last_mut()) {
+                ("tabs", _) => {
+                    groups.push(TabGroup { titles: Vec::new(), panels: Vec::new(), in_tab: false });
+                    stack.push(Block::Group);
+                    continue;
+                }
+                ("tab", Some(group)) if matches!(stack.last(), Some(Block::Group)) => {
+                    group.titles.push(title);
+                    group.panels.push(String::new());
+                    group.in_tab = true;
+                    stack.push(Block::Tab);
+                    continue;
+                }
+                _ => stack.push(Block::Other),
+            }
+        } else if !in_code_block && trimmed.starts_with(":::") {
+            stack.push(Block::Other);
+        }
+
+        if let Some(output) = target(&mut result, &mut groups) {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    // Unclosed groups end with the document.
+    while let Some(group) = groups.pop() {
+        group_count += 1;
+        if let Some(output) = target(&mut result, &mut groups) {
+            output.push_str(&group_html(&group, group_count, mode));
+        }
+    }
+    result
+}
+
+/// Where a line goes: the open tab of the innermost group, the document outside groups, or
+/// nowhere between the tabs of a group.
+fn target<'a>(result: &'a mut String, groups: &'a mut [TabGroup]) -> Option<&'a mut String> {
+    match groups.last_mut() {
+        Some(group) if group.in_tab => group.panels.last_mut(),
+        Some(_) => None,
+        None => Some(result),
+    }
+}
+
+fn group_html(group: &TabGroup, number: usize, mode: TabsMode) -> String {
+    match mode {
+        TabsMode::First => group.panels.first().cloned().unwrap_or_default(),
+        TabsMode::Sections => {
+            let mut html = String::new();
+            for (title, panel) in group.titles.iter().zip(&group.panels) {
+                html.push_str(&format!(
+                    "<div class=\"tab-section\">\n<p class=\"tab-title\"><strong>{}</strong></p>\n\n{panel}\n</div>\n",
+                    escape_html(title)
+                ));
+            }
+            html
+        }
+        TabsMode::Tabs => {
+            let mut html = String::from("<div class=\"tabs\">\n");
+            for (i, title) in group.titles.iter().enumerate() {
+                let checked = if i == 0 { " checked" } else { "" };
+                html.push_str(&format!(
+                    "<input type=\"radio\" name=\"tabs-{number}\" id=\"tabs-{number}-{}\"{checked}><label for=\"tabs-{number}-{}\">{}</label>\n",
+                    i + 1, i + 1, escape_html(title)
+                ));
+            }
+            for panel in &group.panels {
+                html.push_str(&format!("<div class=\"tab-panel\">\n\n{panel}\n</div>\n"));
+            }
+            html.push_str("</div>\n");
+            html
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKDOWN: &str = "Install:\n\n:::tabs\n:::tab Windows\nRun `setup.exe`.\n:::\n\n:::tab macOS\n```\n:::\n```\n:::\n:::\nDone.\n";
+
+    #[test]
+    fn test_render_tabs_as_sections() {
+        assert_eq!(
+            render_tabs(MARKDOWN, TabsMode::Sections),
+            "Install:\n\n<div class=\"tab-section\">\n<p class=\"tab-title\"><strong>Windows</strong></p>\n\nRun `setup.exe`.\n\n</div>\n\
+             <div class=\"tab-section\">\n<p class=\"tab-title\"><strong>macOS</strong></p>\n\n```\n:::\n```\n\n</div>\nDone.\n"
+        );
+        assert_eq!(render_tabs(MARKDOWN, TabsMode::First), "Install:\n\nRun `setup.exe`.\nDone.\n");
+    }
+
+    #[test]
+    fn test_render_tabs_as_tabs() {
+        let html = render_tabs(":::tabs\n:::tab A & B\nOne\n:::\n:::tab C\n:::note\nTwo\n:::\n:::\n:::\n", TabsMode::Tabs);
+        assert_eq!(
+            html,
+            "<div class=\"tabs\">\n<input type=\"radio\" name=\"tabs-1\" id=\"tabs-1-1\" checked><label for=\"tabs-1-1\">A &amp; B</label>\n\
+             <input type=\"radio\" name=\"tabs-1\" id=\"tabs-1-2\"><label for=\"tabs-1-2\">C</label>\n\
+             <div class=\"tab-panel\">\n\nOne\n\n</div>\n<div class=\"tab-panel\">\n\n:::note\nTwo\n:::\n\n</div>\n</div>\n"
+        );
+        assert_eq!(render_tabs(":::tab Alone\nText\n:::\n", TabsMode::Tabs), ":::tab Alone\nText\n:::\n");
+    }
+}
@@ -0,0 +1,210 @@
+use crate::render::escape_html;
+use regex::Regex;
+
+/// Styles of console blocks and terminal stills.
+pub const TERMINAL_CSS: &str = r#"/* Terminal sessions */
+pre.console, pre.terminal, pre.ansi { background: #1e1e1e; color: #e0e0e0; padding: 0.8em 1em; border-radius: 4px; white-space: pre-wrap; }
+pre.console .prompt { color: #8a8a8a; user-select: none; }
+pre.console .command { color: #ffffff; font-weight: bold; }
+pre.console .output { color: #c8c8c8; }
+.terminal-stills { display: flex; flex-direction: column; gap: 1em; margin: 1em 0; }
+.terminal-still { margin: 0; break-inside: avoid; border: 1px solid #444; border-radius: 6px; overflow: hidden; }
+.terminal-still::before { content: "\25CF  \25CF  \25CF"; display: block; padding: 0.2em 0.6em; background: #3a3a3a; color: #888; font-size: 0.7em; }
+.terminal-still pre.terminal { margin: 0; border-radius: 0; }
+.terminal-still figcaption { padding: 0.2em 0.6em; font-size: 0.8em; color: #666; text-align: right; }"#;
+
+/// Languages of fenced blocks holding a terminal session.
+pub const CONSOLE_LANGUAGES: [&str; 3] = ["console", "shell-session", "terminal"];
+
+/// The 16 basic colors (normal then bright), tuned for the dark background of the blocks.
+const ANSI_COLORS: [&str; 16] = [
+    "#3b3b3b", "#e05561", "#8cc265", "#d18f52", "#4aa5f0", "#c162de", "#42b3c2", "#d7dae0",
+    "#6b6b6b", "#ff616e", "#a5e075", "#f0a45d", "#4dc4ff", "#de73ff", "#4cd1e0", "#ffffff",
+];
+
+/// The HTML of a `console` block: prompts dimmed, commands (with their `\` continuation
+/// lines) set apart from their output.
+pub fn render_console(code: &str) -> String {
+    let prompt_re = Regex::new(r"^((?:\([\w.-]+\) )?(?:[\w.-]+@[\w.-]+(?::\S*)?\s?)?[$#%] |PS [^>]*> |>>> |\.\.\. )").unwrap();
+    let mut lines = Vec::new();
+    let mut continued = false;
+    for line in code.trim_end_matches('\n').lines() {
+        let html = match prompt_re.find(line) {
+            Some(prompt) => {
+                let command = &line[prompt.end()..];
+                continued = command.ends_with('\\');
+                format!("<span class=\"prompt\">{}</span><span class=\"command\">{}</span>", escape_html(prompt.as_str()), escape_html(command))
+            }
+            None if continued => {
+                continued = line.ends_with('\\');
+                format!("<span class=\"command\">{}</span>", escape_html(line))
+            }
+            None => format!("<span class=\"output\">{}</span>", escape_html(line)),
+        };
+        lines.push(html);
+    }
+    format!("<pre class=\"console\"><code>{}</code></pre>", lines.join("\n"))
+}
+
+/// The HTML of an `ansi` block: the text of captured output with its SGR escape sequences
+/// (`ESC[31m`, including the `\x1b`, `\033`, `\e` and `\u001b` spellings of `ESC`) turned into
+/// styled spans. Other escape sequences are dropped, and a carriage return starts its line over,
+/// as progress bars expect.
+pub fn render_ansi(code: &str) -> String {
+    let escape_re = Regex::new(r"\\(?:x1[bB]|033|e|u001[bB])\[").unwrap();
+    let code = escape_re.replace_all(code, "\x1b[");
+    let sequence_re = Regex::new(r"\x1b(?:\[([0-9;?]*)([@-~])|\][^\x07\x1b]*(?:\x07|\x1b\\)?|[()][A-Za-z0-9]|[@-_])").unwrap();
+    let mut style = AnsiStyle::default();
+    let mut lines = Vec::new();
+    for line in code.trim_end_matches('\n').lines() {
+        let mut html = String::new();
+        let mut rest = line;
+        while !rest.is_empty() {
+            let (text, sequence) = match sequence_re.find(rest) {
+                Some(sequence) => (&rest[..sequence.start()], Some(sequence)),
+                None => (rest, None),
+            };
+            for (i, segment) in text.split('\r').enumerate() {
+                if i > 0 {
+                    html.clear();
+                }
+                html.push_str(&style.span(segment));
+            }
+            let Some(sequence) = sequence else { break };
+            if let Some(caps) = sequence_re.captures(sequence.as_str()).filter(|caps| caps.get(2).is_some_and(|command| command.as_str() == "m")) {
+                style.apply(&caps[1]);
+            }
+            rest = &rest[sequence.end()..];
+        }
+        lines.push(html);
+    }
+    format!("<pre class=\"ansi\"><code>{}</code></pre>", lines.join("\n"))
+}
+
+/// Text attributes set by SGR sequences.
+#[derive(Default)]
+struct AnsiStyle {
+    foreground: Option<String>,
+    background: Option<String>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+}
+
+impl AnsiStyle {
+    /// Applies the parameters of `ESC[...m`; an empty list resets.
+    fn apply(&mut self, parameters: &str) {
+        let codes: Vec<u32> = parameters.split(';').map(|code| code.parse().unwrap_or(0)).collect();
+        let mut codes = codes.iter().copied();
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = AnsiStyle::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.foreground = Some(ANSI_COLORS[code as usize - 30].to_string()),
+                90..=97 => self.foreground = Some(ANSI_COLORS[code as usize - 82].to_string()),
+                40..=47 => self.background = Some(ANSI_COLORS[code as usize - 40].to_string()),
+                100..=107 => self.background = Some(ANSI_COLORS[code as usize - 92].to_string()),
+                39 => self.foreground = None,
+                49 => self.background = None,
+                38 | 48 => {
+                    let color = match codes.next() {
+                        Some(5) => codes.next().map(palette_color),
+                        Some(2) => match (codes.next(), codes.next(), codes.next()) {
+                            (Some(r), Some(g), Some(b)) => Some(format!("#{:02x}{:02x}{:02x}", r.min(255), g.min(255), b.min(255))),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if code == 38 {
+                        self.foreground = color;
+                    } else {
+                        self.background = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// `text` escaped, in a span carrying the style when it has one.
+    fn span(&self, text: &str) -> String {
+        if text.is_empty() {
+            return String::new();
+        }
+        let (foreground, background) = if self.inverse {
+            (Some(self.background.as_deref().unwrap_or("#1e1e1e")), Some(self.foreground.as_deref().unwrap_or("#e0e0e0")))
+        } else {
+            (self.foreground.as_deref(), self.background.as_deref())
+        };
+        let mut css = Vec::new();
+        css.extend(foreground.map(|color| format!("color: {color}")));
+        css.extend(background.map(|color| format!("background: {color}")));
+        if self.bold {
+            css.push("font-weight: bold".to_string());
+        }
+        if self.dim {
+            css.push("opacity: 0.7".to_string());
+        }
+        if self.italic {
+            css.push("font-style: italic".to_string());
+        }
+        if self.underline {
+            css.push("text-decoration: underline".to_string());
+        }
+        if css.is_empty() {
+            escape_html(text)
+        } else {
+            format!("<span style=\"{}\">{}</span>", css.join("; "), escape_html(text))
+        }
+    }
+}
+
+/// A color of the 256-color palette: the basic colors, a 6x6x6 cube, then 24 grays.
+fn palette_color(index: u32) -> String {
+    match index {
+        0..=15 => ANSI_COLORS[index as usize].to_string(),
+        16..=231 => {
+            let level = |value: u32| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            format!("#{:02x}{:02x}{:02x}", level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        _ => {
+            let gray = 8 + (index.min(255) - 232) * 10;
+            format!("#{gray:02x}{gray:02x}{gray:02x}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_console() {
+        assert_eq!(
+            render_console("$ cargo build \\\n    --release\n   Compiling app\nuser@host:~/app$ ls <dir>\n"),
+            "<pre class=\"console\"><code><span class=\"prompt\">$ </span><span class=\"command\">cargo build \\</span>\n\
+             <span class=\"command\">    --release</span>\n<span class=\"output\">   Compiling app</span>\n\
+             <span class=\"prompt\">user@host:~/app$ </span><span class=\"command\">ls &lt;dir&gt;</span></code></pre>"
+        );
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        assert_eq!(
+            render_ansi("\x1b[1;31merror\x1b[0m: <bad>\n\\x1b[32mok\\x1b[39m done\x1b[K\n10%\r\x1b[38;5;208m100%\x1b[0m\n\x1b]0;title\x07plain\n"),
+            "<pre class=\"ansi\"><code><span style=\"color: #e05561; font-weight: bold\">error</span>: &lt;bad&gt;\n\
+             <span style=\"color: #8cc265\">ok</span> done\n<span style=\"color: #ff8700\">100%</span>\nplain</code></pre>"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::i18n;
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const NARROW_NO_BREAK_SPACE: char = '\u{202F}';
+
+/// Locale typography applied to the text of the document (code and links excluded).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TypographyConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Rules by language, replacing the built-in ones (e.g. `fr`, `de`, `en`).
+    #[serde(default)]
+    pub languages: HashMap<String, TypographyRules>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for TypographyConfig {
+    fn default() -> Self {
+        Self { enabled: default_enabled(), languages: HashMap::new() }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TypographyRules {
+    /// Opening and closing quotes replacing straight double quotes, e.g. `["„", "“"]`.
+    pub quotes: Option<[String; 2]>,
+    /// Punctuation marks preceded by a narrow no-break space, e.g. `";:?!"` in French.
+    #[serde(default)]
+    pub space_before: String,
+}
+
+/// Rules for the document language: the configured ones, or the built-in rules for French and German.
+pub fn rules_for(config: &TypographyConfig, language: &str) -> Option<TypographyRules> {
+    if !config.enabled {
+        return None;
+    }
+    let primary = i18n::primary_language(language);
+    if let Some(rules) = config.languages.get(language).or_else(|| config.languages.get(&primary)) {
+        return Some(rules.clone());
+    }
+    match primary.as_str() {
+        "fr" => Some(TypographyRules {
+            quotes: Some([format!("«{NARROW_NO_BREAK_SPACE}"), format!("{NARROW_NO_BREAK_SPACE}»")]),
+            space_before: ";:?!".to_string(),
+        }),
+        "de" => Some(TypographyRules { quotes: Some(["„".to_string(), "“".to_string()]), space_before: String::new() }),
+        _ => None,
+    }
+}
+
+/// Applies the rules to the text events of a parsed document. Code, raw HTML and link
+/// texts (often URLs) are left untouched. Consecutive text events must be merged first.
+/// The events are transformed as they are read.
+pub fn apply<'a, 'r>(events: impl Iterator<Item = Event<'a>> + 'r, rules: &'r TypographyRules) -> impl Iterator<Item = Event<'a>> + 'r
+where
+    'a: 'r,
+{
+    let mut in_code_block = false;
+    let mut link_depth = 0;
+    let mut open_quote = false;
+
+    events.map(move |event| {
+        match &event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Start(Tag::Link { .. }) => link_depth += 1,
+            Event::End(TagEnd::Link) => link_depth -= 1,
+            Event::Start(Tag::Paragraph | Tag::Heading { .. } | Tag::Item | Tag::TableCell) => open_quote = false,
+            Event::Text(text) if !in_code_block && link_depth == 0 => {
+                return Event::Text(CowStr::from(transform_text(text, rules, &mut open_quote)));
+            }
+            _ => {}
+        }
+        event
+    })
+}
+
+/// `open_quote` carries the quote state across text events split by inline markup.
+fn transform_text(text: &str, rules: &TypographyRules, open_quote: &mut bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+
+    for (i, &c) in chars.iter().enumerate() {
+        let next = chars.get(i + 1).copied();
+
+        if c == '"' {
+            if let Some([open, close]) = &rules.quotes {
+                result.push_str(if *open_quote { close } else { open });
+                *open_quote = !*open_quote;
+                continue;
+            }
+        }
+
+        if rules.space_before.contains(c) {
+            let previous = result.chars().last();
+            let ends_word = next.is_none_or(|n| n.is_whitespace() || rules.space_before.contains(n));
+            match previous {
+                Some(p) if p.is_whitespace() && p != NARROW_NO_BREAK_SPACE => {
+                    result.pop();
+                    result.push(NARROW_NO_BREAK_SPACE);
+                }
+                // A colon between digits (10:30) is not punctuation.
+                Some(p) if (p.is_alphanumeric() || p == '»' || p == ')') && ends_word => result.push(NARROW_NO_BREAK_SPACE),
+                _ => {}
+            }
+        }
+
+        // Spaces written inside « guillemets » become narrow no-break spaces.
+        if c.is_whitespace() && result.ends_with('«') {
+            result.push(NARROW_NO_BREAK_SPACE);
+            continue;
+        }
+        if c == '»' && result.ends_with(' ') {
+            result.pop();
+            result.push(NARROW_NO_BREAK_SPACE);
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{html, Parser, TextMergeStream};
+
+    fn render(markdown: &str, language: &str) -> String {
+        let rules = rules_for(&TypographyConfig::default(), language).unwrap();
+        let events = apply(TextMergeStream::new(Parser::new(markdown)), &rules);
+        let mut output = String::new();
+        html::push_html(&mut output, events);
+        output
+    }
+
+    #[test]
+    fn test_french_typography() {
+        let output = render("Il a dit \"bonjour\" : quoi? Le train part à 10:30 ; « vite »!", "fr");
+        assert_eq!(
+            output.trim(),
+            "<p>Il a dit «\u{202F}bonjour\u{202F}»\u{202F}: quoi\u{202F}? Le train part à 10:30\u{202F}; «\u{202F}vite\u{202F}»\u{202F}!</p>"
+        );
+    }
+
+    #[test]
+    fn test_german_quotes_skip_code() {
+        let output = render("Er sagte \"*hallo*\".\n\n```\nlet s = \"code\";\n```\n\n`\"inline\"`", "de");
+        assert!(output.contains("Er sagte „<em>hallo</em>“."));
+        assert!(output.contains("let s = \"code\";"));
+        assert!(output.contains("<code>\"inline\"</code>"));
+    }
+
+    #[test]
+    fn test_rules_for_language() {
+        assert!(rules_for(&TypographyConfig::default(), "en").is_none());
+        let disabled = TypographyConfig { enabled: false, ..TypographyConfig::default() };
+        assert!(rules_for(&disabled, "fr").is_none());
+    }
+}
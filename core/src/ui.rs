@@ -0,0 +1,92 @@
+use crate::render::escape_html;
+use regex::{Captures, Regex};
+
+/// Styles of keyboard keys and UI paths, added when the document uses them.
+pub const UI_CSS: &str = r#"/* Keyboard keys and UI paths */
+kbd { font-family: 'Consolas', 'Menlo', monospace; font-size: 0.85em; padding: 0.1em 0.4em; border: 1px solid #aaa; border-bottom-width: 2px; border-radius: 3px; background-color: #f6f6f6; color: #1a1a1a; white-space: nowrap; }
+kbd.keys { padding: 0; border: none; background: none; color: inherit; }
+.ui-path { white-space: nowrap; }
+.ui { font-weight: bold; }
+.ui-separator { margin: 0 0.3em; opacity: 0.7; }"#;
+
+/// Renders `[[Ctrl+S]]` keyboard shortcuts and `((Settings > Privacy))` UI paths outside code.
+pub fn render_ui_markup(markdown: &str) -> String {
+    let markup_re = Regex::new(r"\[\[([^\[\]\n]+?)\]\]|\(\(([^()\n]+?)\)\)").unwrap();
+    let mut result = String::with_capacity(markdown.len());
+    let mut in_code_block = false;
+
+    for line in markdown.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block || !line.contains("[[") && !line.contains("((") {
+            result.push_str(line);
+            continue;
+        }
+        // Odd segments between backticks are code spans.
+        for (i, segment) in line.split('`').enumerate() {
+            if i > 0 {
+                result.push('`');
+            }
+            if i % 2 == 1 {
+                result.push_str(segment);
+                continue;
+            }
+            result.push_str(&markup_re.replace_all(segment, |caps: &Captures| match (caps.get(1), caps.get(2)) {
+                (Some(keys), _) => keys_html(keys.as_str()),
+                // `f((x))` is a function call.
+                (None, Some(_)) if segment[..caps.get(0).unwrap().start()].ends_with(|c: char| c.is_alphanumeric() || c == '_') => caps[0].to_string(),
+                (None, Some(path)) => ui_path_html(path.as_str()),
+                (None, None) => caps[0].to_string(),
+            }));
+        }
+    }
+    result
+}
+
+/// `Ctrl+Shift+P` as one `kbd` per key. `Ctrl++` is Ctrl and the plus key.
+fn keys_html(shortcut: &str) -> String {
+    let parts: Vec<&str> = shortcut.split('+').map(str::trim).collect();
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < parts.len() {
+        if parts[i].is_empty() && parts.get(i + 1).is_some_and(|next| next.is_empty()) {
+            keys.push("+");
+            i += 1;
+        } else if !parts[i].is_empty() {
+            keys.push(parts[i]);
+        }
+        i += 1;
+    }
+    let keys: Vec<String> = keys.iter().map(|key| format!("<kbd>{}</kbd>", escape_html(key))).collect();
+    format!("<kbd class=\"keys\">{}</kbd>", keys.join("+"))
+}
+
+/// `Settings > Privacy` as UI elements joined by separators.
+fn ui_path_html(path: &str) -> String {
+    let items: Vec<String> = path.split('>').map(|item| format!("<span class=\"ui\">{}</span>", escape_html(item.trim()))).collect();
+    format!("<span class=\"ui-path\">{}</span>", items.join("<span class=\"ui-separator\">›</span>"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_ui_markup() {
+        let result = render_ui_markup("Press [[Ctrl+S]] in ((Settings > Privacy)).\n`[[x]]` and f((x))\n```\n[[Ctrl+S]]\n```\n");
+        assert_eq!(
+            result,
+            "Press <kbd class=\"keys\"><kbd>Ctrl</kbd>+<kbd>S</kbd></kbd> in <span class=\"ui-path\"><span class=\"ui\">Settings</span>\
+             <span class=\"ui-separator\">›</span><span class=\"ui\">Privacy</span></span>.\n\
+             `[[x]]` and f((x))\n```\n[[Ctrl+S]]\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_keys_html() {
+        assert_eq!(keys_html("Ctrl + +"), "<kbd class=\"keys\"><kbd>Ctrl</kbd>+<kbd>+</kbd></kbd>");
+        assert_eq!(keys_html("+"), "<kbd class=\"keys\"><kbd>+</kbd></kbd>");
+        assert_eq!(keys_html("Alt+<"), "<kbd class=\"keys\"><kbd>Alt</kbd>+<kbd>&lt;</kbd></kbd>");
+    }
+}
@@ -0,0 +1,151 @@
+use crate::i18n;
+use chrono::{DateTime, Datelike, Local};
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Formats `date` with a strftime `format` such as `%-d %B %Y`; month and day names are in
+/// the document language. Returns `None` when the format is invalid.
+pub fn format_date(date: &DateTime<Local>, format: &str, language: &str) -> Option<String> {
+    let month = i18n::month_names(language)[date.month0() as usize];
+    let weekday = i18n::weekday_names(language)[date.weekday().num_days_from_monday() as usize];
+    let abbreviation = |name: &str| name.chars().take(3).collect::<String>();
+    let localized = Regex::new(r"%[%BbhAa]").unwrap().replace_all(format, |caps: &Captures| match &caps[0] {
+        "%B" => month.to_string(),
+        "%b" | "%h" => abbreviation(month),
+        "%A" => weekday.to_string(),
+        "%a" => abbreviation(weekday),
+        escaped => escaped.to_string(),
+    });
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(&localized)).ok()?;
+    Some(formatted)
+}
+
+/// Replaces `{{ name }}` placeholders outside of fenced code blocks, and `{{ now "%Y-%m-%d" }}`
+/// with the current date and time in that format (see `format_date`).
+/// Unknown variables are left untouched so they remain visible in the output.
+pub fn substitute(content: &str, vars: &HashMap<String, String>) -> String {
+    let var_re = variable_regex();
+
+    let mut result = String::with_capacity(content.len());
+    let mut in_code_block = false;
+
+    for line in content.lines() {
+        if line.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+
+        if in_code_block {
+            result.push_str(line);
+        } else {
+            result.push_str(&replace_variables(&var_re, line, vars));
+        }
+        result.push('\n');
+    }
+    result
+}
+
+/// Replaces `{{ name }}` placeholders everywhere in a template (e.g. the HTML shell of a theme).
+/// Substituted values are not scanned again, so a body containing `{{` is inserted verbatim.
+pub fn render_template(template: &str, vars: &HashMap<String, String>) -> String {
+    replace_variables(&variable_regex(), template, vars)
+}
+
+/// `render_template` writing the result to `out` as it goes, so a large value such as the body
+/// of a page is not copied into the result.
+pub fn write_template(template: &str, vars: &HashMap<String, String>, out: &mut impl std::io::Write) -> std::io::Result<()> {
+    let mut last = 0;
+    for caps in variable_regex().captures_iter(template) {
+        let placeholder = caps.get(0).unwrap();
+        out.write_all(&template.as_bytes()[last..placeholder.start()])?;
+        let date;
+        let value = match (caps.get(1), caps.get(2)) {
+            (Some(format), _) => {
+                date = format_date(&Local::now(), format.as_str(), vars.get("language").map(String::as_str).unwrap_or("en"));
+                date.as_deref()
+            }
+            (None, Some(name)) => vars.get(name.as_str()).map(String::as_str),
+            (None, None) => None,
+        };
+        out.write_all(value.unwrap_or(placeholder.as_str()).as_bytes())?;
+        last = placeholder.end();
+    }
+    out.write_all(&template.as_bytes()[last..])
+}
+
+fn variable_regex() -> Regex {
+    Regex::new(r#"\{\{\s*(?:now\s+"([^"]*)"|([A-Za-z_][A-Za-z0-9_]*))\s*\}\}"#).unwrap()
+}
+
+fn replace_variables(var_re: &Regex, text: &str, vars: &HashMap<String, String>) -> String {
+    var_re.replace_all(text, |caps: &Captures| {
+        let value = match (caps.get(1), caps.get(2)) {
+            (Some(format), _) => format_date(&Local::now(), format.as_str(), vars.get("language").map(String::as_str).unwrap_or("en")),
+            (None, Some(name)) => vars.get(name.as_str()).cloned(),
+            (None, None) => None,
+        };
+        value.unwrap_or_else(|| caps[0].to_string())
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn vars() -> HashMap<String, String> {
+        let mut vars = HashMap::new();
+        vars.insert("git_commit".to_string(), "abc1234".to_string());
+        vars
+    }
+
+    #[test]
+    fn test_substitute_known_variable() {
+        let result = substitute("Built from {{ git_commit }}.", &vars());
+        assert_eq!(result.trim(), "Built from abc1234.");
+    }
+
+    #[test]
+    fn test_substitute_keeps_unknown_variable() {
+        let result = substitute("Value: {{missing}}", &vars());
+        assert_eq!(result.trim(), "Value: {{missing}}");
+    }
+
+    #[test]
+    fn test_render_template_does_not_rescan_values() {
+        let mut vars = vars();
+        vars.insert("body".to_string(), "{{ git_commit }}".to_string());
+        let result = render_template("<main>{{ body }}</main><p>{{git_commit}}</p>", &vars);
+        assert_eq!(result, "<main>{{ git_commit }}</main><p>abc1234</p>");
+
+        let mut written = Vec::new();
+        write_template("<main>{{ body }}</main><p>{{git_commit}}</p>{{ missing }}", &vars, &mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), "<main>{{ git_commit }}</main><p>abc1234</p>{{ missing }}");
+    }
+
+    #[test]
+    fn test_format_date() {
+        let date = Local.with_ymd_and_hms(2026, 3, 5, 14, 30, 0).unwrap();
+        assert_eq!(format_date(&date, "%Y-%m-%d %H:%M", "en").unwrap(), "2026-03-05 14:30");
+        assert_eq!(format_date(&date, i18n::long_date_format("en-US"), "en-US").unwrap(), "March 5, 2026");
+        assert_eq!(format_date(&date, i18n::long_date_format("fr"), "fr").unwrap(), "5 mars 2026");
+        assert_eq!(format_date(&date, "%A %-d. %b, 100%%", "de").unwrap(), "Donnerstag 5. Mär, 100%");
+        assert!(format_date(&date, "%Q", "en").is_none());
+    }
+
+    #[test]
+    fn test_substitute_now() {
+        let mut vars = vars();
+        vars.insert("language".to_string(), "en".to_string());
+        let year = Local::now().format("%Y").to_string();
+        assert_eq!(substitute("© {{ now \"%Y\" }}", &vars).trim(), format!("© {year}"));
+        assert_eq!(render_template("{{ now \"%Q\" }}", &vars), "{{ now \"%Q\" }}");
+    }
+
+    #[test]
+    fn test_substitute_ignores_code_blocks() {
+        let result = substitute("```\n{{ git_commit }}\n```", &vars());
+        assert!(result.contains("{{ git_commit }}"));
+    }
+}
@@ -0,0 +1,16 @@
+/// A non-fatal issue of a conversion, returned with its result for the caller to report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Kind of issue, used to group the summary (e.g. "Unknown code language").
+    pub category: &'static str,
+    /// What the issue is about: a file, a language, a link...
+    pub detail: String,
+}
+
+/// Adds a warning to `warnings`, unless it is already there.
+pub fn push(warnings: &mut Vec<Warning>, category: &'static str, detail: impl Into<String>) {
+    let detail = detail.into();
+    if !warnings.iter().any(|warning| warning.category == category && warning.detail == detail) {
+        warnings.push(Warning { category, detail });
+    }
+}
@@ -0,0 +1,364 @@
+use crate::cache;
+use crate::error::AppError;
+use crate::paths;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Which files of `assets/` are copied to `build/assets`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AssetsConfig {
+    /// Mirrors the whole directory instead of the files referenced by the document, for
+    /// resources loaded in ways the scan cannot see (e.g. by scripts).
+    #[serde(default)]
+    pub copy_all: bool,
+}
+
+/// Size and modification time of the source files mirrored by the last build, keyed by
+/// their path relative to the assets directory. Stored in `build/.cache/assets.json`.
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct AssetIndex {
+    files: HashMap<String, FileStamp>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    size: u64,
+    modified: u128,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Result<Self, AppError> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+        Ok(Self { size: metadata.len(), modified })
+    }
+}
+
+/// Mirrors `source_dir` into `dest_dir`, or only the files listed in `only` (paths relative to
+/// `source_dir`). Unchanged files (same size and modification time as in the last build, or
+/// same content) are skipped. Others are cloned (reflink) or hard-linked when the file system
+/// allows it, and otherwise copied in parallel. Files mirrored before but no longer selected
+/// are removed. Symbolic links are handled as described in `list_files`.
+pub fn mirror_assets(source_dir: &Path, dest_dir: &Path, index_path: &Path, only: Option<&HashSet<String>>, project_root: &Path, follow_symlinks: bool) -> Result<(), AppError> {
+    if !source_dir.exists() {
+        return Ok(());
+    }
+    #[cfg(not(test))]
+    {
+        use colored::Colorize;
+        println!("{} {} {} {}...", "Copying assets from".blue(), source_dir.display().to_string().yellow(), "to".blue(), dest_dir.display().to_string().yellow());
+    }
+
+    let previous: AssetIndex = fs::read_to_string(index_path).ok().and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default();
+    let mut index = AssetIndex::default();
+    let mut pending = Vec::new();
+
+    for relative in list_files(source_dir, project_root, follow_symlinks)? {
+        let key = relative.to_string_lossy().replace('\\', "/");
+        if only.is_some_and(|only| !only.contains(&key)) {
+            continue;
+        }
+        let (mut source, dest) = (source_dir.join(&relative), dest_dir.join(&relative));
+        // Links are mirrored as the file they point to, not as a (possibly dangling) link.
+        if fs::symlink_metadata(&source)?.file_type().is_symlink() {
+            source = dunce::canonicalize(&source)?;
+        }
+        let stamp = FileStamp::of(&source)?;
+        if !is_up_to_date(&source, &dest, stamp, previous.files.get(&key))? {
+            pending.push((source, dest));
+        }
+        index.files.insert(key, stamp);
+    }
+
+    copy_files_parallel(&pending)?;
+    for stale in previous.files.keys().filter(|key| !index.files.contains_key(*key)) {
+        fs::remove_file(dest_dir.join(stale)).ok();
+    }
+
+    if let Some(parent) = index_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string(&index).map_err(|e| AppError::BuildError(e.to_string()))?;
+    fs::write(index_path, json)?;
+    Ok(())
+}
+
+/// Files of the assets directory referenced by the page (`src`, `href`, `srcset`, `poster`
+/// attributes and CSS `url()`), as paths relative to that directory.
+pub fn referenced_assets(html: &str) -> HashSet<String> {
+    let mut references = HashSet::new();
+    for caps in reference_re().captures_iter(html) {
+        let values: Vec<&str> = match (caps.get(1), caps.get(2), caps.get(3)) {
+            // `srcset` lists candidates such as `a.png 1x, b.png 2x`.
+            (_, Some(srcset), _) => srcset.as_str().split(',').filter_map(|candidate| candidate.split_whitespace().next()).collect(),
+            (Some(value), _, _) | (_, _, Some(value)) => vec![value.as_str()],
+            _ => Vec::new(),
+        };
+        references.extend(values.into_iter().filter_map(asset_path));
+    }
+    references
+}
+
+fn reference_re() -> Regex {
+    Regex::new(r#"(?:src|href|poster)\s*=\s*["']([^"']+)["']|srcset\s*=\s*["']([^"']+)["']|url\(\s*["']?([^"')]+)["']?\s*\)"#).unwrap()
+}
+
+/// The path relative to the assets directory of a reference such as `./assets/a%20b.png?v=2`
+/// or `url(../assets/font.woff)`, made from the page at the root of the build directory, above
+/// which `..` does not go.
+fn asset_path(value: &str) -> Option<String> {
+    let path = value.split(['?', '#']).next().unwrap_or_default().replace("%20", " ");
+    // `https://...`, `data:...`
+    if path.split('/').next().is_some_and(|first| first.contains(':')) {
+        return None;
+    }
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    match segments.split_first() {
+        Some((&"assets", file)) if !file.is_empty() => Some(file.join("/")),
+        _ => None,
+    }
+}
+
+/// Fingerprinted copies written by the last build, in `build/.cache/fingerprints.json`.
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct FingerprintIndex {
+    files: Vec<String>,
+}
+
+/// Copies each asset referenced by `html` to a name carrying a hash of its content
+/// (`images/logo.3f2a9c1e.png`) and points the references to the copies, so that hosted pages
+/// can be cached for long without showing stale images after an update. Copies left from
+/// earlier builds are removed. Returns the rewritten page.
+pub fn fingerprint_assets(html: &str, assets_dir: &Path, index_path: &Path) -> Result<String, AppError> {
+    let previous: FingerprintIndex = fs::read_to_string(index_path).ok().and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default();
+    let mut names = HashMap::new();
+    for relative in referenced_assets(html) {
+        let Ok(content) = fs::read(assets_dir.join(&relative)) else {
+            continue;
+        };
+        let path = Path::new(&relative);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(extension) => format!("{stem}.{}.{}", &cache::content_hash(&content)[..8], extension.to_string_lossy()),
+            None => format!("{stem}.{}", &cache::content_hash(&content)[..8]),
+        };
+        let fingerprinted = path.with_file_name(name).to_string_lossy().replace('\\', "/");
+        if !assets_dir.join(&fingerprinted).exists() {
+            fs::write(assets_dir.join(&fingerprinted), &content)?;
+        }
+        names.insert(relative, fingerprinted);
+    }
+
+    for stale in previous.files.iter().filter(|file| !names.values().any(|name| name == *file)) {
+        fs::remove_file(assets_dir.join(stale)).ok();
+    }
+    let mut files: Vec<String> = names.values().cloned().collect();
+    files.sort();
+    if let Some(parent) = index_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string(&FingerprintIndex { files }).map_err(|e| AppError::BuildError(e.to_string()))?;
+    fs::write(index_path, json)?;
+
+    let rename = |value: &str| -> String {
+        let Some(name) = asset_path(value).and_then(|relative| names.get(&relative)) else {
+            return value.to_string();
+        };
+        let suffix = value.find(['?', '#']).map_or("", |i| &value[i..]);
+        let prefix = if value.starts_with("./") { "./" } else { "" };
+        format!("{prefix}assets/{}{suffix}", name.replace(' ', "%20"))
+    };
+    let rewritten = reference_re().replace_all(html, |caps: &regex::Captures| {
+        let whole = caps.get(0).unwrap();
+        let value = caps.get(1).or(caps.get(2)).or(caps.get(3)).unwrap();
+        let (start, end) = (value.start() - whole.start(), value.end() - whole.start());
+        let replaced = if caps.get(2).is_some() {
+            value.as_str().split(',').map(|candidate| {
+                let trimmed = candidate.trim_start();
+                let url = trimmed.split_whitespace().next().unwrap_or_default();
+                format!("{}{}{}", &candidate[..candidate.len() - trimmed.len()], rename(url), &trimmed[url.len()..])
+            }).collect::<Vec<_>>().join(",")
+        } else {
+            rename(value.as_str())
+        };
+        format!("{}{}{}", &whole.as_str()[..start], replaced, &whole.as_str()[end..])
+    });
+    Ok(rewritten.into_owned())
+}
+
+/// Files below `dir`, relative to it. Symbolic links (to files or directories) are followed
+/// when `follow_symlinks` is set and they point inside `project_root`, and refused otherwise.
+/// Dangling links are skipped.
+pub fn list_files(dir: &Path, project_root: &Path, follow_symlinks: bool) -> Result<Vec<PathBuf>, AppError> {
+    let mut files = Vec::new();
+    let mut directories = vec![PathBuf::new()];
+    // Linked directories can lead back to one of their parents.
+    let mut visited = HashSet::new();
+    while let Some(relative) = directories.pop() {
+        if !visited.insert(dunce::canonicalize(dir.join(&relative))?) {
+            continue;
+        }
+        for entry in fs::read_dir(dir.join(&relative))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            let full_path = dir.join(&path);
+            if entry.file_type()?.is_symlink() {
+                if !full_path.exists() {
+                    continue;
+                }
+                paths::resolve_within(project_root, &full_path, follow_symlinks)?;
+            }
+            if full_path.is_dir() {
+                directories.push(path);
+            } else if full_path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_up_to_date(source: &Path, dest: &Path, stamp: FileStamp, previous: Option<&FileStamp>) -> Result<bool, AppError> {
+    let Ok(dest_metadata) = fs::metadata(dest) else {
+        return Ok(false);
+    };
+    if dest_metadata.len() != stamp.size {
+        return Ok(false);
+    }
+    if previous == Some(&stamp) {
+        return Ok(true);
+    }
+    // Touched but identical files (e.g. after a fresh clone) need no copy either.
+    Ok(cache::content_hash(fs::read(source)?) == cache::content_hash(fs::read(dest)?))
+}
+
+/// Links or copies the files, spreading the copies over the available cores.
+fn copy_files_parallel(files: &[(PathBuf, PathBuf)]) -> Result<(), AppError> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4).min(files.len());
+    let chunk_size = files.len().div_ceil(workers);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = files.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().try_for_each(|(source, dest)| link_or_copy(source, dest))))
+            .collect();
+        handles.into_iter().try_for_each(|handle| handle.join().unwrap_or_else(|_| Err(AppError::BuildError("Asset copy failed".to_string()))))
+    })
+}
+
+fn link_or_copy(source: &Path, dest: &Path) -> Result<(), AppError> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if dest.exists() {
+        fs::remove_file(dest)?;
+    }
+    if reflink_copy::reflink(source, dest).is_ok() || fs::hard_link(source, dest).is_ok() {
+        return Ok(());
+    }
+    fs::copy(source, dest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_mirror_assets() {
+        let test_dir = TestDir::new("assets_mirror");
+        let dir = test_dir.path();
+        let (source, dest, index) = (dir.join("assets"), dir.join("build").join("assets"), dir.join("assets.json"));
+        fs::create_dir_all(source.join("images")).unwrap();
+        fs::write(source.join("logo.svg"), "<svg/>").unwrap();
+        fs::write(source.join("images").join("photo.png"), "png").unwrap();
+
+        mirror_assets(&source, &dest, &index, None, dir, true).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("images").join("photo.png")).unwrap(), "png");
+        assert!(fs::read_to_string(&index).unwrap().contains("images/photo.png"));
+
+        // A changed source replaces the mirrored file.
+        fs::remove_file(source.join("logo.svg")).unwrap();
+        fs::write(source.join("logo.svg"), "<svg></svg>").unwrap();
+        mirror_assets(&source, &dest, &index, None, dir, true).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("logo.svg")).unwrap(), "<svg></svg>");
+
+        // Files no longer referenced are removed from the mirror.
+        let only = HashSet::from(["logo.svg".to_string()]);
+        mirror_assets(&source, &dest, &index, Some(&only), dir, true).unwrap();
+        assert!(dest.join("logo.svg").exists());
+        assert!(!dest.join("images").join("photo.png").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mirror_assets_symlinks() {
+        let test_dir = TestDir::new("assets_symlinks");
+        let dir = test_dir.path();
+        let (source, dest, index) = (dir.join("assets"), dir.join("build").join("assets"), dir.join("assets.json"));
+        fs::create_dir_all(dir.join("shared")).unwrap();
+        fs::create_dir_all(&source).unwrap();
+        fs::write(dir.join("shared").join("logo.svg"), "<svg/>").unwrap();
+        std::os::unix::fs::symlink(dir.join("shared"), source.join("shared")).unwrap();
+
+        mirror_assets(&source, &dest, &index, None, dir, true).unwrap();
+        assert!(!fs::symlink_metadata(dest.join("shared").join("logo.svg")).unwrap().file_type().is_symlink());
+        assert!(matches!(mirror_assets(&source, &dest, &index, None, dir, false), Err(AppError::SymlinkRefused(_))));
+        // The project root is the assets directory here: the link escapes it.
+        assert!(matches!(mirror_assets(&source, &dest, &index, None, &source, true), Err(AppError::BuildError(_))));
+    }
+
+    #[test]
+    fn test_referenced_assets() {
+        let html = r#"<img src="assets/images/a.png"><img srcset="./assets/b.png 1x, assets/c%20d.png 2x"><a href="https://example.com/assets/x.png">x</a>
+<style>@font-face { src: url('assets/fonts/f.woff2?v=2'); } h1 { background: url(../assets/bg.svg) }</style><video poster="assets/poster.jpg"></video>
+<img src="images/../assets/e.png"><img src="/assets/f.png"><img src="data:image/png;base64,assets/x"><a href="//cdn.example.com/assets/y.png">y</a>"#;
+        let mut references: Vec<String> = referenced_assets(html).into_iter().collect();
+        references.sort();
+        assert_eq!(references, ["b.png", "bg.svg", "c d.png", "e.png", "f.png", "fonts/f.woff2", "images/a.png", "poster.jpg"]);
+    }
+
+    #[test]
+    fn test_fingerprint_assets() {
+        let test_dir = TestDir::new("assets_fingerprint");
+        let dir = test_dir.path();
+        let (assets, index) = (dir.join("assets"), dir.join("fingerprints.json"));
+        fs::create_dir_all(assets.join("images")).unwrap();
+        fs::write(assets.join("images").join("a b.png"), "png").unwrap();
+        fs::write(assets.join("logo.svg"), "<svg/>").unwrap();
+        let hash = |content: &str| cache::content_hash(content)[..8].to_string();
+
+        let html = r#"<img src="./assets/images/a%20b.png?v=1"><img srcset="assets/logo.svg 1x, assets/missing.png 2x"><style>a { background: url(assets/logo.svg); }</style>"#;
+        let result = fingerprint_assets(html, &assets, &index).unwrap();
+        let (png, svg) = (format!("images/a b.{}.png", hash("png")), format!("logo.{}.svg", hash("<svg/>")));
+        assert_eq!(result, format!(
+            r#"<img src="./assets/{}?v=1"><img srcset="assets/{svg} 1x, assets/missing.png 2x"><style>a {{ background: url(assets/{svg}); }}</style>"#,
+            png.replace(' ', "%20")
+        ));
+        assert!(assets.join(&png).exists() && assets.join(&svg).exists());
+
+        // An updated asset gets a new name and the old copy goes.
+        fs::write(assets.join("logo.svg"), "<svg></svg>").unwrap();
+        let result = fingerprint_assets(r#"<img src="assets/logo.svg">"#, &assets, &index).unwrap();
+        let updated = format!("logo.{}.svg", hash("<svg></svg>"));
+        assert_eq!(result, format!(r#"<img src="assets/{updated}">"#));
+        assert!(assets.join(&updated).exists() && !assets.join(&svg).exists() && !assets.join(&png).exists());
+    }
+}
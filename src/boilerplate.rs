@@ -0,0 +1,136 @@
+use crate::builder::{self, Includes};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const TOC_PLACEHOLDER: &str = "<!--TOC_PLACEHOLDER-->";
+
+/// Reusable pages (copyright page, license, disclaimers...) added to every build of the
+/// document, so the sources don't have to include them.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BoilerplateConfig {
+    /// Snippets placed before the table of contents, or at the start of a document without one.
+    #[serde(default)]
+    pub front: Vec<String>,
+    /// Snippets placed at the end of the document.
+    #[serde(default)]
+    pub back: Vec<String>,
+    /// Extra `{{ name }}` variables, e.g. `company: ACME Corp`.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+}
+
+/// Adds the `front` and `back` snippets to the expanded Markdown, each on its own page.
+/// Snippets are Markdown files of the project and may use every directive.
+pub fn insert(project_root: &Path, config: &BoilerplateConfig, markdown: String, includes: &mut Includes) -> Result<String, AppError> {
+    if config.front.is_empty() && config.back.is_empty() {
+        return Ok(markdown);
+    }
+    let mut front = String::new();
+    for file in &config.front {
+        front.push_str(&section(project_root, file, includes)?);
+        front.push_str("<div class=\"page-break\"></div>\n\n");
+    }
+    let mut back = String::new();
+    for file in &config.back {
+        back.push_str("\n<div class=\"page-break\"></div>\n\n");
+        back.push_str(&section(project_root, file, includes)?);
+    }
+
+    let result = match markdown.find(TOC_PLACEHOLDER) {
+        Some(toc) => format!("{}{front}{}{back}", &markdown[..toc], &markdown[toc..]),
+        None => format!("{front}{markdown}{back}"),
+    };
+    includes.check_size(&result)?;
+    Ok(result)
+}
+
+/// Appends the `back_matter` pages (about the author, colophon, other books...) at the end of
+/// the document, each on its own page. Unless `numbered`, their headings are not numbered.
+pub fn append_back_matter(project_root: &Path, files: &[String], numbered: bool, mut markdown: String, includes: &mut Includes) -> Result<String, AppError> {
+    let classes = if numbered { "back-matter" } else { "back-matter unnumbered" };
+    for file in files {
+        markdown.push_str("\n<div class=\"page-break\"></div>\n\n");
+        markdown.push_str(&builder::preprocess_chapter(project_root, &project_root.join(file), includes, classes)?);
+        markdown.push('\n');
+    }
+    includes.check_size(&markdown)?;
+    Ok(markdown)
+}
+
+/// Adds the dedication on its own page, after the `front` snippets: unnumbered and without a
+/// heading, so it stays out of the table of contents.
+pub fn insert_dedication(dedication: &str, markdown: String) -> String {
+    let page = format!(
+        "<section class=\"chapter dedication-page unnumbered\">\n\n<div class=\"dedication\">\n\n{}\n\n</div>\n\n</section>\n\n<div class=\"page-break\"></div>\n\n",
+        dedication.trim()
+    );
+    match markdown.find(TOC_PLACEHOLDER) {
+        Some(toc) => format!("{}{page}{}", &markdown[..toc], &markdown[toc..]),
+        None => format!("{page}{markdown}"),
+    }
+}
+
+fn section(project_root: &Path, file: &str, includes: &mut Includes) -> Result<String, AppError> {
+    let content = builder::preprocess_chapter(project_root, &project_root.join(file), includes, "boilerplate unnumbered")?;
+    Ok(format!("{content}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_insert() {
+        let test_dir = TestDir::new("boilerplate_insert");
+        let dir = test_dir.path();
+        fs::create_dir_all(dir.join("legal")).unwrap();
+        fs::write(dir.join("legal/copyright.md"), "© {{ year }} {{ company }}").unwrap();
+        fs::write(dir.join("legal/license.md"), "## License").unwrap();
+        let config: BoilerplateConfig = serde_yaml::from_str("front: [legal/copyright.md]\nback: [legal/license.md]").unwrap();
+
+        let result = insert(dir, &config, "# Guide\n<!--TOC_PLACEHOLDER-->\n## Intro\n".to_string(), &mut Includes::new(true)).unwrap();
+        assert_eq!(result, "# Guide\n<section class=\"chapter boilerplate unnumbered\" data-src=\"legal/copyright.md\">\n\n\
+            © {{ year }} {{ company }}\n\n</section>\n\n<div class=\"page-break\"></div>\n\n<!--TOC_PLACEHOLDER-->\n## Intro\n\n\
+            <div class=\"page-break\"></div>\n\n<section class=\"chapter boilerplate unnumbered\" data-src=\"legal/license.md\">\n\n\
+            ## License\n\n</section>\n\n");
+
+        let result = insert(dir, &config, "## Intro\n".to_string(), &mut Includes::new(true)).unwrap();
+        assert!(result.starts_with("<section class=\"chapter boilerplate unnumbered\" data-src=\"legal/copyright.md\">\n\n©"));
+
+        let missing: BoilerplateConfig = serde_yaml::from_str("back: [legal/missing.md]").unwrap();
+        assert!(insert(dir, &missing, String::new(), &mut Includes::new(true)).is_err());
+    }
+
+    #[test]
+    fn test_append_back_matter() {
+        let test_dir = TestDir::new("boilerplate_back_matter");
+        let dir = test_dir.path();
+        fs::create_dir_all(dir.join("series")).unwrap();
+        fs::write(dir.join("series/about-author.md"), "## About the Author").unwrap();
+        fs::write(dir.join("series/colophon.md"), "Set in Garamond.").unwrap();
+        let files = ["series/about-author.md".to_string(), "series/colophon.md".to_string()];
+
+        let result = append_back_matter(dir, &files, false, "## Intro\n".to_string(), &mut Includes::new(true)).unwrap();
+        assert_eq!(result, "## Intro\n\n<div class=\"page-break\"></div>\n\n\
+            <section class=\"chapter back-matter unnumbered\" data-src=\"series/about-author.md\">\n\n## About the Author\n\n</section>\n\n\n\
+            <div class=\"page-break\"></div>\n\n\
+            <section class=\"chapter back-matter unnumbered\" data-src=\"series/colophon.md\">\n\nSet in Garamond.\n\n</section>\n\n");
+        let numbered = append_back_matter(dir, &files[..1], true, String::new(), &mut Includes::new(true)).unwrap();
+        assert!(numbered.contains("<section class=\"chapter back-matter\" data-src=\"series/about-author.md\">"));
+        assert!(append_back_matter(dir, &["series/missing.md".to_string()], false, String::new(), &mut Includes::new(true)).is_err());
+    }
+
+    #[test]
+    fn test_insert_dedication() {
+        assert_eq!(
+            insert_dedication("For *Ada*.\n", "# Guide\n<!--TOC_PLACEHOLDER-->\n## Intro\n".to_string()),
+            "# Guide\n<section class=\"chapter dedication-page unnumbered\">\n\n<div class=\"dedication\">\n\nFor *Ada*.\n\n</div>\n\n\
+             </section>\n\n<div class=\"page-break\"></div>\n\n<!--TOC_PLACEHOLDER-->\n## Intro\n"
+        );
+        assert!(insert_dedication("For Ada.", "## Intro\n".to_string()).ends_with("<div class=\"page-break\"></div>\n\n## Intro\n"));
+    }
+}
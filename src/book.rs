@@ -0,0 +1,155 @@
+use crate::builder::{self, Includes};
+use crate::chapter;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Structured alternative to `!include` chains, similar to mdBook's SUMMARY.md.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BookConfig {
+    /// Unnumbered files placed before the table of contents (preface, foreword...).
+    #[serde(default)]
+    pub front_matter: Vec<String>,
+    #[serde(default)]
+    pub parts: Vec<PartConfig>,
+    /// Unnumbered files placed after the last part (appendices, glossary...).
+    #[serde(default)]
+    pub back_matter: Vec<String>,
+    /// Inserts the table of contents after the front matter.
+    #[serde(default = "default_toc")]
+    pub toc: bool,
+}
+
+fn default_toc() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartConfig {
+    /// Title of the part divider page. Without a title, the chapters are added without a divider.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub chapters: Vec<ChapterConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChapterConfig {
+    pub file: String,
+    /// Replaces the chapter's first heading.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl BookConfig {
+    /// Every file of the book, in document order.
+    pub fn files(&self) -> Vec<&str> {
+        self.front_matter.iter().map(String::as_str)
+            .chain(self.parts.iter().flat_map(|p| p.chapters.iter().map(|c| c.file.as_str())))
+            .chain(self.back_matter.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// The same book with every file resolved inside `root` (used for translations).
+    pub fn with_root(&self, root: &Path) -> BookConfig {
+        let rooted = |file: &String| root.join(file).to_string_lossy().replace('\\', "/");
+        BookConfig {
+            front_matter: self.front_matter.iter().map(rooted).collect(),
+            parts: self.parts.iter().map(|part| PartConfig {
+                title: part.title.clone(),
+                chapters: part.chapters.iter().map(|c| ChapterConfig { file: rooted(&c.file), title: c.title.clone() }).collect(),
+            }).collect(),
+            back_matter: self.back_matter.iter().map(rooted).collect(),
+            toc: self.toc,
+        }
+    }
+}
+
+/// Assembles the book into one Markdown document. Parts become level-1 headings on
+/// their own divider page, so heading numbering restarts with each part.
+pub fn assemble_book(project_root: &Path, book: &BookConfig, includes: &mut Includes) -> Result<String, AppError> {
+    let mut markdown = String::new();
+
+    for file in &book.front_matter {
+        push_matter(&mut markdown, project_root, file, "front-matter", includes)?;
+    }
+
+    if book.toc {
+        markdown.push_str("<!--TOC_PLACEHOLDER-->\n\n");
+    }
+
+    for part in &book.parts {
+        if let Some(title) = &part.title {
+            markdown.push_str(&format!("<section class=\"part-divider\">\n\n# {title}\n\n</section>\n\n"));
+        }
+        for chapter_config in &part.chapters {
+            let content = read_file(project_root, &chapter_config.file, "", includes)?;
+            let content = match &chapter_config.title {
+                Some(title) => chapter::override_title(&content, title),
+                None => content,
+            };
+            markdown.push_str(&content);
+            markdown.push('\n');
+        }
+    }
+
+    for file in &book.back_matter {
+        push_matter(&mut markdown, project_root, file, "back-matter", includes)?;
+    }
+    includes.check_size(&markdown)?;
+    Ok(markdown)
+}
+
+fn push_matter(markdown: &mut String, project_root: &Path, file: &str, class: &str, includes: &mut Includes) -> Result<(), AppError> {
+    let content = read_file(project_root, file, &format!("{class} unnumbered"), includes)?;
+    markdown.push_str(&content);
+    markdown.push('\n');
+    Ok(())
+}
+
+/// Expands a file of the book in a chapter section with the extra `classes`.
+fn read_file(project_root: &Path, file: &str, classes: &str, includes: &mut Includes) -> Result<String, AppError> {
+    builder::preprocess_chapter(project_root, &project_root.join(file), includes, classes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_assemble_book() {
+        let test_dir = TestDir::new("book_assemble");
+        let dir = test_dir.path();
+        fs::write(dir.join("preface.md"), "## Preface").unwrap();
+        fs::write(dir.join("install.md"), "## Install").unwrap();
+        fs::write(dir.join("usage.md"), "## Usage").unwrap();
+        fs::write(dir.join("glossary.md"), "## Glossary").unwrap();
+
+        let book: BookConfig = serde_yaml::from_str(
+            r#"
+front_matter: ["preface.md"]
+parts:
+  - title: "Getting Started"
+    chapters:
+      - file: "install.md"
+      - file: "usage.md"
+        title: "Daily Usage"
+back_matter: ["glossary.md"]
+"#,
+        )
+        .unwrap();
+
+        let markdown = assemble_book(dir, &book, &mut Includes::new(true)).unwrap();
+        let position = |needle: &str| markdown.find(needle).unwrap_or_else(|| panic!("missing {needle}"));
+
+        assert!(position("<section class=\"chapter front-matter unnumbered\" data-src=\"preface.md\">") < position("## Preface"));
+        assert!(position("## Preface") < position("<!--TOC_PLACEHOLDER-->"));
+        assert!(position("<!--TOC_PLACEHOLDER-->") < position("# Getting Started"));
+        assert!(position("# Getting Started") < position("## Install"));
+        assert!(position("<section class=\"chapter\" data-src=\"install.md\">") < position("## Install"));
+        assert!(position("## Install") < position("## Daily Usage"));
+        assert!(position("## Daily Usage") < position("<section class=\"chapter back-matter unnumbered\" data-src=\"glossary.md\">"));
+        assert_eq!(book.files(), vec!["preface.md", "install.md", "usage.md", "glossary.md"]);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::builder;
+use crate::error::AppError;
+use crate::network;
+use headless_chrome::LaunchOptions;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+/// How the headless browser is started, e.g. inside a locked-down container.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BrowserConfig {
+    /// Chrome, Chromium or Edge executable, looked for in the usual places when absent.
+    pub path: Option<PathBuf>,
+    /// Extra command-line flags, e.g. `--no-sandbox` or `--disable-dev-shm-usage`.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Proxy server of the pages the browser loads, e.g. `http://proxy.internal:3128`. By
+    /// default the proxy of the `network` settings.
+    pub proxy: Option<String>,
+    /// Profile directory, instead of a temporary one, e.g. on a writable volume.
+    pub user_data_dir: Option<PathBuf>,
+}
+
+impl BrowserConfig {
+    pub fn launch_options(&self) -> Result<LaunchOptions<'_>, AppError> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => builder::find_browser_executable()?,
+        };
+        let mut args: Vec<&OsStr> = self.args.iter().map(OsStr::new).collect();
+        let proxy_server = match self.proxy.as_deref() {
+            Some(proxy) => Some(proxy),
+            None => {
+                let network = network::settings();
+                let proxy = network.browser_proxy();
+                if proxy.is_some() && !network.no_proxy.is_empty() {
+                    args.push(OsStr::new(&network.browser_bypass_list));
+                }
+                proxy
+            }
+        };
+        Ok(LaunchOptions {
+            path: Some(path),
+            // Otherwise the launcher would add `--no-sandbox` a second time.
+            sandbox: !self.args.iter().any(|arg| arg == "--no-sandbox"),
+            args,
+            proxy_server,
+            user_data_dir: self.user_data_dir.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_launch_options() {
+        let config: BrowserConfig = serde_yaml::from_str(
+            "path: /usr/bin/chromium\nargs: [\"--no-sandbox\", \"--disable-dev-shm-usage\"]\nproxy: \"http://proxy:3128\"\nuser_data_dir: /tmp/chrome\n",
+        ).unwrap();
+        let options = config.launch_options().unwrap();
+        assert_eq!(options.path, Some(PathBuf::from("/usr/bin/chromium")));
+        assert!(!options.sandbox);
+        assert_eq!(options.args, [OsStr::new("--no-sandbox"), OsStr::new("--disable-dev-shm-usage")]);
+        assert_eq!(options.proxy_server, Some("http://proxy:3128"));
+        assert_eq!(options.user_data_dir, Some(PathBuf::from("/tmp/chrome")));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::browser::BrowserConfig;
+use crate::error::AppError;
+use headless_chrome::Browser;
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Settings of a `BrowserPool`.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolOptions {
+    /// Maximum number of browsers, and so of documents printed at the same time.
+    pub size: usize,
+    /// Browsers unused for this long are closed.
+    pub idle_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self { size: 2, idle_timeout: Duration::from_secs(300) }
+    }
+}
+
+/// Browsers shared by concurrent builds, so each print does not pay for a browser launch.
+/// Browsers are checked before being handed out and relaunched when they stopped responding.
+pub struct BrowserPool<B = Browser> {
+    options: PoolOptions,
+    launch: Box<dyn Fn() -> Result<B, AppError> + Send + Sync>,
+    healthy: fn(&B) -> bool,
+    idle: Mutex<Vec<IdleBrowser<B>>>,
+    slots: Semaphore,
+}
+
+struct IdleBrowser<B> {
+    browser: B,
+    since: Instant,
+}
+
+impl BrowserPool {
+    pub fn new(options: PoolOptions, browser: BrowserConfig) -> Self {
+        Self::with_launcher(options, move || {
+            let mut launch_options = browser.launch_options()?;
+            // The connection to the browser is closed after `idle_browser_timeout` without events,
+            // which must not happen before the pool closes the browser itself.
+            launch_options.idle_browser_timeout = options.idle_timeout + Duration::from_secs(60);
+            Browser::new(launch_options).map_err(|e| AppError::BrowserLaunchFailed(e.to_string()))
+        }, is_healthy)
+    }
+}
+
+impl<B> BrowserPool<B> {
+    /// A pool of the browsers started by `launch`, handed out while `healthy`.
+    fn with_launcher(options: PoolOptions, launch: impl Fn() -> Result<B, AppError> + Send + Sync + 'static, healthy: fn(&B) -> bool) -> Self {
+        Self { options, launch: Box::new(launch), healthy, idle: Mutex::new(Vec::new()), slots: Semaphore::new(options.size.max(1)) }
+    }
+
+    /// Waits until fewer than `size` browsers are in use, then returns an idle browser that still
+    /// responds, or a newly launched one.
+    pub async fn get(&self) -> Result<PooledBrowser<'_, B>, AppError> {
+        let permit = self.slots.acquire().await.map_err(|e| AppError::BuildError(e.to_string()))?;
+        self.prune_idle();
+        loop {
+            let Some(idle) = self.idle_browsers().pop() else { break };
+            if (self.healthy)(&idle.browser) {
+                return Ok(PooledBrowser { browser: Some(idle.browser), pool: self, _permit: permit });
+            }
+        }
+        let browser = tokio::task::block_in_place(|| (self.launch)())?;
+        Ok(PooledBrowser { browser: Some(browser), pool: self, _permit: permit })
+    }
+
+    /// Launches one browser ahead of the first `get`.
+    pub fn warm_up(&self) -> Result<(), AppError> {
+        let browser = (self.launch)()?;
+        self.idle_browsers().push(IdleBrowser { browser, since: Instant::now() });
+        Ok(())
+    }
+
+    /// Closes the browsers unused for longer than `idle_timeout`.
+    pub fn prune_idle(&self) {
+        let idle_timeout = self.options.idle_timeout;
+        self.idle_browsers().retain(|idle| idle.since.elapsed() < idle_timeout);
+    }
+
+    fn idle_browsers(&self) -> MutexGuard<'_, Vec<IdleBrowser<B>>> {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A browser taken from a `BrowserPool`, given back when dropped.
+pub struct PooledBrowser<'a, B = Browser> {
+    browser: Option<B>,
+    pool: &'a BrowserPool<B>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl<B> PooledBrowser<'_, B> {
+    /// Closes the browser instead of giving it back, e.g. after it crashed.
+    pub fn discard(mut self) {
+        self.browser = None;
+    }
+}
+
+impl<B> Deref for PooledBrowser<'_, B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        self.browser.as_ref().expect("the browser is only taken when dropped")
+    }
+}
+
+impl<B> Drop for PooledBrowser<'_, B> {
+    fn drop(&mut self) {
+        if let Some(browser) = self.browser.take() {
+            self.pool.idle_browsers().push(IdleBrowser { browser, since: Instant::now() });
+        }
+    }
+}
+
+pub fn is_healthy(browser: &Browser) -> bool {
+    browser.get_version().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Stands for a browser: the number of its launch, and whether it still responds.
+    struct FakeBrowser {
+        id: usize,
+        alive: Arc<AtomicBool>,
+    }
+
+    /// A pool of fake browsers, with the number of launches so far.
+    fn fake_pool(size: usize, idle_timeout: Duration) -> (BrowserPool<FakeBrowser>, Arc<AtomicUsize>) {
+        let launches = Arc::new(AtomicUsize::new(0));
+        let counter = launches.clone();
+        let pool = BrowserPool::with_launcher(
+            PoolOptions { size, idle_timeout },
+            move || Ok(FakeBrowser { id: counter.fetch_add(1, Ordering::SeqCst), alive: Arc::new(AtomicBool::new(true)) }),
+            |browser| browser.alive.load(Ordering::SeqCst),
+        );
+        (pool, launches)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_reuses_idle_browsers() {
+        let (pool, launches) = fake_pool(2, Duration::from_secs(300));
+        let first = pool.get().await.unwrap();
+        let second = pool.get().await.unwrap();
+        assert_eq!((first.id, second.id), (0, 1));
+        // Both slots are taken.
+        assert!(tokio::time::timeout(Duration::from_millis(50), pool.get()).await.is_err());
+        drop(second);
+        assert_eq!(pool.get().await.unwrap().id, 1);
+        assert_eq!(launches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prune_idle() {
+        let (pool, launches) = fake_pool(1, Duration::ZERO);
+        pool.warm_up().unwrap();
+        pool.prune_idle();
+        assert!(pool.idle_browsers().is_empty());
+        assert_eq!(pool.get().await.unwrap().id, 1);
+        assert_eq!(launches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dead_browsers_are_replaced() {
+        let (pool, launches) = fake_pool(1, Duration::from_secs(300));
+        let browser = pool.get().await.unwrap();
+        browser.alive.store(false, Ordering::SeqCst);
+        drop(browser);
+        // The idle browser no longer responds: a new one is launched.
+        let browser = pool.get().await.unwrap();
+        assert_eq!(browser.id, 1);
+        browser.discard();
+        assert!(pool.idle_browsers().is_empty());
+        assert_eq!(pool.get().await.unwrap().id, 2);
+        assert_eq!(launches.load(Ordering::SeqCst), 3);
+    }
+}
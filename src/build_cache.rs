@@ -0,0 +1,134 @@
+use crate::assets;
+use crate::builder;
+use crate::cache;
+use crate::error::AppError;
+use crate::Config;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Digest of everything the browser render reads: the page (and its social card), the
+/// mirrored assets, the footer and the settings, with the version of the tool, whose updates
+/// may change the render. Equal digests give the same PDF.
+pub fn render_digest(html_path: &Path, config: &Config, footer: &str) -> Result<String, AppError> {
+    let mut listing = format!("tool {}\n", env!("CARGO_PKG_VERSION"));
+    listing.push_str(&format!("page {}\n", cache::content_hash(fs::read(html_path)?)));
+    if config.social_card.enabled {
+        listing.push_str(&format!("card {}\n", cache::content_hash(fs::read(builder::card_path(html_path))?)));
+    }
+    listing.push_str(&format!("footer {}\n", cache::content_hash(footer)));
+    let settings = serde_json::to_string(config).map_err(|e| AppError::BuildError(e.to_string()))?;
+    listing.push_str(&format!("config {}\n", cache::content_hash(settings)));
+    let build_dir = html_path.parent().unwrap_or(Path::new("."));
+    let assets_dir = build_dir.join("assets");
+    if assets_dir.is_dir() {
+        for file in assets::list_files(&assets_dir, build_dir, true)? {
+            listing.push_str(&format!("asset {} {}\n", file.display(), cache::content_hash(fs::read(assets_dir.join(&file))?)));
+        }
+    }
+    Ok(cache::content_hash(listing))
+}
+
+/// Where the files of the last successful render are kept, with their digest.
+fn cache_dir(build_dir: &Path) -> PathBuf {
+    build_dir.join(".cache").join("last-render")
+}
+
+/// The files written by the browser render, with their name in the cache.
+fn render_outputs(html_path: &Path, config: &Config) -> Vec<(&'static str, PathBuf)> {
+    let pdf_path = html_path.with_extension("pdf");
+    let mut outputs = vec![("pagemap.json", html_path.with_file_name("pagemap.json"))];
+    if config.output.search_index {
+        outputs.push(("index.json", html_path.with_file_name("index.json")));
+    }
+    if config.output.nup.is_some() {
+        outputs.push(("handout.pdf", builder::handout_path(&pdf_path)));
+    }
+    if config.social_card.enabled {
+        outputs.push(("card.png", builder::card_path(html_path).with_extension("png")));
+    }
+    outputs.push(("document.pdf", pdf_path));
+    outputs
+}
+
+/// Writes the files of the last successful render back, when it had the same `digest`.
+/// Returns whether they were restored.
+pub fn restore(build_dir: &Path, digest: &str, html_path: &Path, config: &Config) -> Result<bool, AppError> {
+    let dir = cache_dir(build_dir);
+    if fs::read_to_string(dir.join("digest")).ok().as_deref() != Some(digest) {
+        return Ok(false);
+    }
+    let outputs = render_outputs(html_path, config);
+    if outputs.iter().any(|(name, _)| !dir.join(name).is_file()) || (config.thumbnails.enabled && !dir.join("thumbnails").is_dir()) {
+        return Ok(false);
+    }
+    for (name, path) in outputs {
+        fs::copy(dir.join(name), path)?;
+    }
+    if config.thumbnails.enabled {
+        copy_images(&dir.join("thumbnails"), &thumbnails_dir(html_path))?;
+    }
+    Ok(true)
+}
+
+/// Keeps the files of a successful render for the next builds.
+pub fn store(build_dir: &Path, digest: &str, html_path: &Path, config: &Config) -> Result<(), AppError> {
+    let dir = cache_dir(build_dir);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir)?;
+    for (name, path) in render_outputs(html_path, config) {
+        fs::copy(path, dir.join(name))?;
+    }
+    if config.thumbnails.enabled {
+        copy_images(&thumbnails_dir(html_path), &dir.join("thumbnails"))?;
+    }
+    // Written last, so that an interrupted store is never taken for a complete one.
+    fs::write(dir.join("digest"), digest)?;
+    Ok(())
+}
+
+/// The page images of the render, a directory of their own.
+fn thumbnails_dir(html_path: &Path) -> PathBuf {
+    html_path.with_file_name("thumbnails")
+}
+
+/// Replaces the files of `to` with those of `from`.
+fn copy_images(from: &Path, to: &Path) -> Result<(), AppError> {
+    let _ = fs::remove_dir_all(to);
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        fs::copy(entry.path(), to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_restore_after_store() {
+        let test_dir = TestDir::new("build_cache");
+        let dir = test_dir.path();
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        let config: Config = serde_yaml::from_str("title: Guide\nauthor: Ada\nlanguage: en\ntheme: default\nsyntax_theme: InspiredGitHub\nsource: main.md\noutput:\n  filename: guide\n").unwrap();
+        let html_path = dir.join("guide.html");
+        fs::write(&html_path, "<img src=\"assets/logo.png\">").unwrap();
+        fs::write(dir.join("assets/logo.png"), "logo").unwrap();
+        fs::write(dir.join("guide.pdf"), "%PDF-1").unwrap();
+        fs::write(dir.join("pagemap.json"), "[]").unwrap();
+
+        let digest = render_digest(&html_path, &config, "").unwrap();
+        assert!(!restore(dir, &digest, &html_path, &config).unwrap());
+        store(dir, &digest, &html_path, &config).unwrap();
+        fs::write(dir.join("guide.pdf"), "overwritten").unwrap();
+        assert!(restore(dir, &digest, &html_path, &config).unwrap());
+        assert_eq!(fs::read_to_string(dir.join("guide.pdf")).unwrap(), "%PDF-1");
+
+        assert_eq!(render_digest(&html_path, &config, "").unwrap(), digest);
+        assert_ne!(render_digest(&html_path, &config, "Printed today").unwrap(), digest);
+        fs::write(dir.join("assets/logo.png"), "new logo").unwrap();
+        assert_ne!(render_digest(&html_path, &config, "").unwrap(), digest);
+    }
+}
@@ -1,521 +1,702 @@
+use crate::acronyms;
+use crate::assets;
+use crate::authorities;
+use crate::boilerplate;
+use crate::book;
+use crate::browser::BrowserConfig;
+use crate::build_cache;
+use crate::cache::Cache;
+use crate::cancel::CancelToken;
+use crate::changelog;
+use crate::charts;
+use crate::continued;
+use crate::critic::{self, CommentMode};
+use crate::css_lint;
+use crate::diagrams;
+use crate::directives::Directive;
+use crate::email;
+use crate::encoding;
 use crate::error::AppError;
-use crate::Config;
+use crate::fences;
+use crate::fragments;
+use crate::git::GitInfo;
+use crate::i18n;
+use crate::imposition;
+use crate::inline;
+use crate::jsonschema;
+use crate::latex;
+use crate::man;
+use crate::notebook;
+use crate::numbering;
+use crate::openapi;
+use crate::org;
+use crate::pagemap;
+use crate::paths;
+use crate::plaintext;
+use crate::provenance;
+use crate::raster;
+use crate::remote;
+use crate::render::{self, PageStyles};
+use crate::resume;
+use crate::rustdoc;
+use crate::screenshot::{self, ScreenshotsConfig};
+use crate::site;
+use crate::social;
+use crate::terminal;
+use crate::variables;
+use crate::warnings;
+use crate::{Config, LimitsConfig, OutputFormat};
 use axum::{routing::get_service, Router};
 use colored::*;
-use headless_chrome::{Browser, LaunchOptions, types::PrintToPdfOptions};
+use headless_chrome::{Browser, Tab, types::PrintToPdfOptions};
 use indicatif::ProgressBar;
-use pulldown_cmark::{html, Options, Parser};
-use regex::Regex;
-use scraper::{Html, Selector};
-use std::collections::HashSet;
+use pdfbuilder_core::preprocess::{self, Expansion, Host};
+use pdfbuilder_core::CoreError;
+use std::collections::HashMap;
 use std::fs;
+use std::io::{BufWriter, Write};
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use syntect::highlighting::ThemeSet;
-use syntect::html::{css_for_theme_with_class_style, highlighted_html_for_string, ClassStyle};
-use syntect::parsing::SyntaxSet;
 use tower_http::services::ServeDir;
 
-const DEFAULT_THEME_CSS: &str = r#"/* Simple Dark Theme */
-body { background-color: #1a1a1a; color: #f2f2f2; font-family: 'Georgia', 'Times New Roman', serif; line-height: 1.6; padding: 2em; max-width: 800px; margin: 0 auto; }
-h1, h2, h3 { color: #ffa500; font-family: 'Georgia', 'Times New Roman', serif; }
-h1 { font-size: 2.5em; text-align: center; margin-bottom: 1.5em; border-bottom: 3px solid #ffa500; padding-bottom: 0.5em; }
-h2 { font-size: 1.8em; margin-top: 2em; margin-bottom: 1em; }
-h3 { font-size: 1.4em; margin-top: 1.5em; margin-bottom: 0.8em; }
-code { background-color: #2a2a2a; padding: 2px 4px; border-radius: 4px; font-family: 'Monaco', 'Consolas', monospace; }
-pre { background-color: #2a2a2a; padding: 1em; border-radius: 8px; overflow-x: auto; }
-p { text-align: justify; margin-bottom: 1em; }
-
-/* Table des matières stylisée */
-.toc { 
-    background: linear-gradient(135deg, #2a2a2a 0%, #1a1a1a 100%); 
-    border: 2px solid #ffa500; 
-    border-radius: 12px; 
-    padding: 2.5em; 
-    margin: 3em 0; 
-    font-family: 'Georgia', 'Times New Roman', serif;
-    box-shadow: 0 8px 32px rgba(255, 165, 0, 0.1);
+/// What a successful build produced, for the status line of watch mode.
+#[derive(Debug, Clone, Default)]
+pub struct BuildStats {
+    /// Pages of the PDF, for PDF builds.
+    pub pages: Option<usize>,
+    pub warnings: usize,
 }
 
-.toc-title { 
-    color: #ffa500; 
-    font-size: 2em; 
-    font-weight: bold; 
-    text-align: center; 
-    margin: 0 0 1.5em 0; 
-    text-transform: uppercase; 
-    letter-spacing: 2px;
-    border-bottom: 3px solid #ffa500;
-    padding-bottom: 0.5em;
+/// Builds the document. The build stops with `AppError::BuildCancelled` when `cancel` is set.
+pub async fn run_build(config: &Config, format: OutputFormat, cancel: &CancelToken) -> Result<BuildStats, AppError> {
+    // Define the project root as the current working directory.
+    // All file operations will be relative to this root.
+    let project_root = std::env::current_dir()?;
+    let git_info = GitInfo::collect();
+
+    let (result, warnings) = warnings::collect(async {
+        let full_markdown = expand_markdown(&project_root, Path::new(&config.source), config, &git_info)?;
+        let extra_css = if config.book.is_none() && resume::is_resume(Path::new(&config.source)) { resume::RESUME_CSS } else { "" };
+        render_document(config, full_markdown, &git_info, extra_css, format, cancel).await
+    }).await;
+    let warnings = warnings::print_summary(&warnings);
+    result?;
+    let pages = match format {
+        OutputFormat::Pdf => pagemap::page_count(&config.build_dir().join(format!("{}.pdf", output_filename(config, &git_info)))),
+        _ => None,
+    };
+    Ok(BuildStats { pages, warnings })
 }
 
-.toc-content {
-    font-size: 1.1em;
-    line-height: 1.8;
+/// Resolves includes, directives and template variables of a source file (or of the
+/// `book:` structure when configured) into a single Markdown document.
+pub fn expand_markdown(project_root: &Path, source: &Path, config: &Config, git_info: &GitInfo) -> Result<String, AppError> {
+    expand_markdown_with(project_root, source, config, &variables::builtin_variables(config, git_info))
 }
 
-.toc-entry {
-    display: flex;
-    justify-content: space-between;
-    align-items: baseline;
-    margin: 0.8em 0;
-    padding: 0.4em 0;
-    border-bottom: 1px dotted #555;
+/// `expand_markdown` with the template variables given, e.g. the fields of a mail-merge record.
+pub fn expand_markdown_with(project_root: &Path, source: &Path, config: &Config, vars: &HashMap<String, String>) -> Result<String, AppError> {
+    expand_with_includes(project_root, source, config, vars, includes_for(config))
 }
 
-.toc-entry:last-child {
-    border-bottom: none;
+/// The include state of a new expansion with the settings of `config`.
+pub fn includes_for(config: &Config) -> Includes {
+    let mut includes = Includes::new(config.follow_symlinks).with_limits(config.limits);
+    if config.allow_remote_includes {
+        includes = includes.with_remote_includes(remote::cache_dir(&config.build_dir()));
+    }
+    includes = includes.with_screenshots(config.screenshots.clone(), config.browser.clone(), Cache::in_build_dir(&config.build_dir()));
+    if !config.sanitize_html_fragments {
+        includes = includes.with_unsanitized_fragments();
+    }
+    if config.book.is_none() && resume::is_resume(Path::new(&config.source)) {
+        includes.files.resume_source = Some(PathBuf::from(&config.source));
+    }
+    includes
 }
 
-.toc-entry-h1 {
-    font-weight: bold;
-    font-size: 1.2em;
-    color: #ffa500;
-    margin: 1.2em 0;
-    padding: 0.6em 0;
-    border-bottom: 2px solid #ffa500;
+/// `expand_markdown_with` starting from the include state given.
+pub fn expand_with_includes(project_root: &Path, source: &Path, config: &Config, vars: &HashMap<String, String>, mut includes: Includes) -> Result<String, AppError> {
+    let mut full_markdown = match &config.book {
+        Some(book_config) => book::assemble_book(project_root, book_config, &mut includes)?,
+        None => preprocess_markdown(project_root, source, &mut includes)?,
+    };
+    full_markdown = boilerplate::insert(project_root, &config.boilerplate, full_markdown, &mut includes)?;
+    full_markdown = boilerplate::append_back_matter(project_root, &config.back_matter, config.back_matter_numbered, full_markdown, &mut includes)?;
+    if let Some(dedication) = &config.dedication {
+        full_markdown = boilerplate::insert_dedication(dedication, full_markdown);
+    }
+    full_markdown = variables::substitute(&full_markdown, vars);
+    full_markdown = acronyms::expand(&full_markdown, &config.acronyms, &config.language);
+    if config.numbering {
+        full_markdown = numbering::number_headings(&full_markdown);
+    }
+    Ok(full_markdown)
 }
 
-.toc-entry-h2 {
-    font-weight: 600;
-    color: #e0e0e0;
-    margin-left: 1em;
-}
+/// Turns expanded Markdown into the HTML and PDF outputs. `extra_css` is appended after the theme styles.
+/// With the HTML formats no PDF is generated; `OutputFormat::HtmlInline` also makes the HTML self-contained
+/// and `OutputFormat::EmailHtml` turns it into an email.
+/// Each pass replaces the Markdown, so only the latest version is kept in memory.
+/// `cancel` is checked between the passes and while the browser prints.
+pub async fn render_document(config: &Config, full_markdown: String, git_info: &GitInfo, extra_css: &str, format: OutputFormat, cancel: &CancelToken) -> Result<(), AppError> {
+    let started = chrono::Utc::now();
+    // Text formats need neither the HTML page nor the browser.
+    let text_output = match format {
+        OutputFormat::Man => Some(("Generated man page:", man::write_man_page(config, &full_markdown, git_info)?)),
+        OutputFormat::Txt => Some(("Generated text file:", plaintext::write_text(config, &full_markdown, git_info)?)),
+        _ => None,
+    };
+    if let Some((label, path)) = text_output {
+        if config.output.provenance {
+            provenance::write_sidecars(config, std::slice::from_ref(&path), git_info, started)?;
+        }
+        println!("\n{}", "--------------------------------------------------".green());
+        println!("{} ", "Build completed successfully!".green());
+        println!("{} {}", label.cyan(), path.display().to_string().yellow());
+        println!("{} ", "--------------------------------------------------".green());
+        return Ok(());
+    }
+    let output_html_path = render_html(config, full_markdown, git_info, extra_css, format, cancel)?;
+    let build_dir = config.build_dir();
+
+    if format != OutputFormat::Pdf {
+        let (label, output_path) = match format {
+            OutputFormat::HtmlInline => ("Generated self-contained HTML file:", output_html_path),
+            OutputFormat::EmailHtml => ("Generated email:", email::write_email(config, &output_html_path)?),
+            _ => ("Generated HTML file:", output_html_path),
+        };
+        if config.output.provenance {
+            provenance::write_sidecars(config, std::slice::from_ref(&output_path), git_info, started)?;
+        }
 
-.toc-entry-h3 {
-    color: #c0c0c0;
-    margin-left: 2em;
-    font-style: italic;
-}
+        println!("\n{}", "--------------------------------------------------".green());
+        println!("{} ", "Build completed successfully!".green());
+        println!("{} {}", label.cyan(), output_path.display().to_string().yellow());
+        if format == OutputFormat::Html && config.html.site {
+            println!("{} {}", "Generated site:".cyan(), build_dir.join("index.html").display().to_string().yellow());
+        }
+        println!("{} ", "--------------------------------------------------".green());
+        return Ok(());
+    }
 
-.toc-entry-h4 {
-    color: #a0a0a0;
-    margin-left: 3em;
-    font-size: 0.95em;
-}
+    cancel.check()?;
+    let digest = match config.output.build_cache {
+        true => Some(build_cache::render_digest(&output_html_path, config, &footer_template(config, git_info))?),
+        false => None,
+    };
+    let reused = match &digest {
+        Some(digest) => build_cache::restore(&build_dir, digest, &output_html_path, config)?,
+        None => false,
+    };
+    if reused {
+        println!("{} {}", "Unchanged since the last build, reused:".cyan(), output_html_path.with_extension("pdf").display().to_string().yellow());
+    } else {
+        let browser = launch_browser(&config.browser)?;
+        // Chrome calls block until they complete; a cancelled build kills the browser to return early.
+        let _kill_guard = cancel.kill_on_cancel(browser.get_process_id());
+        let pb = ProgressBar::new_spinner();
+        pb.set_message(format!("{}", "Starting PDF conversion...".blue()));
+        pb.enable_steady_tick(std::time::Duration::from_millis(100));
+        build_pdf_from_html(&browser, &output_html_path, config, git_info, cancel, &pb).await?;
+        if let Some(digest) = &digest {
+            build_cache::store(&build_dir, digest, &output_html_path, config)?;
+        }
+    }
 
-.toc-entry-title {
-    flex: 1;
-    margin-right: 1em;
-}
+    if let Some(archive_dir) = &config.output.archive_dir {
+        let archived = archive_output(&output_html_path.with_extension("pdf"), Path::new(archive_dir))?;
+        println!("{} {}", "Archived PDF file:".cyan(), archived.display().to_string().yellow());
+    }
 
-.toc-entry-dots {
-    flex-grow: 1;
-    border-bottom: 2px dotted #666;
-    margin-left: 1em;
-}
+    if config.output.provenance {
+        let pdf_path = output_html_path.with_extension("pdf");
+        let mut outputs = vec![output_html_path.clone(), pdf_path.clone()];
+        if config.output.nup.is_some() {
+            outputs.push(handout_path(&pdf_path));
+        }
+        provenance::write_sidecars(config, &outputs, git_info, started)?;
+    }
+
+    println!("\n{}", "--------------------------------------------------".green());
+    println!("{} ", "Build completed successfully!".green());
+    println!("{} {}", "Generated HTML file:".cyan(), output_html_path.display().to_string().yellow());
+    println!("{} {}", "Generated PDF file:".cyan(), output_html_path.with_extension("pdf").display().to_string().yellow());
+    println!("{} ", "--------------------------------------------------".green());
 
-.page-break { 
-    page-break-before: always !important; 
-    height: 0; 
-    overflow: hidden; 
-    line-height: 0; 
+    Ok(())
 }
 
-@media print {
-    body { 
-        color: black; 
-        background: white;
-        padding: 0; 
-        margin: 0; 
-        -webkit-print-color-adjust: exact; 
-        print-color-adjust: exact; 
-        max-width: none;
+/// Runs the Markdown passes, writes the HTML page into the build directory and mirrors the
+/// assets it uses; `OutputFormat::HtmlInline` pages are made self-contained. Returns the path
+/// of the page.
+pub fn render_html(config: &Config, mut full_markdown: String, git_info: &GitInfo, extra_css: &str, format: OutputFormat, cancel: &CancelToken) -> Result<PathBuf, AppError> {
+    cancel.check()?;
+    let output_name = output_filename(config, git_info);
+    let build_dir = config.build_dir();
+    let cache = Cache::in_build_dir(&build_dir);
+    full_markdown = fences::replace_fenced_blocks(&full_markdown, |info, code| {
+        cancel.check()?;
+        render_fenced_block(config, &cache, info, code)
+    })?;
+    full_markdown = render::apply_text_passes(&config.render_config(), &full_markdown, format)?;
+    cancel.check()?;
+    let output_html_path = build_html(config, full_markdown, &output_name, extra_css, git_info, format)?;
+
+    // The passes below share one read of the page, written back only when they change it.
+    let fingerprint = format == OutputFormat::Html && config.html.fingerprint_assets;
+    let site = format == OutputFormat::Html && config.html.site;
+    let mut page = String::new();
+    if !config.assets.copy_all || fingerprint || site || format == OutputFormat::HtmlInline {
+        page = fs::read_to_string(&output_html_path)?;
     }
-    
-    .page-break { 
-        page-break-before: always !important; 
-        height: 0; 
-        overflow: hidden; 
-        line-height: 0; 
+
+    // Only the assets the page, its social card and the page footer refer to are copied, unless `assets.copy_all` is set.
+    let referenced = if config.assets.copy_all {
+        None
+    } else {
+        let mut referenced = assets::referenced_assets(&page);
+        if let Ok(card) = fs::read_to_string(card_path(&output_html_path)) {
+            referenced.extend(assets::referenced_assets(&card));
+        }
+        referenced.extend(assets::referenced_assets(&footer_template(config, git_info)));
+        Some(referenced)
+    };
+    assets::mirror_assets(Path::new("assets"), &build_dir.join("assets"), &build_dir.join(".cache").join("assets.json"), referenced.as_ref(), Path::new("."), config.follow_symlinks)?;
+    if fingerprint {
+        page = assets::fingerprint_assets(&page, &build_dir.join("assets"), &build_dir.join(".cache").join("fingerprints.json"))?;
+        fs::write(&output_html_path, &page)?;
     }
-    
-    h1 { 
-        page-break-before: always; 
-        color: #333;
-        border-bottom: 3px solid #333;
+    if format == OutputFormat::HtmlInline {
+        fs::write(&output_html_path, inline::inline_resources(&page, &build_dir))?;
     }
-    
-    h2, h3 { color: #333; }
-    
-    p, li { 
-        orphans: 3; 
-        widows: 3; 
+    if site {
+        site::write_site(&page, &build_dir, config)?;
     }
-    
-    pre, code { 
-        background-color: #f0f0f0; 
-        border: 1px solid #ddd; 
-        page-break-inside: avoid; 
-        color: black;
+    Ok(output_html_path)
+}
+
+/// Renders fenced blocks that are not source code (charts, diagrams) into figures.
+/// Configured `renderers` come first, so they can replace the built-in ones.
+fn render_fenced_block(config: &Config, cache: &Cache, info: &str, code: &str) -> Result<Option<String>, AppError> {
+    if let Some(figure) = diagrams::render_custom_block(&config.renderers, cache, info, code)? {
+        return Ok(Some(figure));
     }
-    
-    .toc { 
-        background: white; 
-        border: 2px solid #333; 
-        box-shadow: none;
-        page-break-inside: avoid;
+    if let Some(chart) = charts::render_chart_block(info, code)? {
+        return Ok(Some(chart));
     }
-    
-    .toc-title {
-        color: #333;
-        border-bottom: 3px solid #333;
+    if let Some(latex) = &config.latex {
+        if let Some(figure) = latex::render_latex_block(latex, cache, info, code)? {
+            return Ok(Some(figure));
+        }
     }
-    
-    .toc-entry {
-        border-bottom: 1px dotted #333;
+    diagrams::render_diagram_block(&config.diagrams, cache, info, code)
+}
+
+/// State of an include expansion, and what it may read.
+#[derive(Debug)]
+pub struct Includes {
+    expansion: Expansion,
+    files: ProjectFiles,
+}
+
+/// The files of the project, read by the expansion.
+#[derive(Debug)]
+struct ProjectFiles {
+    /// Whether included files may be (or be reached through) symbolic links.
+    follow_symlinks: bool,
+    /// Where downloaded includes are kept, when `allow_remote_includes` is set.
+    remote_cache: Option<PathBuf>,
+    /// The allowed hosts of `!screenshot`, how it starts the browser and where its captures are kept.
+    screenshots: Option<(ScreenshotsConfig, BrowserConfig, Cache)>,
+    /// Whether `!html` fragments are sanitized.
+    sanitize_fragments: bool,
+    /// Where images are looked up besides the directory of their file, instead of the project root.
+    image_root: Option<PathBuf>,
+    /// Whether a missing image fails the expansion rather than raising a warning.
+    missing_images_fatal: bool,
+    /// The `source` of the configuration when it is a resume; other YAML and JSON files are included as they are.
+    resume_source: Option<PathBuf>,
+}
+
+impl Includes {
+    pub fn new(follow_symlinks: bool) -> Self {
+        let files = ProjectFiles { follow_symlinks, remote_cache: None, screenshots: None, sanitize_fragments: true, image_root: None, missing_images_fatal: false, resume_source: None };
+        Self { expansion: Expansion::default(), files }
     }
-    
-    .toc-entry-h1 {
-        color: #333;
-        border-bottom: 2px solid #333;
+
+    pub fn with_limits(mut self, limits: LimitsConfig) -> Self {
+        self.expansion = Expansion::new(limits);
+        self
     }
-    
-    .toc-entry-h2 {
-        color: #333;
+
+    /// Allows `!include(https://...)`, keeping the downloaded files in `cache_dir`.
+    pub fn with_remote_includes(mut self, cache_dir: PathBuf) -> Self {
+        self.files.remote_cache = Some(cache_dir);
+        self
     }
-    
-    .toc-entry-h3 {
-        color: #666;
+
+    /// Allows `!screenshot` of the hosts of `config`, keeping the captures in `cache`.
+    pub fn with_screenshots(mut self, config: ScreenshotsConfig, browser: BrowserConfig, cache: Cache) -> Self {
+        self.files.screenshots = Some((config, browser, cache));
+        self
     }
-    
-    .toc-entry-h4 {
-        color: #999;
+
+    /// Keeps scripts and event handlers in `!html` fragments.
+    pub fn with_unsanitized_fragments(mut self) -> Self {
+        self.files.sanitize_fragments = false;
+        self
     }
-}"#;
 
-#[derive(Debug, Clone)]
-struct TocEntry {
-    level: u8,
-    title: String,
-    children: Vec<TocEntry>,
-}
+    /// Looks images up in `root` rather than in the project root, for documents expanded
+    /// outside the project that still use its assets.
+    pub fn with_image_root(mut self, root: PathBuf) -> Self {
+        self.files.image_root = Some(root);
+        self
+    }
 
-pub async fn run_build(config: &Config) -> Result<(), AppError> {
-    // Define the project root as the current working directory.
-    // All file operations will be relative to this root.
-    let project_root = std::env::current_dir()?;
-    
-    let full_markdown = preprocess_markdown(&project_root, &config.source, &mut HashSet::new())?;
-    let assets_source_dir = PathBuf::from("assets");
-    let assets_dest_dir = PathBuf::from("build").join("assets");
-    copy_assets_optimized(&assets_source_dir, &assets_dest_dir)?;
-    let (html_content, output_html_path) = build_html(config, &full_markdown)?;
-    build_pdf_from_html(&html_content, &output_html_path, config).await?;
+    /// Fails on missing images instead of warning about them, as `check` does.
+    pub fn with_missing_images_fatal(mut self) -> Self {
+        self.files.missing_images_fatal = true;
+        self
+    }
 
-    println!("\n{}", "--------------------------------------------------".green());
-    println!("{} ", "Build completed successfully!".green());
-    println!("{} {}", "Generated HTML file:".cyan(), output_html_path.display().to_string().yellow());
-    println!("{} {}", "Generated PDF file:".cyan(), output_html_path.with_extension("pdf").display().to_string().yellow());
-    println!("{} ", "--------------------------------------------------".green());
+    /// Fails once `content` is larger than `limits.max_expanded_size`.
+    pub fn check_size(&self, content: &str) -> Result<(), AppError> {
+        Ok(self.expansion.check_size(content)?)
+    }
+}
 
-    Ok(())
+/// Expands the includes and directives of a source file, which must be in the project.
+/// Included files are wrapped in a `<section class="chapter" data-src="...">` element.
+pub fn preprocess_markdown(project_root: &Path, file_path: &Path, includes: &mut Includes) -> Result<String, AppError> {
+    Ok(preprocess::preprocess_markdown(project_root, file_path, &mut includes.expansion, &mut includes.files)?)
 }
 
-fn preprocess_markdown(project_root: &Path, file_path: &str, visited: &mut HashSet<String>) -> Result<String, AppError> {
-    if !visited.insert(file_path.to_string()) {
-        return Err(AppError::BuildError(format!("Circular dependency detected: '{file_path}'")));
-    }
+/// Expands a file of a book (chapter, front or back matter...) wrapped in a chapter section
+/// with the extra `classes`.
+pub fn preprocess_chapter(project_root: &Path, file_path: &Path, includes: &mut Includes, classes: &str) -> Result<String, AppError> {
+    Ok(preprocess::preprocess_chapter(project_root, file_path, &mut includes.expansion, &mut includes.files, classes)?)
+}
 
-    #[cfg(not(test))]
-    println!("{} {}", "Processing:".blue(), file_path.yellow());
-    
-    let content = fs::read_to_string(file_path).map_err(|_| AppError::SourceNotFound(file_path.to_string()))?;
-    let include_re = Regex::new(r"^\s*!include\(([^)]+)\)\s*$").map_err(|e| AppError::BuildError(e.to_string()))?;
+impl Host for ProjectFiles {
+    fn resolve(&mut self, project_root: &Path, file_path: &Path) -> Result<PathBuf, CoreError> {
+        Ok(paths::resolve_within(project_root, file_path, self.follow_symlinks)?)
+    }
 
-    let mut full_content = String::new();
-    let mut in_code_block = false;
+    fn read_source(&mut self, file_path: &Path) -> Result<String, CoreError> {
+        #[cfg(not(test))]
+        println!("{} {}", "Processing:".blue(), file_path.display().to_string().yellow());
+        Ok(read_source(file_path, self.resume_source.as_deref() == Some(file_path))?)
+    }
 
-    for line in content.lines() {
-        if line.trim().starts_with("```") {
-            in_code_block = !in_code_block;
-        }
+    fn read_stylesheet(&mut self, project_root: &Path, path: &Path) -> Result<String, CoreError> {
+        let resolved = paths::resolve_within(project_root, path, self.follow_symlinks)?;
+        Ok(fs::read_to_string(&resolved).map_err(|_| AppError::SourceNotFound(path.display().to_string()))?)
+    }
 
-        if !in_code_block {
-            if include_re.is_match(line) {
-                if let Some(caps) = include_re.captures(line) {
-                    let include_path_str = caps.get(1).unwrap().as_str();
-                    
-                    let base_path = Path::new(file_path).parent().unwrap_or_else(|| Path::new(""));
-                    let include_path = base_path.join(include_path_str);
-                    
-                    // --- Security: Path Traversal Check ---
-                    let canonical_path = path_clean::clean(include_path.to_str().unwrap());
-                    let canonical_path = project_root.join(canonical_path);
-
-                    if !canonical_path.starts_with(project_root) {
-                        return Err(AppError::BuildError(format!("Unauthorized file access attempt: {}", include_path.display())));
-                    }
-                    // --- End of check ---
-                    
-                    let included_content = preprocess_markdown(project_root, include_path.to_str().unwrap_or(""), visited)?;
-                    full_content.push_str(&included_content);
-                    full_content.push('\n');
-                }
-            } else if line.trim() == "!newpage" {
-                // Replace the directive with a div for the page break
-                full_content.push_str("<div class=\"page-break\"></div>\n");
-            } else if line.trim() == "!toc" {
-                // Replace the directive with a placeholder
-                full_content.push_str("<!--TOC_PLACEHOLDER-->\n");
-            } else {
-                full_content.push_str(line);
-                full_content.push('\n');
+    /// Warns about images found neither next to the Markdown file nor in `image_root`, or
+    /// fails with `missing_images_fatal`.
+    fn check_image(&mut self, project_root: &Path, file_path: &Path, line: usize, image: &str) -> Result<(), CoreError> {
+        let path = image.split(['?', '#']).next().unwrap_or(image).replace("%20", " ");
+        let base_dir = file_path.parent().unwrap_or_else(|| Path::new(""));
+        let image_root = self.image_root.as_deref().unwrap_or(project_root);
+        if !base_dir.join(&path).exists() && !image_root.join(path.trim_start_matches('/')).exists() {
+            if self.missing_images_fatal {
+                return Err(AppError::ImageMissing { file: file_path.display().to_string(), line, image: image.to_string() }.into());
             }
-        } else {
-            full_content.push_str(line);
-            full_content.push('\n');
+            warnings::warn("Image not found", format!("'{image}' ({}, line {line})", file_path.display()));
         }
+        Ok(())
     }
-    Ok(full_content)
-}
 
-fn generate_toc_from_html(html: &str) -> String {
-    let document = Html::parse_document(html);
-    let heading_selector = Selector::parse("h1, h2, h3, h4, h5, h6").unwrap();
-    
-    let mut toc_entries = Vec::new();
-    let mut current_section_count = 0;
-    
-    for element in document.select(&heading_selector) {
-        let tag_name = element.value().name();
-        let level = match tag_name {
-            "h1" => 1,
-            "h2" => 2,
-            "h3" => 3,
-            "h4" => 4,
-            "h5" => 5,
-            "h6" => 6,
-            _ => continue,
+    fn include_url(&mut self, url: &str) -> Result<String, CoreError> {
+        let cache_dir = self.remote_cache.as_deref()
+            .ok_or_else(|| AppError::BuildError(format!("Cannot include '{url}': set 'allow_remote_includes: true' in 'config.yaml' to include URLs")))?;
+        Ok(remote::fetch(cache_dir, url)?)
+    }
+
+    fn expand_directive(&mut self, project_root: &Path, file_path: &Path, directive: &Directive) -> Option<Result<String, CoreError>> {
+        // Files named by directives are read like includes: next to the file, inside the project.
+        let source_dir = paths::SourceDir { project_root, dir: file_path.parent().unwrap_or_else(|| Path::new("")), follow_symlinks: self.follow_symlinks };
+        let expanded = match directive.name {
+            "screenshot" => match &self.screenshots {
+                Some((config, browser, cache)) => screenshot::screenshot_directive(directive, config, browser, cache),
+                None => Err(AppError::BuildError("!screenshot is only available when building a document".to_string())),
+            },
+            "changelog" => changelog::changelog_directive(directive),
+            "openapi" => openapi::openapi_directive(source_dir, directive),
+            "jsonschema" => jsonschema::jsonschema_directive(source_dir, directive),
+            "rustdoc" => rustdoc::rustdoc_directive(source_dir, directive),
+            "html" => fragments::html_directive(source_dir, directive, self.sanitize_fragments),
+            "asciinema" => terminal::asciinema_directive(source_dir, directive),
+            _ => return None,
         };
-        
-        let title = element.text().collect::<String>().trim().to_string();
-        if title.is_empty() {
-            continue;
-        }
-        
-        // Check if this heading is inside a section
-        let is_in_section = element.parent().and_then(|parent| {
-            parent.ancestors().find(|ancestor| {
-                ancestor.value().as_element().map_or(false, |el| el.name() == "section")
-            })
-        }).is_some();
-        
-        if level == 2 && is_in_section {
-            // This is a section heading, count it
-            if title.to_lowercase().starts_with("titre 1") || title.to_lowercase().contains("section") {
-                current_section_count += 1;
-                let section_title = format!("section {}", current_section_count);
-                toc_entries.push(TocEntry {
-                    level,
-                    title: section_title,
-                    children: Vec::new(),
-                });
-            } else {
-                toc_entries.push(TocEntry {
-                    level,
-                    title,
-                    children: Vec::new(),
-                });
-            }
-        } else {
-            toc_entries.push(TocEntry {
-                level,
-                title,
-                children: Vec::new(),
-            });
-        }
+        Some(expanded.map_err(CoreError::from))
     }
-    
-    // Build hierarchical structure
-    let hierarchical_toc = build_toc_hierarchy(toc_entries);
-    
-    // Generate TOC HTML
-    generate_toc_html(&hierarchical_toc)
 }
 
-fn build_toc_hierarchy(entries: Vec<TocEntry>) -> Vec<TocEntry> {
-    let mut result = Vec::new();
-    let mut stack: Vec<TocEntry> = Vec::new();
-    
-    for entry in entries {
-        // Pop items from stack until we find a parent or the stack is empty
-        while let Some(last) = stack.last() {
-            if last.level < entry.level {
-                break;
-            }
-            if let Some(parent) = stack.pop() {
-                if let Some(grandparent) = stack.last_mut() {
-                    grandparent.children.push(parent);
-                } else {
-                    result.push(parent);
-                }
-            }
-        }
-        
-        stack.push(entry);
+/// Reads a source file as Markdown, converting other supported formats (Jupyter notebooks, Org-mode,
+/// and with `as_resume` a JSON Resume in YAML or JSON, which only the document `source` can be).
+/// Files that are not UTF-8 are transcoded with a warning; line endings and Unicode are normalized.
+pub fn read_source(file_path: &Path, as_resume: bool) -> Result<String, AppError> {
+    let bytes = fs::read(file_path).map_err(|_| AppError::SourceNotFound(file_path.display().to_string()))?;
+    let (content, transcoded) = encoding::decode(&bytes);
+    if let Some(encoding) = transcoded {
+        warnings::warn("Source not UTF-8", format!("'{}' read as {encoding}", file_path.display()));
     }
-    
-    // Push remaining items from stack to result
-    while let Some(entry) = stack.pop() {
-        if let Some(parent) = stack.last_mut() {
-            parent.children.push(entry);
-        } else {
-            result.push(entry);
-        }
+    match file_path.extension().and_then(|e| e.to_str()) {
+        Some("ipynb") => notebook::notebook_to_markdown(&content, &file_path.display().to_string()),
+        Some("org") => Ok(org::org_to_markdown(&content)),
+        Some(_) if as_resume && resume::is_resume(file_path) => resume::resume_to_markdown(&content, &file_path.display().to_string()),
+        _ => Ok(content),
     }
-    
-    result
 }
 
-fn generate_toc_html(entries: &[TocEntry]) -> String {
-    if entries.is_empty() {
-        return String::new();
-    }
-    
-    let mut html = String::from(r#"<div class="toc">
-<div class="toc-title">Table des matières</div>
-<div class="toc-content">"#);
-    
-    for entry in entries {
-        generate_toc_entry_html(&mut html, entry);
-    }
-    
-    html.push_str("</div>\n</div>");
-    html
+/// Resolves the `{version}` and `{date}` placeholders and the `{{ name }}` variables of `output.filename`.
+pub fn output_filename(config: &Config, git_info: &GitInfo) -> String {
+    render_output_template(&config.output.filename, config, git_info)
 }
 
-fn generate_toc_entry_html(html: &mut String, entry: &TocEntry) {
-    let class_name = format!("toc-entry toc-entry-h{}", entry.level);
-    
-    html.push_str(&format!(
-        r#"<div class="{}">
-    <span class="toc-entry-title">{}</span>
-    <span class="toc-entry-dots"></span>
-</div>"#,
-        class_name, entry.title
-    ));
-    
-    // Recursively add children
-    for child in &entry.children {
-        generate_toc_entry_html(html, child);
+/// Resolves the placeholders of `output.filename` in another template, e.g. a publish destination.
+pub fn render_output_template(template: &str, config: &Config, git_info: &GitInfo) -> String {
+    let version = variables::document_version(config, git_info);
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let expanded = expand_filename_template(template, &version, &date);
+    variables::render_template(&expanded, &variables::builtin_variables(config, git_info))
+}
+
+fn expand_filename_template(template: &str, version: &str, date: &str) -> String {
+    template.replace("{version}", version).replace("{date}", date)
+}
+
+/// `<name>-handout.pdf`, next to the PDF.
+pub fn handout_path(pdf_path: &Path) -> PathBuf {
+    pdf_path.with_file_name(format!("{}-handout.pdf", pdf_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default()))
+}
+
+/// Copies a generated file into the archive directory. If a file with the same name was
+/// archived before, a numeric suffix is added so earlier builds are never overwritten.
+fn archive_output(file: &Path, archive_dir: &Path) -> Result<PathBuf, AppError> {
+    fs::create_dir_all(archive_dir)?;
+    let file_name = file.file_name().ok_or_else(|| AppError::BuildError(format!("Invalid output file: {}", file.display())))?;
+    let mut dest = archive_dir.join(file_name);
+
+    let stem = file.file_stem().unwrap_or(file_name).to_string_lossy().to_string();
+    let extension = file.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    let mut counter = 2;
+    while dest.exists() {
+        dest = archive_dir.join(format!("{stem}-{counter}.{extension}"));
+        counter += 1;
     }
+
+    fs::copy(file, &dest)?;
+    Ok(dest)
 }
 
-fn build_html(config: &Config, markdown_content: &str) -> Result<(String, PathBuf), AppError> {
+/// Renders the Markdown into the HTML page with the project theme and writes it, returning its path.
+fn build_html(config: &Config, markdown_content: String, output_name: &str, extra_css: &str, git_info: &GitInfo, format: OutputFormat) -> Result<PathBuf, AppError> {
     #[cfg(not(test))]
     println!("{}", "Starting HTML build...".blue());
-    let build_dir = Path::new("build");
-    fs::create_dir_all(build_dir)?;
-
-    let theme_css_path = Path::new("themes").join(&config.theme).join("style.css");
-    let output_html_path = build_dir.join(format!("{}.html", config.output.filename));
-
-    let ss = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
-    let theme = ts.themes.get(&config.syntax_theme).ok_or_else(|| AppError::BuildError(format!("Syntax theme '{}' not found", config.syntax_theme)))?;
-
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_TASKLISTS);
-    let parser = Parser::new_ext(markdown_content, options);
-    let mut body_html = String::new();
-    html::push_html(&mut body_html, parser);
-
-    // Fix relative image paths
-    let img_re = Regex::new(r#"<img src=\".\\../([^\"]+)\""#).map_err(|e| AppError::BuildError(e.to_string()))?;
-    body_html = img_re.replace_all(&body_html, r#"<img src=\"$1\""#).to_string();
-
-    let fragment = Html::parse_fragment(&body_html);
-    let pre_selector = Selector::parse("pre").unwrap();
-    let code_selector = Selector::parse("code[class*='language-']").unwrap();
-    for pre_element in fragment.select(&pre_selector) {
-        if let Some(code_element) = pre_element.select(&code_selector).next() {
-            let lang = code_element.value().classes().find(|c| c.starts_with("language-")).map(|c| c.trim_start_matches("language-")).unwrap_or("text");
-            let code = code_element.text().collect::<String>();
-            let syntax = ss.find_syntax_by_token(lang).unwrap_or_else(|| ss.find_syntax_plain_text());
-            let highlighted_code = highlighted_html_for_string(&code, &ss, syntax, theme).map_err(|e| AppError::BuildError(e.to_string()))?;
-            body_html = body_html.replace(&pre_element.html(), &highlighted_code);
-        }
+    let build_dir = config.build_dir();
+    fs::create_dir_all(&build_dir)?;
+    let output_html_path = build_dir.join(format!("{}.html", output_name));
+
+    let theme = ThemeFiles::read(config);
+    let mut page = BufWriter::new(fs::File::create(&output_html_path)?);
+    let page_warnings = render::write_page(&config.render_config(), markdown_content, &theme.styles(extra_css), &variables::builtin_variables(config, git_info), format, &mut page)?;
+    warnings::report(page_warnings);
+    page.flush()?;
+    if config.social_card.enabled && format == OutputFormat::Pdf {
+        let template = match &config.social_card.template {
+            Some(path) => Some(fs::read_to_string(path).map_err(|_| AppError::SourceNotFound(path.clone()))?),
+            None => None,
+        };
+        fs::write(card_path(&output_html_path), social::card_html(config, &theme.styles(extra_css), template.as_deref()))?;
     }
 
-    // Generate and insert TOC
-    if body_html.contains("<!--TOC_PLACEHOLDER-->") {
-        let toc_html = generate_toc_from_html(&body_html);
-        body_html = body_html.replace("<!--TOC_PLACEHOLDER-->", &toc_html);
-    }
+    #[cfg(not(test))]
+    println!("{} {}", "Standalone HTML generated:".green(), output_html_path.display().to_string().yellow());
 
-    let syntax_theme_css = css_for_theme_with_class_style(theme, ClassStyle::Spaced).map_err(|e| AppError::BuildError(e.to_string()))?;
-    let theme_css = match fs::read_to_string(&theme_css_path) {
-        Ok(s) => {
-            #[cfg(not(test))]
-            println!("{} {}", "Using custom CSS theme:".cyan(), theme_css_path.display().to_string().yellow());
-            s
-        }
-        Err(_) => {
-            #[cfg(not(test))]
-            println!("{} {}{}", "Custom CSS theme not found at".yellow(), theme_css_path.display().to_string().yellow(), ". Using default theme.".yellow());
-            DEFAULT_THEME_CSS.to_string()
+    Ok(output_html_path)
+}
+
+/// Page of the social preview card of the document at `html_path`; the image is saved next to it as PNG.
+pub fn card_path(html_path: &Path) -> PathBuf {
+    let stem = html_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    html_path.with_file_name(format!("{stem}-card.html"))
+}
+
+/// The theme files and `custom_css` of the project. Missing files fall back to the built-in theme.
+#[derive(Debug, Default)]
+pub struct ThemeFiles {
+    theme_css: Option<String>,
+    pdf_css: Option<String>,
+    custom_css: Option<String>,
+    template: Option<String>,
+}
+
+impl ThemeFiles {
+    pub fn read(config: &Config) -> Self {
+        let theme_dir = Path::new("themes").join(&config.theme);
+        let theme_css = match theme_stylesheet(&theme_dir) {
+            Some(s) => {
+                #[cfg(not(test))]
+                println!("{} {}", "Using custom CSS theme:".cyan(), theme_dir.display().to_string().yellow());
+                Some(s)
+            }
+            None => {
+                warnings::warn("Default theme used", format!("'{}' not found", theme_dir.join("style.css").display()));
+                None
+            }
+        };
+        let pdf_css = fs::read_to_string(theme_dir.join("pdf.css")).ok();
+        if let Some(css) = &pdf_css {
+            warn_css_issues(&theme_dir.join("pdf.css").display().to_string(), css);
         }
-    };
-    let mut final_css = format!("{}\n{}", theme_css, syntax_theme_css);
 
-    if let Some(custom_css_path_str) = &config.custom_css {
-        if !custom_css_path_str.is_empty() {
+        let custom_css = config.custom_css.as_deref().filter(|path| !path.is_empty()).and_then(|custom_css_path_str| {
             match fs::read_to_string(custom_css_path_str) {
                 Ok(s) => {
                     #[cfg(not(test))]
                     println!("{} {}", "Using custom CSS file:".cyan(), custom_css_path_str.yellow());
-                    final_css.push_str("\n\n/* Custom CSS */\n");
-                    final_css.push_str(&s);
+                    warn_css_issues(custom_css_path_str, &s);
+                    Some(s)
                 }
                 Err(_) => {
-                    #[cfg(not(test))]
-                    println!("{} '{}' {}.", "Warning: Custom CSS file not found at".yellow(), custom_css_path_str.yellow(), "Ignored".yellow())
-                },
+                    warnings::warn("Custom CSS not found", format!("'{custom_css_path_str}' ignored"));
+                    None
+                }
             }
+        });
+
+        let template_path = theme_dir.join("template.html");
+        let template = fs::read_to_string(&template_path).ok();
+        #[cfg(not(test))]
+        if template.is_some() {
+            println!("{} {}", "Using HTML template:".cyan(), template_path.display().to_string().yellow());
         }
+        Self { theme_css, pdf_css, custom_css, template }
     }
 
-    let final_html = format!(r#"<!DOCTYPE html><html lang="{}"><head><meta charset="UTF-8"><title>{}</title><meta name="author" content="{}"><style>{}</style></head><body><main>{}</main></body></html>"# , config.language, config.title, config.author, final_css, body_html);
-    fs::write(&output_html_path, &final_html)?;
-    
-    #[cfg(not(test))]
-    println!("{} {}", "Standalone HTML generated:".green(), output_html_path.display().to_string().yellow());
+    pub fn styles<'a>(&'a self, extra_css: &'a str) -> PageStyles<'a> {
+        PageStyles {
+            theme_css: self.theme_css.as_deref(),
+            pdf_css: self.pdf_css.as_deref(),
+            custom_css: self.custom_css.as_deref(),
+            template: self.template.as_deref(),
+            extra_css,
+        }
+    }
+}
+
+/// Warns about the declarations of a stylesheet that Chrome ignores when printing or that break pagination.
+fn warn_css_issues(file: &str, css: &str) {
+    for issue in css_lint::lint_css(css) {
+        warnings::warn("Print CSS", format!("{file}:{}: {}", issue.line, issue.message));
+    }
+}
 
-    Ok((final_html, output_html_path))
+/// The styles of a theme directory: `style.css`, then `screen.css` and `print.css` wrapped in
+/// the matching media queries. `None` when the theme has none of them.
+fn theme_stylesheet(theme_dir: &Path) -> Option<String> {
+    let files = [("style.css", None), ("screen.css", Some("screen")), ("print.css", Some("print"))];
+    let parts: Vec<String> = files.iter()
+        .filter_map(|(file, media)| {
+            let css = fs::read_to_string(theme_dir.join(file)).ok()?;
+            if *media != Some("screen") {
+                warn_css_issues(&theme_dir.join(file).display().to_string(), &css);
+            }
+            Some(match media {
+                Some(media) => format!("@media {media} {{\n{css}\n}}"),
+                None => css,
+            })
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("\n"))
 }
 
-async fn build_pdf_from_html(_html_content: &str, html_path: &Path, config: &Config) -> Result<(), AppError> {
-    let pb = ProgressBar::new_spinner();
-    pb.set_message(format!("{}", "Starting PDF conversion...".blue()));
-    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+/// Starts a headless Chrome or Edge.
+pub fn launch_browser(config: &BrowserConfig) -> Result<Browser, AppError> {
+    Browser::new(config.launch_options()?).map_err(|e| AppError::BrowserLaunchFailed(e.to_string()))
+}
 
-    let browser_path = find_browser_executable()?;
-    let browser = Browser::new(LaunchOptions { path: Some(browser_path), ..Default::default() }).map_err(|e| AppError::BuildError(format!("Could not launch browser: {e}")))?;
+/// Prints the HTML page to the PDF next to it, in a new tab of `browser` closed afterwards,
+/// reporting its progress on `pb`.
+pub async fn build_pdf_from_html(browser: &Browser, html_path: &Path, config: &Config, git_info: &GitInfo, cancel: &CancelToken, pb: &ProgressBar) -> Result<(), AppError> {
     let tab = browser.new_tab().map_err(|e| AppError::BuildError(e.to_string()))?;
 
-    let app = Router::new().nest_service("/", get_service(ServeDir::new("build")));
+    let build_dir = html_path.parent().unwrap_or(Path::new("."));
+    let app = Router::new().nest_service("/", get_service(ServeDir::new(build_dir)));
     let addr = SocketAddr::from(([127, 0, 0, 1], 0));
     let listener = tokio::net::TcpListener::bind(addr).await?;
     let actual_port = listener.local_addr()?.port();
     let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
 
     let server_task = tokio::spawn(async move {
-        axum::serve(listener, app.into_make_service()).with_graceful_shutdown(async { shutdown_rx.await.ok(); }).await.unwrap();
+        axum::serve(listener, app.into_make_service()).with_graceful_shutdown(async { shutdown_rx.await.ok(); }).await
+    });
+
+    let file_name = html_path.file_name().ok_or_else(|| AppError::BuildError(format!("Invalid output file: {}", html_path.display())))?;
+    let local_url = format!("http://127.0.0.1:{}/{}", actual_port, url_path_segment(&file_name.to_string_lossy()));
+    // Other tasks (such as the server above) keep running while this thread waits on Chrome.
+    let result = tokio::task::block_in_place(|| {
+        print_document(&tab, &local_url, html_path, config, git_info, cancel, pb)?;
+        if config.social_card.enabled {
+            let card = card_path(html_path);
+            let card_url = format!("http://127.0.0.1:{}/{}", actual_port, url_path_segment(&card.file_name().unwrap_or_default().to_string_lossy()));
+            social::capture_card(&tab, &card_url, &card.with_extension("png"))?;
+            println!("{} {}", "Social card:".cyan(), card.with_extension("png").display().to_string().yellow());
+        }
+        if config.thumbnails.enabled {
+            let pdf_path = html_path.with_extension("pdf");
+            let pdf_url = format!("http://127.0.0.1:{}/{}", actual_port, url_path_segment(&pdf_path.file_name().unwrap_or_default().to_string_lossy()));
+            let thumbnails_dir = build_dir.join("thumbnails");
+            let last_page = config.thumbnails.first_page_only.then_some(1);
+            let images = raster::capture_pages(&tab, &pdf_url, &pdf_path, &thumbnails_dir, config.thumbnails.dpi, last_page)?;
+            println!("{} {} {}", "Thumbnails:".cyan(), images.len(), thumbnails_dir.display().to_string().yellow());
+        }
+        Ok(())
     });
+    tab.close(false).ok();
+
+    shutdown_tx.send(()).ok();
+    server_task.await.map_err(|e| AppError::BuildError(e.to_string()))??;
+
+    match result {
+        // Calls to a killed browser fail with unrelated errors.
+        Err(_) if cancel.is_cancelled() => {
+            pb.finish_and_clear();
+            Err(AppError::BuildCancelled)
+        }
+        result => result,
+    }
+}
+
+/// Percent-encodes the characters of a file name that are not allowed as is in a URL path.
+fn url_path_segment(name: &str) -> String {
+    name.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        _ => format!("%{byte:02X}"),
+    }).collect()
+}
 
-    let local_url = format!("http://127.0.0.1:{}/{}", actual_port, html_path.file_name().unwrap().to_str().unwrap());
-    pb.set_message(format!("{} {}", "Navigating to:".blue(), local_url.yellow()));
-    tab.navigate_to(&local_url).map_err(|e| AppError::BuildError(e.to_string()))?;
+/// Loads the page served at `url` in the tab and prints it to the PDF next to `html_path`.
+fn print_document(tab: &Tab, url: &str, html_path: &Path, config: &Config, git_info: &GitInfo, cancel: &CancelToken, pb: &ProgressBar) -> Result<(), AppError> {
+    pb.set_message(format!("{} {}", "Navigating to:".blue(), url.yellow()));
+    tab.navigate_to(url).map_err(|e| AppError::BuildError(e.to_string()))?;
     tab.wait_for_element("body").map_err(|e| AppError::BuildError(e.to_string()))?;
 
-    pb.set_message(format!("{}", "Generating PDF...".blue()));
     let pdf_path = html_path.with_extension("pdf");
     
-    let pdf_options = PrintToPdfOptions {
-        display_header_footer: Some(true),
+    // With a grid imposition every page is printed at the size of a cell, without page numbers.
+    let page_size = config.output.imposition.and_then(|imposition| imposition.page_size());
+    let pdf_options = || PrintToPdfOptions {
+        paper_width: page_size.map(|size| size.width / 72.0),
+        paper_height: page_size.map(|size| size.height / 72.0),
+        display_header_footer: Some(page_size.is_none()),
         header_template: Some("<span></span>".to_string()),
-        footer_template: Some(r#"<div style="font-size:10px; margin-right: 1cm; text-align: right; width: 100%;"><span class="pageNumber page-number"></span></div>"#.to_string()),
+        footer_template: Some(footer_template(config, git_info)),
         margin_top: Some(config.margins.top),
         margin_bottom: Some(config.margins.bottom),
         margin_left: Some(config.margins.left),
@@ -523,22 +704,95 @@ async fn build_pdf_from_html(_html_content: &str, html_path: &Path, config: &Con
         ..Default::default()
     };
 
-    let pdf_data = tab.print_to_pdf(Some(pdf_options)).map_err(|e| AppError::BuildError(e.to_string()))?;
+    // A first print locates the headings and citations; the page numbers are then written into the
+    // tables of contents and authorities and the final PDF is printed. Filling them in does not
+    // move any heading.
+    cancel.check()?;
+    pb.set_message(format!("{}", "Measuring page numbers...".blue()));
+    let headings = tab.evaluate(pagemap::MARK_HEADINGS_SCRIPT, false).map_err(|e| AppError::BuildError(e.to_string()))?
+        .value
+        .and_then(|value| value.as_str().and_then(|json| serde_json::from_str::<Vec<(u8, String)>>(json).ok()))
+        .unwrap_or_default();
+    if config.continued_markers {
+        tab.evaluate(continued::MARK_BLOCKS_SCRIPT, false).map_err(|e| AppError::BuildError(e.to_string()))?;
+    }
+    tab.evaluate(authorities::MARK_CITATIONS_SCRIPT, false).map_err(|e| AppError::BuildError(e.to_string()))?;
+    let measure_pdf = tab.print_to_pdf(Some(pdf_options())).map_err(|e| AppError::BuildError(e.to_string()))?;
+    let mut page_texts = pagemap::extract_page_texts(&measure_pdf)?;
+
+    // Tables and code blocks running over a page break are split there; the captions added
+    // may move later headings, so the pages are measured again.
+    if config.continued_markers {
+        cancel.check()?;
+        let splits = continued::split_points(&pagemap::marker_pages(&page_texts));
+        tab.evaluate(&continued::split_blocks_script(&splits, i18n::continued_label(&config.language)), false).map_err(|e| AppError::BuildError(e.to_string()))?;
+        if !splits.is_empty() {
+            let measure_pdf = tab.print_to_pdf(Some(pdf_options())).map_err(|e| AppError::BuildError(e.to_string()))?;
+            page_texts = pagemap::extract_page_texts(&measure_pdf)?;
+        }
+    }
+    if config.book.is_none() && resume::is_resume(Path::new(&config.source)) && page_texts.len() > 1 {
+        warnings::warn("Resume longer than one page", format!("{} pages", page_texts.len()));
+    }
+    let page_map = pagemap::build_page_map(&headings, &page_texts);
+    pagemap::write_page_map(&html_path.with_file_name("pagemap.json"), &page_map)?;
+    let authority_pages = authorities::authority_pages(&pagemap::marker_pages(&page_texts));
+    tab.evaluate(&authorities::fill_pages_script(&authority_pages), false).map_err(|e| AppError::BuildError(e.to_string()))?;
+    let pages: Vec<Option<usize>> = page_map.iter().map(|entry| entry.page).collect();
+    tab.evaluate(&pagemap::fill_toc_script(&pages), false).map_err(|e| AppError::BuildError(e.to_string()))?;
+
+    cancel.check()?;
+    pb.set_message(format!("{}", "Generating PDF...".blue()));
+    let pdf_data = tab.print_to_pdf(Some(pdf_options())).map_err(|e| AppError::BuildError(e.to_string()))?;
+    cancel.check()?;
+    if config.output.search_index {
+        let index = pagemap::build_search_index(&page_map, &pagemap::extract_page_texts(&pdf_data)?);
+        pagemap::write_search_index(&html_path.with_file_name("index.json"), &index)?;
+    }
+    let pdf_data = if config.comments == CommentMode::Pdf { critic::comment_links_to_annotations(&pdf_data)? } else { pdf_data };
+    if let Some(per_sheet) = config.output.nup {
+        fs::write(handout_path(&pdf_path), imposition::handout(&pdf_data, per_sheet, config.output.nup_notes)?)?;
+    }
+    let pdf_data = match &config.output.imposition {
+        Some(layout) => imposition::impose(&pdf_data, layout)?,
+        None => pdf_data,
+    };
     fs::write(&pdf_path, pdf_data)?;
     pb.finish_with_message(format!("{} {}", "PDF generated: ".green(), pdf_path.display().to_string().yellow()));
-
-    shutdown_tx.send(()).ok();
-    server_task.await.map_err(|e| AppError::BuildError(e.to_string()))?;
-
     Ok(())
 }
 
-fn find_browser_executable() -> Result<PathBuf, AppError> {
+fn footer_template(config: &Config, git_info: &GitInfo) -> String {
+    let text = config.output.footer.as_ref()
+        .map(|footer| render::escape_html(&variables::render_template(footer, &variables::builtin_variables(config, git_info))));
+    let stamp = if config.git.footer_stamp { git_info.stamp() } else { None };
+    let left: Vec<String> = text.into_iter().chain(stamp).collect();
+    match (!left.is_empty()).then(|| left.join(" · ")) {
+        Some(stamp) => format!(
+            r#"<div style="font-size:10px; margin: 0 1cm; width: 100%; display: flex; justify-content: space-between;"><span>{}</span><span class="pageNumber page-number"></span></div>"#,
+            stamp
+        ),
+        None => r#"<div style="font-size:10px; margin-right: 1cm; text-align: right; width: 100%;"><span class="pageNumber page-number"></span></div>"#.to_string(),
+    }
+}
+
+/// Looks for Chrome, Chromium or Edge in their usual install locations, then on the `PATH`
+/// (and in `CHROME`) the way headless_chrome does.
+pub fn find_browser_executable() -> Result<PathBuf, AppError> {
     let candidates = [
         "C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe",
         "C:\\Program Files (x86)\\Google\\Chrome\\Application\\chrome.exe",
         "C:\\Program Files\\Microsoft\\Edge\\Application\\msedge.exe",
         "C:\\Program Files (x86)\\Microsoft\\Edge\\Application\\msedge.exe",
+        "/usr/bin/chromium",
+        "/usr/bin/chromium-browser",
+        "/usr/bin/google-chrome",
+        "/usr/bin/google-chrome-stable",
+        "/usr/lib/chromium/chromium",
+        "/opt/google/chrome/chrome",
+        "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+        "/Applications/Chromium.app/Contents/MacOS/Chromium",
+        "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
     ];
     for path in candidates.iter() {
         let p = PathBuf::from(path);
@@ -546,7 +800,8 @@ fn find_browser_executable() -> Result<PathBuf, AppError> {
             return Ok(p);
         }
     }
-    Err(AppError::BuildError("No compatible browser (Chrome, Edge) was found.".to_string()))
+    headless_chrome::browser::default_executable()
+        .map_err(|_| AppError::BrowserLaunchFailed("No compatible browser (Chrome, Chromium, Edge) was found; set browser.path.".to_string()))
 }
 
 pub fn init_project(title: Option<String>, author: Option<String>, language: Option<String>) -> Result<(), AppError> {
@@ -566,12 +821,170 @@ source: "main.md"
 custom_css: ""
 output:
   filename: "{}"
+  # Text on the left of the page footer, with variables such as {{{{ today }}}} or {{{{ build_number }}}} (optional)
+  # footer: "{{{{ title }}}}, printed {{{{ today }}}}"
+  # Directory of the generated files, "build" by default (optional)
+  # dir: "build"
+  # Keep a copy of every generated PDF (optional)
+  # archive_dir: "archive"
+  # Write the text of every page with its headings to index.json, e.g. for a site search (optional)
+  # search_index: true
+  # Tile the pages on printed sheets with cut marks, e.g. for labels, or lay them out for a folded booklet (optional)
+  # imposition: "2x4 on A4"
+  # imposition: "booklet of 16 on A4"
+  # Also write a handout with 2, 4 or 6 pages per sheet, with lines for notes (optional)
+  # nup: 4
+  # nup_notes: true
+  # Write a .sha256 checksum and a .provenance.json (inputs, config and tool version) next to every generated file (optional)
+  # provenance: true
+  # Reuse the last PDF without starting the browser when the page, its assets and the settings are unchanged (optional)
+  # build_cache: true
 # Margins in inches (optional)
 # margins:
 #   top: 1.0
 #   bottom: 1.0
 #   left: 1.0
 #   right: 1.0
+# Structured book instead of '!include' chains (optional, replaces 'source')
+# book:
+#   front_matter: ["preface.md"]
+#   parts:
+#     - title: "Part I"
+#       chapters:
+#         - file: "chapters/chapter1.md"
+#   back_matter: ["appendix.md"]
+# Copyright page, license and disclaimers added to every build, with {{{{ year }}}}, {{{{ version }}}} and these variables (optional)
+# boilerplate:
+#   front: ["legal/copyright.md"]
+#   back: ["legal/license.md"]
+#   variables:
+#     company: "ACME Corp"
+# Pages shared by the books of a series, appended at the end, each on its own page (optional)
+# back_matter: ["series/about-author.md", "series/colophon.md", "series/other-books.md"]
+# back_matter_numbered: true   # number their headings
+# Dedication on its own page, centered, before the table of contents (optional)
+# dedication: "For Ada, who asked why."
+# Locale typography (French spacing and guillemets, German quotes), on by default (optional)
+# typography:
+#   enabled: true
+#   languages:
+#     en:
+#       quotes: ["“", "”"]
+# Rendering of ```plantuml and ```dot blocks, cached in build/.cache (optional)
+# diagrams:
+#   server: "https://kroki.io"   # or local tools:
+#   plantuml_command: "plantuml -tsvg -pipe"
+#   dot_command: "dot -Tsvg"
+# Rendering of ```latex blocks (equations, TikZ) with tectonic or a TeX engine, cached in build/.cache (optional)
+# latex:
+#   engine: "tectonic"   # or "pdflatex", "xelatex", "lualatex"
+#   preamble: "\\usepackage{{amsmath,amssymb}} \\usepackage{{tikz}}"
+# External renderers for other fence languages: block on stdin, image on stdout
+# (or use {{input}} / {{output}} files), cached in build/.cache (optional)
+# renderers:
+#   vega:
+#     command: "vg2svg {{input}} {{output}}"
+#     format: svg
+# Extra numbered environments for `:::name` blocks (optional)
+# environments:
+#   algorithm: "Algorithm"
+# Where `:::solution` blocks go: inline, appendix or omit (optional, `build --solutions`)
+# solutions: inline
+# Translated sources, built with `build --lang fr` or `build --lang all` (optional)
+# translations:
+#   fr: "fr/main.md"
+# Metadata for publishing the HTML output (optional)
+# html:
+#   description: "A short summary"
+#   canonical_url: "https://example.com/book.html"
+#   og_image: "https://example.com/cover.png"
+#   head_extra: ['<meta name="robots" content="index">']
+#   toc: sidebar            # sticky sidebar in HTML exports instead of the printed table of contents
+#   fingerprint_assets: true  # name assets after their content hash in --format html, for long-lived caching
+#   site: true              # also write one page per chapter and an index.html landing page in --format html
+# Repeat table headers and add "(continued)" captions where tables and code blocks break across pages (optional)
+# continued_markers: true
+# Reviewer comments {{>> ... <<}}: margin (default), pdf (PDF sticky notes) or hide (optional)
+# comments: pdf
+# Changes {{++insert++}}, {{--delete--}}, {{~~old~>new~~}}: show (default), accept or fail (optional)
+# changes: fail
+# Copy the whole assets/ directory instead of only the files the document uses (optional)
+# assets:
+#   copy_all: true
+# Images of 'build --format email-html': base64 data URIs (default), or cid attachments of a .eml message (optional)
+# email:
+#   images: cid
+#   subject: "Release notes"
+# Column at which 'build --format txt' wraps the text (optional)
+# text:
+#   width: 72
+# PNG images of the pages in build/thumbnails, also written with `build --thumbnails` (optional)
+# thumbnails:
+#   enabled: true
+#   dpi: 72
+#   first_page_only: true
+# Preview image for links shared on social networks, build/<filename>-card.png (optional)
+# social_card:
+#   enabled: true
+#   cover: "assets/cover.png"
+# Refuse symbolic links among sources and assets; by default links inside the project are followed (optional)
+# follow_symlinks: false
+# Allow '!include(https://...)' of shared Markdown, downloaded at most once a day into build/.cache (optional)
+# allow_remote_includes: true
+# Keep '!html' fragments as written; by default only an allowlist of elements and attributes is kept (optional)
+# sanitize_html_fragments: false
+# Sites '!screenshot(https://...)' may capture at build time, and the default window size (optional)
+# screenshots:
+#   allowed_hosts: ["app.example.com", "*.example.org"]
+#   width: 1280
+#   height: 800
+# Proxy of downloads, uploads and the browser, by default from HTTPS_PROXY and NO_PROXY, and the timeout in seconds (optional)
+# network:
+#   proxy: "http://proxy.corp.example.com:3128"
+#   no_proxy: ["localhost", ".corp.example.com"]
+#   timeout: 30
+# How the headless browser is started, e.g. inside a Docker image (optional)
+# browser:
+#   path: "/usr/bin/chromium"
+#   args: ["--no-sandbox", "--disable-dev-shm-usage"]
+#   proxy: "http://proxy.internal:3128"
+#   user_data_dir: "/tmp/chrome-profile"
+# Destinations of 'PdfBuilder publish', with {{version}}, {{date}} and variables; credentials come from the environment (optional)
+# publish:
+#   - url: "s3://docs-bucket/guides/{{version}}/"
+#     region: "eu-west-1"   # or endpoint: "https://minio.example.com"
+#     formats: ["pdf", "html"]
+#   - url: "sftp://deploy@docs.example.com/var/www/guides/{{date}}/"
+#   - url: "https://cloud.example.com/remote.php/dav/files/ci/guides/"   # WebDAV
+# Abort builds whose sources expand beyond these bounds, e.g. for untrusted documents (optional)
+# limits:
+#   max_include_depth: 32
+#   max_expanded_size: 100000000   # bytes
+#   max_images: 10000
+# Acronyms written as +API+, expanded at their first use and listed at '!acronyms' (optional)
+# acronyms:
+#   API: "Application Programming Interface"
+# Printing of collapsible ':::details' and <details> blocks: expanded, collapsed or omit (optional)
+# details: collapsed
+# Rendering of ':::tabs' groups: tabs, sections (every tab under its title) or first (optional)
+# tabs:
+#   html: tabs
+#   pdf: sections
+# Number chapter and section headings (optional)
+# numbering: true
+# Markdown lint rules checked by 'PdfBuilder check' (optional)
+# lint:
+#   disable: ["bare-url"]
+#   max_code_line_length: 90
+#   external:
+#     - name: "vale"
+#       command: "vale --output=line {{file}}"
+# Print the git revision in the footer (optional)
+# git:
+#   footer_stamp: true
+# Also replace the PDF with a page showing the error when a 'build --watch' rebuild fails (optional)
+# watch:
+#   error_pdf: true
 "#, default_title, default_author, default_language, default_title.to_lowercase().replace(" ", "-"));
     fs::write("config.yaml", config_content)?;
     #[cfg(not(test))]
@@ -605,57 +1018,13 @@ Welcome!
     Ok(())
 }
 
-fn copy_assets_optimized(source_dir: &Path, dest_dir: &Path) -> Result<(), AppError> {
-    if !source_dir.exists() {
-        return Ok(()); 
-    }
-    fs::create_dir_all(dest_dir)?;
-    #[cfg(not(test))]
-    println!("{} {} {} {}...", "Copying assets from".blue(), source_dir.display().to_string().yellow(), "to".blue(), dest_dir.display().to_string().yellow());
-
-    for entry in fs::read_dir(source_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        let file_name = path.file_name().ok_or_else(|| AppError::IoError(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid file name")))?;
-        let dest_path = dest_dir.join(file_name);
-
-        if path.is_file() {
-            let should_copy = !dest_path.exists() || (fs::metadata(&path)?.modified()? > fs::metadata(&dest_path)?.modified()?);
-            if should_copy {
-                fs::copy(&path, &dest_path)?;
-            }
-        } else if path.is_dir() {
-            copy_assets_optimized(&path, &dest_path)?;
-        }
-    }
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::TestDir;
     use std::fs;
     use std::path::Path;
 
-    struct TestDir {
-        path: PathBuf,
-    }
-
-    impl TestDir {
-        fn new(test_name: &str) -> Self {
-            let path = std::env::temp_dir().join("pdfbuilder_tests").join(test_name);
-            if path.exists() {
-                fs::remove_dir_all(&path).unwrap();
-            }
-            fs::create_dir_all(&path).unwrap();
-            TestDir { path }
-        }
-
-        fn path(&self) -> &Path {
-            &self.path
-        }
-    }
-
     #[test]
     fn test_init_project_creates_all_files() {
         let test_dir = TestDir::new("init_project_test");
@@ -678,7 +1047,7 @@ mod tests {
        let file_path = test_dir.path().join("main.md");
        fs::write(&file_path, "Hello World").unwrap();
 
-       let result = preprocess_markdown(test_dir.path(), file_path.to_str().unwrap(), &mut HashSet::new()).unwrap();
+       let result = preprocess_markdown(test_dir.path(), &file_path, &mut Includes::new(true)).unwrap();
        assert_eq!(result.trim(), "Hello World");
    }
 
@@ -691,7 +1060,7 @@ mod tests {
        fs::write(&main_path, "Book\n!include(chap1.md)").unwrap();
        fs::write(&chap1_path, "Content of chapter 1").unwrap();
 
-       let result = preprocess_markdown(test_dir.path(), main_path.to_str().unwrap(), &mut HashSet::new()).unwrap();
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
        assert!(result.contains("Book"));
        assert!(result.contains("Content of chapter 1"));
    }
@@ -705,8 +1074,86 @@ mod tests {
        fs::write(&a_path, "!include(b.md)").unwrap();
        fs::write(&b_path, "!include(a.md)").unwrap();
 
-       let result = preprocess_markdown(test_dir.path(), a_path.to_str().unwrap(), &mut HashSet::new());
-       assert!(matches!(result, Err(AppError::BuildError(_))));
+       let result = preprocess_markdown(test_dir.path(), &a_path, &mut Includes::new(true));
+       assert!(matches!(result, Err(AppError::IncludeCycle(cycle)) if cycle.len() == 3 && cycle[0] == cycle[2]));
+
+       // The same file under another path.
+       fs::write(&b_path, "!include(./a.md)").unwrap();
+       let result = preprocess_markdown(test_dir.path(), &a_path, &mut Includes::new(true));
+       assert!(matches!(result, Err(AppError::IncludeCycle(cycle)) if cycle.len() == 3 && cycle[2].ends_with("./a.md")));
+   }
+
+   #[test]
+   fn test_preprocess_markdown_resume_source_only() {
+       let test_dir = TestDir::new("preprocess_resume");
+       let resume_path = test_dir.path().join("cv.yaml");
+       fs::write(&resume_path, "basics:\n  name: Ada Lovelace\n").unwrap();
+       let main_path = test_dir.path().join("main.md");
+       fs::write(&main_path, "!include(cv.yaml)\n").unwrap();
+
+       // An included YAML file is inserted as it is.
+       let included = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+       assert!(included.contains("basics:\n  name: Ada Lovelace"));
+       let mut includes = Includes::new(true);
+       includes.files.resume_source = Some(resume_path.clone());
+       assert!(preprocess_markdown(test_dir.path(), &resume_path, &mut includes).unwrap().contains("resume-header"));
+   }
+
+   #[test]
+   fn test_preprocess_markdown_missing_image() {
+       let test_dir = TestDir::new("preprocess_missing_image");
+       fs::create_dir_all(test_dir.path().join("assets")).unwrap();
+       fs::write(test_dir.path().join("assets").join("logo.png"), "png").unwrap();
+       let main_path = test_dir.path().join("main.md");
+       fs::write(&main_path, "---\ntitle: Intro\n---\n![Logo](assets/logo.png) ![Remote](https://example.com/a.png)\n```md\n![In code](none.png)\n```\n![Chart](assets/chart.png)\n").unwrap();
+
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true).with_missing_images_fatal());
+       assert!(matches!(result, Err(AppError::ImageMissing { line: 8, image, .. }) if image == "assets/chart.png"));
+       // Builds only warn.
+       assert!(preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).is_ok());
+
+       // A document expanded outside the project finds the images of the project.
+       let project = TestDir::new("preprocess_image_root");
+       fs::create_dir_all(project.path().join("assets")).unwrap();
+       fs::write(project.path().join("assets").join("logo.png"), "png").unwrap();
+       fs::write(project.path().join("assets").join("chart.png"), "png").unwrap();
+       fs::remove_dir_all(test_dir.path().join("assets")).unwrap();
+       let mut includes = Includes::new(true).with_image_root(project.path().to_path_buf()).with_missing_images_fatal();
+       assert!(preprocess_markdown(test_dir.path(), &main_path, &mut includes).is_ok());
+   }
+
+   #[cfg(unix)]
+   #[test]
+   fn test_preprocess_markdown_non_utf8_path() {
+       use std::os::unix::ffi::OsStrExt;
+       let test_dir = TestDir::new("preprocess_non_utf8");
+       let chapter_dir = test_dir.path().join(std::ffi::OsStr::from_bytes(b"chap\xe9"));
+       fs::create_dir_all(&chapter_dir).unwrap();
+       fs::write(chapter_dir.join("main.md"), "# Title\n!include(part.md)").unwrap();
+       fs::write(chapter_dir.join("part.md"), "Part").unwrap();
+
+       let result = preprocess_markdown(test_dir.path(), &chapter_dir.join("main.md"), &mut Includes::new(true)).unwrap();
+       assert_eq!(result, "# Title\n<section class=\"chapter\" data-src=\"chap\u{fffd}/part.md\">\n\nPart\n\n</section>\n\n");
+   }
+
+   #[test]
+   fn test_preprocess_markdown_limits() {
+       let test_dir = TestDir::new("preprocess_limits");
+       let limits = |max_include_depth, max_expanded_size, max_images| LimitsConfig { max_include_depth, max_expanded_size, max_images };
+       // Each level includes the next one ten times: the expansion grows tenfold per level.
+       for level in 0..2 {
+           fs::write(test_dir.path().join(format!("level{level}.md")), format!("!include(level{}.md)\n", level + 1).repeat(10)).unwrap();
+       }
+       fs::write(test_dir.path().join("level2.md"), "![Remote](https://example.com/a.png) padding").unwrap();
+       let main_path = test_dir.path().join("level0.md");
+
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true).with_limits(limits(2, 100_000_000, 100_000)));
+       assert!(matches!(result, Err(AppError::LimitExceeded("max_include_depth", 2))));
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true).with_limits(limits(32, 1_000, 100_000)));
+       assert!(matches!(result, Err(AppError::LimitExceeded("max_expanded_size", 1_000))));
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true).with_limits(limits(32, 100_000_000, 50)));
+       assert!(matches!(result, Err(AppError::LimitExceeded("max_images", 50))));
+       assert!(preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).is_ok());
    }
 
    #[test]
@@ -715,7 +1162,7 @@ mod tests {
        let main_path = test_dir.path().join("main.md");
        fs::write(&main_path, "!include(nonexistent.md)").unwrap();
 
-       let result = preprocess_markdown(test_dir.path(), main_path.to_str().unwrap(), &mut HashSet::new());
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true));
        assert!(matches!(result, Err(AppError::SourceNotFound(_))));
    }
 
@@ -734,7 +1181,7 @@ mod tests {
        let traversal_path = if cfg!(windows) { "..\\..\\secret.txt" } else { "../../secret.txt" };
        fs::write(&main_md_path, format!("!include({})", traversal_path)).unwrap();
 
-       let result = preprocess_markdown(project_dir, main_md_path.to_str().unwrap(), &mut HashSet::new());
+       let result = preprocess_markdown(project_dir, &main_md_path, &mut Includes::new(true));
        assert!(matches!(result, Err(AppError::BuildError(_))));
        
        // Cleanup the secret file
@@ -748,7 +1195,7 @@ mod tests {
        let content = "Example:\n```\n!include(some/file.md)\n```";
        fs::write(&main_path, content).unwrap();
 
-       let result = preprocess_markdown(test_dir.path(), main_path.to_str().unwrap(), &mut HashSet::new()).unwrap();
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
        assert!(result.contains("!include(some/file.md)"));
    }
 
@@ -759,11 +1206,21 @@ mod tests {
        let content = "Example:\n```\n!newpage\n```";
        fs::write(&main_path, content).unwrap();
 
-       let result = preprocess_markdown(test_dir.path(), main_path.to_str().unwrap(), &mut HashSet::new()).unwrap();
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
        assert!(result.contains("!newpage"));
        assert!(!result.contains("<div class=\"page-break\"></div>"));
    }
 
+   #[test]
+   fn test_preprocess_markdown_keeps_escaped_and_raw_directives() {
+       let test_dir = TestDir::new("preprocess_raw_directives");
+       let main_path = test_dir.path().join("main.md");
+       fs::write(&main_path, "\\!include(missing.md)\n!raw\n!toc\n!include(missing.md)\n!endraw\n!newpage").unwrap();
+
+       let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+       assert_eq!(result, "!include(missing.md)\n\n!toc\n!include(missing.md)\n\n<div class=\"page-break\"></div>\n");
+   }
+
    #[test]
    fn test_preprocess_markdown_handles_newpage() {
        let test_dir = TestDir::new("preprocess_newpage");
@@ -771,11 +1228,78 @@ mod tests {
       let content = "Line 1\n!newpage\nLine 2";
       fs::write(&main_path, content).unwrap();
 
-      let result = preprocess_markdown(test_dir.path(), main_path.to_str().unwrap(), &mut HashSet::new()).unwrap();
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
       assert!(result.contains("<div class=\"page-break\"></div>"));
       assert!(!result.contains("!newpage"));
   }
 
+  #[test]
+  fn test_preprocess_markdown_handles_qrcode() {
+      let test_dir = TestDir::new("preprocess_qrcode");
+      let main_path = test_dir.path().join("main.md");
+      fs::write(&main_path, "Scan me:\n\n!qrcode(https://example.com/survey, size=2cm)").unwrap();
+
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+      assert!(result.contains(r#"<img class="qrcode" src="data:image/svg+xml;base64,"#));
+      assert!(!result.contains("!qrcode"));
+  }
+
+  #[test]
+  fn test_preprocess_markdown_scopes_styles() {
+      let test_dir = TestDir::new("preprocess_scoped_styles");
+      fs::create_dir_all(test_dir.path().join("chapters")).unwrap();
+      fs::write(test_dir.path().join("chapters/tables.css"), "td { padding: 0; }").unwrap();
+      fs::write(test_dir.path().join("chapters/appendix.md"), "---\nclass: appendix\ncss: tables.css\n---\n## Appendix").unwrap();
+      fs::write(test_dir.path().join("chapters/notes.md"), "!style(tables.css)\n## Notes\n```\n!style(x.css)\n```").unwrap();
+      let main_path = test_dir.path().join("main.md");
+      fs::write(&main_path, "# Book\n!include(chapters/appendix.md)\n!include(chapters/notes.md)").unwrap();
+
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+      assert!(result.contains("<section class=\"chapter appendix scope-1\" data-src=\"chapters/appendix.md\"><style>.scope-1 td { padding: 0; }</style>\n\n## Appendix"));
+      assert!(result.contains("<section class=\"chapter scope-2\" data-src=\"chapters/notes.md\"><style>.scope-2 td { padding: 0; }</style>\n\n\n## Notes\n```\n!style(x.css)\n```\n\n</section>"));
+
+      fs::write(test_dir.path().join("chapters/notes.md"), "!style(missing.css)").unwrap();
+      assert!(preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).is_err());
+  }
+
+  #[test]
+  fn test_theme_stylesheet() {
+      let test_dir = TestDir::new("theme_stylesheet");
+      assert_eq!(theme_stylesheet(test_dir.path()), None);
+
+      fs::write(test_dir.path().join("print.css"), "a { color: black; }").unwrap();
+      assert_eq!(theme_stylesheet(test_dir.path()).unwrap(), "@media print {\na { color: black; }\n}");
+
+      fs::write(test_dir.path().join("style.css"), "body { margin: 0; }").unwrap();
+      fs::write(test_dir.path().join("screen.css"), "a { color: blue; }").unwrap();
+      assert_eq!(
+          theme_stylesheet(test_dir.path()).unwrap(),
+          "body { margin: 0; }\n@media screen {\na { color: blue; }\n}\n@media print {\na { color: black; }\n}"
+      );
+  }
+
+  #[test]
+  fn test_preprocess_markdown_handles_barcode() {
+      let test_dir = TestDir::new("preprocess_barcode");
+      let main_path = test_dir.path().join("main.md");
+      fs::write(&main_path, "!barcode(isbn, 978-0-306-40615-7)\n\n```\n!barcode(isbn, 1)\n```").unwrap();
+
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+      assert!(result.starts_with(r#"<img class="barcode" src="data:image/svg+xml;base64,"#));
+      assert!(result.contains("```\n!barcode(isbn, 1)\n```"));
+  }
+
+  #[test]
+  fn test_preprocess_markdown_handles_authorities() {
+      let test_dir = TestDir::new("preprocess_authorities");
+      let main_path = test_dir.path().join("main.md");
+      fs::write(&main_path, "# Brief\n!authorities\n## Argument").unwrap();
+
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+      assert!(result.contains("<!--AUTHORITIES_PLACEHOLDER-->"));
+      assert!(!result.contains("!authorities"));
+  }
+
   #[test]
   fn test_preprocess_markdown_handles_toc() {
       let test_dir = TestDir::new("preprocess_toc");
@@ -783,7 +1307,7 @@ mod tests {
       let content = "# Title\n!toc\n## Chapter 1";
       fs::write(&main_path, content).unwrap();
 
-      let result = preprocess_markdown(test_dir.path(), main_path.to_str().unwrap(), &mut HashSet::new()).unwrap();
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
       assert!(result.contains("<!--TOC_PLACEHOLDER-->"));
       assert!(!result.contains("!toc"));
   }
@@ -791,22 +1315,27 @@ mod tests {
   
 
   #[test]
-  fn test_build_toc_hierarchy() {
-      let entries = vec![
-          TocEntry { level: 1, title: "Main".to_string(), children: Vec::new() },
-          TocEntry { level: 2, title: "Chapter 1".to_string(), children: Vec::new() },
-          TocEntry { level: 3, title: "Section 1".to_string(), children: Vec::new() },
-          TocEntry { level: 2, title: "Chapter 2".to_string(), children: Vec::new() },
-      ];
-
-      let hierarchy = build_toc_hierarchy(entries);
-      assert_eq!(hierarchy.len(), 1);
-      assert_eq!(hierarchy[0].title, "Main");
-      assert_eq!(hierarchy[0].children.len(), 2);
-      assert_eq!(hierarchy[0].children[0].title, "Chapter 1");
-      assert_eq!(hierarchy[0].children[0].children.len(), 1);
-      assert_eq!(hierarchy[0].children[0].children[0].title, "Section 1");
-      assert_eq!(hierarchy[0].children[1].title, "Chapter 2");
+  fn test_preprocess_markdown_chapter_front_matter() {
+      let test_dir = TestDir::new("preprocess_front_matter");
+      let main_path = test_dir.path().join("main.md");
+      let appendix_path = test_dir.path().join("appendix.md");
+      fs::write(&main_path, "# Book\n!include(appendix.md)").unwrap();
+      fs::write(&appendix_path, "---\ntitle: Appendix A\nnumbering: false\nclass: appendix\n---\n## Draft title\nText").unwrap();
+
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+      assert!(result.contains("<section class=\"chapter appendix unnumbered\" data-src=\"appendix.md\">\n\n## Appendix A\nText\n\n</section>"));
+      assert!(!result.contains("numbering: false"));
+  }
+
+  #[test]
+  fn test_preprocess_markdown_includes_notebook() {
+      let test_dir = TestDir::new("preprocess_notebook");
+      let main_path = test_dir.path().join("main.md");
+      fs::write(&main_path, "# Report\n!include(analysis.ipynb)").unwrap();
+      fs::write(test_dir.path().join("analysis.ipynb"), r###"{"metadata": {}, "cells": [{"cell_type": "markdown", "source": "## Analysis"}]}"###).unwrap();
+
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+      assert!(result.contains("## Analysis"));
   }
 
   #[test]
@@ -816,64 +1345,72 @@ mod tests {
       let content = "Example:\n```\n!toc\n```";
       fs::write(&main_path, content).unwrap();
 
-      let result = preprocess_markdown(test_dir.path(), main_path.to_str().unwrap(), &mut HashSet::new()).unwrap();
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
       assert!(result.contains("!toc"));
       assert!(!result.contains("<!--TOC_PLACEHOLDER-->"));
   }
 
   #[test]
-  fn test_generate_toc_entry_html() {
-      let entry = TocEntry {
-          level: 1,
-          title: "Test Chapter".to_string(),
-          children: vec![
-              TocEntry {
-                  level: 2,
-                  title: "Sub Section".to_string(),
-                  children: Vec::new(),
-              }
-          ],
-      };
-
-      let mut html = String::new();
-      generate_toc_entry_html(&mut html, &entry);
-      
-      assert!(html.contains("Test Chapter"));
-      assert!(html.contains("Sub Section"));
-      assert!(html.contains("toc-entry-h1"));
-      assert!(html.contains("toc-entry-h2"));
-      assert!(!html.contains("5"));
-      assert!(!html.contains("6"));
-      assert!(html.contains("toc-entry-dots"));
+  fn test_preprocess_markdown_directive_options() {
+      let test_dir = TestDir::new("preprocess_directive_options");
+      let main_path = test_dir.path().join("main.md");
+      fs::write(test_dir.path().join("part (old).md"), "Part").unwrap();
+      fs::write(&main_path, "!toc(depth=2, title=\"Contents\")\n!include(file=\"part (old).md\")\n!include(part.md, shift=1)").unwrap();
+      fs::write(test_dir.path().join("part.md"), "# Part\n## Section").unwrap();
+
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).unwrap();
+      assert!(result.starts_with("<!--TOC_OPTIONS {\"depth\":2,\"title\":\"Contents\"}--><!--TOC_PLACEHOLDER-->\n"));
+      assert!(result.contains("\n\nPart\n"));
+      assert!(result.contains("\n\n## Part\n### Section\n"));
+
+      for content in ["!toc(depth=9)", "!include(part.md, shift=one)", "!newpage(now)", "!include(a.md, b.md)", "!include"] {
+          fs::write(&main_path, content).unwrap();
+          assert!(preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).is_err(), "{content}");
+      }
+  }
+
+  #[test]
+  fn test_preprocess_markdown_remote_include() {
+      let test_dir = TestDir::new("preprocess_remote_include");
+      let main_path = test_dir.path().join("main.md");
+      let url = "http://127.0.0.1:9/shared.md";
+      fs::write(&main_path, format!("!include({url}, shift=1)")).unwrap();
+      assert!(preprocess_markdown(test_dir.path(), &main_path, &mut Includes::new(true)).is_err());
+
+      // A fresh cached copy is used without downloading; its directives stay text.
+      let cache_dir = test_dir.path().join("cache");
+      fs::create_dir_all(&cache_dir).unwrap();
+      fs::write(cache_dir.join(format!("{}.md", crate::cache::content_hash(url))), "# Shared\n!include(main.md)").unwrap();
+      let mut includes = Includes::new(true).with_remote_includes(cache_dir);
+      let result = preprocess_markdown(test_dir.path(), &main_path, &mut includes).unwrap();
+      assert_eq!(result, format!("<section class=\"chapter\" data-src=\"{url}\">\n\n## Shared\n!include(main.md)\n\n</section>\n\n"));
+  }
+
+  #[test]
+  fn test_expand_filename_template() {
+      let name = expand_filename_template("user-guide-{version}-{date}", "v1.2", "2024-05-01");
+      assert_eq!(name, "user-guide-v1.2-2024-05-01");
   }
 
   #[test]
-  fn test_generate_toc_html_empty() {
-      let entries = vec![];
-      let result = generate_toc_html(&entries);
-      assert_eq!(result, "");
+  fn test_archive_output_does_not_overwrite() {
+      let test_dir = TestDir::new("archive_output");
+      let pdf_path = test_dir.path().join("book.pdf");
+      let archive_dir = test_dir.path().join("archive");
+      fs::write(&pdf_path, "first").unwrap();
+      let first = archive_output(&pdf_path, &archive_dir).unwrap();
+
+      fs::write(&pdf_path, "second").unwrap();
+      let second = archive_output(&pdf_path, &archive_dir).unwrap();
+
+      assert_eq!(first, archive_dir.join("book.pdf"));
+      assert_eq!(second, archive_dir.join("book-2.pdf"));
+      assert_eq!(fs::read_to_string(first).unwrap(), "first");
+      assert_eq!(fs::read_to_string(second).unwrap(), "second");
   }
 
   #[test]
-  fn test_generate_toc_html_with_entries() {
-      let entries = vec![
-          TocEntry {
-              level: 1,
-              title: "Chapter 1".to_string(),
-              children: Vec::new(),
-          },
-          TocEntry {
-              level: 2,
-              title: "Section 1.1".to_string(),
-              children: Vec::new(),
-          }
-      ];
-
-      let result = generate_toc_html(&entries);
-      assert!(result.contains("Table des matières"));
-      assert!(result.contains("Chapter 1"));
-      assert!(result.contains("Section 1.1"));
-      assert!(result.contains("toc-content"));
-      assert!(result.contains("toc-title"));
+  fn test_url_path_segment() {
+      assert_eq!(url_path_segment("my book #2 (été).html"), "my%20book%20%232%20%28%C3%A9t%C3%A9%29.html");
   }
 }
\ No newline at end of file
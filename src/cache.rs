@@ -0,0 +1,71 @@
+use crate::error::AppError;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Content-addressed cache for the output of slow external tools (diagram renderers...).
+/// Entries are keyed by a hash of their input, so changed blocks are rendered again and
+/// unchanged ones are reused across builds.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The project cache, `.cache` in the build directory.
+    pub fn in_build_dir(build_dir: &Path) -> Self {
+        Self::new(build_dir.join(".cache"))
+    }
+
+    /// A scratch directory for tools working on files, named after `key`.
+    pub fn work_dir(&self, key: &str) -> PathBuf {
+        self.dir.join("work").join(key)
+    }
+
+    /// Returns the cached output for `input`, or runs `produce` and stores its result.
+    /// `namespace` separates tools, so the same input rendered by two tools is cached twice.
+    pub fn get_or_insert_with<F>(&self, namespace: &str, input: &str, extension: &str, produce: F) -> Result<Vec<u8>, AppError>
+    where
+        F: FnOnce() -> Result<Vec<u8>, AppError>,
+    {
+        let path = self.dir.join(namespace).join(format!("{}.{}", content_hash(input), extension));
+        if let Ok(content) = fs::read(&path) {
+            return Ok(content);
+        }
+
+        let content = produce()?;
+        fs::create_dir_all(self.dir.join(namespace))?;
+        fs::write(&path, &content)?;
+        Ok(content)
+    }
+}
+
+/// Hex-encoded SHA-256 of a text or file content.
+pub fn content_hash(input: impl AsRef<[u8]>) -> String {
+    Sha256::digest(input.as_ref()).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_cache_reuses_output() {
+        let test_dir = TestDir::new("cache");
+        let dir = test_dir.path();
+        let cache = Cache::new(dir);
+
+        let first = cache.get_or_insert_with("dot", "digraph { a -> b }", "svg", || Ok(b"<svg/>".to_vec())).unwrap();
+        let second = cache.get_or_insert_with("dot", "digraph { a -> b }", "svg", || panic!("output should be cached")).unwrap();
+        assert_eq!(first, second);
+
+        let other = cache.get_or_insert_with("dot", "digraph { b -> a }", "svg", || Ok(b"<svg></svg>".to_vec())).unwrap();
+        assert_eq!(other, b"<svg></svg>");
+        assert_eq!(content_hash("").len(), 64);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::error::AppError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Shared flag telling a running build to stop, set by watch mode when a newer change arrives.
+/// The build checks it between its passes; `kill_on_cancel` also interrupts the browser.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Returns `AppError::BuildCancelled` once the build was cancelled.
+    pub fn check(&self) -> Result<(), AppError> {
+        if self.is_cancelled() {
+            Err(AppError::BuildCancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Kills the process `pid` if the build is cancelled before the returned guard is dropped.
+    /// A killed browser makes its pending call (e.g. a long PDF print) fail at once instead of
+    /// running to completion.
+    pub fn kill_on_cancel(&self, pid: Option<u32>) -> CancelGuard {
+        let done = Arc::new(AtomicBool::new(false));
+        if let Some(pid) = pid {
+            let (token, done) = (self.clone(), done.clone());
+            std::thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    if token.is_cancelled() {
+                        kill_process(pid);
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            });
+        }
+        CancelGuard { done }
+    }
+}
+
+/// Stops the watch started by `CancelToken::kill_on_cancel` when dropped.
+pub struct CancelGuard {
+    done: Arc<AtomicBool>,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+}
+
+fn kill_process(pid: u32) {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = std::process::Command::new("taskkill");
+        command.args(["/F", "/T", "/PID", &pid.to_string()]);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = std::process::Command::new("kill");
+        command.args(["-9", &pid.to_string()]);
+        command
+    };
+    command.output().ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_token() {
+        let token = CancelToken::default();
+        let shared = token.clone();
+        assert!(token.check().is_ok());
+        shared.cancel();
+        assert!(matches!(token.check(), Err(AppError::BuildCancelled)));
+    }
+}
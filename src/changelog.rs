@@ -0,0 +1,138 @@
+use crate::directives::Directive;
+use crate::error::AppError;
+use crate::git;
+use regex::Regex;
+
+/// Sections of the changelog, in order: the conventional commit types listed and their titles.
+/// Commits of other types (`chore`, `ci`, `test`, ...) are left out.
+const SECTIONS: [(&str, &str); 5] = [
+    ("feat", "Features"),
+    ("fix", "Bug Fixes"),
+    ("perf", "Performance"),
+    ("refactor", "Refactoring"),
+    ("docs", "Documentation"),
+];
+
+const BREAKING_TITLE: &str = "Breaking Changes";
+
+/// A commit whose subject follows the conventional commit format, `type(scope)!: description`.
+#[derive(Debug, PartialEq, Eq)]
+struct Commit {
+    hash: String,
+    kind: String,
+    scope: Option<String>,
+    description: String,
+    breaking: bool,
+}
+
+/// Returns the changelog of `!changelog(since=v1.4, until=HEAD, level=3)` as Markdown: the
+/// conventional commits since the `since` revision (the whole history without it), one heading
+/// of `level` per type.
+pub fn changelog_directive(directive: &Directive) -> Result<String, AppError> {
+    directive.expect_positional(1)?;
+    let revision_re = Regex::new(r"^[\w./@^~{}-]+$").unwrap();
+    let revision = |name: &str, index: Option<usize>| -> Result<Option<String>, AppError> {
+        let value = match index {
+            Some(index) => directive.value(name, index),
+            None => directive.option(name),
+        };
+        match value {
+            Some(value) if value.starts_with('-') || !revision_re.is_match(value) => {
+                Err(AppError::BuildError(format!("Invalid !changelog {name} '{value}', expected a tag, branch or commit")))
+            }
+            value => Ok(value.map(str::to_string)),
+        }
+    };
+    let since = revision("since", Some(0))?;
+    let until = revision("until", None)?.unwrap_or_else(|| "HEAD".to_string());
+    let level = match directive.option("level") {
+        Some(level) => level.parse().ok().filter(|level| (1..=6).contains(level))
+            .ok_or_else(|| AppError::BuildError(format!("Invalid !changelog level '{level}', expected 1 to 6")))?,
+        None => 3,
+    };
+
+    let range = match since {
+        Some(since) => format!("{since}..{until}"),
+        None => until,
+    };
+    Ok(render_changelog(&parse_log(&git::log(&range)?), level))
+}
+
+/// Conventional commits of the output of `git::log`; other commits are skipped.
+fn parse_log(log: &str) -> Vec<Commit> {
+    let subject_re = Regex::new(r"^(\w+)(?:\(([^)]+)\))?(!)?:\s*(.+)$").unwrap();
+    log.split('\x1e')
+        .filter_map(|entry| {
+            let mut fields = entry.trim_start_matches('\n').splitn(3, '\x1f');
+            let (hash, subject, body) = (fields.next()?, fields.next()?, fields.next().unwrap_or_default());
+            let caps = subject_re.captures(subject.trim())?;
+            Some(Commit {
+                hash: hash.to_string(),
+                kind: caps[1].to_lowercase(),
+                scope: caps.get(2).map(|scope| scope.as_str().trim().to_string()),
+                description: caps[4].trim().to_string(),
+                breaking: caps.get(3).is_some() || body.lines().any(|line| line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")),
+            })
+        })
+        .collect()
+}
+
+/// Breaking changes first, then a list per commit type, each entry with its scope in bold and
+/// its short hash.
+fn render_changelog(commits: &[Commit], level: usize) -> String {
+    let hashes = "#".repeat(level);
+    let mut sections = Vec::new();
+    let mut section = |title: &str, entries: Vec<&Commit>| {
+        if entries.is_empty() {
+            return;
+        }
+        let items: Vec<String> = entries.iter()
+            .map(|commit| match &commit.scope {
+                Some(scope) => format!("- **{scope}:** {} ({})", commit.description, commit.hash),
+                None => format!("- {} ({})", commit.description, commit.hash),
+            })
+            .collect();
+        sections.push(format!("{hashes} {title}\n\n{}", items.join("\n")));
+    };
+
+    section(BREAKING_TITLE, commits.iter().filter(|commit| commit.breaking).collect());
+    for (kind, title) in SECTIONS {
+        section(title, commits.iter().filter(|commit| commit.kind == kind).collect());
+    }
+    if sections.is_empty() {
+        return "*No notable changes.*".to_string();
+    }
+    sections.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directives;
+
+    #[test]
+    fn test_render_changelog() {
+        let log = "a1b2c3d\x1ffeat(pdf): add bookmarks\x1f\x1e\n\
+                   b2c3d4e\x1ffix: keep tables on one page\x1fCloses #12\n\x1e\n\
+                   c3d4e5f\x1fUpdate README\x1f\x1e\n\
+                   d4e5f6a\x1frefactor(config)!: rename the output options\x1f\x1e\n\
+                   e5f6a7b\x1fchore: bump dependencies\x1f\x1e\n\
+                   f6a7b8c\x1ffeat: drop the legacy theme\x1fBREAKING CHANGE: use the default theme instead\n\x1e\n";
+        assert_eq!(
+            render_changelog(&parse_log(log), 3),
+            "### Breaking Changes\n\n- **config:** rename the output options (d4e5f6a)\n- drop the legacy theme (f6a7b8c)\n\n\
+             ### Features\n\n- **pdf:** add bookmarks (a1b2c3d)\n- drop the legacy theme (f6a7b8c)\n\n\
+             ### Bug Fixes\n\n- keep tables on one page (b2c3d4e)\n\n\
+             ### Refactoring\n\n- **config:** rename the output options (d4e5f6a)"
+        );
+        assert_eq!(render_changelog(&parse_log("e5f6a7b\x1fchore: bump\x1f\x1e\n"), 2), "*No notable changes.*");
+    }
+
+    #[test]
+    fn test_changelog_directive_errors() {
+        let changelog = |line: &str| changelog_directive(&directives::parse(line).unwrap().unwrap());
+        assert!(changelog("!changelog(since=--output=x)").is_err());
+        assert!(changelog("!changelog(since=v1.4, level=9)").is_err());
+        assert!(changelog("!changelog(v1.4, v1.5)").is_err());
+    }
+}
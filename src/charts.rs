@@ -0,0 +1,245 @@
+use crate::render::escape_html;
+use crate::error::AppError;
+use serde::Deserialize;
+use std::f64::consts::PI;
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 340.0;
+const MARGIN_LEFT: f64 = 56.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 20.0;
+const MARGIN_BOTTOM: f64 = 40.0;
+const PALETTE: [&str; 8] = ["#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#9c755f"];
+
+/// Content of a ` ```chart ` block, written in YAML or JSON.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChartSpec {
+    #[serde(rename = "type")]
+    kind: ChartKind,
+    title: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    series: Vec<Series>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ChartKind {
+    Bar,
+    Line,
+    Pie,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Series {
+    #[serde(default)]
+    name: String,
+    values: Vec<f64>,
+}
+
+/// Renders ` ```chart ` blocks as SVG figures; other blocks are left to the highlighter.
+pub fn render_chart_block(info: &str, code: &str) -> Result<Option<String>, AppError> {
+    if info.split_whitespace().next() != Some("chart") {
+        return Ok(None);
+    }
+    let spec: ChartSpec = serde_yaml::from_str(code).map_err(|e| AppError::BuildError(format!("Invalid chart: {e}")))?;
+    render_chart(&spec).map(Some)
+}
+
+fn render_chart(spec: &ChartSpec) -> Result<String, AppError> {
+    if spec.series.is_empty() || spec.series.iter().any(|s| s.values.is_empty()) {
+        return Err(AppError::BuildError("Invalid chart: every series needs values".to_string()));
+    }
+    let points = spec.series[0].values.len();
+    if spec.kind != ChartKind::Pie && spec.series.iter().any(|s| s.values.len() != points) {
+        return Err(AppError::BuildError("Invalid chart: all series must have the same number of values".to_string()));
+    }
+    if !spec.labels.is_empty() && spec.labels.len() != points {
+        return Err(AppError::BuildError(format!("Invalid chart: {} labels for {} values", spec.labels.len(), points)));
+    }
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {WIDTH} {HEIGHT}" width="100%" font-family="sans-serif" font-size="12">"#
+    );
+    match spec.kind {
+        ChartKind::Pie => render_pie(&mut svg, spec),
+        ChartKind::Bar | ChartKind::Line => render_axes_chart(&mut svg, spec),
+    }
+    svg.push_str("</svg>");
+
+    let caption = spec.title.as_ref().map(|t| format!("<figcaption>{}</figcaption>", escape_html(t))).unwrap_or_default();
+    Ok(format!(r#"<figure class="chart">{svg}{caption}</figure>"#))
+}
+
+fn render_axes_chart(svg: &mut String, spec: &ChartSpec) {
+    let values = spec.series.iter().flat_map(|s| s.values.iter().copied());
+    let (min, max) = values.fold((0.0f64, 0.0f64), |(min, max), v| (min.min(v), max.max(v)));
+    let step = nice_step((max - min) / 5.0);
+    let (min, max) = ((min / step).floor() * step, (max / step).ceil() * step);
+    let (min, max) = if max > min { (min, max) } else { (0.0, 1.0) };
+
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let y = |v: f64| MARGIN_TOP + plot_height * (max - v) / (max - min);
+
+    // Grid lines and y-axis ticks.
+    let mut tick = min;
+    while tick <= max + step / 2.0 {
+        svg.push_str(&format!(
+            r##"<line x1="{MARGIN_LEFT}" y1="{y:.1}" x2="{x2}" y2="{y:.1}" stroke="#ddd"/><text x="{tx}" y="{ty:.1}" text-anchor="end">{label}</text>"##,
+            y = y(tick), x2 = WIDTH - MARGIN_RIGHT, tx = MARGIN_LEFT - 6.0, ty = y(tick) + 4.0, label = format_number(tick)
+        ));
+        tick += step;
+    }
+
+    let points = spec.series[0].values.len();
+    let slot = plot_width / points as f64;
+    for (index, label) in spec.labels.iter().enumerate() {
+        svg.push_str(&format!(
+            r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#,
+            MARGIN_LEFT + slot * (index as f64 + 0.5), HEIGHT - MARGIN_BOTTOM + 18.0, escape_html(label)
+        ));
+    }
+
+    let series_count = spec.series.len() as f64;
+    for (series_index, series) in spec.series.iter().enumerate() {
+        let color = PALETTE[series_index % PALETTE.len()];
+        match spec.kind {
+            ChartKind::Bar => {
+                let bar_width = slot * 0.8 / series_count;
+                for (index, value) in series.values.iter().enumerate() {
+                    let x = MARGIN_LEFT + slot * index as f64 + slot * 0.1 + bar_width * series_index as f64;
+                    let (top, bottom) = (y(value.max(0.0)), y(value.min(0.0)));
+                    svg.push_str(&format!(
+                        r#"<rect x="{x:.1}" y="{top:.1}" width="{bar_width:.1}" height="{:.1}" fill="{color}"/>"#,
+                        bottom - top
+                    ));
+                }
+            }
+            _ => {
+                let coordinates: Vec<String> = series.values.iter().enumerate()
+                    .map(|(index, value)| format!("{:.1},{:.1}", MARGIN_LEFT + slot * (index as f64 + 0.5), y(*value)))
+                    .collect();
+                svg.push_str(&format!(r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="2"/>"#, coordinates.join(" ")));
+                for point in &coordinates {
+                    let (cx, cy) = point.split_once(',').unwrap_or_default();
+                    svg.push_str(&format!(r#"<circle cx="{cx}" cy="{cy}" r="3" fill="{color}"/>"#));
+                }
+            }
+        }
+    }
+
+    svg.push_str(&format!(
+        r##"<line x1="{MARGIN_LEFT}" y1="{MARGIN_TOP}" x2="{MARGIN_LEFT}" y2="{b}" stroke="#333"/><line x1="{MARGIN_LEFT}" y1="{zero:.1}" x2="{r}" y2="{zero:.1}" stroke="#333"/>"##,
+        b = HEIGHT - MARGIN_BOTTOM, zero = y(0.0), r = WIDTH - MARGIN_RIGHT
+    ));
+
+    if spec.series.len() > 1 {
+        let names: Vec<&str> = spec.series.iter().map(|s| s.name.as_str()).collect();
+        render_legend(svg, &names);
+    }
+}
+
+fn render_pie(svg: &mut String, spec: &ChartSpec) {
+    let values = &spec.series[0].values;
+    let total: f64 = values.iter().map(|v| v.max(0.0)).sum();
+    let (cx, cy, radius) = (HEIGHT / 2.0, HEIGHT / 2.0, HEIGHT / 2.0 - MARGIN_TOP);
+    let mut angle = -PI / 2.0;
+
+    for (index, value) in values.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+        let share = if total > 0.0 { value.max(0.0) / total } else { 0.0 };
+        if share >= 1.0 {
+            svg.push_str(&format!(r#"<circle cx="{cx}" cy="{cy}" r="{radius}" fill="{color}"/>"#));
+        } else if share > 0.0 {
+            let end = angle + share * 2.0 * PI;
+            let large_arc = if share > 0.5 { 1 } else { 0 };
+            svg.push_str(&format!(
+                r#"<path d="M{cx},{cy} L{:.1},{:.1} A{radius},{radius} 0 {large_arc} 1 {:.1},{:.1} Z" fill="{color}"/>"#,
+                cx + radius * angle.cos(), cy + radius * angle.sin(), cx + radius * end.cos(), cy + radius * end.sin()
+            ));
+            angle = end;
+        }
+    }
+
+    let names: Vec<String> = values.iter().enumerate().map(|(index, value)| {
+        let label = spec.labels.get(index).cloned().unwrap_or_else(|| format!("{}", index + 1));
+        let percent = if total > 0.0 { value.max(0.0) / total * 100.0 } else { 0.0 };
+        format!("{label} ({percent:.0}%)")
+    }).collect();
+    render_legend(svg, &names.iter().map(String::as_str).collect::<Vec<_>>());
+}
+
+fn render_legend(svg: &mut String, names: &[&str]) {
+    let x = WIDTH - 170.0;
+    for (index, name) in names.iter().enumerate() {
+        let y = MARGIN_TOP + 18.0 * index as f64;
+        svg.push_str(&format!(
+            r#"<rect x="{x}" y="{y}" width="12" height="12" fill="{}"/><text x="{}" y="{}">{}</text>"#,
+            PALETTE[index % PALETTE.len()], x + 18.0, y + 10.0, escape_html(name)
+        ));
+    }
+}
+
+/// Rounds a tick interval to 1, 2 or 5 times a power of ten.
+fn nice_step(raw: f64) -> f64 {
+    if raw <= 0.0 || !raw.is_finite() {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let fraction = raw / magnitude;
+    let nice = if fraction <= 1.0 { 1.0 } else if fraction <= 2.0 { 2.0 } else if fraction <= 5.0 { 5.0 } else { 10.0 };
+    nice * magnitude
+}
+
+fn format_number(value: f64) -> String {
+    if (value - value.round()).abs() < 1e-9 {
+        format!("{}", value.round() as i64)
+    } else {
+        format!("{value:.2}").trim_end_matches('0').to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_bar_chart() {
+        let code = "type: bar\ntitle: Sales & costs\nlabels: [Q1, Q2, Q3]\nseries:\n  - name: Sales\n    values: [10, 25, 17]\n  - name: Costs\n    values: [8, 12, 30]\n";
+        let html = render_chart_block("chart", code).unwrap().unwrap();
+        assert!(html.starts_with(r#"<figure class="chart"><svg"#));
+        assert_eq!(html.matches("<rect x").count(), 6 + 2);
+        assert!(html.contains(">Q2</text>"));
+        assert!(html.contains(">30</text>"));
+        assert!(html.contains("<figcaption>Sales &amp; costs</figcaption>"));
+        assert!(!html.contains('\n'));
+    }
+
+    #[test]
+    fn test_render_line_and_pie_charts() {
+        let line = render_chart_block("chart", r#"{"type": "line", "series": [{"values": [1, 3, 2]}]}"#).unwrap().unwrap();
+        assert!(line.contains("<polyline"));
+        assert_eq!(line.matches("<circle").count(), 3);
+
+        let pie = render_chart_block("chart", "type: pie\nlabels: [A, B]\nseries:\n  - values: [3, 1]\n").unwrap().unwrap();
+        assert_eq!(pie.matches("<path").count(), 2);
+        assert!(pie.contains(">A (75%)</text>"));
+    }
+
+    #[test]
+    fn test_render_chart_errors() {
+        assert!(render_chart_block("rust", "fn main() {}").unwrap().is_none());
+        assert!(render_chart_block("chart", "type: radar\nseries: []").is_err());
+        assert!(render_chart_block("chart", "type: bar\nlabels: [A]\nseries:\n  - values: [1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_nice_step() {
+        assert_eq!(nice_step(6.0), 10.0);
+        assert_eq!(nice_step(0.3), 0.5);
+        assert_eq!(format_number(2.5), "2.5");
+    }
+}
@@ -0,0 +1,223 @@
+use crate::book::BookConfig;
+use crate::builder;
+use crate::changelog;
+use crate::chapter;
+use crate::directives::{self, LineScanner, SourceLine};
+use crate::error::AppError;
+use crate::fragments;
+use crate::git::GitInfo;
+use crate::jsonschema;
+use crate::lint;
+use crate::openapi;
+use crate::paths::SourceDir;
+use crate::prose_linter;
+use crate::remote;
+use crate::rustdoc;
+use crate::spelling::{self, Dictionary};
+use crate::variables;
+use crate::Config;
+use colored::*;
+use pdfbuilder_core::preprocess;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// A problem found in a source file by one of the `check` passes.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub rule: String,
+    pub message: String,
+}
+
+/// A Markdown file reachable from the configured source through `!include`.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+#[derive(Debug, Default)]
+pub struct CheckOptions {
+    pub spelling: bool,
+}
+
+/// Validates the document without building it and reports diagnostics with file and line.
+pub fn run_check(config: &Config, options: &CheckOptions) -> Result<(), AppError> {
+    let project_root = std::env::current_dir()?;
+
+    // Expanding the document surfaces missing includes and cycles. Boilerplate pages, back
+    // matter and the dedication are left out so expanded lines map back to the sources.
+    let mut expand_config = config.clone();
+    expand_config.boilerplate = Default::default();
+    expand_config.back_matter.clear();
+    expand_config.dedication = None;
+    // Missing images, which only raise warnings in builds, fail the check.
+    let includes = builder::includes_for(&expand_config).with_missing_images_fatal();
+    let vars = variables::builtin_variables(&expand_config, &GitInfo::default());
+    let expanded_markdown = builder::expand_with_includes(&project_root, Path::new(&config.source), &expand_config, &vars, includes)?;
+    let sources = config_sources(config)?;
+
+    let mut diagnostics = lint::lint_sources(&sources.files, &config.lint);
+    if options.spelling {
+        let dictionary = Dictionary::load(&config.language)?;
+        diagnostics.extend(spelling::check_spelling(&sources.files, &dictionary));
+    }
+    for linter in &config.lint.external {
+        println!("{} {}", "Running external linter:".blue(), linter.name.yellow());
+        diagnostics.extend(prose_linter::run_external_linter(linter, &expanded_markdown, &sources, &config.build_dir())?);
+    }
+
+    for diagnostic in &diagnostics {
+        println!(
+            "{}:{}: {} {}",
+            diagnostic.file.yellow(),
+            diagnostic.line,
+            format!("[{}]", diagnostic.rule).cyan(),
+            diagnostic.message
+        );
+    }
+
+    if diagnostics.is_empty() {
+        println!("{} {}", "Check passed:".green(), format!("{} file(s) checked.", sources.files.len()).green());
+        Ok(())
+    } else {
+        Err(AppError::CheckFailed(diagnostics.len()))
+    }
+}
+
+/// The files making up the document and, for each line of the expanded Markdown,
+/// the `(file index, line number)` it comes from.
+#[derive(Debug, Default)]
+pub struct Sources {
+    pub files: Vec<SourceFile>,
+    pub line_origins: Vec<(usize, usize)>,
+}
+
+impl Sources {
+    /// Maps a 1-based line of the expanded Markdown back to its source file and line.
+    pub fn origin(&self, expanded_line: usize) -> Option<(&str, usize)> {
+        let (file, line) = self.line_origins.get(expanded_line.checked_sub(1)?)?;
+        Some((self.files[*file].path.as_str(), *line))
+    }
+}
+
+/// The sources of the document: the `book:` structure, or the `source` file, with the files
+/// they include.
+pub fn config_sources(config: &Config) -> Result<Sources, AppError> {
+    match &config.book {
+        Some(book) => collect_book_sources(book),
+        None => collect_sources(&config.source, config.allow_remote_includes.then(|| remote::cache_dir(&config.build_dir())).as_deref()),
+    }
+}
+
+/// Reads the source file and every file it includes, in document order. Remote includes are
+/// not checked; their lines, read from `remote_cache`, map to the include line.
+pub fn collect_sources(source: &str, remote_cache: Option<&Path>) -> Result<Sources, AppError> {
+    let mut sources = Sources::default();
+    collect_sources_recursive(Path::new(source), false, remote_cache, &mut HashSet::new(), &mut sources)?;
+    Ok(sources)
+}
+
+/// Reads every file of a `book:` structure and the files they include.
+/// Expanded lines are not mapped back because the assembly adds part dividers and wrappers.
+pub fn collect_book_sources(book: &BookConfig) -> Result<Sources, AppError> {
+    let mut sources = Sources::default();
+    let mut visited = HashSet::new();
+    for file in book.files() {
+        collect_sources_recursive(Path::new(file), true, None, &mut visited, &mut sources)?;
+    }
+    sources.line_origins.clear();
+    Ok(sources)
+}
+
+/// `included` files are wrapped in a chapter section, like those with front matter or `!style` lines.
+fn collect_sources_recursive(path: &Path, included: bool, remote_cache: Option<&Path>, visited: &mut HashSet<String>, sources: &mut Sources) -> Result<(), AppError> {
+    let path_str = path_clean::clean(path).display().to_string();
+    if !visited.insert(path_str.clone()) {
+        return Ok(());
+    }
+
+    // Only the `source` itself can be a resume.
+    let content = builder::read_source(path, !included)?;
+    let base_path = path.parent().unwrap_or_else(|| Path::new(""));
+    // Symbolic links were already checked by the expansion of the document.
+    let source_dir = SourceDir { project_root: Path::new("."), dir: base_path, follow_symlinks: true };
+    let file_index = sources.files.len();
+    sources.files.push(SourceFile { path: path_str, content: content.clone() });
+
+    // Mirrors `preprocess_markdown`: one expanded line per source line, with an
+    // included file's lines followed by one extra line for the include itself.
+    // Included files, front matter and `!style` lines add a two-line section wrapper around the body.
+    let (meta, body) = chapter::split_front_matter(&content, &sources.files[file_index].path)?;
+    let first_body_line = content[..content.len() - body.len()].lines().count() + 1;
+    let wrapped = included || meta.is_some() || !preprocess::style_directives(body).is_empty();
+    if wrapped {
+        let title_lines = if meta.as_ref().is_some_and(|meta| meta.title.is_some()) && chapter::first_heading(body).is_none() { 2 } else { 0 };
+        sources.line_origins.extend(std::iter::repeat_n((file_index, first_body_line), 2 + title_lines));
+    }
+
+    let mut scanner = LineScanner::default();
+    for (index, line) in body.lines().enumerate() {
+        let kind = scanner.scan(line);
+        let directive = if kind == SourceLine::Directive { directives::parse(line)? } else { None };
+        // Generated Markdown spans several lines, all pointing at the directive.
+        let generated = match directive.as_ref() {
+            Some(directive) if directive.name == "changelog" => Some(changelog::changelog_directive(directive)?),
+            Some(directive) if directive.name == "openapi" => Some(openapi::openapi_directive(source_dir, directive)?),
+            Some(directive) if directive.name == "jsonschema" => Some(jsonschema::jsonschema_directive(source_dir, directive)?),
+            Some(directive) if directive.name == "rustdoc" => Some(rustdoc::rustdoc_directive(source_dir, directive)?),
+            // Sanitizing keeps the lines of the fragment.
+            Some(directive) if directive.name == "html" => Some(fragments::html_directive(source_dir, directive, false)?),
+            _ => None,
+        };
+        if let Some(generated) = generated {
+            sources.line_origins.extend(std::iter::repeat_n((file_index, first_body_line + index), generated.lines().count().saturating_sub(1)));
+        }
+        let directive = directive.filter(|directive| directive.name == "include");
+        match directive.as_ref().and_then(|directive| directive.value("file", 0)) {
+            Some(url) if remote::is_remote(url) => {
+                let lines = remote_cache.and_then(|dir| remote::cached(dir, url)).map_or(0, |content| content.lines().count());
+                sources.line_origins.extend(std::iter::repeat_n((file_index, first_body_line + index), lines + 4));
+            }
+            Some(file) => collect_sources_recursive(&base_path.join(file), true, remote_cache, visited, sources)?,
+            None => {}
+        }
+        sources.line_origins.push((file_index, first_body_line + index));
+    }
+
+    if wrapped {
+        let last_line = first_body_line + body.lines().count().saturating_sub(1);
+        sources.line_origins.extend([(file_index, last_line), (file_index, last_line)]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_collect_sources_follows_includes() {
+        let test_dir = TestDir::new("check_collect_sources");
+        let dir = test_dir.path();
+        fs::create_dir_all(dir.join("chapters")).unwrap();
+        fs::write(dir.join("main.md"), "# Book\n!include(chapters/one.md)\n```\n!include(ignored.md)\n```").unwrap();
+        fs::write(dir.join("chapters/one.md"), "## One").unwrap();
+
+        let sources = collect_sources(dir.join("main.md").to_str().unwrap(), None).unwrap();
+        assert_eq!(sources.files.len(), 2);
+        assert!(sources.files[1].path.ends_with("one.md"));
+        assert_eq!(sources.files[1].content, "## One");
+
+        // Line 4 of the expanded document is the first line of the included chapter, after
+        // the opening of its section.
+        let (file, line) = sources.origin(4).unwrap();
+        assert!(file.ends_with("one.md"));
+        assert_eq!(line, 1);
+        assert!(sources.origin(6).unwrap().0.ends_with("one.md"));
+        assert!(sources.origin(7).unwrap().0.ends_with("main.md"));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::error::AppError;
+use crate::pagemap;
+use crate::raster;
+use colored::*;
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Resolution at which the pages are compared.
+const COMPARE_DPI: u32 = 72;
+
+/// Side of the square cells in which changes are highlighted, in pixels.
+const CELL: usize = 12;
+
+/// Channel difference below which two pixels are considered the same, so anti-aliasing
+/// differences are not reported.
+const TOLERANCE: u8 = 32;
+
+/// Compares two PDFs page by page and writes an image of every changed page to `out_dir`,
+/// with the changed regions in red, along with the pages added and removed.
+pub fn run_compare(old: &Path, new: &Path, out_dir: &Path) -> Result<(), AppError> {
+    let old_texts = pagemap::extract_page_texts(&fs::read(old)?)?;
+    let new_texts = pagemap::extract_page_texts(&fs::read(new)?)?;
+    let pages_dir = out_dir.join(".pages");
+    let old_images = raster::rasterize_pdf(old, &pages_dir.join("old"), COMPARE_DPI)?;
+    let new_images = raster::rasterize_pdf(new, &pages_dir.join("new"), COMPARE_DPI)?;
+
+    // Images of an earlier comparison would be mistaken for current changes.
+    fs::create_dir_all(out_dir)?;
+    for entry in fs::read_dir(out_dir)?.filter_map(Result::ok) {
+        if entry.path().extension().is_some_and(|extension| extension == "png") {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    let (mut changed, mut added, mut removed) = (Vec::new(), Vec::new(), Vec::new());
+    for pair in align_pages(&old_texts, &new_texts) {
+        match pair {
+            PagePair::Both(old_page, new_page) => {
+                let (Some(old_image), Some(new_image)) = (old_images.get(old_page), new_images.get(new_page)) else { continue };
+                if let Some(diff) = diff_image(&PageImage::load(old_image)?, &PageImage::load(new_image)?) {
+                    diff.save(&out_dir.join(format!("page-{:03}.png", new_page + 1)))?;
+                    changed.push(new_page + 1);
+                }
+            }
+            PagePair::Added(new_page) => {
+                if let Some(image) = new_images.get(new_page) {
+                    fs::copy(image, out_dir.join(format!("added-page-{:03}.png", new_page + 1)))?;
+                }
+                added.push(new_page + 1);
+            }
+            PagePair::Removed(old_page) => {
+                if let Some(image) = old_images.get(old_page) {
+                    fs::copy(image, out_dir.join(format!("removed-page-{:03}.png", old_page + 1)))?;
+                }
+                removed.push(old_page + 1);
+            }
+        }
+    }
+    fs::remove_dir_all(&pages_dir).ok();
+
+    let pages = |pages: &[usize]| pages.iter().map(|page| page.to_string()).collect::<Vec<_>>().join(", ");
+    let summary = [
+        format!("Pages: {} -> {}", old_texts.len(), new_texts.len()),
+        format!("Changed pages ({}): {}", changed.len(), pages(&changed)),
+        format!("Added pages ({}): {}", added.len(), pages(&added)),
+        format!("Removed pages ({}, numbered as in the old PDF): {}", removed.len(), pages(&removed)),
+    ]
+    .join("\n");
+    fs::write(out_dir.join("summary.txt"), format!("{summary}\n"))?;
+    println!("{summary}");
+    println!("{} {}", "Page images written to".green(), out_dir.display().to_string().yellow());
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum PagePair {
+    Both(usize, usize),
+    Added(usize),
+    Removed(usize),
+}
+
+/// Pairs the pages of both versions by their text, so an inserted page shows up as added
+/// instead of shifting every following page.
+fn align_pages(old: &[String], new: &[String]) -> Vec<PagePair> {
+    let mut pairs = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, old, new) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Delete => pairs.extend(old_range.map(PagePair::Removed)),
+            DiffTag::Insert => pairs.extend(new_range.map(PagePair::Added)),
+            DiffTag::Equal | DiffTag::Replace => {
+                let paired = old_range.len().min(new_range.len());
+                pairs.extend(old_range.clone().zip(new_range.clone()).map(|(old_page, new_page)| PagePair::Both(old_page, new_page)));
+                pairs.extend(old_range.skip(paired).map(PagePair::Removed));
+                pairs.extend(new_range.skip(paired).map(PagePair::Added));
+            }
+        }
+    }
+    pairs
+}
+
+/// An RGB image of a page.
+#[derive(Debug, Clone, PartialEq)]
+struct PageImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl PageImage {
+    fn load(path: &Path) -> Result<Self, AppError> {
+        let image_error = |e: png::DecodingError| AppError::RasterizerError(format!("'{}': {e}", path.display()));
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(image_error)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(image_error)?;
+        let channels = info.color_type.samples();
+        let pixels = buffer[..info.buffer_size()]
+            .chunks(channels)
+            .map(|pixel| match pixel.len() {
+                1 | 2 => [pixel[0]; 3],
+                _ => [pixel[0], pixel[1], pixel[2]],
+            })
+            .collect();
+        Ok(Self { width: info.width as usize, height: info.height as usize, pixels })
+    }
+
+    fn save(&self, path: &Path) -> Result<(), AppError> {
+        let image_error = |e: png::EncodingError| AppError::RasterizerError(format!("'{}': {e}", path.display()));
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(image_error)?;
+        writer.write_image_data(&self.pixels.concat()).map_err(image_error)
+    }
+
+    /// The pixel at (x, y), white outside the image.
+    fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x]
+        } else {
+            [255; 3]
+        }
+    }
+}
+
+/// The new page, faded, with the cells that changed since the old one in red. `None` when
+/// the pages look the same.
+fn diff_image(old: &PageImage, new: &PageImage) -> Option<PageImage> {
+    let (width, height) = (old.width.max(new.width), old.height.max(new.height));
+    let columns = width.div_ceil(CELL);
+    let mut changed = vec![false; columns * height.div_ceil(CELL)];
+    for y in 0..height {
+        for x in 0..width {
+            let (a, b) = (old.pixel(x, y), new.pixel(x, y));
+            if a.iter().zip(b).any(|(a, b)| a.abs_diff(b) > TOLERANCE) {
+                changed[(y / CELL) * columns + x / CELL] = true;
+            }
+        }
+    }
+    if !changed.contains(&true) {
+        return None;
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = new.pixel(x, y);
+            pixels.push(if changed[(y / CELL) * columns + x / CELL] {
+                [255, g / 2, b / 2]
+            } else {
+                [255 - (255 - r) / 3, 255 - (255 - g) / 3, 255 - (255 - b) / 3]
+            });
+        }
+    }
+    Some(PageImage { width, height, pixels })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_pages() {
+        let pages = |texts: &[&str]| texts.iter().map(|text| text.to_string()).collect::<Vec<_>>();
+        let pairs = align_pages(&pages(&["Title", "Intro", "Usage", "Index"]), &pages(&["Title", "Intro", "Install", "Usage", "Index v2"]));
+        assert_eq!(pairs, [
+            PagePair::Both(0, 0),
+            PagePair::Both(1, 1),
+            PagePair::Added(2),
+            PagePair::Both(2, 3),
+            PagePair::Both(3, 4),
+        ]);
+    }
+
+    #[test]
+    fn test_diff_image() {
+        let blank = PageImage { width: 30, height: 20, pixels: vec![[255; 3]; 600] };
+        assert_eq!(diff_image(&blank, &blank.clone()), None);
+
+        let mut edited = blank.clone();
+        edited.pixels[5 * 30 + 25] = [0, 0, 0];
+        let diff = diff_image(&blank, &edited).unwrap();
+        assert_eq!(diff.pixel(25, 5), [255, 0, 0]);
+        assert_eq!(diff.pixel(24, 0), [255, 127, 127]);
+        assert_eq!(diff.pixel(0, 0), [255, 255, 255]);
+        assert_eq!(diff.pixel(25, 15), [255, 255, 255]);
+    }
+}
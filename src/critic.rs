@@ -0,0 +1,69 @@
+use crate::error::AppError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use lopdf::{Object, StringFormat};
+
+pub use pdfbuilder_core::critic::*;
+
+/// Turns the comment links printed by Chrome into PDF text annotations at the same place.
+pub fn comment_links_to_annotations(pdf: &[u8]) -> Result<Vec<u8>, AppError> {
+    let pdf_error = |e: lopdf::Error| AppError::BuildError(format!("Could not add PDF comments: {e}"));
+    let mut document = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+
+    let mut annotation_ids = Vec::new();
+    for page_id in document.get_pages().into_values() {
+        let page = document.get_dictionary(page_id).map_err(pdf_error)?;
+        let Ok(annotations) = page.get(b"Annots") else {
+            continue;
+        };
+        let (_, annotations) = document.dereference(annotations).map_err(pdf_error)?;
+        if let Ok(annotations) = annotations.as_array() {
+            annotation_ids.extend(annotations.iter().filter_map(|annotation| annotation.as_reference().ok()));
+        }
+    }
+
+    for id in annotation_ids {
+        let comment = {
+            let annotation = document.get_dictionary(id).map_err(pdf_error)?;
+            let uri = annotation.get(b"A").ok()
+                .and_then(|action| document.dereference(action).ok())
+                .and_then(|(_, action)| action.as_dict().ok()?.get(b"URI").ok()?.as_str().ok());
+            uri.and_then(|uri| uri.strip_prefix(COMMENT_LINK_PREFIX.as_bytes()))
+                .and_then(|encoded| URL_SAFE_NO_PAD.decode(encoded).ok())
+                .map(|decoded| String::from_utf8_lossy(&decoded).to_string())
+        };
+        let Some(comment) = comment else {
+            continue;
+        };
+
+        let annotation = document.get_object_mut(id).and_then(Object::as_dict_mut).map_err(pdf_error)?;
+        annotation.remove(b"A");
+        annotation.remove(b"Border");
+        annotation.set("Subtype", "Text");
+        annotation.set("Name", "Comment");
+        annotation.set("Contents", Object::String(pdf_text_string(&comment), StringFormat::Hexadecimal));
+        annotation.set("Open", false);
+        annotation.set("C", vec![Object::Real(1.0), Object::Real(0.65), Object::Real(0.0)]);
+    }
+
+    let mut output = Vec::new();
+    document.save_to(&mut output)?;
+    Ok(output)
+}
+
+/// UTF-16BE with a byte order mark, the PDF encoding for text outside Latin-1.
+fn pdf_text_string(text: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdf_text_string() {
+        assert_eq!(pdf_text_string("é"), vec![0xFE, 0xFF, 0x00, 0xE9]);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::cache::{self, Cache};
+use crate::error::AppError;
+use crate::network;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// How ` ```plantuml ` and ` ```dot ` blocks are rendered.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiagramsConfig {
+    /// Kroki-compatible rendering server (e.g. `https://kroki.io`). Local tools are used when absent.
+    pub server: Option<String>,
+    /// Command reading PlantUML on stdin and writing SVG on stdout.
+    #[serde(default = "default_plantuml_command")]
+    pub plantuml_command: String,
+    /// Command reading Graphviz DOT on stdin and writing SVG on stdout.
+    #[serde(default = "default_dot_command")]
+    pub dot_command: String,
+}
+
+fn default_plantuml_command() -> String {
+    "plantuml -tsvg -pipe".to_string()
+}
+
+fn default_dot_command() -> String {
+    "dot -Tsvg".to_string()
+}
+
+impl Default for DiagramsConfig {
+    fn default() -> Self {
+        Self { server: None, plantuml_command: default_plantuml_command(), dot_command: default_dot_command() }
+    }
+}
+
+/// External command rendering the blocks of one fence language (`renderers:` config).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RendererConfig {
+    /// Receives the block on stdin and writes the image on stdout. `{input}` and `{output}`
+    /// are replaced by files for tools that cannot use stdin or stdout.
+    pub command: String,
+    #[serde(default)]
+    pub format: ImageFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "svg",
+            ImageFormat::Png => "png",
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "image/svg+xml",
+            ImageFormat::Png => "image/png",
+        }
+    }
+}
+
+/// Renders blocks whose fence language has a configured renderer, cached like diagrams.
+pub fn render_custom_block(renderers: &BTreeMap<String, RendererConfig>, cache: &Cache, info: &str, code: &str) -> Result<Option<String>, AppError> {
+    let Some((language, renderer)) = info.split_whitespace().next().and_then(|lang| renderers.get_key_value(lang)) else {
+        return Ok(None);
+    };
+    let extension = renderer.format.extension();
+    let image = cache.get_or_insert_with(&format!("renderer-{language}"), &format!("{}\n{}", renderer.command, code), extension, || {
+        run_renderer(language, renderer, &cache.work_dir(&cache::content_hash(code)), code)
+    })?;
+    Ok(Some(image_figure(&image, renderer.format.mime(), &format!("diagram diagram-{language}"))))
+}
+
+fn run_renderer(language: &str, renderer: &RendererConfig, work_dir: &Path, code: &str) -> Result<Vec<u8>, AppError> {
+    let uses_files = renderer.command.contains("{input}") || renderer.command.contains("{output}");
+    if !uses_files {
+        return run_converter(language, &renderer.command, code.as_bytes());
+    }
+
+    // The command is split on spaces: a build directory containing spaces cannot be used here.
+    fs::create_dir_all(work_dir)?;
+    let input = work_dir.join("input");
+    let output = work_dir.join(format!("output.{}", renderer.format.extension()));
+    fs::write(&input, code)?;
+
+    let command = renderer.command.replace("{input}", &input.to_string_lossy()).replace("{output}", &output.to_string_lossy());
+    let result = run_converter(language, &command, code.as_bytes()).and_then(|stdout| {
+        if renderer.command.contains("{output}") {
+            fs::read(&output).map_err(|e| AppError::RendererError(language.to_string(), format!("no output written to {}: {}", output.display(), e)))
+        } else {
+            Ok(stdout)
+        }
+    });
+    fs::remove_dir_all(work_dir).ok();
+    result
+}
+
+/// Renders ` ```plantuml ` (or `puml`) and ` ```dot ` (or `graphviz`) blocks to SVG figures.
+/// Results are cached by content, so only changed diagrams are rendered again.
+pub fn render_diagram_block(config: &DiagramsConfig, cache: &Cache, info: &str, code: &str) -> Result<Option<String>, AppError> {
+    let (kind, command) = match info.split_whitespace().next() {
+        Some("plantuml" | "puml") => ("plantuml", &config.plantuml_command),
+        Some("dot" | "graphviz") => ("graphviz", &config.dot_command),
+        _ => return Ok(None),
+    };
+
+    // The backend is part of the key: another tool or server may render differently.
+    let backend = config.server.as_deref().unwrap_or(command);
+    let svg = cache.get_or_insert_with(kind, &format!("{backend}\n{code}"), "svg", || match &config.server {
+        Some(server) => render_with_server(server, kind, code),
+        None => run_converter(kind, command, code.as_bytes()),
+    })?;
+    Ok(Some(image_figure(&svg, "image/svg+xml", &format!("diagram diagram-{kind}"))))
+}
+
+/// Runs `command` with `input` on stdin and returns its stdout.
+pub fn run_converter(name: &str, command: &str, input: &[u8]) -> Result<Vec<u8>, AppError> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    let Some((program, args)) = args.split_first() else {
+        return Err(AppError::RendererError(name.to_string(), "empty command".to_string()));
+    };
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AppError::RendererError(name.to_string(), format!("could not run '{program}': {e}")))?;
+
+    // Written from another thread so a large output cannot block the input.
+    let mut stdin = child.stdin.take().ok_or_else(|| AppError::RendererError(name.to_string(), "no stdin".to_string()))?;
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = child.wait_with_output()?;
+    let written = writer.join().map_err(|_| AppError::RendererError(name.to_string(), "could not write input".to_string()))?;
+
+    // A failing tool may exit before reading its input: report its error rather than the broken pipe.
+    if !output.status.success() {
+        return Err(AppError::RendererError(name.to_string(), String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    written?;
+    Ok(output.stdout)
+}
+
+fn render_with_server(server: &str, kind: &str, code: &str) -> Result<Vec<u8>, AppError> {
+    let url = format!("{}/{}/svg", server.trim_end_matches('/'), kind);
+    let response = network::agent(&url).post(&url)
+        .header("Content-Type", "text/plain")
+        .send(code)
+        .map_err(|e| AppError::RendererError(kind.to_string(), format!("{url}: {e}")))?;
+    response.into_body().read_to_vec().map_err(|e| AppError::RendererError(kind.to_string(), format!("{url}: {e}")))
+}
+
+/// Embeds a rendered image as a data URI, so it needs no file next to the HTML.
+pub fn image_figure(content: &[u8], mime: &str, class: &str) -> String {
+    format!(r#"<figure class="{class}"><img src="data:{mime};base64,{}" alt=""></figure>"#, STANDARD.encode(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_render_diagram_block_ignores_other_languages() {
+        let test_dir = TestDir::new("diagrams_other");
+        let cache = Cache::new(test_dir.path());
+        assert!(render_diagram_block(&DiagramsConfig::default(), &cache, "rust", "fn main() {}").unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_render_diagram_block_with_local_command() {
+        let test_dir = TestDir::new("diagrams_local");
+        let dir = test_dir.path();
+        let cache = Cache::new(dir);
+        let config = DiagramsConfig { dot_command: "cat".to_string(), ..DiagramsConfig::default() };
+
+        let figure = render_diagram_block(&config, &cache, "dot", "<svg/>").unwrap().unwrap();
+        assert_eq!(figure, format!(r#"<figure class="diagram diagram-graphviz"><img src="data:image/svg+xml;base64,{}" alt=""></figure>"#, STANDARD.encode("<svg/>")));
+        assert_eq!(std::fs::read_dir(dir.join("graphviz")).unwrap().count(), 1);
+
+        let failing = DiagramsConfig { dot_command: "false".to_string(), ..DiagramsConfig::default() };
+        assert!(render_diagram_block(&failing, &cache, "dot", "digraph {}").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_render_custom_block() {
+        let test_dir = TestDir::new("diagrams_custom");
+        let dir = test_dir.path();
+        let cache = Cache::new(dir);
+        let renderers: BTreeMap<String, RendererConfig> = serde_yaml::from_str(
+            "abc:\n  command: \"cat\"\nvega:\n  command: \"cp {input} {output}\"\n  format: png\n",
+        ).unwrap();
+
+        let stdin_figure = render_custom_block(&renderers, &cache, "abc", "X:1").unwrap().unwrap();
+        assert!(stdin_figure.contains(&format!("data:image/svg+xml;base64,{}", STANDARD.encode("X:1"))));
+
+        let file_figure = render_custom_block(&renderers, &cache, "vega", "{}").unwrap().unwrap();
+        assert!(file_figure.starts_with(r#"<figure class="diagram diagram-vega"><img src="data:image/png;base64,"#));
+        assert!(render_custom_block(&renderers, &cache, "lilypond", "c d e").unwrap().is_none());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::builder;
+use crate::cancel::CancelToken;
+use crate::error::AppError;
+use crate::git::{GitInfo, Worktree};
+use crate::warnings;
+use crate::{Config, OutputFormat};
+use colored::*;
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::path::Path;
+
+const REDLINE_CSS: &str = r#"/* Redline */
+ins.redline, del.redline { display: block; text-decoration: none; padding-left: 0.6em; margin: 0.5em 0; }
+ins.redline { background-color: #e6ffed; border-left: 4px solid #2cbe4e; color: #1a1a1a; }
+del.redline { background-color: #ffeef0; border-left: 4px solid #cb2431; color: #86181d; }
+del.redline, del.redline * { text-decoration: line-through; }"#;
+
+/// Builds a "redline" PDF of the current document with the changes since `against`
+/// (a git reference or a directory containing a previous version) marked up.
+pub async fn run_diff(config: &Config, against: &str) -> Result<(), AppError> {
+    let project_root = std::env::current_dir()?;
+    let git_info = GitInfo::collect();
+    let new_markdown = builder::expand_markdown(&project_root, Path::new(&config.source), config, &git_info)?;
+
+    let against_path = Path::new(against);
+    let old_markdown = if against_path.is_dir() {
+        println!("{} {}", "Comparing against directory:".blue(), against.yellow());
+        let old_root = path_clean::clean(project_root.join(against_path));
+        builder::expand_markdown(&old_root, &old_root.join(&config.source), config, &git_info)?
+    } else {
+        println!("{} {}", "Comparing against revision:".blue(), against.yellow());
+        let worktree = Worktree::checkout(against)?;
+        let old_root = worktree.project_root();
+        builder::expand_markdown(old_root, &old_root.join(&config.source), config, &git_info)?
+    };
+
+    let mut redline_config = config.clone();
+    redline_config.output.filename = format!("{}-redline", config.output.filename);
+
+    let markdown = redline_markdown(&old_markdown, &new_markdown);
+    let (result, warnings) = warnings::collect(builder::render_document(&redline_config, markdown, &git_info, REDLINE_CSS, OutputFormat::Pdf, &CancelToken::default())).await;
+    warnings::print_summary(&warnings);
+    result
+}
+
+/// Diffs two Markdown documents block by block and wraps removed blocks in `<del>`
+/// and added blocks in `<ins>`, keeping the Markdown inside them renderable.
+fn redline_markdown(old: &str, new: &str) -> String {
+    let old_blocks = split_blocks(old);
+    let new_blocks = split_blocks(new);
+
+    let mut result = String::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_blocks, &new_blocks) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {
+                for block in &new_blocks[new_range] {
+                    push_block(&mut result, block, None);
+                }
+            }
+            DiffTag::Delete => {
+                for block in &old_blocks[old_range] {
+                    push_block(&mut result, block, Some("del"));
+                }
+            }
+            DiffTag::Insert => {
+                for block in &new_blocks[new_range] {
+                    push_block(&mut result, block, Some("ins"));
+                }
+            }
+            DiffTag::Replace => {
+                for block in &old_blocks[old_range] {
+                    push_block(&mut result, block, Some("del"));
+                }
+                for block in &new_blocks[new_range] {
+                    push_block(&mut result, block, Some("ins"));
+                }
+            }
+        }
+    }
+    result
+}
+
+fn push_block(result: &mut String, block: &str, tag: Option<&str>) {
+    match tag {
+        // Blank lines around the block let the Markdown inside the HTML tag be parsed normally.
+        Some(tag) => result.push_str(&format!("<{tag} class=\"redline\">\n\n{block}\n\n</{tag}>\n\n")),
+        None => {
+            result.push_str(block);
+            result.push_str("\n\n");
+        }
+    }
+}
+
+/// Splits Markdown into blank-line separated blocks, keeping fenced code blocks whole.
+fn split_blocks(markdown: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        if line.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+
+        if !in_code_block && line.trim().is_empty() {
+            if !current.is_empty() {
+                blocks.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current.join("\n"));
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_blocks_keeps_code_blocks_whole() {
+        let blocks = split_blocks("Intro\n\n```\nline 1\n\nline 2\n```\n\nOutro");
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[1], "```\nline 1\n\nline 2\n```");
+    }
+
+    #[test]
+    fn test_redline_marks_insertions_and_deletions() {
+        let old = "# Title\n\nOld paragraph.\n\nKept paragraph.";
+        let new = "# Title\n\nNew paragraph.\n\nKept paragraph.\n\nAdded at the end.";
+        let result = redline_markdown(old, new);
+
+        assert!(result.contains("<del class=\"redline\">\n\nOld paragraph.\n\n</del>"));
+        assert!(result.contains("<ins class=\"redline\">\n\nNew paragraph.\n\n</ins>"));
+        assert!(result.contains("<ins class=\"redline\">\n\nAdded at the end.\n\n</ins>"));
+        assert!(!result.contains("<ins class=\"redline\">\n\nKept paragraph."));
+    }
+
+    #[test]
+    fn test_redline_identical_documents_have_no_marks() {
+        let doc = "# Title\n\nSame.";
+        let result = redline_markdown(doc, doc);
+        assert!(!result.contains("redline"));
+    }
+}
@@ -0,0 +1,325 @@
+use crate::error::AppError;
+use crate::inline;
+use crate::Config;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use regex::{Captures, Regex};
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How `--format email-html` embeds images.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailImages {
+    /// Data URIs in the HTML file.
+    #[default]
+    Base64,
+    /// Attachments of a `.eml` message, referenced as `cid:` URLs.
+    Cid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EmailConfig {
+    #[serde(default)]
+    pub images: EmailImages,
+    /// Subject of the `.eml` message. Defaults to the title.
+    pub subject: Option<String>,
+}
+
+/// Elements dropped with their content: email clients strip or refuse them.
+const DROPPED_ELEMENTS: [&str; 12] = ["script", "style", "link", "noscript", "template", "iframe", "object", "embed", "form", "input", "button", "select"];
+
+/// Layout elements many clients do not know, written as `<div>`.
+const DIV_ELEMENTS: [&str; 11] = ["section", "nav", "main", "article", "header", "footer", "aside", "figure", "figcaption", "details", "summary"];
+
+const VOID_ELEMENTS: [&str; 12] = ["area", "base", "br", "col", "embed", "hr", "img", "input", "meta", "source", "track", "wbr"];
+
+/// A `property: value` pair of a style rule.
+type Declaration = (String, String);
+
+/// Rewrites the HTML page at `html_path` as an email and returns the file to send: the page
+/// itself, or `<name>.eml` next to it when images are attached with `email.images: cid`.
+pub fn write_email(config: &Config, html_path: &Path) -> Result<PathBuf, AppError> {
+    let base_dir = html_path.parent().unwrap_or_else(|| Path::new(""));
+    let html = to_email_html(&inline::inline_resources(&fs::read_to_string(html_path)?, base_dir));
+    fs::write(html_path, &html)?;
+    if config.email.images == EmailImages::Base64 {
+        return Ok(html_path.to_path_buf());
+    }
+    let eml_path = html_path.with_extension("eml");
+    fs::write(&eml_path, mime_message(config.email.subject.as_deref().unwrap_or(&config.title), &html))?;
+    Ok(eml_path)
+}
+
+/// Inlines the stylesheets of a page into `style` attributes and limits it to the markup email
+/// clients display: scripts, forms and embeds go, HTML5 layout elements become `<div>`, classes
+/// are removed and the body is wrapped in a presentation table carrying its styles. Rules are
+/// applied by specificity, then in order, before the element's own `style`; `@media`, `@page`
+/// and other at-rules, and selectors with pseudo-elements or states such as `:hover`, are dropped.
+pub fn to_email_html(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let style_selector = Selector::parse("style").unwrap();
+    let css: String = document.select(&style_selector).flat_map(|style| style.text()).collect();
+
+    // Elements are keyed by address, stable while the document lives.
+    let mut matches: HashMap<*const Element, Vec<(u32, usize, &Declaration)>> = HashMap::new();
+    let rules = parse_rules(&css);
+    for (order, (selectors, declarations)) in rules.iter().enumerate() {
+        for selector_text in split_outside_parentheses(selectors, ',') {
+            if selector_text.contains("::") || [":hover", ":focus", ":active", ":visited"].iter().any(|state| selector_text.contains(state)) {
+                continue;
+            }
+            let Ok(selector) = Selector::parse(selector_text) else {
+                continue;
+            };
+            let specificity = specificity(selector_text);
+            for element in document.select(&selector) {
+                let entry = matches.entry(element.value() as *const Element).or_default();
+                entry.extend(declarations.iter().map(|declaration| (specificity, order, declaration)));
+            }
+        }
+    }
+    let styles: HashMap<*const Element, String> = matches.into_iter()
+        .map(|(element, mut declarations)| {
+            declarations.sort_by_key(|(specificity, order, _)| (*specificity, *order));
+            (element, declarations.into_iter().map(|(_, _, declaration)| declaration.clone()).collect::<Vec<_>>())
+        })
+        .map(|(element, declarations)| (element, style_attribute(declarations)))
+        .collect();
+
+    let mut email = String::from("<!DOCTYPE html>");
+    write_element(&mut email, document.root_element(), &styles);
+    email
+}
+
+fn write_element(html: &mut String, element: ElementRef, styles: &HashMap<*const Element, String>) {
+    let value = element.value();
+    let name = value.name();
+    if DROPPED_ELEMENTS.contains(&name) {
+        return;
+    }
+    let tag = if DIV_ELEMENTS.contains(&name) { "div" } else { name };
+
+    let mut style = styles.get(&(value as *const Element)).cloned().unwrap_or_default();
+    if let Some(own) = value.attr("style") {
+        style = merge_styles(&style, own);
+    }
+    // The body styles go on the wrapper table, which clients keep when they drop the body.
+    let body_style = if name == "body" { std::mem::take(&mut style) } else { String::new() };
+
+    html.push('<');
+    html.push_str(tag);
+    for (attribute, attribute_value) in value.attrs() {
+        if matches!(attribute, "class" | "style") || attribute.starts_with("on") || attribute.starts_with("data-") {
+            continue;
+        }
+        html.push_str(&format!(" {attribute}=\"{}\"", attribute_value.replace('&', "&amp;").replace('"', "&quot;")));
+    }
+    if !style.is_empty() {
+        html.push_str(&format!(" style=\"{}\"", style.replace('&', "&amp;").replace('"', "&quot;")));
+    }
+    html.push('>');
+    if VOID_ELEMENTS.contains(&name) {
+        return;
+    }
+    if name == "body" {
+        html.push_str("<table role=\"presentation\" width=\"100%\" cellpadding=\"0\" cellspacing=\"0\" border=\"0\"");
+        if !body_style.is_empty() {
+            html.push_str(&format!(" style=\"{}\"", body_style.replace('&', "&amp;").replace('"', "&quot;")));
+        }
+        html.push_str("><tr><td>");
+    }
+
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => html.push_str(&text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")),
+            Node::Element(_) => write_element(html, ElementRef::wrap(child).unwrap(), styles),
+            _ => {}
+        }
+    }
+
+    if name == "body" {
+        html.push_str("</td></tr></table>");
+    }
+    html.push_str(&format!("</{tag}>"));
+}
+
+/// `(selectors, declarations)` of the style rules of a stylesheet, at-rules left out.
+fn parse_rules(css: &str) -> Vec<(String, Vec<Declaration>)> {
+    let css = Regex::new(r"(?s)/\*.*?\*/").unwrap().replace_all(css, "");
+    let mut rules = Vec::new();
+    let mut rest = css.as_ref();
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let Some(open) = rest.find('{') else {
+            break;
+        };
+        if rest.starts_with('@') {
+            // `@import ...;` or a block such as `@media print { ... }`.
+            match rest.find(';').filter(|semicolon| *semicolon < open) {
+                Some(semicolon) => rest = &rest[semicolon + 1..],
+                None => rest = &rest[block_end(rest, open)..],
+            }
+            continue;
+        }
+        let end = block_end(rest, open);
+        let body = &rest[open + 1..end];
+        let declarations = split_outside_parentheses(body.strip_suffix('}').unwrap_or(body), ';')
+            .into_iter()
+            .filter_map(|declaration| declaration.split_once(':'))
+            .map(|(property, value)| (property.trim().to_lowercase(), value.trim().to_string()))
+            .filter(|(property, value)| !property.is_empty() && !value.is_empty())
+            .collect();
+        rules.push((rest[..open].trim().to_string(), declarations));
+        rest = &rest[end..];
+    }
+    rules
+}
+
+/// The position after the `}` closing the block opened at `open`.
+fn block_end(css: &str, open: usize) -> usize {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in css[open..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return open + i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    css.len()
+}
+
+/// Splits at `separator` outside parentheses and quotes, e.g. the declarations of a rule
+/// whose `url(data:...;base64,...)` holds semicolons.
+fn split_outside_parentheses(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut quote, mut start) = (0usize, None, 0);
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Specificity of a selector as `ids, classes, types` packed in one number.
+fn specificity(selector: &str) -> u32 {
+    let (mut ids, mut classes, mut types) = (0, 0, 0);
+    for compound in selector.split(|c: char| c.is_whitespace() || matches!(c, '>' | '+' | '~')).filter(|compound| !compound.is_empty()) {
+        if compound.starts_with(|c: char| c.is_alphabetic()) {
+            types += 1;
+        }
+        ids += compound.matches('#').count() as u32;
+        classes += (compound.matches('.').count() + compound.matches('[').count() + compound.matches(':').count()) as u32;
+    }
+    (ids << 16) | (classes << 8) | types
+}
+
+/// Declarations as a `style` attribute, each property keeping its last value.
+fn style_attribute(declarations: Vec<Declaration>) -> String {
+    let mut properties: Vec<Declaration> = Vec::new();
+    for (property, value) in declarations {
+        properties.retain(|(existing, _)| *existing != property);
+        properties.push((property, value));
+    }
+    properties.iter().map(|(property, value)| format!("{property}: {value}")).collect::<Vec<_>>().join("; ")
+}
+
+fn merge_styles(rules: &str, own: &str) -> String {
+    let declarations = split_outside_parentheses(rules, ';').into_iter().chain(split_outside_parentheses(own, ';'))
+        .filter_map(|declaration| declaration.split_once(':'))
+        .map(|(property, value)| (property.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    style_attribute(declarations)
+}
+
+/// A `multipart/related` message with the images of the page as attachments.
+fn mime_message(subject: &str, html: &str) -> String {
+    let image_re = Regex::new(r#"src="data:([^;",]+);base64,([^"]+)""#).unwrap();
+    let mut images: Vec<(String, String)> = Vec::new();
+    let html = image_re.replace_all(html, |caps: &Captures| {
+        let index = match images.iter().position(|(_, data)| *data == caps[2]) {
+            Some(index) => index,
+            None => {
+                images.push((caps[1].to_string(), caps[2].to_string()));
+                images.len() - 1
+            }
+        };
+        format!("src=\"cid:image{}@pdfbuilder\"", index + 1)
+    });
+
+    let boundary = "pdfbuilder-related";
+    let subject = if subject.is_ascii() { subject.to_string() } else { format!("=?UTF-8?B?{}?=", STANDARD.encode(subject)) };
+    let mut message = format!("MIME-Version: 1.0\r\nSubject: {subject}\r\nContent-Type: multipart/related; boundary=\"{boundary}\"; type=\"text/html\"\r\n\r\n");
+    message.push_str(&format!("--{boundary}\r\nContent-Type: text/html; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n"));
+    message.push_str(&wrap_base64(&STANDARD.encode(html.as_bytes())));
+    for (index, (mime, data)) in images.iter().enumerate() {
+        message.push_str(&format!(
+            "--{boundary}\r\nContent-Type: {mime}\r\nContent-Transfer-Encoding: base64\r\nContent-ID: <image{}@pdfbuilder>\r\nContent-Disposition: inline\r\n\r\n",
+            index + 1
+        ));
+        message.push_str(&wrap_base64(data));
+    }
+    message.push_str(&format!("--{boundary}--\r\n"));
+    message
+}
+
+/// Base64 in lines of 76 characters, as MIME requires.
+fn wrap_base64(data: &str) -> String {
+    data.as_bytes().chunks(76).map(|line| format!("{}\r\n", String::from_utf8_lossy(line))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_email_html() {
+        let html = r#"<html><head><meta charset="UTF-8"><title>Notes</title><style>
+            /* theme */ body { color: #222; } p { margin: 0 0 1em; color: black; } .note p { color: red; }
+            @media print { p { color: gray; } } a:hover { color: blue; } h1::after { content: "x"; }
+            .bg { background: url("data:image/png;base64,AA;BB") }
+            </style><script>alert(1)</script></head>
+            <body><main><section class="note" data-src="a.md"><p style="color: green; font-weight: bold" onclick="x()">Hi &amp; <a href="https://example.com">bye</a></p></section><div class="bg"></div></main></body></html>"#;
+        assert_eq!(
+            to_email_html(html),
+            "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><title>Notes</title></head>\n            \
+             <body><table role=\"presentation\" width=\"100%\" cellpadding=\"0\" cellspacing=\"0\" border=\"0\" style=\"color: #222\"><tr><td>\
+             <div><div><p style=\"margin: 0 0 1em; color: green; font-weight: bold\">Hi &amp; <a href=\"https://example.com\">bye</a></p></div>\
+             <div style=\"background: url(&quot;data:image/png;base64,AA;BB&quot;)\"></div></div></td></tr></table></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_mime_message() {
+        let message = mime_message("Notes ü", "<img src=\"data:image/png;base64,iVBOR\"><img src=\"data:image/png;base64,iVBOR\">");
+        assert!(message.starts_with("MIME-Version: 1.0\r\nSubject: =?UTF-8?B?Tm90ZXMgw7w=?=\r\n"));
+        assert!(message.contains(&wrap_base64(&STANDARD.encode("<img src=\"cid:image1@pdfbuilder\"><img src=\"cid:image1@pdfbuilder\">"))));
+        assert!(message.contains("Content-ID: <image1@pdfbuilder>\r\nContent-Disposition: inline\r\n\r\niVBOR\r\n--pdfbuilder-related--\r\n"));
+        assert!(!message.contains("image2"));
+    }
+}
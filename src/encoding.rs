@@ -0,0 +1,61 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use unicode_normalization::UnicodeNormalization;
+
+/// Decodes a source file to UTF-8 text. UTF-8 and UTF-16 are recognized by their byte order
+/// mark (or, for UTF-16 without one, by its zero bytes); other files are read as Windows-1252,
+/// the usual encoding of files saved by Windows editors. Returns the name of the encoding
+/// when the file was not UTF-8.
+pub fn decode(bytes: &[u8]) -> (String, Option<&'static str>) {
+    let encoding = match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => encoding,
+        None if std::str::from_utf8(bytes).is_ok() => UTF_8,
+        None => utf16_without_bom(bytes).unwrap_or(WINDOWS_1252),
+    };
+    // `decode` removes the byte order mark.
+    let (text, _, _) = encoding.decode(bytes);
+    let transcoded = (encoding != UTF_8).then(|| encoding.name());
+    (normalize(&text), transcoded)
+}
+
+/// Strips a leftover byte order mark, converts CRLF and CR line endings to LF and composes
+/// characters (NFC), so `é` typed as `e` + accent matches the precomposed `é`.
+pub fn normalize(text: &str) -> String {
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    text.nfc().collect()
+}
+
+/// UTF-16 text in a Latin script has a zero in every other byte.
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 4 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let zeros_at = |offset: usize| bytes.iter().skip(offset).step_by(2).filter(|b| **b == 0).count();
+    let half = bytes.len() / 2;
+    if zeros_at(1) * 2 > half && zeros_at(0) == 0 {
+        Some(UTF_16LE)
+    } else if zeros_at(0) * 2 > half && zeros_at(1) == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf8() {
+        assert_eq!(decode("\u{FEFF}# Titre\r\nCafe\u{301}\r".as_bytes()), ("# Titre\nCafé\n".to_string(), None));
+    }
+
+    #[test]
+    fn test_decode_other_encodings() {
+        assert_eq!(decode(b"# Caf\xe9 \x93quoted\x94"), ("# Café \u{201C}quoted\u{201D}".to_string(), Some("windows-1252")));
+
+        let utf16: Vec<u8> = "# Été".encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+        assert_eq!(decode(&[&[0xFF, 0xFE][..], &utf16].concat()), ("# Été".to_string(), Some("UTF-16LE")));
+        assert_eq!(decode(&utf16), ("# Été".to_string(), Some("UTF-16LE")));
+    }
+}
@@ -1,22 +1,134 @@
+use miette::Diagnostic;
+use pdfbuilder_core::CoreError;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+/// Errors of the commands. Each variant has a stable code (e.g. `pdfbuilder::source::not_found`)
+/// for tools wrapping PdfBuilder, and most carry a hint on how to fix the problem.
+#[derive(Debug, Error, Diagnostic)]
 pub enum AppError {
     #[error("Error reading the 'config.yaml' configuration file.")]
+    #[diagnostic(code(pdfbuilder::config::read), help("Run the command inside a project, or create one with 'PdfBuilder init'."))]
     ConfigReadError(#[source] std::io::Error),
 
     #[error("The format of 'config.yaml' is invalid: {0}")]
+    #[diagnostic(code(pdfbuilder::config::parse), help("Compare the field names and indentation with the 'config.yaml' written by 'PdfBuilder init'."))]
     ConfigParseError(#[from] serde_yaml::Error),
 
     #[error("The source file '{0}' specified in 'config.yaml' was not found.")]
+    #[diagnostic(code(pdfbuilder::source::not_found), help("Paths in 'config.yaml' and in '!include(...)' are relative to the project root and to the including file."))]
     SourceNotFound(String),
 
     #[error("The project already exists. 'init' can only be run in an uninitialized directory.")]
+    #[diagnostic(code(pdfbuilder::init::exists))]
     ProjectAlreadyExists,
 
     #[error("I/O Error: {0}")]
+    #[diagnostic(code(pdfbuilder::io))]
     IoError(#[from] std::io::Error),
 
     #[error("Error while building the document: {0}")]
+    #[diagnostic(code(pdfbuilder::build))]
     BuildError(String),
-}
\ No newline at end of file
+
+    #[error("Syntax theme '{0}' not found.")]
+    #[diagnostic(code(pdfbuilder::theme::not_found), help("Set 'syntax_theme' to a built-in theme such as 'InspiredGitHub', 'base16-ocean.dark' or 'Solarized (light)'."))]
+    ThemeNotFound(String),
+
+    #[error("Could not launch the browser: {0}")]
+    #[diagnostic(code(pdfbuilder::browser::launch), help("PDF output needs Chrome, Chromium or Edge. Install one, set its path in 'browser.path' of 'config.yaml', or use '--format html'."))]
+    BrowserLaunchFailed(String),
+
+    #[error("Circular include: {}", .0.join(" -> "))]
+    #[diagnostic(code(pdfbuilder::include::cycle), help("Remove one of the '!include(...)' directives of the cycle."))]
+    IncludeCycle(Vec<String>),
+
+    #[error("Image '{image}' not found ({file}, line {line}).")]
+    #[diagnostic(code(pdfbuilder::image::missing), help("Image paths are relative to the Markdown file or to the project root; images usually go in 'assets/'."))]
+    ImageMissing { file: String, line: usize, image: String },
+
+    #[error("'{0}' is a symbolic link, which 'follow_symlinks: false' refuses.")]
+    #[diagnostic(code(pdfbuilder::path::symlink), help("Replace the link with the file, or set 'follow_symlinks: true' in 'config.yaml' to follow links that stay inside the project."))]
+    SymlinkRefused(String),
+
+    #[error("The document exceeds 'limits.{0}' ({1}).")]
+    #[diagnostic(code(pdfbuilder::limits::exceeded), help("Check the includes for unintended repetitions, or raise the limit in 'config.yaml' if the document is trusted."))]
+    LimitExceeded(&'static str, usize),
+
+    #[error("Git error: {0}")]
+    #[diagnostic(code(pdfbuilder::git), help("Check that git is installed and that the project is a git repository."))]
+    GitError(String),
+
+    #[error("No spelling dictionary found for language '{0}'.")]
+    #[diagnostic(code(pdfbuilder::check::dictionary), help("Add '{0}.dic' (and '{0}.aff') to the 'dictionaries/' directory."))]
+    DictionaryNotFound(String),
+
+    #[error("Import failed: {0}")]
+    #[diagnostic(code(pdfbuilder::import))]
+    ImportError(String),
+
+    #[error("External linter '{0}' failed: {1}")]
+    #[diagnostic(code(pdfbuilder::check::external_linter), help("Check that the linter is installed and its 'command' under 'lint.external' in 'config.yaml'."))]
+    ExternalLinterError(String, String),
+
+    #[error("Rendering '{0}' block failed: {1}")]
+    #[diagnostic(code(pdfbuilder::render::block), help("Check the renderer command or the diagram server configured in 'config.yaml'."))]
+    RendererError(String, String),
+
+    #[error("No translation configured for language '{0}'.")]
+    #[diagnostic(code(pdfbuilder::i18n::translation), help("Add it to 'translations' in 'config.yaml'."))]
+    TranslationNotFound(String),
+
+    #[error("{0} unresolved CriticMarkup change(s), the first being '{1}'.")]
+    #[diagnostic(code(pdfbuilder::review::unresolved_changes), help("Accept or reject them, or set 'changes' to 'show' or 'accept' in 'config.yaml'."))]
+    UnresolvedChanges(usize, String),
+
+    #[error("Could not render the PDF pages to images: {0}")]
+    #[diagnostic(code(pdfbuilder::raster), help("Page images are rendered with 'pdftoppm', part of Poppler (e.g. the 'poppler-utils' package)."))]
+    RasterizerError(String),
+
+    #[error("The document differs from its snapshot in {0} place(s).")]
+    #[diagnostic(code(pdfbuilder::test::snapshot), help("If the changes are intended, record them with 'PdfBuilder test --update' and commit the snapshot."))]
+    SnapshotMismatch(usize),
+
+    #[error("Invalid data file: {0}")]
+    #[diagnostic(code(pdfbuilder::merge::data), help("Use a CSV file with a header row, or a YAML or JSON list of records such as '- {{ client: ACME, amount: 120 }}'."))]
+    MergeDataError(String),
+
+    #[error("Could not publish '{0}': {1}")]
+    #[diagnostic(code(pdfbuilder::publish), help("Check 'publish' in 'config.yaml' and the credentials in the environment: AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY for S3, WEBDAV_USERNAME and WEBDAV_PASSWORD for WebDAV, an SSH key for SFTP."))]
+    PublishError(String, String),
+
+    #[error("The build was cancelled.")]
+    #[diagnostic(code(pdfbuilder::build::cancelled))]
+    BuildCancelled,
+
+    #[error("{0} problem(s) found.")]
+    #[diagnostic(code(pdfbuilder::check::failed))]
+    CheckFailed(usize),
+}
+
+/// Errors of the conversion, which has the same kinds of errors. The errors of the `Host` of an
+/// expansion come back as they were raised.
+impl From<CoreError> for AppError {
+    fn from(error: CoreError) -> Self {
+        match error {
+            CoreError::BuildError(message) => AppError::BuildError(message),
+            CoreError::ThemeNotFound(theme) => AppError::ThemeNotFound(theme),
+            CoreError::IncludeCycle(files) => AppError::IncludeCycle(files),
+            CoreError::LimitExceeded(limit, value) => AppError::LimitExceeded(limit, value),
+            CoreError::UnresolvedChanges(count, first) => AppError::UnresolvedChanges(count, first),
+            CoreError::IoError(error) => AppError::IoError(error),
+            CoreError::Host(error) => match error.downcast::<AppError>() {
+                Ok(error) => *error,
+                Err(error) => AppError::BuildError(error.to_string()),
+            },
+        }
+    }
+}
+
+/// Errors of the files read by an expansion, passed through the conversion.
+impl From<AppError> for CoreError {
+    fn from(error: AppError) -> Self {
+        CoreError::Host(Box::new(error))
+    }
+}
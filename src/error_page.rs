@@ -0,0 +1,139 @@
+use crate::builder;
+use crate::render::escape_html;
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::{Config, OutputFormat};
+use encoding_rs::WINDOWS_1252;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream, StringFormat};
+use std::fs;
+use std::path::Path;
+
+const OVERLAY_START: &str = "<!--pdfbuilder-error-->";
+const OVERLAY_END: &str = "<!--/pdfbuilder-error-->";
+
+const OVERLAY_STYLE: &str = "position: fixed; inset: 0; z-index: 10000; overflow: auto; padding: 2em; background: rgba(20, 20, 20, 0.92); color: #ffb4b4; font-family: monospace; font-size: 14px;";
+
+/// Lines per page and characters per line of the error PDF (Helvetica 11pt on A4).
+const PDF_MAX_LINES: usize = 60;
+const PDF_LINE_WIDTH: usize = 90;
+
+/// Shows `error` in place of the outputs of a failed watch rebuild: on top of the HTML page
+/// and, with `watch.error_pdf`, as a one-page PDF replacing the PDF.
+pub fn write_error_outputs(config: &Config, format: OutputFormat, error: &AppError) -> Result<(), AppError> {
+    let html_path = config.build_dir().join(format!("{}.html", builder::output_filename(config, &GitInfo::collect())));
+    let message = error.to_string();
+    write_error_overlay(&html_path, &message)?;
+    if format == OutputFormat::Pdf && config.watch.error_pdf {
+        fs::write(html_path.with_extension("pdf"), error_pdf(&message)?)?;
+    }
+    Ok(())
+}
+
+/// Shows the error of a failed rebuild on top of the last generated page, or on an error page
+/// when there is none, so the preview does not silently keep showing stale content.
+/// The next successful build rewrites the page without the overlay.
+fn write_error_overlay(html_path: &Path, message: &str) -> Result<(), AppError> {
+    let overlay = format!(
+        "{OVERLAY_START}<div style=\"{OVERLAY_STYLE}\"><h2 style=\"color: #ff6b6b;\">Build failed</h2><pre style=\"white-space: pre-wrap;\">{}</pre><p style=\"color: #ccc;\">The page will be rebuilt on the next change.</p></div>{OVERLAY_END}",
+        escape_html(message)
+    );
+    let html = match fs::read_to_string(html_path) {
+        Ok(html) => with_overlay(&html, &overlay),
+        Err(_) => format!("<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Build failed</title></head><body>{overlay}</body></html>"),
+    };
+    if let Some(parent) = html_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(html_path, html)?;
+    Ok(())
+}
+
+/// Replaces the previous overlay of `html`, if any, and inserts `overlay` at the start of the body.
+fn with_overlay(html: &str, overlay: &str) -> String {
+    let mut html = html.to_string();
+    if let (Some(start), Some(end)) = (html.find(OVERLAY_START), html.find(OVERLAY_END)) {
+        html.replace_range(start..end + OVERLAY_END.len(), "");
+    }
+    let insert_at = html.find("<body").and_then(|body| html[body..].find('>').map(|end| body + end + 1)).unwrap_or(0);
+    html.insert_str(insert_at, overlay);
+    html
+}
+
+/// A one-page PDF showing the error, written in place of the PDF of a failed rebuild.
+pub fn error_pdf(message: &str) -> Result<Vec<u8>, AppError> {
+    let mut lines = vec!["Build failed".to_string(), String::new()];
+    for line in message.lines() {
+        let chars: Vec<char> = line.chars().collect();
+        lines.extend(chars.chunks(PDF_LINE_WIDTH).map(|chunk| chunk.iter().collect::<String>()));
+        if chars.is_empty() {
+            lines.push(String::new());
+        }
+    }
+    lines.truncate(PDF_MAX_LINES);
+
+    let mut operations = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec!["F1".into(), 11.into()]),
+        Operation::new("TL", vec![13.into()]),
+        Operation::new("Td", vec![50.into(), 790.into()]),
+    ];
+    for line in &lines {
+        // The standard fonts only cover Windows-1252 text.
+        let (bytes, _, _) = WINDOWS_1252.encode(line);
+        operations.push(Operation::new("Tj", vec![Object::String(bytes.into_owned(), StringFormat::Literal)]));
+        operations.push(Operation::new("T*", vec![]));
+    }
+    operations.push(Operation::new("ET", vec![]));
+
+    let pdf_error = |e: lopdf::Error| AppError::BuildError(format!("Could not write the error PDF: {e}"));
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let font_id = document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let resources_id = document.add_object(dictionary! { "Font" => dictionary! { "F1" => font_id } });
+    let content_id = document.add_object(Stream::new(dictionary! {}, Content { operations }.encode().map_err(pdf_error)?));
+    let page_id = document.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+    });
+    document.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page_id.into()],
+        "Count" => 1,
+        "Resources" => resources_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+    }));
+    let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    document.trailer.set("Root", catalog_id);
+
+    let mut output = Vec::new();
+    document.save_to(&mut output)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pagemap;
+
+    #[test]
+    fn test_with_overlay_replaces_previous_one() {
+        let html = with_overlay("<html><body class=\"doc\"><p>Text</p></body></html>", "<!--pdfbuilder-error-->first<!--/pdfbuilder-error-->");
+        let html = with_overlay(&html, "<!--pdfbuilder-error-->second<!--/pdfbuilder-error-->");
+        assert_eq!(html, "<html><body class=\"doc\"><!--pdfbuilder-error-->second<!--/pdfbuilder-error--><p>Text</p></body></html>");
+    }
+
+    #[test]
+    fn test_error_pdf() {
+        let pdf = error_pdf("Error while building the document: café").unwrap();
+        let pages = pagemap::extract_page_texts(&pdf).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].contains("Build failed"));
+    }
+}
@@ -0,0 +1,66 @@
+use crate::error::AppError;
+
+/// Replaces fenced code blocks with the output of `render`, called with the info string
+/// (e.g. `chart`) and the code of each block. Blocks for which `render` returns `None`
+/// are kept as is. The replacement is followed by a blank line so it ends any HTML block.
+pub fn replace_fenced_blocks<F>(markdown: &str, mut render: F) -> Result<String, AppError>
+where
+    F: FnMut(&str, &str) -> Result<Option<String>, AppError>,
+{
+    let mut result = String::with_capacity(markdown.len());
+    // Opening fence, info string and code of the block being read.
+    let mut block: Option<(String, String, String)> = None;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        match block.as_mut() {
+            None if trimmed.starts_with("```") => {
+                block = Some((line.to_string(), trimmed.trim_start_matches('`').trim().to_string(), String::new()));
+            }
+            None => {
+                result.push_str(line);
+                result.push('\n');
+            }
+            Some(_) if trimmed.starts_with("```") && trimmed.trim_end().chars().all(|c| c == '`') => {
+                let (fence, info, code) = block.take().unwrap_or_default();
+                match render(&info, &code)? {
+                    Some(rendered) => {
+                        result.push_str(rendered.trim_end());
+                        result.push_str("\n\n");
+                    }
+                    None => {
+                        result.push_str(&fence);
+                        result.push('\n');
+                        result.push_str(&code);
+                        result.push_str(line);
+                        result.push('\n');
+                    }
+                }
+            }
+            Some((_, _, code)) => {
+                code.push_str(line);
+                code.push('\n');
+            }
+        }
+    }
+
+    // An unclosed block runs to the end of the document and is left untouched.
+    if let Some((fence, _, code)) = block {
+        result.push_str(&fence);
+        result.push('\n');
+        result.push_str(&code);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_fenced_blocks() {
+        let markdown = "Text\n```upper\nabc\n```\n```rust\nfn main() {}\n```\n```upper\nnot closed";
+        let result = replace_fenced_blocks(markdown, |info, code| Ok((info == "upper").then(|| code.to_uppercase()))).unwrap();
+        assert_eq!(result, "Text\nABC\n\n```rust\nfn main() {}\n```\n```upper\nnot closed\n");
+    }
+}
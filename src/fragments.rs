@@ -0,0 +1,163 @@
+use crate::directives::Directive;
+use crate::error::AppError;
+use crate::paths::SourceDir;
+use crate::warnings;
+use scraper::{ElementRef, Html, Node};
+use std::fs;
+
+/// Elements kept in sanitized fragments; the others are removed, their content kept.
+const ALLOWED_ELEMENTS: [&str; 63] = [
+    "a", "abbr", "address", "article", "aside", "b", "bdi", "bdo", "blockquote", "br", "caption", "cite", "code", "col",
+    "colgroup", "data", "dd", "del", "details", "dfn", "div", "dl", "dt", "em", "figcaption", "figure", "footer", "h1", "h2",
+    "h3", "h4", "h5", "h6", "header", "hr", "i", "img", "ins", "kbd", "li", "mark", "ol", "p", "pre", "q", "s", "samp",
+    "section", "small", "span", "strong", "sub", "summary", "sup", "table", "tbody", "td", "tfoot", "th", "thead", "time",
+    "tr", "ul",
+];
+/// Elements removed with their content: scripts, styles, frames, embedded objects.
+const DROPPED_ELEMENTS: [&str; 14] = [
+    "script", "style", "noscript", "template", "iframe", "frame", "frameset", "object", "embed", "applet", "svg", "math",
+    "textarea", "select",
+];
+const VOID_ELEMENTS: [&str; 4] = ["br", "col", "hr", "img"];
+/// Attributes kept on any allowed element.
+const GLOBAL_ATTRIBUTES: [&str; 5] = ["class", "id", "title", "lang", "dir"];
+/// Attributes holding a URL, kept only with a safe scheme.
+const URL_ATTRIBUTES: [&str; 3] = ["href", "src", "cite"];
+const URL_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tel"];
+
+/// Returns the HTML fragment of `!html(snippets/table.html)`, read relative to `source_dir` (inside
+/// the project), as a raw HTML block: the fragment in a `<div class="html-fragment">`, its blank
+/// lines replaced by empty comments so Markdown does not end the block early. With `sanitize`,
+/// only an allowlist of elements and attributes is kept.
+pub fn html_directive(source_dir: SourceDir, directive: &Directive, sanitize: bool) -> Result<String, AppError> {
+    directive.expect_positional(1)?;
+    let file = directive.value("file", 0)
+        .ok_or_else(|| AppError::BuildError("!html needs a fragment file, e.g. !html(snippets/pricing-table.html)".to_string()))?;
+    let path = source_dir.dir.join(file);
+    let content = fs::read_to_string(source_dir.resolve(file)?).map_err(|e| AppError::BuildError(format!("Could not read HTML fragment '{}': {e}", path.display())))?;
+
+    let content = if sanitize {
+        let (sanitized, removed) = sanitize_fragment(&content);
+        if removed > 0 {
+            warnings::warn("Unsafe HTML removed", format!("{removed} elements or attributes not allowed in '{}'", path.display()));
+        }
+        sanitized
+    } else {
+        content
+    };
+    let lines: Vec<&str> = content.trim_end_matches('\n').lines().map(|line| if line.trim().is_empty() { "<!---->" } else { line }).collect();
+    Ok(format!("<div class=\"html-fragment\">\n{}\n</div>", lines.join("\n")))
+}
+
+/// The fragment reduced to the allowed elements and attributes, and how many were removed.
+/// Removed elements leave their line breaks behind.
+fn sanitize_fragment(html: &str) -> (String, usize) {
+    let fragment = Html::parse_fragment(html);
+    let mut sanitized = String::new();
+    let mut removed = 0;
+    // The parser puts the fragment in an `<html>` element, which is not written.
+    write_children(&mut sanitized, fragment.root_element(), &mut removed);
+    (sanitized, removed)
+}
+
+fn write_children(html: &mut String, element: ElementRef, removed: &mut usize) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => html.push_str(&text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")),
+            Node::Element(_) => write_element(html, ElementRef::wrap(child).unwrap(), removed),
+            _ => {}
+        }
+    }
+}
+
+fn write_element(html: &mut String, element: ElementRef, removed: &mut usize) {
+    let value = element.value();
+    let name = value.name();
+    if DROPPED_ELEMENTS.contains(&name) {
+        *removed += 1;
+        html.push_str(&"\n".repeat(element.text().map(|text| text.matches('\n').count()).sum()));
+        return;
+    }
+    if !ALLOWED_ELEMENTS.contains(&name) {
+        *removed += 1;
+        write_children(html, element, removed);
+        return;
+    }
+
+    html.push('<');
+    html.push_str(name);
+    for (attribute, attribute_value) in value.attrs() {
+        let allowed = if URL_ATTRIBUTES.contains(&attribute) {
+            allowed_attribute(name, attribute) && is_safe_url(attribute_value)
+        } else {
+            allowed_attribute(name, attribute)
+        };
+        if allowed {
+            html.push_str(&format!(" {attribute}=\"{}\"", attribute_value.replace('&', "&amp;").replace('"', "&quot;")));
+        } else {
+            *removed += 1;
+        }
+    }
+    html.push('>');
+    if VOID_ELEMENTS.contains(&name) {
+        return;
+    }
+    write_children(html, element, removed);
+    html.push_str(&format!("</{name}>"));
+}
+
+fn allowed_attribute(element: &str, attribute: &str) -> bool {
+    GLOBAL_ATTRIBUTES.contains(&attribute)
+        || matches!(
+            (element, attribute),
+            ("a", "href" | "name")
+                | ("img", "src" | "alt" | "width" | "height")
+                | ("td" | "th", "colspan" | "rowspan" | "headers" | "align" | "valign")
+                | ("th", "scope" | "abbr")
+                | ("col" | "colgroup", "span" | "width")
+                | ("ol", "start" | "type" | "reversed")
+                | ("li", "value")
+                | ("blockquote" | "q" | "del" | "ins", "cite")
+                | ("del" | "ins" | "time", "datetime")
+                | ("data", "value")
+                | ("details", "open")
+        )
+}
+
+/// Whether a URL is relative or uses a scheme that cannot run code. The parser has already
+/// decoded the character references; browsers also ignore whitespace and control characters
+/// in the scheme, so they are left out before comparing.
+fn is_safe_url(url: &str) -> bool {
+    let url: String = url.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect();
+    match url.find([':', '/', '?', '#']) {
+        Some(i) if url[i..].starts_with(':') => URL_SCHEMES.contains(&url[..i].to_lowercase().as_str()),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_fragment() {
+        let html = "<table onclick=\"steal()\">\n<tr><td><a href=\" javascript:alert(1)\">Buy</a></td></tr>\n</table>\n\
+                    <SCRIPT>\nalert(1);\n</script>\n<iframe src=\"https://example.com\"></iframe><embed src=\"x.swf\">";
+        let (sanitized, removed) = sanitize_fragment(html);
+        assert_eq!(sanitized, "<table>\n<tbody><tr><td><a>Buy</a></td></tr>\n</tbody></table>\n\n\n\n");
+        assert_eq!(removed, 5);
+        assert_eq!(sanitized.matches('\n').count(), html.matches('\n').count());
+
+        // Markup the parser reads differently than a pattern would.
+        let (sanitized, removed) = sanitize_fragment("<img/onerror=alert(1) src=\"assets/a.png\"><a href=\"jav&#x61;script:alert(1)\">x</a>");
+        assert_eq!(sanitized, "<img src=\"assets/a.png\"><a>x</a>");
+        assert_eq!(removed, 2);
+        let (sanitized, removed) = sanitize_fragment("<a href=\"java\tscript:alert(1)\">x</a><a href=\"https://example.com/a?b=c:d\">y</a>");
+        assert_eq!(sanitized, "<a>x</a><a href=\"https://example.com/a?b=c:d\">y</a>");
+        assert_eq!(removed, 1);
+        let (sanitized, removed) = sanitize_fragment("<style>body { background: url(https://example.com) }</style>\
+                                                      <form action=\"https://example.com\"><p style=\"color: red\">Name</p><input name=\"n\"></form>");
+        assert_eq!(sanitized, "<p>Name</p>");
+        assert_eq!(removed, 4);
+    }
+}
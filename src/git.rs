@@ -0,0 +1,133 @@
+use crate::error::AppError;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Revision information gathered from the git repository at build time.
+/// Every field falls back to `None` when the project is not inside a repository
+/// or when `git` is not installed.
+#[derive(Debug, Clone, Default)]
+pub struct GitInfo {
+    pub commit: Option<String>,
+    pub tag: Option<String>,
+    pub date: Option<String>,
+}
+
+impl GitInfo {
+    pub fn collect() -> Self {
+        GitInfo {
+            commit: git_output(&["rev-parse", "--short", "HEAD"]),
+            tag: git_output(&["describe", "--tags", "--abbrev=0"]),
+            date: git_output(&["log", "-1", "--format=%cs"]),
+        }
+    }
+
+    /// Short text identifying the revision, used for the footer stamp.
+    pub fn stamp(&self) -> Option<String> {
+        let commit = self.commit.as_ref()?;
+        let mut stamp = format!("Rev. {commit}");
+        if let Some(tag) = &self.tag {
+            stamp.push_str(&format!(" ({tag})"));
+        }
+        if let Some(date) = &self.date {
+            stamp.push_str(&format!(" - {date}"));
+        }
+        Some(stamp)
+    }
+}
+
+/// A temporary checkout of another revision, removed again when dropped.
+pub struct Worktree {
+    path: PathBuf,
+    project_root: PathBuf,
+}
+
+impl Worktree {
+    pub fn checkout(reference: &str) -> Result<Self, AppError> {
+        let prefix = git_output(&["rev-parse", "--show-prefix"]).unwrap_or_default();
+        let path = std::env::temp_dir().join(format!("pdfbuilder-worktree-{}", std::process::id()));
+
+        let output = Command::new("git")
+            .args(["worktree", "add", "--detach"])
+            .arg(&path)
+            .arg(reference)
+            .output()
+            .map_err(|e| AppError::GitError(format!("Could not run git: {e}")))?;
+        if !output.status.success() {
+            return Err(AppError::GitError(format!(
+                "Could not check out '{}': {}",
+                reference,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let project_root = path.join(prefix);
+        Ok(Worktree { path, project_root })
+    }
+
+    /// The project directory inside the checkout, matching the current directory's place in the repository.
+    pub fn project_root(&self) -> &Path {
+        &self.project_root
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let _ = Command::new("git").args(["worktree", "remove", "--force"]).arg(&self.path).output();
+    }
+}
+
+/// Commits of `range` (e.g. `v1.4..HEAD`), newest first, without merges: for each one its
+/// short hash, subject and body separated by `\x1f`, commits separated by `\x1e`.
+pub fn log(range: &str) -> Result<String, AppError> {
+    let output = Command::new("git")
+        .args(["log", "--no-merges", "--format=%h%x1f%s%x1f%b%x1e", range, "--"])
+        .output()
+        .map_err(|e| AppError::GitError(format!("Could not run git: {e}")))?;
+    if !output.status.success() {
+        return Err(AppError::GitError(format!(
+            "Could not read the history of '{}': {}",
+            range,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The URL of a remote of the repository, e.g. `origin`.
+pub fn remote_url(name: &str) -> Option<String> {
+    git_output(&["remote", "get-url", name])
+}
+
+fn git_output(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_without_commit_is_none() {
+        let info = GitInfo::default();
+        assert!(info.stamp().is_none());
+    }
+
+    #[test]
+    fn test_stamp_with_all_fields() {
+        let info = GitInfo {
+            commit: Some("abc1234".to_string()),
+            tag: Some("v1.2".to_string()),
+            date: Some("2024-05-01".to_string()),
+        };
+        assert_eq!(info.stamp().unwrap(), "Rev. abc1234 (v1.2) - 2024-05-01");
+    }
+}
@@ -0,0 +1,88 @@
+use crate::error::AppError;
+use crate::Config;
+use std::path::Path;
+
+pub use pdfbuilder_core::i18n::*;
+
+/// Value of `build --lang` selecting every configured language.
+pub const ALL_LANGUAGES: &str = "all";
+
+/// Returns the configurations to build for `build --lang`: the main language when no
+/// language is given, one translation, or every language with `all`.
+pub fn language_configs(config: &Config, lang: Option<&str>) -> Result<Vec<Config>, AppError> {
+    match lang {
+        None => Ok(vec![config.clone()]),
+        Some(lang) if lang == config.language => Ok(vec![config.clone()]),
+        Some(ALL_LANGUAGES) => {
+            let mut configs = vec![config.clone()];
+            configs.extend(config.translations.iter().map(|(language, source)| translated_config(config, language, source)));
+            Ok(configs)
+        }
+        Some(lang) => match config.translations.get(lang) {
+            Some(source) => Ok(vec![translated_config(config, lang, source)]),
+            None => Err(AppError::TranslationNotFound(lang.to_string())),
+        },
+    }
+}
+
+/// Derives the configuration of a translation. The translated source replaces `source`;
+/// in book mode it is the directory holding the translated files, laid out like the
+/// original ones. Themes, assets and other settings are shared.
+fn translated_config(config: &Config, language: &str, source: &str) -> Config {
+    let mut translated = config.clone();
+    translated.language = language.to_string();
+    translated.output.filename = format!("{}-{}", config.output.filename, language);
+    translated.translations.clear();
+    match &config.book {
+        Some(book) => translated.book = Some(book.with_root(Path::new(source))),
+        None => translated.source = source.to_string(),
+    }
+    translated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: &str) -> Config {
+        serde_yaml::from_str(&format!(r#"
+title: "Manual"
+author: "Ada"
+language: "en"
+theme: "dark"
+syntax_theme: "InspiredGitHub"
+source: "en/main.md"
+custom_css: ""
+output:
+  filename: "manual"
+translations:
+  fr: "fr/main.md"
+  de: "de/main.md"
+{extra}"#)).unwrap()
+    }
+
+    #[test]
+    fn test_language_configs() {
+        let config = config("");
+        assert_eq!(language_configs(&config, None).unwrap()[0].source, "en/main.md");
+
+        let french = language_configs(&config, Some("fr")).unwrap();
+        assert_eq!(french.len(), 1);
+        assert_eq!(french[0].language, "fr");
+        assert_eq!(french[0].source, "fr/main.md");
+        assert_eq!(french[0].output.filename, "manual-fr");
+
+        let all: Vec<String> = language_configs(&config, Some("all")).unwrap().into_iter().map(|c| c.language).collect();
+        assert_eq!(all, vec!["en", "de", "fr"]);
+
+        assert!(matches!(language_configs(&config, Some("it")), Err(AppError::TranslationNotFound(_))));
+    }
+
+    #[test]
+    fn test_translated_book_files() {
+        let mut config = config("book:\n  parts:\n    - chapters:\n        - file: \"intro.md\"");
+        config.translations.insert("de".to_string(), "de".to_string());
+        let german = language_configs(&config, Some("de")).unwrap();
+        assert_eq!(german[0].book.as_ref().unwrap().files(), vec!["de/intro.md"]);
+    }
+}
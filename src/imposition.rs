@@ -0,0 +1,506 @@
+use crate::error::AppError;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Space left around the grid for the cut marks, in points (10 mm).
+const SHEET_MARGIN: f64 = 28.35;
+
+/// Gap between a cut mark and the edge of the grid, in points.
+const MARK_OFFSET: f64 = 4.0;
+
+/// Space between the pages of a handout and their note lines, in points.
+const HANDOUT_GAP: f64 = 14.0;
+
+/// Space between the note lines of a handout, in points.
+const NOTE_LINE_SPACING: f64 = 20.0;
+
+/// A paper size, in points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaperSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PaperSize {
+    const NAMED: [(&'static str, f64, f64); 6] = [
+        ("a3", 841.89, 1190.55),
+        ("a4", 595.28, 841.89),
+        ("a5", 419.53, 595.28),
+        ("letter", 612.0, 792.0),
+        ("legal", 612.0, 1008.0),
+        ("tabloid", 792.0, 1224.0),
+    ];
+
+    /// `A4`, `letter`... optionally followed by `landscape`.
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_lowercase();
+        let (name, landscape) = match text.strip_suffix("landscape") {
+            Some(name) => (name.trim(), true),
+            None => (text.as_str(), false),
+        };
+        let (_, width, height) = Self::NAMED.iter().find(|(named, _, _)| *named == name)?;
+        Some(if landscape { Self { width: *height, height: *width } } else { Self { width: *width, height: *height } })
+    }
+
+    fn name(&self) -> String {
+        Self::NAMED.iter()
+            .find_map(|(name, width, height)| {
+                if (*width, *height) == (self.width, self.height) {
+                    Some(name.to_uppercase())
+                } else if (*height, *width) == (self.width, self.height) {
+                    Some(format!("{} landscape", name.to_uppercase()))
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// How the pages of the PDF are laid out on the printed sheets (`output.imposition`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Imposition {
+    /// `2x4 on A4`: the document is printed at the size of a cell and its pages are tiled in
+    /// `columns` by `rows` cells on each sheet, with cut marks (labels, tickets, flashcards).
+    Grid { columns: usize, rows: usize, sheet: PaperSize },
+    /// `booklet`, `booklet of 16 on A4`: two pages per side of a sheet, ordered so the sheets
+    /// printed double-sided (flipped on the short edge) fold into a saddle-stitched booklet.
+    /// Long documents are split into signatures of `signature` pages, folded separately and
+    /// stacked. Without a sheet size, the sheets are two pages wide.
+    Booklet { signature: Option<usize>, sheet: Option<PaperSize> },
+}
+
+impl TryFrom<String> for Imposition {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid imposition '{text}', expected e.g. '2x4 on A4' or 'booklet of 16'");
+        let (layout, sheet) = match text.split_once(" on ") {
+            Some((layout, sheet)) => (layout.trim(), Some(PaperSize::parse(sheet).ok_or_else(invalid)?)),
+            None => (text.trim(), None),
+        };
+        if let Some(signature) = layout.strip_prefix("booklet") {
+            let signature = match signature.trim().strip_prefix("of") {
+                Some(pages) => Some(pages.trim().parse::<usize>().ok().filter(|pages| *pages > 0 && pages % 4 == 0)
+                    .ok_or_else(|| format!("invalid imposition '{text}': signatures have a multiple of 4 pages"))?),
+                None if signature.trim().is_empty() => None,
+                None => return Err(invalid()),
+            };
+            // Booklet sheets hold two pages side by side.
+            let sheet = sheet.map(|sheet| PaperSize { width: sheet.width.max(sheet.height), height: sheet.width.min(sheet.height) });
+            return Ok(Imposition::Booklet { signature, sheet });
+        }
+        let (columns, rows) = layout.split_once('x').ok_or_else(invalid)?;
+        let (columns, rows) = (columns.trim().parse().map_err(|_| invalid())?, rows.trim().parse().map_err(|_| invalid())?);
+        if columns == 0 || rows == 0 {
+            return Err(invalid());
+        }
+        let sheet = sheet.or_else(|| PaperSize::parse("a4")).ok_or_else(invalid)?;
+        Ok(Imposition::Grid { columns, rows, sheet })
+    }
+}
+
+impl From<Imposition> for String {
+    fn from(imposition: Imposition) -> Self {
+        imposition.to_string()
+    }
+}
+
+impl fmt::Display for Imposition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Imposition::Grid { columns, rows, sheet } => write!(f, "{columns}x{rows} on {}", sheet.name()),
+            Imposition::Booklet { signature, sheet } => {
+                write!(f, "booklet")?;
+                if let Some(signature) = signature {
+                    write!(f, " of {signature}")?;
+                }
+                match sheet {
+                    // Booklet sheets are always in landscape.
+                    Some(sheet) => write!(f, " on {}", sheet.name().trim_end_matches(" landscape")),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl Imposition {
+    /// Size the document must be printed at, in points, when the layout dictates it.
+    pub fn page_size(&self) -> Option<PaperSize> {
+        match self {
+            Imposition::Grid { columns, rows, sheet } => Some(PaperSize {
+                width: (sheet.width - 2.0 * SHEET_MARGIN) / *columns as f64,
+                height: (sheet.height - 2.0 * SHEET_MARGIN) / *rows as f64,
+            }),
+            Imposition::Booklet { .. } => None,
+        }
+    }
+
+    /// The sheets for pages with the given media boxes.
+    fn sheets(&self, page_boxes: &[[f64; 4]]) -> Vec<Sheet> {
+        match self {
+            Imposition::Grid { columns, rows, sheet } => grid_sheets(page_boxes.len(), *columns, *rows, *sheet),
+            Imposition::Booklet { signature, sheet } => {
+                let [x0, y0, x1, y1] = page_boxes.first().copied().unwrap_or([0.0, 0.0, 612.0, 792.0]);
+                let sheet = sheet.unwrap_or(PaperSize { width: 2.0 * (x1 - x0), height: y1 - y0 });
+                booklet_sheets(page_boxes.len(), *signature, sheet)
+            }
+        }
+    }
+}
+
+/// A page of the document drawn on a sheet, scaled to fit the `width` by `height` box whose
+/// bottom-left corner is at (`x`, `y`).
+#[derive(Debug, Clone, PartialEq)]
+struct Placement {
+    page: usize,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// A printed sheet: the pages drawn on it and the lines (cut marks...) around them.
+#[derive(Debug, Clone, PartialEq)]
+struct Sheet {
+    size: PaperSize,
+    placements: Vec<Placement>,
+    lines: Vec<[f64; 4]>,
+}
+
+/// Tiles the pages left to right, top to bottom, with cut marks in the sheet margins at every
+/// column and row boundary.
+fn grid_sheets(page_count: usize, columns: usize, rows: usize, size: PaperSize) -> Vec<Sheet> {
+    let (cell_width, cell_height) = ((size.width - 2.0 * SHEET_MARGIN) / columns as f64, (size.height - 2.0 * SHEET_MARGIN) / rows as f64);
+    let (left, right, bottom, top) = (SHEET_MARGIN, size.width - SHEET_MARGIN, SHEET_MARGIN, size.height - SHEET_MARGIN);
+    let mut lines = Vec::new();
+    for column in 0..=columns {
+        let x = left + column as f64 * cell_width;
+        lines.push([x, top + MARK_OFFSET, x, size.height - MARK_OFFSET]);
+        lines.push([x, MARK_OFFSET, x, bottom - MARK_OFFSET]);
+    }
+    for row in 0..=rows {
+        let y = bottom + row as f64 * cell_height;
+        lines.push([MARK_OFFSET, y, left - MARK_OFFSET, y]);
+        lines.push([right + MARK_OFFSET, y, size.width - MARK_OFFSET, y]);
+    }
+
+    (0..page_count).collect::<Vec<_>>().chunks(columns * rows).map(|pages| Sheet {
+        size,
+        placements: pages.iter().enumerate().map(|(cell, page)| Placement {
+            page: *page,
+            x: left + (cell % columns) as f64 * cell_width,
+            y: top - (cell / columns + 1) as f64 * cell_height,
+            width: cell_width,
+            height: cell_height,
+        }).collect(),
+        lines: lines.clone(),
+    }).collect()
+}
+
+/// Pages on the left and right halves of each side of the sheets of a booklet, in printing
+/// order: the outer sheet of a signature carries its last and first pages on the front, its
+/// second and second to last pages on the back. `None` are the blank pages completing the
+/// last signature.
+fn booklet_order(page_count: usize, signature: Option<usize>) -> Vec<[Option<usize>; 2]> {
+    let padded = page_count.div_ceil(4).max(1) * 4;
+    let signature = signature.unwrap_or(padded);
+    let page = |index: usize| (index < page_count).then_some(index);
+    let mut sides = Vec::new();
+    for start in (0..padded).step_by(signature) {
+        let size = signature.min(padded - start);
+        for sheet in 0..size / 4 {
+            sides.push([page(start + size - 1 - 2 * sheet), page(start + 2 * sheet)]);
+            sides.push([page(start + 2 * sheet + 1), page(start + size - 2 - 2 * sheet)]);
+        }
+    }
+    sides
+}
+
+fn booklet_sheets(page_count: usize, signature: Option<usize>, size: PaperSize) -> Vec<Sheet> {
+    let half = size.width / 2.0;
+    booklet_order(page_count, signature).into_iter().map(|side| Sheet {
+        size,
+        placements: side.iter().enumerate()
+            .filter_map(|(position, page)| Some(Placement { page: (*page)?, x: position as f64 * half, y: 0.0, width: half, height: size.height }))
+            .collect(),
+        lines: Vec::new(),
+    }).collect()
+}
+
+/// Handout sheets, in the orientation of the pages: in a column with notes or with 2 pages per
+/// sheet, otherwise in two columns.
+fn handout_sheets(page_boxes: &[[f64; 4]], per_sheet: usize, notes: bool) -> Vec<Sheet> {
+    let [x0, y0, x1, y1] = page_boxes.first().copied().unwrap_or([0.0, 0.0, 612.0, 792.0]);
+    let (page_width, page_height) = (x1 - x0, y1 - y0);
+    let size = PaperSize { width: page_width.min(page_height), height: page_width.max(page_height) };
+    let columns = if notes || per_sheet == 2 { 1 } else { 2 };
+    let rows = per_sheet / columns;
+    let cell_width = (size.width - 2.0 * SHEET_MARGIN - (columns - 1) as f64 * HANDOUT_GAP) / columns as f64;
+    let cell_height = (size.height - 2.0 * SHEET_MARGIN - (rows - 1) as f64 * HANDOUT_GAP) / rows as f64;
+    // With notes, the pages take a bit more than half of the width and the lines the rest.
+    let scale = ((if notes { cell_width * 0.55 } else { cell_width }) / page_width).min(cell_height / page_height);
+    let (width, height) = (page_width * scale, page_height * scale);
+
+    (0..page_boxes.len()).collect::<Vec<_>>().chunks(per_sheet).map(|pages| {
+        let mut placements = Vec::new();
+        let mut lines = Vec::new();
+        for (cell, page) in pages.iter().enumerate() {
+            let cell_x = SHEET_MARGIN + (cell % columns) as f64 * (cell_width + HANDOUT_GAP);
+            let cell_top = size.height - SHEET_MARGIN - (cell / columns) as f64 * (cell_height + HANDOUT_GAP);
+            let x = if notes { cell_x } else { cell_x + (cell_width - width) / 2.0 };
+            let y = cell_top - (cell_height + height) / 2.0;
+            placements.push(Placement { page: *page, x, y, width, height });
+            lines.extend([[x, y, x + width, y], [x + width, y, x + width, y + height], [x + width, y + height, x, y + height], [x, y + height, x, y]]);
+            if notes {
+                let mut line_y = y + height - NOTE_LINE_SPACING;
+                while line_y >= y {
+                    lines.push([x + width + HANDOUT_GAP, line_y, cell_x + cell_width, line_y]);
+                    line_y -= NOTE_LINE_SPACING;
+                }
+            }
+        }
+        Sheet { size, placements, lines }
+    }).collect()
+}
+
+/// Lays the pages of `pdf` out on sheets. Links, bookmarks and the tagged structure refer to
+/// the original pages and are dropped.
+pub fn impose(pdf: &[u8], imposition: &Imposition) -> Result<Vec<u8>, AppError> {
+    lay_out(pdf, |page_boxes| imposition.sheets(page_boxes))
+}
+
+/// A handout of `pdf`: `per_sheet` (2, 4 or 6) framed pages on each sheet, with lines for
+/// notes next to them when `notes` is set.
+pub fn handout(pdf: &[u8], per_sheet: usize, notes: bool) -> Result<Vec<u8>, AppError> {
+    if ![2, 4, 6].contains(&per_sheet) {
+        return Err(AppError::BuildError(format!("'output.nup' must be 2, 4 or 6, not {per_sheet}.")));
+    }
+    lay_out(pdf, |page_boxes| handout_sheets(page_boxes, per_sheet, notes))
+}
+
+/// Replaces the pages of `pdf` with the sheets laid out by `sheets` from their media boxes.
+fn lay_out(pdf: &[u8], sheets: impl FnOnce(&[[f64; 4]]) -> Vec<Sheet>) -> Result<Vec<u8>, AppError> {
+    let pdf_error = |e: lopdf::Error| AppError::BuildError(format!("Could not impose the PDF pages: {e}"));
+    let mut document = Document::load_mem(pdf).map_err(pdf_error)?;
+
+    // Every page becomes a form XObject, which the sheets draw where they need it.
+    let mut forms: Vec<(ObjectId, [f64; 4])> = Vec::new();
+    for page_id in document.get_pages().into_values() {
+        let media_box = media_box(&document, page_id).unwrap_or([0.0, 0.0, 612.0, 792.0]);
+        let content = document.get_page_content(page_id).map_err(pdf_error)?;
+        let resources = inherited(&document, page_id, b"Resources").cloned().unwrap_or_else(|| Object::Dictionary(Dictionary::new()));
+        let form = Stream::new(dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => media_box.iter().map(|value| Object::Real(*value as f32)).collect::<Vec<_>>(),
+            "Resources" => resources,
+        }, content);
+        forms.push((document.add_object(form), media_box));
+    }
+
+    let pages_id = document.catalog().and_then(|catalog| catalog.get(b"Pages")).and_then(Object::as_reference).map_err(pdf_error)?;
+    let mut kids = Vec::new();
+    let page_boxes: Vec<[f64; 4]> = forms.iter().map(|(_, media_box)| *media_box).collect();
+    for sheet in sheets(&page_boxes) {
+        let mut operations = Vec::new();
+        let mut xobjects = Dictionary::new();
+        for placement in &sheet.placements {
+            let (form_id, [x0, y0, x1, y1]) = forms[placement.page];
+            let scale = (placement.width / (x1 - x0)).min(placement.height / (y1 - y0));
+            // Centered in its box.
+            let x = placement.x + (placement.width - (x1 - x0) * scale) / 2.0 - x0 * scale;
+            let y = placement.y + (placement.height - (y1 - y0) * scale) / 2.0 - y0 * scale;
+            let name = format!("P{}", placement.page);
+            operations.push(Operation::new("q", vec![]));
+            operations.push(Operation::new("cm", [scale, 0.0, 0.0, scale, x, y].iter().map(|value| Object::Real(*value as f32)).collect()));
+            operations.push(Operation::new("Do", vec![Object::Name(name.clone().into_bytes())]));
+            operations.push(Operation::new("Q", vec![]));
+            xobjects.set(name, form_id);
+        }
+        if !sheet.lines.is_empty() {
+            operations.push(Operation::new("w", vec![Object::Real(0.25)]));
+            operations.push(Operation::new("G", vec![0.into()]));
+            for [x1, y1, x2, y2] in &sheet.lines {
+                operations.push(Operation::new("m", vec![Object::Real(*x1 as f32), Object::Real(*y1 as f32)]));
+                operations.push(Operation::new("l", vec![Object::Real(*x2 as f32), Object::Real(*y2 as f32)]));
+                operations.push(Operation::new("S", vec![]));
+            }
+        }
+        let content_id = document.add_object(Stream::new(dictionary! {}, Content { operations }.encode().map_err(pdf_error)?));
+        kids.push(Object::Reference(document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), Object::Real(sheet.size.width as f32), Object::Real(sheet.size.height as f32)],
+            "Resources" => dictionary! { "XObject" => xobjects },
+            "Contents" => content_id,
+        })));
+    }
+
+    let count = kids.len() as i64;
+    document.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }));
+    let catalog = document.catalog_mut().map_err(pdf_error)?;
+    for key in [&b"Outlines"[..], b"StructTreeRoot", b"MarkInfo", b"Names"] {
+        catalog.remove(key);
+    }
+    document.prune_objects();
+    document.compress();
+
+    let mut output = Vec::new();
+    document.save_to(&mut output)?;
+    Ok(output)
+}
+
+/// The size of every page of `pdf`, from their media boxes.
+pub fn page_sizes(pdf: &[u8]) -> Result<Vec<PaperSize>, AppError> {
+    let document = Document::load_mem(pdf).map_err(|e| AppError::BuildError(format!("Could not read the PDF pages: {e}")))?;
+    Ok(document.get_pages().values()
+        .filter_map(|page_id| media_box(&document, *page_id))
+        .map(|[left, bottom, right, top]| PaperSize { width: right - left, height: top - bottom })
+        .collect())
+}
+
+/// The media box of a page.
+fn media_box(document: &Document, page_id: ObjectId) -> Option<[f64; 4]> {
+    let (_, value) = document.dereference(inherited(document, page_id, b"MediaBox")?).ok()?;
+    let values: Vec<f64> = value.as_array().ok()?.iter().filter_map(|value| value.as_float().ok().map(f64::from)).collect();
+    values.try_into().ok()
+}
+
+/// An entry of a page dictionary, which may be inherited from the page tree.
+fn inherited<'a>(document: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = document.get_dictionary(page_id).ok()?;
+    loop {
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        node = document.get_dictionary(node.get(b"Parent").and_then(Object::as_reference).ok()?).ok()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PDF with `count` empty pages of 200 by 100 points.
+    fn pdf_with_pages(count: usize) -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let kids: Vec<Object> = (0..count).map(|_| {
+            let content_id = document.add_object(Stream::new(dictionary! {}, b"0 0 200 100 re f".to_vec()));
+            Object::Reference(document.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => content_id }))
+        }).collect();
+        document.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count as i64,
+            "Resources" => dictionary! {},
+            "MediaBox" => vec![0.into(), 0.into(), 200.into(), 100.into()],
+        }));
+        let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        document.trailer.set("Root", catalog_id);
+        let mut output = Vec::new();
+        document.save_to(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_parse_imposition() {
+        let imposition = Imposition::try_from("2x4 on A4".to_string()).unwrap();
+        assert_eq!(imposition, Imposition::Grid { columns: 2, rows: 4, sheet: PaperSize { width: 595.28, height: 841.89 } });
+        assert_eq!(imposition.to_string(), "2x4 on A4");
+        assert_eq!(Imposition::try_from("3x2 on letter landscape".to_string()).unwrap().to_string(), "3x2 on LETTER landscape");
+        assert!(Imposition::try_from("0x4 on A4".to_string()).is_err());
+        assert!(Imposition::try_from("2x4 on A9".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_booklet() {
+        assert_eq!(Imposition::try_from("booklet".to_string()).unwrap(), Imposition::Booklet { signature: None, sheet: None });
+        let imposition = Imposition::try_from("booklet of 16 on A4".to_string()).unwrap();
+        assert_eq!(imposition, Imposition::Booklet { signature: Some(16), sheet: Some(PaperSize { width: 841.89, height: 595.28 }) });
+        assert_eq!(imposition.to_string(), "booklet of 16 on A4");
+        assert!(Imposition::try_from("booklet of 10".to_string()).is_err());
+        assert!(Imposition::try_from("booklets".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_booklet_order() {
+        assert_eq!(booklet_order(8, None), [
+            [Some(7), Some(0)], [Some(1), Some(6)],
+            [Some(5), Some(2)], [Some(3), Some(4)],
+        ]);
+        // 6 pages are completed by two blank pages.
+        assert_eq!(booklet_order(6, None)[0], [None, Some(0)]);
+        assert_eq!(booklet_order(6, None)[1], [Some(1), None]);
+        // Two signatures of 4 pages, then the 2 remaining pages.
+        assert_eq!(booklet_order(10, Some(4)), [
+            [Some(3), Some(0)], [Some(1), Some(2)],
+            [Some(7), Some(4)], [Some(5), Some(6)],
+            [None, Some(8)], [Some(9), None],
+        ]);
+    }
+
+    #[test]
+    fn test_grid_sheets() {
+        let sheets = grid_sheets(5, 2, 2, PaperSize { width: 256.7, height: 456.7 });
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(sheets[0].placements.len(), 4);
+        let last = &sheets[1].placements[0];
+        assert_eq!((last.page, last.x), (4, SHEET_MARGIN));
+        assert!((last.y - 228.35).abs() < 1e-6 && (last.width - 100.0).abs() < 1e-6 && (last.height - 200.0).abs() < 1e-6);
+        assert_eq!(sheets[0].placements[1].x, SHEET_MARGIN + last.width);
+        // Two marks per column and row boundary.
+        assert_eq!(sheets[0].lines.len(), 12);
+    }
+
+    #[test]
+    fn test_handout_sheets() {
+        let pages = vec![[0.0, 0.0, 612.0, 792.0]; 5];
+        let sheets = handout_sheets(&pages, 4, false);
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(sheets[0].size, PaperSize { width: 612.0, height: 792.0 });
+        let (first, second) = (&sheets[0].placements[0], &sheets[0].placements[1]);
+        assert!(first.y == second.y && first.x < second.x);
+        assert!((first.height / first.width - 792.0 / 612.0).abs() < 1e-9);
+        // A frame around each page.
+        assert_eq!(sheets[1].lines.len(), 4);
+
+        let sheets = handout_sheets(&pages, 2, true);
+        assert_eq!(sheets.len(), 3);
+        assert!(sheets[0].placements[0].y > sheets[0].placements[1].y);
+        assert!(sheets[0].lines.len() > 8);
+        assert!(sheets[0].lines.iter().skip(4).take(3).all(|[x1, _, _, _]| *x1 > sheets[0].placements[0].width));
+    }
+
+    #[test]
+    fn test_page_sizes() {
+        assert_eq!(page_sizes(&pdf_with_pages(2)).unwrap(), vec![PaperSize { width: 200.0, height: 100.0 }; 2]);
+    }
+
+    #[test]
+    fn test_impose() {
+        let imposition = Imposition::try_from("2x4 on A4".to_string()).unwrap();
+        let imposed = impose(&pdf_with_pages(9), &imposition).unwrap();
+        let document = Document::load_mem(&imposed).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        let first_sheet = document.get_dictionary(pages[&1]).unwrap();
+        assert_eq!(media_box(&document, pages[&1]).map(|[_, _, width, height]| (width.round(), height.round())), Some((595.0, 842.0)));
+        let xobjects = first_sheet.get(b"Resources").and_then(Object::as_dict).and_then(|resources| resources.get(b"XObject")).and_then(Object::as_dict).unwrap();
+        assert_eq!(xobjects.len(), 8);
+
+        let booklet = impose(&pdf_with_pages(3), &Imposition::try_from("booklet".to_string()).unwrap()).unwrap();
+        let document = Document::load_mem(&booklet).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(media_box(&document, pages[&1]), Some([0.0, 0.0, 400.0, 100.0]));
+
+        assert_eq!(Document::load_mem(&handout(&pdf_with_pages(5), 4, true).unwrap()).unwrap().get_pages().len(), 2);
+        assert!(handout(&pdf_with_pages(5), 3, false).is_err());
+    }
+}
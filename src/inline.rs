@@ -0,0 +1,131 @@
+use crate::warnings;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use regex::{Captures, Regex};
+use std::fs;
+use std::path::Path;
+
+/// Makes an HTML page self-contained: local images, fonts and other `url(...)` resources
+/// become data URIs, and local stylesheets and scripts are inlined. Paths are resolved
+/// against `base_dir`, the directory of the HTML file. Remote and missing resources are
+/// left untouched.
+pub fn inline_resources(html: &str, base_dir: &Path) -> String {
+    let stylesheet_re = Regex::new(r#"<link\s[^>]*?href="([^"]+)"[^>]*>"#).unwrap();
+    let script_re = Regex::new(r#"<script\s[^>]*?src="([^"]+)"[^>]*>\s*</script>"#).unwrap();
+    let src_re = Regex::new(r#"(<(?:img|source|video|audio)\s[^>]*?src=")([^"]+)(")"#).unwrap();
+
+    let html = stylesheet_re.replace_all(html, |caps: &Captures| {
+        if !caps[0].contains("stylesheet") {
+            return caps[0].to_string();
+        }
+        // Relative URLs in a stylesheet are resolved against the stylesheet itself.
+        let css_dir = base_dir.join(Path::new(&caps[1]).parent().unwrap_or(Path::new("")));
+        match read_local(base_dir, &caps[1]) {
+            Some(css) => format!("<style>{}</style>", inline_css_urls(&String::from_utf8_lossy(&css), &css_dir)),
+            None => caps[0].to_string(),
+        }
+    });
+
+    let html = script_re.replace_all(&html, |caps: &Captures| match read_local(base_dir, &caps[1]) {
+        Some(script) => format!("<script>{}</script>", String::from_utf8_lossy(&script)),
+        None => caps[0].to_string(),
+    });
+
+    let html = src_re.replace_all(&html, |caps: &Captures| match data_uri(base_dir, &caps[2]) {
+        Some(uri) => format!("{}{}{}", &caps[1], uri, &caps[3]),
+        None => caps[0].to_string(),
+    });
+
+    // Only stylesheets are scanned, so `url(...)` written in the document text is kept.
+    let style_re = Regex::new(r"(?s)(<style[^>]*>)(.*?)(</style>)").unwrap();
+    style_re.replace_all(&html, |caps: &Captures| {
+        format!("{}{}{}", &caps[1], inline_css_urls(&caps[2], base_dir), &caps[3])
+    }).to_string()
+}
+
+/// Replaces `url(...)` references (background images, `@font-face` sources) with data URIs.
+fn inline_css_urls(css: &str, base_dir: &Path) -> String {
+    let url_re = Regex::new(r#"url\(\s*['"]?([^'")]+)['"]?\s*\)"#).unwrap();
+    url_re.replace_all(css, |caps: &Captures| match data_uri(base_dir, &caps[1]) {
+        Some(uri) => format!("url(\"{uri}\")"),
+        None => caps[0].to_string(),
+    }).to_string()
+}
+
+fn data_uri(base_dir: &Path, reference: &str) -> Option<String> {
+    let content = read_local(base_dir, reference)?;
+    Some(format!("data:{};base64,{}", mime_type(reference), STANDARD.encode(content)))
+}
+
+fn read_local(base_dir: &Path, reference: &str) -> Option<Vec<u8>> {
+    if reference.starts_with("data:") || reference.starts_with('#') || reference.contains("://") || reference.starts_with("//") {
+        return None;
+    }
+    // Query strings and fragments are used for cache busting and SVG sprites.
+    let path = reference.split(['?', '#']).next().unwrap_or(reference);
+    let path = path_clean::clean(path.trim_start_matches('/'));
+    if path.starts_with("..") {
+        return None;
+    }
+    let content = fs::read(base_dir.join(&path)).ok();
+    if content.is_none() {
+        warnings::warn("Missing resource not inlined", reference);
+    }
+    content
+}
+
+fn mime_type(reference: &str) -> &'static str {
+    let extension = Path::new(reference.split(['?', '#']).next().unwrap_or(reference))
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_inline_resources() {
+        let test_dir = TestDir::new("inline_resources");
+        let dir = test_dir.path();
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        fs::write(dir.join("assets/logo.png"), [1u8, 2, 3]).unwrap();
+        fs::write(dir.join("assets/font.woff2"), "font").unwrap();
+        fs::write(dir.join("assets/extra.css"), "h1 { background: url('logo.png'); }").unwrap();
+        fs::write(dir.join("assets/app.js"), "console.log(1);").unwrap();
+
+        let html = r#"<link rel="stylesheet" href="assets/extra.css"><script src="assets/app.js"></script>
+<style>@font-face { src: url("assets/font.woff2"); }</style>
+<img src="assets/logo.png" alt="logo"><img src="https://example.com/a.png"><img src="assets/missing.png">"#;
+        let inlined = inline_resources(html, dir);
+
+        assert!(inlined.contains(r#"<img src="data:image/png;base64,AQID" alt="logo">"#));
+        assert!(inlined.contains(r#"url("data:font/woff2;base64,Zm9udA==")"#));
+        assert!(inlined.contains(r#"h1 { background: url("data:image/png;base64,AQID"); }"#));
+        assert!(inlined.contains("<script>console.log(1);</script>"));
+        assert!(inlined.contains(r#"<img src="https://example.com/a.png">"#));
+        assert!(inlined.contains(r#"<img src="assets/missing.png">"#));
+        assert!(!inlined.contains("<link"));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::directives::Directive;
+use crate::error::AppError;
+use crate::paths::SourceDir;
+use serde_yaml::Value;
+use std::fs;
+
+/// Returns the reference of `!jsonschema(schema.json)` as Markdown: the schema's description and
+/// a table of its properties with their types, defaults and descriptions. Nested objects are
+/// listed under their dotted path (`output.filename`, `files[].name`). The schema, JSON or YAML,
+/// is read relative to `source_dir` and must be inside the project.
+pub fn jsonschema_directive(source_dir: SourceDir, directive: &Directive) -> Result<String, AppError> {
+    directive.expect_positional(1)?;
+    let file = directive.value("file", 0)
+        .ok_or_else(|| AppError::BuildError("!jsonschema needs a schema file, e.g. !jsonschema(schema/config.schema.json)".to_string()))?;
+    let path = source_dir.dir.join(file);
+    let content = fs::read_to_string(source_dir.resolve(file)?).map_err(|e| AppError::BuildError(format!("Could not read JSON Schema '{}': {e}", path.display())))?;
+    // YAML is a superset of JSON, and its mappings keep the order of the schema.
+    let schema: Value = serde_yaml::from_str(&content).map_err(|e| AppError::BuildError(format!("Invalid JSON Schema '{}': {e}", path.display())))?;
+    render_schema(&schema)
+}
+
+fn render_schema(root: &Value) -> Result<String, AppError> {
+    let mut rows = Vec::new();
+    property_rows(root, root, "", &mut Vec::new(), &mut rows);
+    if rows.is_empty() {
+        return Err(AppError::BuildError("The JSON Schema has no properties".to_string()));
+    }
+    let mut markdown = String::new();
+    if let Some(description) = text(root, "description") {
+        markdown.push_str(&format!("{description}\n\n"));
+    }
+    markdown.push_str("| Property | Type | Default | Description |\n|---|---|---|---|");
+    for row in rows {
+        markdown.push('\n');
+        markdown.push_str(&row);
+    }
+    Ok(markdown)
+}
+
+/// Rows of the properties of `schema`, then of the objects they hold. `refs` are the
+/// references being followed, so recursive schemas stop.
+fn property_rows(root: &Value, schema: &Value, prefix: &str, refs: &mut Vec<String>, rows: &mut Vec<String>) {
+    let parts: Vec<&Value> = match schema.get("allOf").and_then(Value::as_sequence) {
+        Some(parts) => parts.iter().map(|part| resolve(root, part)).collect(),
+        None => vec![schema],
+    };
+    for part in parts {
+        let required: Vec<&str> = part.get("required").and_then(Value::as_sequence).into_iter().flatten().filter_map(Value::as_str).collect();
+        for (name, property) in entries(part.get("properties")) {
+            let reference = property.get("$ref").and_then(Value::as_str);
+            let property = resolve(root, property);
+            let path = format!("{prefix}{name}");
+            let mut description: Vec<String> = Vec::new();
+            if required.contains(&name.as_str()) {
+                description.push("**Required.**".to_string());
+            }
+            if property.get("deprecated").and_then(Value::as_bool).unwrap_or(false) {
+                description.push("*Deprecated.*".to_string());
+            }
+            description.extend(text(property, "description").or_else(|| text(property, "title")).map(str::to_string));
+            if let Some(values) = property.get("enum").and_then(Value::as_sequence) {
+                description.push(format!("One of {}.", values.iter().map(|value| format!("`{}`", literal(value))).collect::<Vec<_>>().join(", ")));
+            }
+            rows.push(format!(
+                "| `{path}` | {} | {} | {} |",
+                type_name(root, property),
+                property.get("default").map(|default| format!("`{}`", cell(&literal(default)))).unwrap_or_default(),
+                cell(&description.join(" "))
+            ));
+
+            let items = property.get("items");
+            let (nested, nested_prefix, reference) = if is_object(property) {
+                (property, format!("{path}."), reference)
+            } else if let Some(items) = items.filter(|items| is_object(resolve(root, items))) {
+                (resolve(root, items), format!("{path}[]."), items.get("$ref").and_then(Value::as_str))
+            } else {
+                continue;
+            };
+            if reference.is_some_and(|reference| refs.iter().any(|followed| followed == reference)) {
+                continue;
+            }
+            refs.extend(reference.map(str::to_string));
+            property_rows(root, nested, &nested_prefix, refs, rows);
+            if reference.is_some() {
+                refs.pop();
+            }
+        }
+    }
+}
+
+fn is_object(schema: &Value) -> bool {
+    schema.get("properties").is_some() || schema.get("allOf").is_some()
+}
+
+/// A short description of a schema's type: `string (uri)`, `array of integer`, `map of string`,
+/// `string or null`.
+fn type_name(root: &Value, schema: &Value) -> String {
+    let schema = resolve(root, schema);
+    for keyword in ["oneOf", "anyOf"] {
+        if let Some(parts) = schema.get(keyword).and_then(Value::as_sequence) {
+            return parts.iter().map(|part| type_name(root, part)).collect::<Vec<_>>().join(" or ");
+        }
+    }
+    let kinds: Vec<&str> = match schema.get("type") {
+        Some(Value::String(kind)) => vec![kind.as_str()],
+        Some(Value::Sequence(kinds)) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ if is_object(schema) => vec!["object"],
+        _ => vec![],
+    };
+    let mut names: Vec<String> = kinds.iter()
+        .map(|kind| match *kind {
+            "array" => format!("array of {}", schema.get("items").map_or_else(|| "any".to_string(), |items| type_name(root, items))),
+            "object" => match schema.get("additionalProperties").filter(|values| values.is_mapping()) {
+                Some(values) if schema.get("properties").is_none() => format!("map of {}", type_name(root, values)),
+                _ => "object".to_string(),
+            },
+            "null" => "null".to_string(),
+            kind => match text(schema, "format") {
+                Some(format) => format!("{kind} ({format})"),
+                None => kind.to_string(),
+            },
+        })
+        .collect();
+    if names.is_empty() {
+        names.push(schema.get("const").map_or_else(|| "any".to_string(), |value| format!("`{}`", literal(value))));
+    }
+    names.join(" or ")
+}
+
+/// The target of a `$ref` within the document, or the value itself.
+pub fn resolve<'a>(root: &'a Value, value: &'a Value) -> &'a Value {
+    match value.get("$ref").and_then(Value::as_str).and_then(|reference| reference.strip_prefix('#')) {
+        Some(path) => pointer(root, path).unwrap_or(value),
+        None => value,
+    }
+}
+
+/// The value at a JSON pointer such as `/components/schemas/Payment`.
+pub fn pointer<'a>(root: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('/').skip(1).try_fold(root, |value, key| value.get(key.replace("~1", "/").replace("~0", "~")))
+}
+
+/// Entries of a mapping, in order, with their keys as text (response codes may be numbers).
+pub fn entries(value: Option<&Value>) -> Vec<(String, &Value)> {
+    value.and_then(Value::as_mapping).into_iter().flatten()
+        .map(|(key, value)| (key.as_str().map_or_else(|| literal(key), str::to_string), value))
+        .collect()
+}
+
+/// The trimmed text of a string field, `None` when missing or empty.
+pub fn text<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str).map(str::trim).filter(|text| !text.is_empty())
+}
+
+/// A value as written in JSON, strings without their quotes.
+pub fn literal(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+/// Text kept on one line of a table cell.
+pub fn cell(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_schema() {
+        let schema: Value = serde_yaml::from_str(r##"{
+            "description": "Settings of the build.",
+            "type": "object",
+            "required": ["title"],
+            "properties": {
+                "title": { "type": "string", "description": "Title of the document." },
+                "margins": { "type": "number", "default": 0.5, "deprecated": true },
+                "output": {
+                    "type": "object",
+                    "properties": {
+                        "format": { "enum": ["pdf", "html"], "type": "string", "default": "pdf" },
+                        "url": { "type": ["string", "null"], "format": "uri" }
+                    }
+                },
+                "files": { "title": "Sources | folders", "type": "array", "items": { "$ref": "#/$defs/file" } },
+                "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+                "mode": { "oneOf": [{ "type": "integer" }, { "const": "auto" }] }
+            },
+            "$defs": {
+                "file": {
+                    "type": "object",
+                    "properties": { "name": { "type": "string" }, "children": { "type": "array", "items": { "$ref": "#/$defs/file" } } }
+                }
+            }
+        }"##).unwrap();
+        assert_eq!(
+            render_schema(&schema).unwrap(),
+            "Settings of the build.\n\n\
+             | Property | Type | Default | Description |\n|---|---|---|---|\n\
+             | `title` | string |  | **Required.** Title of the document. |\n\
+             | `margins` | number | `0.5` | *Deprecated.* |\n\
+             | `output` | object |  |  |\n\
+             | `output.format` | string | `pdf` | One of `pdf`, `html`. |\n\
+             | `output.url` | string (uri) or null |  |  |\n\
+             | `files` | array of object |  | Sources \\| folders |\n\
+             | `files[].name` | string |  |  |\n\
+             | `files[].children` | array of object |  |  |\n\
+             | `labels` | map of string |  |  |\n\
+             | `mode` | integer or `auto` |  |  |"
+        );
+        assert!(render_schema(&serde_yaml::from_str("type: string").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::cache::{self, Cache};
+use crate::diagrams;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// How ` ```latex ` blocks are rendered. Blocks stay source code unless `latex:` is set.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LatexConfig {
+    /// `tectonic`, or a TeX engine such as `pdflatex`, `xelatex` or `lualatex`.
+    #[serde(default = "default_engine")]
+    pub engine: String,
+    /// Lines placed before `\begin{document}`, such as `\usepackage{...}`.
+    #[serde(default = "default_preamble")]
+    pub preamble: String,
+}
+
+fn default_engine() -> String {
+    "tectonic".to_string()
+}
+
+fn default_preamble() -> String {
+    "\\usepackage{amsmath,amssymb}\n\\usepackage{tikz}".to_string()
+}
+
+impl Default for LatexConfig {
+    fn default() -> Self {
+        Self { engine: default_engine(), preamble: default_preamble() }
+    }
+}
+
+/// Renders ` ```latex ` blocks to SVG figures: the block is compiled as a standalone document
+/// cropped to its content, then converted with Poppler's `pdftocairo`. Results are cached by
+/// content, engine and preamble, so only changed blocks are compiled again.
+pub fn render_latex_block(config: &LatexConfig, cache: &Cache, info: &str, code: &str) -> Result<Option<String>, AppError> {
+    if info.split_whitespace().next() != Some("latex") {
+        return Ok(None);
+    }
+    let svg = cache.get_or_insert_with("latex", &format!("{}\n{}\n{}", config.engine, config.preamble, code), "svg", || {
+        compile(config, &cache.work_dir(&cache::content_hash(code)), code)
+    })?;
+    Ok(Some(diagrams::image_figure(&svg, "image/svg+xml", "latex")))
+}
+
+fn document(config: &LatexConfig, code: &str) -> String {
+    format!("\\documentclass[border=2pt,varwidth]{{standalone}}\n{}\n\\begin{{document}}\n{}\n\\end{{document}}\n", config.preamble.trim(), code.trim())
+}
+
+fn compile(config: &LatexConfig, work_dir: &Path, code: &str) -> Result<Vec<u8>, AppError> {
+    fs::create_dir_all(work_dir)?;
+    fs::write(work_dir.join("block.tex"), document(config, code))?;
+    let result = run(work_dir, &config.engine, &engine_args(&config.engine))
+        .and_then(|_| run(work_dir, "pdftocairo", &["-svg", "block.pdf", "block.svg"]))
+        .and_then(|_| Ok(fs::read(work_dir.join("block.svg"))?));
+    fs::remove_dir_all(work_dir).ok();
+    result
+}
+
+fn engine_args(engine: &str) -> Vec<&'static str> {
+    if Path::new(engine).file_stem().is_some_and(|stem| stem == "tectonic") {
+        vec!["--chatter", "minimal", "block.tex"]
+    } else {
+        vec!["-interaction=nonstopmode", "-halt-on-error", "block.tex"]
+    }
+}
+
+fn run(work_dir: &Path, program: &str, args: &[&str]) -> Result<(), AppError> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(work_dir)
+        .output()
+        .map_err(|e| AppError::RendererError("latex".to_string(), format!("could not run '{program}': {e}")))?;
+    if !output.status.success() {
+        let log = format!("{}\n{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        return Err(AppError::RendererError("latex".to_string(), tex_error(&log)));
+    }
+    Ok(())
+}
+
+/// The useful part of a failed run: TeX's `! message` with its `l.<line>` context, an
+/// `error:` line of tectonic, or the last line of the output.
+fn tex_error(log: &str) -> String {
+    let lines: Vec<&str> = log.lines().map(str::trim_end).filter(|line| !line.trim().is_empty()).collect();
+    if let Some(index) = lines.iter().position(|line| line.starts_with("! ")) {
+        let context = lines[index..].iter().find(|line| line.starts_with("l."));
+        return [Some(&lines[index]), context].into_iter().flatten().copied().collect::<Vec<_>>().join(" ");
+    }
+    lines.iter().find(|line| line.starts_with("error:")).or(lines.last()).map_or_else(String::new, |line| line.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_render_latex_block() {
+        let test_dir = TestDir::new("latex");
+        let dir = test_dir.path();
+        let cache = Cache::new(dir);
+        let config = LatexConfig::default();
+        assert!(render_latex_block(&config, &cache, "rust", "fn main() {}").unwrap().is_none());
+
+        let code = "\\begin{tikzpicture}\\draw (0,0) circle (1);\\end{tikzpicture}";
+        cache.get_or_insert_with("latex", &format!("{}\n{}\n{}", config.engine, config.preamble, code), "svg", || Ok(b"<svg/>".to_vec())).unwrap();
+        assert_eq!(render_latex_block(&config, &cache, "latex", code).unwrap().unwrap(), diagrams::image_figure(b"<svg/>", "image/svg+xml", "latex"));
+    }
+
+    #[test]
+    fn test_tex_error() {
+        let pdflatex = "This is pdfTeX\n! Undefined control sequence.\n<recently read> \\frak\n\nl.4 $\\frak\n                 g$\nNo pages of output.";
+        assert_eq!(tex_error(pdflatex), "! Undefined control sequence. l.4 $\\frak");
+        assert_eq!(tex_error("note: Running TeX ...\nerror: block.tex:4: Undefined control sequence\nerror: halted on potentially-recoverable error"), "error: block.tex:4: Undefined control sequence");
+        assert_eq!(engine_args("/usr/bin/tectonic")[0], "--chatter");
+    }
+}
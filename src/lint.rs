@@ -0,0 +1,190 @@
+use crate::check::{Diagnostic, SourceFile};
+use crate::LintConfig;
+use regex::Regex;
+use std::collections::HashSet;
+
+pub const HEADING_INCREMENT: &str = "heading-increment";
+pub const TRAILING_WHITESPACE: &str = "trailing-whitespace";
+pub const BARE_URL: &str = "bare-url";
+pub const LIST_MARKER_STYLE: &str = "list-marker-style";
+pub const CODE_LINE_LENGTH: &str = "code-line-length";
+
+/// Keyword used in HTML comments to toggle rules inside a source file:
+/// `<!-- pdfbuilder-disable bare-url -->`, `<!-- pdfbuilder-enable -->`,
+/// `<!-- pdfbuilder-disable-next-line trailing-whitespace -->`.
+const DIRECTIVE_PREFIX: &str = "pdfbuilder-";
+
+/// Tracks which rules are switched off by inline comments while walking a file.
+#[derive(Default)]
+struct Suppressions {
+    disabled: HashSet<String>,
+    next_line: HashSet<String>,
+    all_disabled: bool,
+    next_line_all: bool,
+}
+
+impl Suppressions {
+    fn apply(&mut self, action: &str, rules: Vec<String>) {
+        match action {
+            "disable" if rules.is_empty() => self.all_disabled = true,
+            "disable" => self.disabled.extend(rules),
+            "enable" if rules.is_empty() => {
+                self.all_disabled = false;
+                self.disabled.clear();
+            }
+            "enable" => rules.iter().for_each(|r| {
+                self.disabled.remove(r);
+            }),
+            "disable-next-line" if rules.is_empty() => self.next_line_all = true,
+            "disable-next-line" => self.next_line.extend(rules),
+            _ => {}
+        }
+    }
+
+    fn is_suppressed(&self, rule: &str) -> bool {
+        self.all_disabled || self.next_line_all || self.disabled.contains(rule) || self.next_line.contains(rule)
+    }
+
+    fn end_line(&mut self) {
+        self.next_line.clear();
+        self.next_line_all = false;
+    }
+}
+
+/// Runs the Markdown style rules that are not disabled in `config.disable`.
+pub fn lint_sources(sources: &[SourceFile], config: &LintConfig) -> Vec<Diagnostic> {
+    sources.iter().flat_map(|source| lint_file(source, config)).collect()
+}
+
+fn lint_file(source: &SourceFile, config: &LintConfig) -> Vec<Diagnostic> {
+    let directive_re = Regex::new(&format!(r"<!--\s*{DIRECTIVE_PREFIX}(disable-next-line|disable|enable)((?:\s+[a-z-]+)*)\s*-->")).unwrap();
+    let heading_re = Regex::new(r"^(#{1,6})\s").unwrap();
+    let list_marker_re = Regex::new(r"^\s*([-*+])\s+\S").unwrap();
+    let thematic_break_re = Regex::new(r"^\s*([-*_])(\s*[-*_]){2,}\s*$").unwrap();
+    let ignore_re = Regex::new(r"`[^`]*`|<https?://[^>]*>|\]\([^)]*\)|<[^>]+>").unwrap();
+    let url_re = Regex::new(r"https?://\S+").unwrap();
+
+    let mut diagnostics = Vec::new();
+    let mut suppressions = Suppressions::default();
+    let mut in_code_block = false;
+    let mut previous_heading_level: Option<usize> = None;
+    let mut list_marker: Option<char> = None;
+
+    for (index, line) in source.content.lines().enumerate() {
+        let mut report = |rule: &str, message: String, suppressions: &Suppressions| {
+            if !config.disable.iter().any(|r| r == rule) && !suppressions.is_suppressed(rule) {
+                diagnostics.push(Diagnostic { file: source.path.clone(), line: index + 1, rule: rule.to_string(), message });
+            }
+        };
+
+        if let Some(caps) = directive_re.captures(line) {
+            let rules = caps[2].split_whitespace().map(str::to_string).collect();
+            suppressions.apply(&caps[1], rules);
+            continue;
+        }
+
+        if line.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+            suppressions.end_line();
+            continue;
+        }
+
+        if in_code_block {
+            let length = line.chars().count();
+            if length > config.max_code_line_length {
+                report(
+                    CODE_LINE_LENGTH,
+                    format!("Code line is {} characters long (max {}) and will overflow the printed page", length, config.max_code_line_length),
+                    &suppressions,
+                );
+            }
+            suppressions.end_line();
+            continue;
+        }
+
+        let trailing = line.len() - line.trim_end().len();
+        // Exactly two trailing spaces are a Markdown hard line break.
+        if trailing > 0 && !(trailing == 2 && line.ends_with("  ")) {
+            report(TRAILING_WHITESPACE, "Trailing whitespace".to_string(), &suppressions);
+        }
+
+        if let Some(caps) = heading_re.captures(line) {
+            let level = caps[1].len();
+            if let Some(previous) = previous_heading_level {
+                if level > previous + 1 {
+                    report(HEADING_INCREMENT, format!("Heading level jumps from h{previous} to h{level}"), &suppressions);
+                }
+            }
+            previous_heading_level = Some(level);
+        }
+
+        if !thematic_break_re.is_match(line) {
+            if let Some(caps) = list_marker_re.captures(line) {
+                let marker = caps[1].chars().next().unwrap_or('-');
+                match list_marker {
+                    Some(expected) if expected != marker => report(
+                        LIST_MARKER_STYLE,
+                        format!("List marker '{marker}' is inconsistent with '{expected}' used earlier in this file"),
+                        &suppressions,
+                    ),
+                    None => list_marker = Some(marker),
+                    _ => {}
+                }
+            }
+        }
+
+        let text = ignore_re.replace_all(line, " ");
+        // Directive arguments such as `!qrcode(https://...)` are not rendered as text.
+        let is_directive = line.trim_start().starts_with('!');
+        if let Some(url) = url_re.find(&text).filter(|_| !is_directive) {
+            report(BARE_URL, format!("Bare URL '{}', use <...> or a [link](...)", url.as_str()), &suppressions);
+        }
+
+        suppressions.end_line();
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(content: &str) -> Vec<Diagnostic> {
+        let source = SourceFile { path: "main.md".to_string(), content: content.to_string() };
+        lint_file(&source, &LintConfig::default())
+    }
+
+    fn rules(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|d| d.rule.as_str()).collect()
+    }
+
+    #[test]
+    fn test_lint_reports_each_rule() {
+        let long_line = "x".repeat(200);
+        let content = format!("# Title\n### Jump\nText \n- one\n* two\nSee https://example.com\n```\n{long_line}\n```");
+        let diagnostics = lint(&content);
+        assert_eq!(rules(&diagnostics), vec![HEADING_INCREMENT, TRAILING_WHITESPACE, LIST_MARKER_STYLE, BARE_URL, CODE_LINE_LENGTH]);
+        assert_eq!(diagnostics[0].line, 2);
+    }
+
+    #[test]
+    fn test_lint_accepts_clean_markdown() {
+        let content = "# Title\n## Section\nHard break  \n- one\n- two\n\n---\n\nSee <https://example.com> or [docs](https://example.com).\n!qrcode(https://example.com)";
+        assert!(lint(content).is_empty());
+    }
+
+    #[test]
+    fn test_lint_inline_disable_comments() {
+        let content = "<!-- pdfbuilder-disable-next-line bare-url -->\nhttps://a.example\nhttps://b.example\n<!-- pdfbuilder-disable -->\nhttps://c.example\n<!-- pdfbuilder-enable -->";
+        let diagnostics = lint(content);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 3);
+    }
+
+    #[test]
+    fn test_lint_config_disables_rule() {
+        let source = SourceFile { path: "main.md".to_string(), content: "Text \n".to_string() };
+        let config = LintConfig { disable: vec![TRAILING_WHITESPACE.to_string()], ..LintConfig::default() };
+        assert!(lint_file(&source, &config).is_empty());
+    }
+}
@@ -1,14 +1,78 @@
+mod assets;
+mod boilerplate;
+mod book;
+mod browser;
+mod browser_pool;
+mod build_cache;
 mod builder;
+mod cache;
+mod cancel;
+mod changelog;
+mod charts;
+mod check;
+mod compare;
+mod critic;
+mod diagrams;
+mod diff;
+mod email;
+mod encoding;
 mod error;
+mod error_page;
+mod fences;
+mod fragments;
+mod git;
+mod i18n;
+mod imposition;
+mod inline;
+mod jsonschema;
+mod latex;
+mod lint;
+mod man;
+mod mdbook;
+mod merge;
+mod model;
+mod network;
+mod notebook;
+mod openapi;
+mod org;
+mod pagemap;
+mod paths;
+mod plaintext;
+mod prose_linter;
+mod provenance;
+mod publish;
+mod raster;
+mod remote;
+mod resume;
+mod rustdoc;
+mod screenshot;
+mod series;
+mod server;
+mod site;
+mod snapshot;
+mod social;
+mod spelling;
+mod terminal;
+#[cfg(test)]
+mod test_support;
+mod tui;
+mod variables;
+mod warnings;
+mod watch_status;
 
+use crate::cancel::CancelToken;
 use crate::error::AppError;
-use clap::{Parser, Subcommand};
+// The Markdown to HTML conversion, which also builds for the browser.
+use pdfbuilder_core::{acronyms, authorities, chapter, continued, css_lint, details, directives, environments, numbering, render, sidebar, tabs, typography};
+use pdfbuilder_core::config::{HtmlConfig, LimitsConfig, OutputFormat, RenderConfig};
+use clap::{Parser, Subcommand, ValueEnum};
 use colored::*;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-use std::sync::mpsc::channel;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
-use notify::{Config as NotifyConfig, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{Config as NotifyConfig, Event, EventHandler, RecommendedWatcher, RecursiveMode, Watcher};
 
 
 /// A simple and fast PDF builder from Markdown.
@@ -26,6 +90,67 @@ enum Commands {
         /// Enables "watch" mode to automatically recompile on changes.
         #[arg(long)]
         watch: bool,
+        /// In watch mode, keeps the output of earlier builds instead of clearing the screen.
+        #[arg(long, requires = "watch")]
+        no_clear: bool,
+        /// In watch mode, shows a dashboard of the inputs, the build log and the diagnostics.
+        #[arg(long, requires = "watch")]
+        tui: bool,
+        /// Output format: a PDF (with its HTML source), the HTML only, or a single self-contained HTML file.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Pdf)]
+        format: OutputFormat,
+        /// Builds a translation listed in 'translations', or every language with 'all'.
+        #[arg(long)]
+        lang: Option<String>,
+        /// Overrides 'solutions' from 'config.yaml', e.g. 'omit' for a student edition.
+        #[arg(long, value_enum)]
+        solutions: Option<environments::SolutionMode>,
+        /// Review copy: DRAFT watermark, numbered paragraphs and highlighted TODO/FIXME notes.
+        #[arg(long)]
+        draft: bool,
+        /// Overrides 'output.dir' from 'config.yaml', e.g. a workspace-specific directory in CI.
+        #[arg(long)]
+        out_dir: Option<String>,
+        /// Also renders the PDF pages to PNG images in 'thumbnails/' of the output directory.
+        #[arg(long)]
+        thumbnails: bool,
+        /// Writes something else than the document: 'model' writes its structure as JSON.
+        #[arg(long, value_enum, conflicts_with = "watch")]
+        emit: Option<Emit>,
+        /// Builds every book of the series ('series.yaml') of the current directory.
+        #[arg(long, conflicts_with = "watch")]
+        all: bool,
+    },
+    /// Checks the document sources without building them.
+    Check {
+        /// Reports misspelled words using the dictionary of the document language.
+        #[arg(long)]
+        spelling: bool,
+    },
+    /// Builds a redline PDF marking the changes since a previous version.
+    Diff {
+        /// A git reference (tag, branch, commit) or a directory containing the previous version.
+        #[arg(long)]
+        against: String,
+    },
+    /// Uploads the built document to the 'publish' destinations (S3, SFTP, WebDAV).
+    Publish {
+        /// Attaches the PDFs and their checksums to this existing GitHub release, e.g. 'v1.2'.
+        #[arg(long)]
+        github_release: Option<String>,
+    },
+    /// Writes images of the pages that differ between two PDFs, with the changes highlighted.
+    Compare {
+        old: PathBuf,
+        new: PathBuf,
+        /// Directory receiving the page images and 'summary.txt'.
+        #[arg(long, default_value = "diff")]
+        out: PathBuf,
+    },
+    /// Creates a PdfBuilder configuration from another documentation format.
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
     },
     /// Initializes a new project with the base files.
     Init {
@@ -36,6 +161,63 @@ enum Commands {
         #[arg(long)]
         language: Option<String>,
     },
+    /// Builds one document per record of a data file from a Markdown template (mail merge).
+    Render {
+        /// Markdown template using the fields of the records as '{{ field }}' variables.
+        template: PathBuf,
+        /// CSV file with a header row, or YAML or JSON list of records.
+        #[arg(long)]
+        data: PathBuf,
+        /// Name of the generated files, e.g. 'invoice-{{ number }}'. Defaults to the template name and the record number.
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Pdf)]
+        format: OutputFormat,
+        /// Directory receiving the generated files, instead of 'output.dir'.
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Builds the PDF and compares it with the committed snapshot in 'snapshots/'.
+    Test {
+        /// Records the current PDF as the snapshot instead of comparing.
+        #[arg(long)]
+        update: bool,
+        /// Also compares images of the pages, rendered with 'pdftoppm'.
+        #[arg(long)]
+        raster: bool,
+        /// Tests a translation listed in 'translations', or every language with 'all'.
+        #[arg(long)]
+        lang: Option<String>,
+    },
+    /// Runs an HTTP service converting Markdown to PDF, with the project settings if run in one.
+    Server {
+        /// Address to listen on; use 0.0.0.0 to accept connections from other machines.
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        /// Maximum number of browsers, and so of documents converted at the same time.
+        #[arg(long, default_value_t = 2)]
+        pool_size: usize,
+        /// Seconds after which an unused browser is closed.
+        #[arg(long, default_value_t = 300)]
+        idle_timeout: u64,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// Sections, blocks, assets and cross-references of the document, in `<output.dir>/<name>.model.json`.
+    Model,
+}
+
+#[derive(Subcommand, Debug)]
+enum ImportSource {
+    /// Reads 'book.toml' and 'SUMMARY.md' of an mdBook project and writes an equivalent 'config.yaml' next to them.
+    Mdbook {
+        /// Directory containing 'book.toml'.
+        path: PathBuf,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -70,24 +252,252 @@ pub struct Config {
     pub title: String,
     pub author: String,
     pub language: String,
+    /// Document version used in output file names. Defaults to the latest git tag.
+    pub version: Option<String>,
     pub theme: String,
     pub syntax_theme: String,
+    /// Main Markdown file. Not needed when the document is described by `book`.
+    #[serde(default)]
     pub source: String,
     pub custom_css: Option<String>,
     pub output: OutputConfig,
     #[serde(default)]
     pub margins: MarginsConfig,
+    pub book: Option<book::BookConfig>,
+    #[serde(default)]
+    pub boilerplate: boilerplate::BoilerplateConfig,
+    /// Pages appended to the document (about the author, colophon...), each on its own page.
+    #[serde(default)]
+    pub back_matter: Vec<String>,
+    /// Numbers the headings of the `back_matter` pages.
+    #[serde(default)]
+    pub back_matter_numbered: bool,
+    /// Dedication, in Markdown, on its own page before the table of contents.
+    pub dedication: Option<String>,
+    /// Translated sources by language, e.g. `fr: fr/main.md`. In book mode, the directory
+    /// holding the translated book files. Each translation is built to `<filename>-<lang>`.
+    #[serde(default)]
+    pub translations: BTreeMap<String, String>,
+    #[serde(default)]
+    pub html: HtmlConfig,
+    #[serde(default)]
+    pub assets: assets::AssetsConfig,
+    /// Layout of `--format txt` exports.
+    #[serde(default)]
+    pub text: plaintext::TextConfig,
+    /// How `--format email-html` embeds images, and the subject of `.eml` messages.
+    #[serde(default)]
+    pub email: email::EmailConfig,
+    #[serde(default)]
+    pub typography: typography::TypographyConfig,
+    /// How `<details>` and `:::details` blocks are printed: `expanded` (default), `collapsed` or `omit`.
+    #[serde(default)]
+    pub details: details::DetailsMode,
+    /// How `:::tabs` groups are rendered in HTML exports and in the PDF.
+    #[serde(default)]
+    pub tabs: tabs::TabsConfig,
+    /// Where `:::solution` blocks go: `inline` (default), `appendix` or `omit`.
+    #[serde(default)]
+    pub solutions: environments::SolutionMode,
+    #[serde(default)]
+    pub diagrams: diagrams::DiagramsConfig,
+    /// Renders ` ```latex ` blocks with a local TeX installation; they stay code when unset.
+    #[serde(default)]
+    pub latex: Option<latex::LatexConfig>,
+    /// External commands rendering other fence languages to images, e.g. `lilypond`.
+    #[serde(default)]
+    pub renderers: BTreeMap<String, diagrams::RendererConfig>,
+    /// Acronyms marked as `+API+` in the sources and their definitions, e.g. `API: Application Programming Interface`.
+    #[serde(default)]
+    pub acronyms: BTreeMap<String, String>,
+    /// Additional numbered environments (`:::name` blocks) and their captions, e.g. `algorithm: Algorithm`.
+    #[serde(default)]
+    pub environments: BTreeMap<String, String>,
+    /// Numbers `##` to `####` headings (1, 1.1, 1.1.1). Chapters can opt out with `numbering: false`.
+    #[serde(default)]
+    pub numbering: bool,
+    /// Splits tables and code blocks at page breaks, repeating table headers under a "(continued)" caption.
+    #[serde(default)]
+    pub continued_markers: bool,
+    /// How `{>> comment <<}` notes are rendered: `margin` (default), `pdf` annotations or `hide`.
+    #[serde(default)]
+    pub comments: critic::CommentMode,
+    /// How `{++insert++}`, `{--delete--}` and `{~~old~>new~~}` changes are rendered: `show` (default), `accept` or `fail`.
+    #[serde(default)]
+    pub changes: critic::ChangeMode,
+    /// Builds review copies, as with `build --draft`.
+    #[serde(default)]
+    pub draft: bool,
+    /// Follows symbolic links among the sources and assets, as long as they point inside the
+    /// project. When `false`, any link is refused.
+    #[serde(default = "default_follow_symlinks")]
+    pub follow_symlinks: bool,
+    /// Allows `!include(https://...)`. Downloads are cached for a day in the build directory.
+    #[serde(default)]
+    pub allow_remote_includes: bool,
+    /// Keeps only an allowlist of elements and attributes in `!html` fragments.
+    #[serde(default = "default_sanitize_html_fragments")]
+    pub sanitize_html_fragments: bool,
+    /// Proxy and timeout of downloads, uploads and the pages loaded by the browser.
+    #[serde(default)]
+    pub network: network::NetworkConfig,
+    /// How the headless browser is started: executable, flags, proxy and profile directory.
+    #[serde(default)]
+    pub browser: browser::BrowserConfig,
+    /// Where `PdfBuilder publish` uploads the built files.
+    #[serde(default)]
+    pub publish: Vec<publish::PublishTarget>,
+    /// Hosts `!screenshot` may capture, and its default window size.
+    #[serde(default)]
+    pub screenshots: screenshot::ScreenshotsConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    #[serde(default)]
+    pub lint: LintConfig,
+    #[serde(default)]
+    pub thumbnails: ThumbnailsConfig,
+    #[serde(default)]
+    pub social_card: SocialCardConfig,
+    #[serde(default)]
+    pub watch: WatchConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct OutputConfig {
+    /// Output file name without extension. Supports the `{version}` and `{date}` placeholders
+    /// and the `{{ name }}` variables, e.g. `{{ build_number }}`.
     pub filename: String,
+    /// Text on the left of the page footer, with `{{ name }}` variables, e.g. `Printed {{ today }}`.
+    pub footer: Option<String>,
+    /// Directory where every generated PDF is also copied, without overwriting earlier builds.
+    pub archive_dir: Option<String>,
+    /// Directory receiving the HTML, the PDF, the copied assets and the cache. Defaults to `build`.
+    #[serde(default = "default_output_dir")]
+    pub dir: String,
+    /// Also writes `index.json` next to the PDF: the text of every page with its headings.
+    #[serde(default)]
+    pub search_index: bool,
+    /// Lays the pages out on printed sheets, e.g. `2x4 on A4` for labels or `booklet` for a
+    /// folded booklet.
+    #[serde(default)]
+    pub imposition: Option<imposition::Imposition>,
+    /// Also writes `<filename>-handout.pdf` with 2, 4 or 6 pages per sheet.
+    pub nup: Option<usize>,
+    /// Adds lines for notes next to the pages of the handout.
+    #[serde(default)]
+    pub nup_notes: bool,
+    /// Also writes `<file>.sha256` and `<file>.provenance.json` next to every generated file.
+    #[serde(default)]
+    pub provenance: bool,
+    /// Reuses the PDF of the last build, without starting the browser, when the page, its
+    /// assets and the settings are unchanged.
+    #[serde(default)]
+    pub build_cache: bool,
+}
+
+fn default_follow_symlinks() -> bool {
+    true
+}
+
+fn default_sanitize_html_fragments() -> bool {
+    true
+}
+
+fn default_output_dir() -> String {
+    "build".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LintConfig {
+    /// Rule IDs turned off for the whole project, e.g. `["bare-url"]`.
+    #[serde(default)]
+    pub disable: Vec<String>,
+    #[serde(default = "default_max_code_line_length")]
+    pub max_code_line_length: usize,
+    /// External prose linters (Vale, write-good...) run on the expanded Markdown.
+    #[serde(default)]
+    pub external: Vec<ExternalLinterConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExternalLinterConfig {
+    pub name: String,
+    /// Command line to run; `{file}` is replaced by the expanded Markdown file (appended if absent).
+    pub command: String,
+    /// Regex with `line`, `message` and optional `rule` named groups used to parse the output.
+    pub pattern: Option<String>,
+}
+
+fn default_max_code_line_length() -> usize {
+    90
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            disable: Vec::new(),
+            max_code_line_length: default_max_code_line_length(),
+            external: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GitConfig {
+    /// Prints the commit, tag and date of the build in the page footer.
+    #[serde(default)]
+    pub footer_stamp: bool,
+}
+
+/// Open Graph / Twitter preview image written next to the PDF as `<filename>-card.png`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SocialCardConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Cover art shown next to the title, e.g. `assets/cover.png`.
+    pub cover: Option<String>,
+    /// HTML template of the card, with the `{{ title }}`, `{{ author }}`, `{{ description }}`,
+    /// `{{ cover }}` and `{{ css }}` variables.
+    pub template: Option<String>,
+}
+
+/// PNG images of the PDF pages written to `<output.dir>/thumbnails`, e.g. for a cover preview.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ThumbnailsConfig {
+    /// Also enabled for one build with `build --thumbnails`.
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_thumbnail_dpi")]
+    pub dpi: u32,
+    /// Renders the first page only, instead of every page.
+    #[serde(default)]
+    pub first_page_only: bool,
+}
+
+fn default_thumbnail_dpi() -> u32 {
+    72
+}
+
+impl Default for ThumbnailsConfig {
+    fn default() -> Self {
+        Self { enabled: false, dpi: default_thumbnail_dpi(), first_page_only: false }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WatchConfig {
+    /// When a rebuild fails, also replaces the PDF with a one-page PDF showing the error.
+    #[serde(default)]
+    pub error_pdf: bool,
 }
 
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
-        eprintln!("{} {}", "Error:".red().bold(), e.to_string().red());
+        // Code, message and hint of the error, e.g. `pdfbuilder::source::not_found`.
+        eprintln!("{:?}", miette::Report::new(e));
         std::process::exit(1);
     }
 }
@@ -96,76 +506,334 @@ async fn run() -> Result<(), AppError> {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Build { watch } => {
-            let config_str = std::fs::read_to_string("config.yaml").map_err(AppError::ConfigReadError)?;
-            let config: Config = serde_yaml::from_str(&config_str)?;
+        Commands::Build { watch, no_clear, tui, format, lang, solutions, draft, out_dir, thumbnails, emit, all } => {
+            // The projects to build, with the output directory given by --out-dir: one
+            // subdirectory per book of a series.
+            let (invocation_dir, projects) = if *all {
+                let invocation_dir = std::env::current_dir()?;
+                let series_dir = if invocation_dir.join(series::SERIES_FILE).is_file() {
+                    invocation_dir.clone()
+                } else {
+                    series::find_series_dir(&invocation_dir).ok_or_else(|| AppError::BuildError(format!("'--all' needs a '{}' in the current directory or above", series::SERIES_FILE)))?
+                };
+                let projects = series::members(&series_dir)?.into_iter()
+                    .map(|member| {
+                        let out_dir = out_dir.as_ref().map(|dir| invocation_dir.join(dir).join(member.file_name().unwrap_or_default()));
+                        (member, out_dir)
+                    })
+                    .collect();
+                (invocation_dir, projects)
+            } else {
+                let invocation_dir = enter_project_root()?;
+                let out_dir = out_dir.as_ref().map(|dir| invocation_dir.join(dir));
+                (invocation_dir.clone(), vec![(std::env::current_dir()?, out_dir)])
+            };
+
+            let mut configs = Vec::new();
+            for (project_dir, out_dir) in projects {
+                if *all {
+                    println!("{} {}", "Building book".blue(), project_dir.strip_prefix(&invocation_dir).unwrap_or(&project_dir).display().to_string().yellow());
+                    std::env::set_current_dir(&project_dir)?;
+                }
+                let mut config = load_config()?;
+                if let Some(solutions) = solutions {
+                    config.solutions = *solutions;
+                }
+                config.draft |= *draft;
+                config.thumbnails.enabled |= *thumbnails;
+                if let Some(out_dir) = out_dir {
+                    config.output.dir = out_dir.display().to_string();
+                }
+                configs = i18n::language_configs(&config, lang.as_deref())?;
+                check_sources(&configs)?;
 
-            if !Path::new(&config.source).exists() {
-                return Err(AppError::SourceNotFound(config.source.clone()));
-            }
+                if *emit == Some(Emit::Model) {
+                    for config in &configs {
+                        model::write_model(config)?;
+                    }
+                    continue;
+                }
 
-            // First build
-            builder::run_build(&config).await?;
+                // First build
+                for config in &configs {
+                    builder::run_build(config, *format, &CancelToken::default()).await?;
+                }
+            }
 
-            if *watch {
+            if *watch && *tui {
+                tui::run_dashboard(configs, *format, invocation_dir)?;
+            } else if *watch {
                 println!("\n{}", "--------------------------------------------------".purple());
                 println!("{}", "Watch mode enabled. Waiting for changes...".purple());
                 println!("{}", "Press Ctrl+C to exit.".purple());
                 println!("{}", "--------------------------------------------------".purple());
-                run_watch_mode(config).await?;
+                run_watch_mode(configs, *format, !*no_clear).await?;
+            }
+        }
+        Commands::Check { spelling } => {
+            enter_project_root()?;
+            let config = load_config()?;
+            check_sources(std::slice::from_ref(&config))?;
+            check::run_check(&config, &check::CheckOptions { spelling: *spelling })?;
+        }
+        Commands::Diff { against } => {
+            let invocation_dir = enter_project_root()?;
+            let config = load_config()?;
+            check_sources(std::slice::from_ref(&config))?;
+            // A directory given relative to where the command was run, or a git reference.
+            let against_dir = invocation_dir.join(against);
+            let against = if against_dir.is_dir() { against_dir.display().to_string() } else { against.clone() };
+            diff::run_diff(&config, &against).await?;
+        }
+        Commands::Publish { github_release } => {
+            enter_project_root()?;
+            let config = load_config()?;
+            check_sources(&i18n::language_configs(&config, Some(i18n::ALL_LANGUAGES))?)?;
+            if let Some(tag) = github_release {
+                publish::publish_github_release(&config, tag)?;
+            }
+            // With a release, the 'publish' destinations are optional.
+            if github_release.is_none() || !config.publish.is_empty() {
+                publish::run_publish(&config)?;
             }
         }
+        Commands::Compare { old, new, out } => compare::run_compare(old, new, out)?,
+        Commands::Import { source } => match source {
+            ImportSource::Mdbook { path } => mdbook::import_mdbook(path)?,
+        },
         Commands::Init { title, author, language } => {
             if Path::new("config.yaml").exists() {
                 return Err(AppError::ProjectAlreadyExists);
             }
             builder::init_project(title.clone(), author.clone(), language.clone())?;
         }
+        Commands::Render { template, data, name, format, out_dir } => {
+            let invocation_dir = enter_project_root()?;
+            let mut config = load_config()?;
+            check_sources(std::slice::from_ref(&config))?;
+            if let Some(out_dir) = out_dir {
+                config.output.dir = invocation_dir.join(out_dir).display().to_string();
+            }
+            // Paths are given relative to where the command was run.
+            let options = merge::MergeOptions { name: name.clone(), format: *format };
+            merge::run_merge(&config, &invocation_dir.join(template), &invocation_dir.join(data), &options).await?;
+        }
+        Commands::Test { update, raster, lang } => {
+            enter_project_root()?;
+            let config = load_config()?;
+            let options = snapshot::TestOptions { update: *update, raster: *raster };
+            let configs = i18n::language_configs(&config, lang.as_deref())?;
+            check_sources(&configs)?;
+            for config in configs {
+                snapshot::run_test(&config, &options).await?;
+            }
+        }
+        Commands::Server { host, port, pool_size, idle_timeout } => {
+            let invocation_dir = enter_project_root()?;
+            let config = if find_project_root(&invocation_dir).is_some() { Some(load_config()?) } else { None };
+            let pool = browser_pool::PoolOptions { size: *pool_size, idle_timeout: Duration::from_secs(*idle_timeout) };
+            server::run_server(config, host, *port, pool).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves to the project root, the closest directory containing 'config.yaml' from the current
+/// one upwards (like cargo with 'Cargo.toml'), so commands work from any subdirectory.
+/// Returns the directory the command was run from, to resolve paths given on the command line.
+fn enter_project_root() -> Result<PathBuf, AppError> {
+    let invocation_dir = std::env::current_dir()?;
+    if let Some(root) = find_project_root(&invocation_dir) {
+        if root != invocation_dir {
+            println!("{} {}", "Using project at".blue(), root.display().to_string().yellow());
+            std::env::set_current_dir(&root)?;
+        }
     }
+    Ok(invocation_dir)
+}
+
+fn find_project_root(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find(|dir| dir.join("config.yaml").is_file()).map(Path::to_path_buf)
+}
+
+fn load_config() -> Result<Config, AppError> {
+    let config_str = std::fs::read_to_string("config.yaml").map_err(AppError::ConfigReadError)?;
+    // Books of a series inherit the defaults of its 'series.yaml'.
+    let config: Config = match series::find_series_dir(&std::env::current_dir()?) {
+        Some(series_dir) => {
+            let defaults = series::read_series(&series_dir)?.defaults;
+            serde_yaml::from_value(series::with_defaults(serde_yaml::from_str(&config_str)?, &defaults))?
+        }
+        None => serde_yaml::from_str(&config_str)?,
+    };
+    network::configure(&config.network);
+    Ok(config)
+}
 
+/// Fails on the first missing source file of the documents about to be built, before any of
+/// them is. Translations that are not built are not checked.
+fn check_sources(configs: &[Config]) -> Result<(), AppError> {
+    for config in configs {
+        if let Some(missing) = config.source_files().into_iter().find(|source| !Path::new(source).exists()) {
+            return Err(AppError::SourceNotFound(missing));
+        }
+    }
     Ok(())
 }
 
-async fn run_watch_mode(config: Config) -> Result<(), AppError> {
-    let (tx, rx) = channel();
+impl Config {
+    /// The Markdown files the document is built from: `source`, or every file of `book`.
+    pub fn source_files(&self) -> Vec<String> {
+        match &self.book {
+            Some(book) => book.files().into_iter().map(str::to_string).collect(),
+            None => vec![self.source.clone()],
+        }
+    }
+
+    /// The directory of the generated files (`output.dir`).
+    pub fn build_dir(&self) -> PathBuf {
+        PathBuf::from(&self.output.dir)
+    }
+
+    /// The settings of the Markdown to HTML conversion.
+    pub fn render_config(&self) -> RenderConfig {
+        RenderConfig {
+            title: self.title.clone(),
+            author: self.author.clone(),
+            language: self.language.clone(),
+            syntax_theme: self.syntax_theme.clone(),
+            html: self.html.clone(),
+            typography: self.typography.clone(),
+            details: self.details,
+            tabs: self.tabs,
+            solutions: self.solutions,
+            environments: self.environments.clone(),
+            numbering: self.numbering,
+            continued_markers: self.continued_markers,
+            comments: self.comments,
+            changes: self.changes,
+            draft: self.draft,
+        }
+    }
+}
 
+/// Watches the inputs of the documents ('config.yaml', the sources, the custom CSS, the assets
+/// and the themes), passing file events to `handler` until the watcher is dropped.
+fn watch_inputs(configs: &[Config], handler: impl EventHandler) -> Result<RecommendedWatcher, AppError> {
     let watcher_config = NotifyConfig::default().with_poll_interval(Duration::from_secs(2));
-    let mut watcher: RecommendedWatcher = Watcher::new(tx, watcher_config)
+    let mut watcher: RecommendedWatcher = Watcher::new(handler, watcher_config)
         .map_err(|e| AppError::BuildError(format!("Could not create watcher: {e}")))?;
 
     // Define paths to watch
-    watcher.watch(Path::new("config.yaml"), RecursiveMode::NonRecursive).unwrap();
-    if let Some(parent) = Path::new(&config.source).parent() {
-         if parent.to_str() != Some("") {
-            watcher.watch(parent, RecursiveMode::Recursive).unwrap();
-         } else {
-            watcher.watch(Path::new("."), RecursiveMode::NonRecursive).unwrap();
-         }
+    let mut watch = |path: &Path, mode: RecursiveMode| {
+        watcher.watch(path, mode).map_err(|e| AppError::BuildError(format!("Could not watch '{}': {e}", path.display())))
+    };
+    watch(Path::new("config.yaml"), RecursiveMode::NonRecursive)?;
+    let config = &configs[0];
+    let sources: Vec<String> = configs.iter().flat_map(Config::source_files).collect();
+    for source in &sources {
+        if let Some(parent) = Path::new(source).parent() {
+            if !parent.as_os_str().is_empty() {
+                watch(parent, RecursiveMode::Recursive)?;
+            } else {
+                watch(Path::new("."), RecursiveMode::NonRecursive)?;
+            }
+        }
     }
     if let Some(css_path) = &config.custom_css {
         if !css_path.is_empty() && Path::new(css_path).exists() {
-            watcher.watch(Path::new(css_path), RecursiveMode::NonRecursive).unwrap();
+            watch(Path::new(css_path), RecursiveMode::NonRecursive)?;
         }
     }
     if Path::new("assets").exists() {
-        watcher.watch(Path::new("assets"), RecursiveMode::Recursive).unwrap();
+        watch(Path::new("assets"), RecursiveMode::Recursive)?;
     }
-     if Path::new("themes").exists() {
-        watcher.watch(Path::new("themes"), RecursiveMode::Recursive).unwrap();
+    if Path::new("themes").exists() {
+        watch(Path::new("themes"), RecursiveMode::Recursive)?;
     }
+    Ok(watcher)
+}
 
+async fn run_watch_mode(configs: Vec<Config>, format: OutputFormat, clear: bool) -> Result<(), AppError> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let _watcher = watch_inputs(&configs, move |res| { tx.send(res).ok(); })?;
 
-    for res in rx {
+    let project_root = std::env::current_dir()?;
+    // The last finished build, shared with the build task, for the status line.
+    let last_build: Arc<Mutex<Option<watch_status::LastBuild>>> = Arc::default();
+    // The running build, cancelled when a newer change arrives so only the latest content is rendered.
+    let mut current_build: Option<(CancelToken, tokio::task::JoinHandle<()>)> = None;
+    while let Some(res) = rx.recv().await {
         match res {
-            Ok(Event { kind, .. }) => {
-                if kind.is_modify() || kind.is_create() || kind.is_remove() {
-                    clearscreen::clear().expect("failed to clear screen");
+            Ok(event) => {
+                if event.kind.is_modify() || event.kind.is_create() || event.kind.is_remove() {
+                    let mut changed = Vec::new();
+                    let mut record = |event: Event| {
+                        for path in event.paths {
+                            let path = path.strip_prefix(&project_root).unwrap_or(&path).display().to_string();
+                            if !changed.contains(&path) {
+                                changed.push(path);
+                            }
+                        }
+                    };
+                    record(event);
+                    // A save often emits several events: wait for them to settle.
+                    while let Ok(Some(res)) = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await {
+                        if let Ok(event) = res {
+                            record(event);
+                        }
+                    }
+
+                    if let Some((cancel, build)) = current_build.take() {
+                        if !build.is_finished() {
+                            println!("{}", watch_status::status_line(None, &changed).yellow());
+                            println!("{}", "Change detected, cancelling the current build...".yellow());
+                            cancel.cancel();
+                            build.await.ok();
+                        }
+                    }
+
+                    if clear {
+                        // Not every terminal can be cleared (e.g. output redirected to a file).
+                        clearscreen::clear().ok();
+                        // The outcome of the previous build stays visible above the new one.
+                        let status = watch_status::status_line(last_build.lock().unwrap_or_else(PoisonError::into_inner).as_ref(), &[]);
+                        if !status.is_empty() {
+                            println!("{}", status.purple());
+                        }
+                    }
                     println!("{}", "--------------------------------------------------".cyan());
-                    println!("{}", "Change detected, recompiling...".cyan());
+                    println!("{} {}", "Change detected, recompiling:".cyan(), changed.join(", ").yellow());
                     println!("{}", "--------------------------------------------------".cyan());
-                    if let Err(e) = builder::run_build(&config).await {
-                        eprintln!("{} {}", "Error during recompilation:".red().bold(), e.to_string().red());
-                    }
+                    let cancel = CancelToken::default();
+                    let (configs, token, last_build) = (configs.clone(), cancel.clone(), last_build.clone());
+                    let build = tokio::spawn(async move {
+                        let started = std::time::Instant::now();
+                        let mut stats = Vec::new();
+                        let mut failure = None;
+                        for config in &configs {
+                            match builder::run_build(config, format, &token).await {
+                                Err(AppError::BuildCancelled) => return,
+                                Err(e) => {
+                                    if let Err(e) = error_page::write_error_outputs(config, format, &e) {
+                                        eprintln!("{} {}", "Could not write the error page:".red().bold(), e.to_string().red());
+                                    }
+                                    failure.get_or_insert_with(|| e.to_string().lines().next().unwrap_or_default().to_string());
+                                    eprintln!("{}\n{:?}", "Error during recompilation:".red().bold(), miette::Report::new(e));
+                                }
+                                Ok(build_stats) => stats.push(build_stats),
+                            }
+                        }
+                        let last = watch_status::LastBuild {
+                            finished: chrono::Local::now(),
+                            duration: started.elapsed(),
+                            outcome: failure.map_or(watch_status::Outcome::Built(stats), watch_status::Outcome::Failed),
+                        };
+                        println!("\n{}", watch_status::status_line(Some(&last), &[]).purple());
+                        *last_build.lock().unwrap_or_else(PoisonError::into_inner) = Some(last);
+                    });
+                    current_build = Some((cancel, build));
                 }
             }
             Err(e) => eprintln!("{} {}", "Watcher error:".red().bold(), e.to_string().red()),
@@ -227,6 +895,17 @@ output:
         }
     }
 
+    #[test]
+    fn test_find_project_root() {
+        let project = TestProject::new("find_root");
+        project.setup_build_success();
+        let chapters = project.root.join("chapters").join("part1");
+        fs::create_dir_all(&chapters).unwrap();
+
+        assert_eq!(find_project_root(&chapters), Some(project.root.clone()));
+        assert_eq!(find_project_root(&project.root), Some(project.root.clone()));
+    }
+
     #[tokio::test]
     async fn test_cli_init_command() {
         let project = TestProject::new("cli_init");
@@ -0,0 +1,344 @@
+use crate::builder;
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::render;
+use crate::variables;
+use crate::{Config, OutputFormat};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::fs;
+use std::path::PathBuf;
+
+/// Header fields of the man page (`.TH`).
+pub struct ManPage<'a> {
+    pub name: &'a str,
+    pub section: &'a str,
+    pub date: &'a str,
+    pub version: &'a str,
+    pub title: &'a str,
+}
+
+/// Writes the document as a section 1 man page, `<output.dir>/<name>.1`, and returns its path.
+/// The date is the one of the last commit, or today.
+pub fn write_man_page(config: &Config, markdown: &str, git_info: &GitInfo) -> Result<PathBuf, AppError> {
+    let markdown = render::apply_text_passes(&config.render_config(), markdown, OutputFormat::Man)?;
+    let name = builder::output_filename(config, git_info);
+    let date = git_info.date.clone().unwrap_or_else(|| chrono::Local::now().format("%Y-%m-%d").to_string());
+    let version = variables::document_version(config, git_info);
+    let page = ManPage { name: &name, section: "1", date: &date, version: &version, title: &config.title };
+    fs::create_dir_all(config.build_dir())?;
+    let path = config.build_dir().join(format!("{name}.1"));
+    fs::write(&path, to_roff(&markdown, &page))?;
+    Ok(path)
+}
+
+/// Converts Markdown into a roff man page. A document with a single level-1 heading at the top
+/// uses it as its name and its level-2 headings as sections (`NAME`, `SYNOPSIS`...); otherwise
+/// level-1 headings are sections and level-2 ones subsections. List items starting with an
+/// option in a code span (`` - `-v`, `--verbose`: Print more ``) become tagged paragraphs, as
+/// in the OPTIONS section of man pages. Inline HTML is left out.
+pub fn to_roff(markdown: &str, page: &ManPage) -> String {
+    let events: Vec<Event> = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_FOOTNOTES).collect();
+    let h1_count = events.iter().filter(|event| matches!(event, Event::Start(Tag::Heading { level: HeadingLevel::H1, .. }))).count();
+    let titled = h1_count == 1 && matches!(events.first(), Some(Event::Start(Tag::Heading { level: HeadingLevel::H1, .. })));
+
+    let mut writer = RoffWriter::default();
+    let mut i = 0;
+    // The text of the heading being read, with its level after the title shift.
+    let mut heading: Option<(usize, String)> = None;
+    // One entry per open list: the number of its next item, for ordered lists.
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut links: Vec<String> = Vec::new();
+    let mut table_row: Vec<String> = Vec::new();
+    let mut table_rows: Vec<Vec<String>> = Vec::new();
+    let mut in_table = false;
+    // The first paragraph of an item continues the `.IP` line.
+    let mut item_started = false;
+    let mut strip_separator = false;
+
+    while i < events.len() {
+        let event = &events[i];
+        i += 1;
+        if let Some((_, text)) = heading.as_mut() {
+            match event {
+                Event::Text(t) | Event::Code(t) => text.push_str(t),
+                Event::End(TagEnd::Heading(_)) => {
+                    let (level, text) = heading.take().unwrap();
+                    match level {
+                        0 => {}
+                        1 => writer.request(&format!(".SH \"{}\"", escape(&text.to_uppercase()).replace('"', "\\(dq"))),
+                        2 => writer.request(&format!(".SS \"{}\"", escape(&text).replace('"', "\\(dq"))),
+                        _ => {
+                            writer.request(".PP");
+                            writer.text(&format!("\\fB{}\\fR", escape(&text)));
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if in_table {
+            match event {
+                Event::Text(t) | Event::Code(t) => table_row.last_mut().unwrap().push_str(t),
+                Event::Start(Tag::TableCell) => table_row.push(String::new()),
+                Event::End(TagEnd::TableHead) | Event::End(TagEnd::TableRow) => table_rows.push(std::mem::take(&mut table_row)),
+                Event::End(TagEnd::Table) => {
+                    in_table = false;
+                    writer.table(&std::mem::take(&mut table_rows));
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let level = *level as usize;
+                heading = Some((if titled { level - 1 } else { level }, String::new()));
+            }
+            Event::Start(Tag::Paragraph) => {
+                let continues_item = std::mem::take(&mut item_started);
+                if !continues_item {
+                    writer.request(if lists.is_empty() { ".PP" } else { ".IP" });
+                }
+            }
+            Event::End(TagEnd::Paragraph) => writer.end_line(),
+            Event::Start(Tag::CodeBlock(_)) => {
+                let continues_item = std::mem::take(&mut item_started);
+                if !continues_item {
+                    writer.request(if lists.is_empty() { ".PP" } else { ".IP" });
+                }
+                writer.request(".RS 4");
+                writer.request(".nf");
+                writer.literal = true;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                writer.literal = false;
+                writer.end_line();
+                writer.request(".fi");
+                writer.request(".RE");
+            }
+            Event::Start(Tag::BlockQuote(_)) => writer.request(".RS 4"),
+            Event::End(TagEnd::BlockQuote(_)) => writer.request(".RE"),
+            Event::Start(Tag::List(start)) => {
+                if !lists.is_empty() {
+                    writer.request(".RS 4");
+                }
+                lists.push(*start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if !lists.is_empty() {
+                    writer.request(".RE");
+                }
+            }
+            Event::Start(Tag::Item) => {
+                // `- `-v`, `--verbose`: text`, possibly in a paragraph of a loose list.
+                let first = if matches!(events.get(i), Some(Event::Start(Tag::Paragraph))) { i + 1 } else { i };
+                let mut option = Vec::new();
+                let mut next = first;
+                while let Some(Event::Code(code)) = events.get(next) {
+                    if option.is_empty() && !code.starts_with('-') {
+                        break;
+                    }
+                    option.push(format!("\\fB{}\\fR", escape(code)));
+                    next += 1;
+                    match events.get(next) {
+                        Some(Event::Text(t)) if t.trim() == "," && matches!(events.get(next + 1), Some(Event::Code(_))) => next += 1,
+                        _ => break,
+                    }
+                }
+                if !option.is_empty() {
+                    writer.request(".TP");
+                    writer.text(&option.join(", "));
+                    writer.end_line();
+                    // The paragraph, if any, is already open.
+                    i = next;
+                    strip_separator = true;
+                    item_started = false;
+                } else {
+                    let marker = match lists.last_mut() {
+                        Some(Some(number)) => {
+                            *number += 1;
+                            format!(".IP \"{}.\" 4", *number - 1)
+                        }
+                        _ => ".IP \\(bu 2".to_string(),
+                    };
+                    writer.request(&marker);
+                    item_started = true;
+                }
+            }
+            Event::End(TagEnd::Item) => {
+                item_started = false;
+                writer.end_line();
+            }
+            Event::Start(Tag::Emphasis) => writer.font("I"),
+            Event::Start(Tag::Strong) => writer.font("B"),
+            Event::End(TagEnd::Emphasis) | Event::End(TagEnd::Strong) => writer.end_font(),
+            Event::Start(Tag::Link { dest_url, .. }) => links.push(dest_url.to_string()),
+            Event::End(TagEnd::Link) => {
+                if let Some(url) = links.pop().filter(|url| !url.starts_with('#')) {
+                    writer.text(&format!(" <{}>", escape(&url)));
+                }
+            }
+            Event::Start(Tag::Table(_)) => in_table = true,
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                writer.request(".PP");
+                writer.text(&format!("[{}] ", escape(label)));
+                item_started = true;
+            }
+            Event::Text(text) => {
+                let text = if std::mem::take(&mut strip_separator) {
+                    text.trim_start().trim_start_matches([':', '-', '\u{2013}', '\u{2014}']).trim_start()
+                } else {
+                    text
+                };
+                if writer.literal {
+                    writer.literal_text(text);
+                } else {
+                    writer.text(&escape(text));
+                }
+            }
+            Event::Code(code) => writer.text(&format!("\\fB{}\\f{}", escape(code), writer.current_font())),
+            Event::SoftBreak => writer.text("\n"),
+            Event::HardBreak => {
+                writer.end_line();
+                writer.request(".br");
+            }
+            Event::Rule => writer.request(".sp"),
+            Event::FootnoteReference(label) => writer.text(&format!("[{}]", escape(label))),
+            Event::TaskListMarker(done) => writer.text(if *done { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    let mut roff = String::new();
+    if writer.has_tables {
+        roff.push_str("'\\\" t\n");
+    }
+    roff.push_str(&format!(
+        ".TH \"{}\" \"{}\" \"{}\" \"{}\" \"{}\"\n",
+        escape(&page.name.to_uppercase()),
+        escape(page.section),
+        escape(page.date),
+        escape(page.version),
+        escape(page.title)
+    ));
+    roff.push_str(writer.out.trim_start_matches('\n'));
+    if !roff.ends_with('\n') {
+        roff.push('\n');
+    }
+    roff
+}
+
+#[derive(Default)]
+struct RoffWriter {
+    out: String,
+    fonts: Vec<&'static str>,
+    literal: bool,
+    has_tables: bool,
+}
+
+impl RoffWriter {
+    fn end_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    /// A request (`.PP`, `.SH`...) on its own line.
+    fn request(&mut self, request: &str) {
+        self.end_line();
+        self.out.push_str(request);
+        self.out.push('\n');
+    }
+
+    /// Escaped text; lines starting with a control character are protected with `\&`.
+    fn text(&mut self, text: &str) {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            if (self.out.is_empty() || self.out.ends_with('\n')) && (part.starts_with('.') || part.starts_with('\'')) {
+                self.out.push_str("\\&");
+            }
+            self.out.push_str(if self.out.ends_with('\n') { part.trim_start() } else { part });
+        }
+    }
+
+    /// Text of a code block, kept line for line.
+    fn literal_text(&mut self, text: &str) {
+        for line in text.split_inclusive('\n') {
+            if line.starts_with('.') || line.starts_with('\'') {
+                self.out.push_str("\\&");
+            }
+            self.out.push_str(&escape(line));
+        }
+    }
+
+    fn font(&mut self, font: &'static str) {
+        self.fonts.push(font);
+        self.out.push_str(&format!("\\f{font}"));
+    }
+
+    fn end_font(&mut self) {
+        self.fonts.pop();
+        let font = self.current_font();
+        self.out.push_str(&format!("\\f{font}"));
+    }
+
+    fn current_font(&self) -> &'static str {
+        self.fonts.last().copied().unwrap_or("R")
+    }
+
+    /// A table for the `tbl` preprocessor, with a bold header row.
+    fn table(&mut self, rows: &[Vec<String>]) {
+        let Some(columns) = rows.iter().map(Vec::len).max().filter(|columns| *columns > 0) else {
+            return;
+        };
+        self.has_tables = true;
+        self.request(".TS");
+        self.request("allbox tab(|);");
+        self.request(&format!("{}\n{}.", vec!["lb"; columns].join(" "), vec!["l"; columns].join(" ")));
+        for row in rows {
+            let cells: Vec<String> = row.iter().map(|cell| escape(cell.trim()).replace('|', "\\(ba")).collect();
+            self.text(&cells.join("|"));
+            self.end_line();
+        }
+        self.request(".TE");
+    }
+}
+
+/// Escapes backslashes and hyphens, so options such as `--verbose` can be copied from the page.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: ManPage = ManPage { name: "mytool", section: "1", date: "2024-05-01", version: "1.2", title: "User Commands" };
+
+    #[test]
+    fn test_to_roff() {
+        let markdown = "# mytool\n\n## Name\n\nmytool - do *things* with `files`\n\n## Options\n\n\
+            - `-v`, `--verbose`: Print **more**.\n- `--color`\n\n## Examples\n\n```sh\n.hidden --all\n```\n\n\
+            1. First\n2. See [the site](https://example.com).\n";
+        assert_eq!(
+            to_roff(markdown, &PAGE),
+            ".TH \"MYTOOL\" \"1\" \"2024\\-05\\-01\" \"1.2\" \"User Commands\"\n\
+             .SH \"NAME\"\n.PP\nmytool \\- do \\fIthings\\fR with \\fBfiles\\fR\n\
+             .SH \"OPTIONS\"\n.TP\n\\fB\\-v\\fR, \\fB\\-\\-verbose\\fR\nPrint \\fBmore\\fR.\n.TP\n\\fB\\-\\-color\\fR\n\
+             .SH \"EXAMPLES\"\n.PP\n.RS 4\n.nf\n\\&.hidden \\-\\-all\n.fi\n.RE\n\
+             .IP \"1.\" 4\nFirst\n.IP \"2.\" 4\nSee the site <https://example.com>.\n"
+        );
+    }
+
+    #[test]
+    fn test_to_roff_sections_and_tables() {
+        let roff = to_roff("# Usage\n\nText\n\n## Files\n\n| Path | Use |\n|---|---|\n| a\\|b | x |\n\n# Bugs\n", &PAGE);
+        assert!(roff.starts_with("'\\\" t\n.TH"));
+        assert!(roff.contains(".SH \"USAGE\"\n.PP\nText\n.SS \"Files\"\n.TS\nallbox tab(|);\nlb lb\nl l.\nPath|Use\na\\(bab|x\n.TE\n.SH \"BUGS\"\n"));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::book::{BookConfig, ChapterConfig, PartConfig};
+use crate::error::AppError;
+use colored::*;
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Deserialize)]
+struct MdBookToml {
+    #[serde(default)]
+    book: MdBookSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MdBookSection {
+    title: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    language: Option<String>,
+    src: Option<String>,
+}
+
+/// Generates a `config.yaml` with a `book:` structure equivalent to an mdBook
+/// project's `book.toml` and `SUMMARY.md`, written next to `book.toml`.
+pub fn import_mdbook(book_dir: &Path) -> Result<(), AppError> {
+    let toml_path = book_dir.join("book.toml");
+    let toml_content = fs::read_to_string(&toml_path).map_err(|_| AppError::SourceNotFound(toml_path.display().to_string()))?;
+    let book_toml: MdBookToml = toml::from_str(&toml_content)
+        .map_err(|e| AppError::ImportError(format!("Invalid '{}': {}", toml_path.display(), e)))?;
+
+    let config_path = book_dir.join("config.yaml");
+    if config_path.exists() {
+        return Err(AppError::ProjectAlreadyExists);
+    }
+
+    let src_dir = book_toml.book.src.clone().unwrap_or_else(|| "src".to_string());
+    let summary_path = book_dir.join(&src_dir).join("SUMMARY.md");
+    let summary = fs::read_to_string(&summary_path).map_err(|_| AppError::SourceNotFound(summary_path.display().to_string()))?;
+    let book = parse_summary(&summary, &src_dir);
+
+    let title = book_toml.book.title.clone().unwrap_or_else(|| {
+        book_dir.canonicalize().ok().and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string())).unwrap_or_else(|| "My Book".to_string())
+    });
+    let author = if book_toml.book.authors.is_empty() { "Your Name".to_string() } else { book_toml.book.authors.join(", ") };
+    let language = book_toml.book.language.clone().unwrap_or_else(|| "en".to_string());
+
+    let book_yaml = serde_yaml::to_string(&book).map_err(|e| AppError::ImportError(e.to_string()))?;
+    let book_yaml: String = book_yaml.lines().map(|line| format!("  {line}\n")).collect();
+
+    let config_content = format!(r#"title: "{}"
+author: "{}"
+language: "{}"
+theme: "dark"
+syntax_theme: "InspiredGitHub"
+custom_css: ""
+output:
+  filename: "{}"
+numbering: true
+# Generated from '{}/SUMMARY.md'
+book:
+{}"#, title, author, language, title.to_lowercase().replace(' ', "-"), src_dir, book_yaml);
+    fs::write(&config_path, config_content)?;
+
+    println!("{} {}", "Imported mdBook project into".green(), config_path.display().to_string().yellow());
+    println!(
+        "{} {} {} {} {}",
+        "Found".cyan(),
+        book.parts.iter().map(|p| p.chapters.len()).sum::<usize>().to_string().yellow(),
+        "numbered chapter(s) in".cyan(),
+        book.parts.len().to_string().yellow(),
+        "part(s).".cyan()
+    );
+    println!("{} {}", "To build, run:".cyan(), "PdfBuilder build".yellow());
+    Ok(())
+}
+
+/// Converts an mdBook SUMMARY.md into a book structure: prefix chapters become front
+/// matter, `# Part` headings become parts, nested chapters are flattened in reading
+/// order, and suffix chapters after the numbered list become back matter.
+fn parse_summary(summary: &str, src_dir: &str) -> BookConfig {
+    let link_re = Regex::new(r"^(\s*)(?:[-*]\s+)?\[([^\]]*)\]\(([^)]*)\)").unwrap();
+    let mut book = BookConfig { toc: true, ..BookConfig::default() };
+    let mut seen_numbered = false;
+    let mut suffix: Vec<String> = Vec::new();
+
+    for line in summary.lines() {
+        let trimmed = line.trim();
+        if let Some(part_title) = trimmed.strip_prefix("# ") {
+            // The first heading is usually the "# Summary" title of the file itself.
+            if seen_numbered || !book.parts.is_empty() || !part_title.eq_ignore_ascii_case("summary") {
+                book.parts.push(PartConfig { title: Some(part_title.trim().to_string()), chapters: Vec::new() });
+            }
+            continue;
+        }
+
+        let Some(caps) = link_re.captures(line) else { continue };
+        let file = caps[3].trim();
+        // Draft chapters have an empty link target and no file to include.
+        if file.is_empty() {
+            continue;
+        }
+        let file = format!("{}/{}", src_dir.trim_end_matches('/'), file);
+        let is_numbered = trimmed.starts_with('-') || trimmed.starts_with('*');
+
+        if is_numbered {
+            // Suffix chapters can only follow the last numbered chapter.
+            suffix.clear();
+            seen_numbered = true;
+            if book.parts.is_empty() {
+                book.parts.push(PartConfig { title: None, chapters: Vec::new() });
+            }
+            let part = book.parts.last_mut().unwrap();
+            part.chapters.push(ChapterConfig { file, title: None });
+        } else if seen_numbered {
+            suffix.push(file);
+        } else {
+            book.front_matter.push(file);
+        }
+    }
+
+    book.back_matter = suffix;
+    book
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    const SUMMARY: &str = r#"# Summary
+
+[Introduction](README.md)
+
+# Getting Started
+
+- [Installation](install.md)
+    - [Windows](install/windows.md)
+- [Draft]()
+
+# Reference
+
+- [Configuration](config.md)
+
+---
+
+[Contributors](contributors.md)
+"#;
+
+    #[test]
+    fn test_parse_summary() {
+        let book = parse_summary(SUMMARY, "src");
+        assert_eq!(book.front_matter, vec!["src/README.md"]);
+        assert_eq!(book.parts.len(), 2);
+        assert_eq!(book.parts[0].title.as_deref(), Some("Getting Started"));
+        let files: Vec<&str> = book.parts[0].chapters.iter().map(|c| c.file.as_str()).collect();
+        assert_eq!(files, vec!["src/install.md", "src/install/windows.md"]);
+        assert_eq!(book.parts[1].chapters[0].file, "src/config.md");
+        assert_eq!(book.back_matter, vec!["src/contributors.md"]);
+    }
+
+    #[test]
+    fn test_import_mdbook_writes_config() {
+        let test_dir = TestDir::new("import_mdbook");
+        let dir = test_dir.path();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("book.toml"), "[book]\ntitle = \"Rust Notes\"\nauthors = [\"Ada\", \"Linus\"]\n").unwrap();
+        fs::write(dir.join("src/SUMMARY.md"), SUMMARY).unwrap();
+
+        import_mdbook(dir).unwrap();
+
+        let config_content = fs::read_to_string(dir.join("config.yaml")).unwrap();
+        let config: crate::Config = serde_yaml::from_str(&config_content).unwrap();
+        assert_eq!(config.title, "Rust Notes");
+        assert_eq!(config.author, "Ada, Linus");
+        assert_eq!(config.output.filename, "rust-notes");
+        assert_eq!(config.book.unwrap().files().len(), 5);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::builder;
+use crate::cancel::CancelToken;
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::variables;
+use crate::{Config, OutputFormat};
+use colored::*;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A data record: the value of every field, by field name.
+pub type Record = HashMap<String, String>;
+
+#[derive(Debug)]
+pub struct MergeOptions {
+    /// Name of the generated files, with `{{ field }}` placeholders. Defaults to the name of
+    /// the template followed by the record number.
+    pub name: Option<String>,
+    pub format: OutputFormat,
+}
+
+/// Builds one document per record of `data`, with the `{{ field }}` placeholders of the
+/// template replaced by the fields of the record.
+pub async fn run_merge(config: &Config, template: &Path, data: &Path, options: &MergeOptions) -> Result<(), AppError> {
+    let records = read_records(data)?;
+    if records.is_empty() {
+        return Err(AppError::MergeDataError(format!("'{}' has no records.", data.display())));
+    }
+    let project_root = std::env::current_dir()?;
+    let git_info = GitInfo::collect();
+    let stem = template.file_stem().and_then(|stem| stem.to_str()).unwrap_or("document");
+    let name_template = options.name.clone().unwrap_or_else(|| format!("{stem}-{{{{ record }}}}"));
+
+    for (index, record) in records.iter().enumerate() {
+        let mut vars = variables::builtin_variables(config, &git_info);
+        vars.insert("record".to_string(), (index + 1).to_string());
+        vars.extend(record.iter().map(|(field, value)| (field.clone(), value.clone())));
+
+        let mut record_config = config.clone();
+        record_config.book = None;
+        record_config.output.filename = file_name(&variables::render_template(&name_template, &vars));
+        let markdown = builder::expand_markdown_with(&project_root, template, &record_config, &vars)?;
+        builder::render_document(&record_config, markdown, &git_info, "", options.format, &CancelToken::default()).await?;
+    }
+    println!("{} {}", "Documents generated:".green(), records.len().to_string().yellow());
+    Ok(())
+}
+
+/// Reads the records of a CSV file (with a header row) or of a YAML or JSON list of mappings.
+pub fn read_records(path: &Path) -> Result<Vec<Record>, AppError> {
+    let content = fs::read_to_string(path).map_err(|_| AppError::SourceNotFound(path.display().to_string()))?;
+    let invalid = |e: String| AppError::MergeDataError(format!("'{}': {e}", path.display()));
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => csv_records(&content).map_err(|e| invalid(e.to_string())),
+        Some("yaml" | "yml" | "json") => yaml_records(&content).map_err(invalid),
+        _ => Err(invalid("data files must be '.csv', '.yaml', '.yml' or '.json'".to_string())),
+    }
+}
+
+fn csv_records(content: &str) -> Result<Vec<Record>, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::Headers).from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    reader.records()
+        .map(|row| row.map(|row| headers.iter().zip(row.iter()).map(|(field, value)| (field.to_string(), value.to_string())).collect()))
+        .collect()
+}
+
+fn yaml_records(content: &str) -> Result<Vec<Record>, String> {
+    let value: Value = serde_yaml::from_str(content).map_err(|e| e.to_string())?;
+    let Value::Sequence(items) = value else {
+        return Err("expected a list of records".to_string());
+    };
+    items.iter().enumerate().map(|(index, item)| {
+        let Value::Mapping(fields) = item else {
+            return Err(format!("record {} is not a mapping", index + 1));
+        };
+        Ok(fields.iter().filter_map(|(field, value)| Some((scalar(field)?, scalar(value).unwrap_or_default()))).collect())
+    }).collect()
+}
+
+/// Text of a scalar value; nested lists and mappings have none.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        Value::Null => Some(String::new()),
+        _ => None,
+    }
+}
+
+/// Keeps a generated name inside the output directory.
+fn file_name(name: &str) -> String {
+    name.trim().chars().map(|c| if matches!(c, '/' | '\\' | ':') { '-' } else { c }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_records() {
+        let records = csv_records("number, client,amount\n2024-001,\"ACME, Inc.\",120\n2024-002,Globex,80\n").unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["client"], "ACME, Inc.");
+        assert_eq!(records[1]["number"], "2024-002");
+        assert!(csv_records("a,b\n1,2,3\n").is_err());
+    }
+
+    #[test]
+    fn test_yaml_records() {
+        let records = yaml_records("- { number: 7, client: ACME, paid: true, lines: [a, b] }\n- client: Globex\n").unwrap();
+        assert_eq!(records[0]["number"], "7");
+        assert_eq!(records[0]["paid"], "true");
+        assert_eq!(records[0]["lines"], "");
+        assert_eq!(records[1]["client"], "Globex");
+        assert!(yaml_records(r#"{"client": "ACME"}"#).is_err());
+        assert!(yaml_records("- just text").is_err());
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name(" invoice-2024/001 "), "invoice-2024-001");
+    }
+}
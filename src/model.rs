@@ -0,0 +1,335 @@
+use crate::builder;
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::render;
+use crate::sidebar;
+use crate::{Config, OutputFormat};
+use colored::*;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the JSON written by `build --emit model`, increased on incompatible changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Structure of the document, for tools that translate, index or render it themselves.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DocumentModel {
+    pub schema_version: u32,
+    pub title: String,
+    pub author: String,
+    pub language: String,
+    /// Blocks before the first heading.
+    pub blocks: Vec<Block>,
+    pub sections: Vec<Section>,
+    /// Local images, in order of first use.
+    pub assets: Vec<String>,
+    /// Links to anchors of the document (`#id`).
+    pub cross_references: Vec<CrossReference>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Section {
+    pub level: u8,
+    pub title: String,
+    /// Anchor of the heading: its `{#id}` attribute, or the one generated for the HTML sidebar.
+    pub id: String,
+    pub blocks: Vec<Block>,
+    /// Sections of a lower level up to the next heading of this level or above.
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Paragraph { text: String },
+    Code { language: Option<String>, code: String },
+    /// List items as plain text; the items of nested lists follow the item containing them.
+    List { ordered: bool, items: Vec<String> },
+    Quote { text: String },
+    /// Header row first.
+    Table { rows: Vec<Vec<String>> },
+    Image { src: String, alt: String },
+    Footnote { label: String, text: String },
+    Html { html: String },
+    Rule,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CrossReference {
+    pub target: String,
+    /// Whether a heading or an element of the document has this id.
+    pub resolved: bool,
+}
+
+/// Writes the model of the document to `<output.dir>/<output.filename>.model.json`.
+pub fn write_model(config: &Config) -> Result<PathBuf, AppError> {
+    let project_root = std::env::current_dir()?;
+    let git_info = GitInfo::collect();
+    let markdown = builder::expand_markdown(&project_root, Path::new(&config.source), config, &git_info)?;
+    let model = document_model(config, &render::apply_text_passes(&config.render_config(), &markdown, OutputFormat::Html)?);
+
+    let build_dir = config.build_dir();
+    fs::create_dir_all(&build_dir)?;
+    let model_path = build_dir.join(format!("{}.model.json", builder::output_filename(config, &git_info)));
+    let json = serde_json::to_string_pretty(&model).map_err(|e| AppError::BuildError(e.to_string()))?;
+    fs::write(&model_path, json)?;
+    println!("{} {}", "Document model written:".green(), model_path.display().to_string().yellow());
+    Ok(model_path)
+}
+
+/// The model of expanded Markdown, once its includes and directives are resolved.
+pub fn document_model(config: &Config, markdown: &str) -> DocumentModel {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_TASKLISTS | Options::ENABLE_HEADING_ATTRIBUTES);
+    let mut builder = ModelBuilder::default();
+    for event in Parser::new_ext(markdown, options) {
+        builder.push(event);
+    }
+
+    let id_re = Regex::new(r#"\bid="([^"]*)""#).unwrap();
+    let mut ids = builder.ids.clone();
+    ids.extend(id_re.captures_iter(markdown).map(|caps| caps[1].to_string()));
+    let cross_references = builder.links.iter()
+        .map(|target| CrossReference { target: target.clone(), resolved: ids.contains(target) })
+        .collect();
+
+    let (blocks, sections) = builder.finish();
+    DocumentModel {
+        schema_version: SCHEMA_VERSION,
+        title: config.title.clone(),
+        author: config.author.clone(),
+        language: config.language.clone(),
+        blocks,
+        sections,
+        assets: builder.assets,
+        cross_references,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Container {
+    List,
+    Table,
+    /// A footnote definition and its label.
+    Footnote(String),
+    Other,
+}
+
+/// Turns the Markdown events into blocks and nested sections.
+#[derive(Default)]
+struct ModelBuilder {
+    blocks: Vec<Block>,
+    /// Top-level sections already closed.
+    sections: Vec<Section>,
+    /// Sections still receiving blocks, from the outermost one.
+    open: Vec<Section>,
+    /// Block containers entered and not yet left; text only becomes a block at depth 0.
+    depth: usize,
+    /// The outermost container, which decides how its content is collected.
+    container: Option<Container>,
+    text: String,
+    code_language: Option<String>,
+    list: Vec<String>,
+    rows: Vec<Vec<String>>,
+    row: Vec<String>,
+    /// Start of the alt text of the current image in `text`.
+    image: Option<(String, usize)>,
+    /// Images of the current block, listed after it.
+    images: Vec<Block>,
+    ids: HashSet<String>,
+    /// `{#id}` attribute of the heading being read.
+    heading_id: Option<String>,
+    links: Vec<String>,
+    assets: Vec<String>,
+}
+
+impl ModelBuilder {
+    fn push(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) | Event::Code(text) => self.text.push_str(&text),
+            Event::Html(html) => self.text.push_str(&html),
+            Event::SoftBreak => self.text.push(' '),
+            Event::HardBreak => self.text.push('\n'),
+            Event::Rule if self.depth == 0 => self.push_block(Block::Rule),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Link { dest_url, .. } => {
+                if let Some(target) = dest_url.strip_prefix('#') {
+                    if !self.links.iter().any(|link| link == target) {
+                        self.links.push(target.to_string());
+                    }
+                }
+            }
+            Tag::Image { dest_url, .. } => {
+                let src = dest_url.to_string();
+                if !src.contains("://") && !src.starts_with("data:") && !self.assets.contains(&src) {
+                    self.assets.push(src.clone());
+                }
+                self.image = Some((src, self.text.len()));
+            }
+            Tag::CodeBlock(kind) => {
+                self.enter(Container::Other);
+                self.code_language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split(' ').next().filter(|lang| !lang.is_empty()).map(str::to_string),
+                    CodeBlockKind::Indented => None,
+                };
+            }
+            // The text of an item containing a nested list is taken before the nested items.
+            Tag::Item => self.take_item(),
+            Tag::TableCell if self.container == Some(Container::Table) => self.text.clear(),
+            Tag::List(_) => self.enter(Container::List),
+            Tag::Table(_) => self.enter(Container::Table),
+            Tag::FootnoteDefinition(label) => self.enter(Container::Footnote(label.to_string())),
+            Tag::Heading { id, .. } => {
+                self.heading_id = id.map(|id| id.to_string());
+                self.enter(Container::Other);
+            }
+            Tag::Paragraph | Tag::BlockQuote(_) | Tag::HtmlBlock => self.enter(Container::Other),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Image => {
+                if let Some((src, alt_start)) = self.image.take() {
+                    let alt = self.text.get(alt_start..).unwrap_or_default().to_string();
+                    self.images.push(Block::Image { src, alt });
+                }
+            }
+            TagEnd::Item => self.take_item(),
+            TagEnd::TableCell if self.container == Some(Container::Table) => self.row.push(std::mem::take(&mut self.text).trim().to_string()),
+            TagEnd::TableHead | TagEnd::TableRow if self.container == Some(Container::Table) => self.rows.push(std::mem::take(&mut self.row)),
+            _ => {}
+        }
+        let is_block = matches!(tag, TagEnd::Heading(_) | TagEnd::Paragraph | TagEnd::BlockQuote(_) | TagEnd::HtmlBlock | TagEnd::List(_) | TagEnd::Table | TagEnd::FootnoteDefinition | TagEnd::CodeBlock);
+        if !is_block {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+        let text = std::mem::take(&mut self.text);
+        let container = self.container.take();
+        match tag {
+            TagEnd::Heading(level) => self.open_section(level as u8, text.trim().to_string()),
+            TagEnd::Paragraph => self.push_block(Block::Paragraph { text: text.trim().to_string() }),
+            TagEnd::BlockQuote(_) => self.push_block(Block::Quote { text: text.trim().to_string() }),
+            TagEnd::HtmlBlock => self.push_block(Block::Html { html: text.trim_end().to_string() }),
+            TagEnd::CodeBlock => {
+                let language = self.code_language.take();
+                self.push_block(Block::Code { language, code: text });
+            }
+            TagEnd::List(ordered) => {
+                let items = std::mem::take(&mut self.list);
+                self.push_block(Block::List { ordered, items });
+            }
+            TagEnd::Table => {
+                let rows = std::mem::take(&mut self.rows);
+                self.push_block(Block::Table { rows });
+            }
+            TagEnd::FootnoteDefinition => {
+                let label = match container {
+                    Some(Container::Footnote(label)) => label,
+                    _ => String::new(),
+                };
+                self.push_block(Block::Footnote { label, text: text.trim().to_string() });
+            }
+            _ => {}
+        }
+    }
+
+    fn enter(&mut self, container: Container) {
+        if self.depth == 0 {
+            self.text.clear();
+            self.container = Some(container);
+        }
+        self.depth += 1;
+    }
+
+    fn take_item(&mut self) {
+        if self.container != Some(Container::List) {
+            return;
+        }
+        let item = std::mem::take(&mut self.text);
+        if !item.trim().is_empty() {
+            self.list.push(item.trim().to_string());
+        }
+    }
+
+    fn push_block(&mut self, block: Block) {
+        let blocks = match self.open.last_mut() {
+            Some(section) => &mut section.blocks,
+            None => &mut self.blocks,
+        };
+        blocks.push(block);
+        blocks.append(&mut self.images);
+    }
+
+    fn open_section(&mut self, level: u8, title: String) {
+        while self.open.last().is_some_and(|section| section.level >= level) {
+            self.close_section();
+        }
+        let id = self.heading_id.take().unwrap_or_else(|| sidebar::unique_slug(&title, &mut self.ids));
+        self.ids.insert(id.clone());
+        self.open.push(Section { level, title, id, blocks: Vec::new(), sections: Vec::new() });
+    }
+
+    fn close_section(&mut self) {
+        if let Some(section) = self.open.pop() {
+            match self.open.last_mut() {
+                Some(parent) => parent.sections.push(section),
+                None => self.sections.push(section),
+            }
+        }
+    }
+
+    fn finish(&mut self) -> (Vec<Block>, Vec<Section>) {
+        while !self.open.is_empty() {
+            self.close_section();
+        }
+        (std::mem::take(&mut self.blocks), std::mem::take(&mut self.sections))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_model() {
+        let config: Config = serde_yaml::from_str("title: Guide\nauthor: Ada\nlanguage: en\ntheme: default\nsyntax_theme: InspiredGitHub\nsource: main.md\noutput:\n  filename: guide\n").unwrap();
+        let markdown = "Intro text.\n\n# Setup\n\nSee [usage](#usage) and [missing](#nowhere).\n\n![Diagram](assets/diagram.png)\n\n## Install\n\n- one\n- two\n  1. nested\n\n```sh\ncargo install\n```\n\n# Usage\n\n| A | B |\n|---|---|\n| 1 | 2 |\n";
+        let model = document_model(&config, markdown);
+
+        assert_eq!(model.schema_version, SCHEMA_VERSION);
+        assert_eq!(model.blocks, [Block::Paragraph { text: "Intro text.".to_string() }]);
+        assert_eq!(model.sections.len(), 2);
+        let setup = &model.sections[0];
+        assert_eq!((setup.level, setup.title.as_str(), setup.id.as_str()), (1, "Setup", "setup"));
+        assert_eq!(setup.blocks[1], Block::Paragraph { text: "Diagram".to_string() });
+        assert_eq!(setup.blocks[2], Block::Image { src: "assets/diagram.png".to_string(), alt: "Diagram".to_string() });
+        let install = &setup.sections[0];
+        assert_eq!(install.blocks, [
+            Block::List { ordered: false, items: vec!["one".to_string(), "two".to_string(), "nested".to_string()] },
+            Block::Code { language: Some("sh".to_string()), code: "cargo install\n".to_string() },
+        ]);
+        assert_eq!(model.sections[1].blocks, [Block::Table { rows: vec![vec!["A".to_string(), "B".to_string()], vec!["1".to_string(), "2".to_string()]] }]);
+        assert_eq!(model.assets, ["assets/diagram.png"]);
+        assert_eq!(model.cross_references, [
+            CrossReference { target: "usage".to_string(), resolved: true },
+            CrossReference { target: "nowhere".to_string(), resolved: false },
+        ]);
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Duration;
+use ureq::{Agent, Proxy};
+
+/// Proxy and timeout of the connections: remote includes, diagram servers, publishing, and
+/// the pages loaded by the browser.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetworkConfig {
+    /// Proxy of every connection, e.g. `http://proxy.corp.example.com:3128`. By default the
+    /// `HTTPS_PROXY`, `HTTP_PROXY` or `ALL_PROXY` environment variable.
+    pub proxy: Option<String>,
+    /// Hosts reached without the proxy: `example.com`, `.example.com` for its subdomains, or
+    /// `*`. By default the `NO_PROXY` environment variable.
+    pub no_proxy: Option<Vec<String>>,
+    /// Seconds to wait for a connection or an answer before giving up.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self { proxy: None, no_proxy: None, timeout: default_timeout() }
+    }
+}
+
+/// The settings in use, with the environment variables filled in.
+#[derive(Debug)]
+pub struct Network {
+    pub proxy: Option<String>,
+    pub no_proxy: Vec<String>,
+    /// `no_proxy` as a `--proxy-bypass-list` flag of the browser.
+    pub browser_bypass_list: String,
+    proxied: Agent,
+    direct: Agent,
+}
+
+static NETWORK: OnceLock<Network> = OnceLock::new();
+
+/// Applies the `network` settings of the project to the connections opened afterwards. Only
+/// the first call counts, before which the environment variables apply.
+pub fn configure(config: &NetworkConfig) {
+    NETWORK.get_or_init(|| Network::new(config));
+}
+
+pub fn settings() -> &'static Network {
+    NETWORK.get_or_init(|| Network::new(&NetworkConfig::default()))
+}
+
+/// The HTTP client of `url`, through the proxy unless its host is listed in `no_proxy`.
+pub fn agent(url: &str) -> &'static Agent {
+    let network = settings();
+    match host(url) {
+        Some(host) if network.bypasses(host) => &network.direct,
+        _ => &network.proxied,
+    }
+}
+
+impl Network {
+    fn new(config: &NetworkConfig) -> Self {
+        let env = |names: &[&str]| names.iter().find_map(|name| std::env::var(name).ok().filter(|value| !value.trim().is_empty()));
+        let proxy = config.proxy.clone().or_else(|| env(&["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]));
+        let no_proxy = config.no_proxy.clone().unwrap_or_else(|| {
+            env(&["NO_PROXY", "no_proxy"]).map(|hosts| hosts.split(',').map(|host| host.trim().to_string()).filter(|host| !host.is_empty()).collect()).unwrap_or_default()
+        });
+        let agent = |proxy: Option<Proxy>| {
+            let timeout = Some(Duration::from_secs(config.timeout));
+            Agent::config_builder().proxy(proxy).timeout_connect(timeout).timeout_recv_response(timeout).build().new_agent()
+        };
+        // An invalid proxy address is reported by the first request, as a failed connection.
+        let proxied = agent(proxy.as_deref().and_then(|proxy| Proxy::new(proxy).ok()));
+        let browser_bypass_list = format!("--proxy-bypass-list={}", no_proxy.join(";"));
+        Self { proxy, no_proxy, browser_bypass_list, proxied, direct: agent(None) }
+    }
+
+    /// The proxy of the browser, unless every host bypasses it.
+    pub fn browser_proxy(&self) -> Option<&str> {
+        self.proxy.as_deref().filter(|_| !self.no_proxy.iter().any(|entry| entry == "*"))
+    }
+
+    fn bypasses(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        self.no_proxy.iter().any(|entry| {
+            let entry = entry.to_lowercase();
+            match entry.strip_prefix('*').unwrap_or(&entry) {
+                "" => true,
+                suffix if suffix.starts_with('.') => host.ends_with(suffix) || host == suffix[1..],
+                name => host == name,
+            }
+        })
+    }
+}
+
+/// The host of a URL, without port or credentials.
+fn host(url: &str) -> Option<&str> {
+    let authority = url.split_once("://")?.1.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next(),
+        None => host.split(':').next(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bypasses() {
+        let config = NetworkConfig {
+            proxy: Some("http://proxy.corp:3128".to_string()),
+            no_proxy: Some(vec!["localhost".to_string(), ".corp.example.com".to_string(), "*.internal".to_string()]),
+            ..NetworkConfig::default()
+        };
+        let network = Network::new(&config);
+        assert_eq!(network.proxy.as_deref(), Some("http://proxy.corp:3128"));
+        assert!(network.bypasses("LOCALHOST"));
+        assert!(network.bypasses("docs.corp.example.com"));
+        assert!(network.bypasses("corp.example.com"));
+        assert!(network.bypasses("kroki.internal"));
+        assert!(!network.bypasses("kroki.io"));
+        assert!(!network.bypasses("notlocalhost"));
+        assert_eq!(network.browser_bypass_list, "--proxy-bypass-list=localhost;.corp.example.com;*.internal");
+
+        assert_eq!(host("https://user:pw@docs.corp.example.com:8443/a?b"), Some("docs.corp.example.com"));
+        assert_eq!(host("http://[::1]:8080/"), Some("::1"));
+    }
+}
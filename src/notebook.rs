@@ -0,0 +1,122 @@
+use crate::error::AppError;
+use serde_json::Value;
+
+/// Converts a Jupyter notebook (nbformat 4) into Markdown: Markdown cells are kept
+/// as is, code cells become fenced blocks in the kernel language, and outputs are
+/// rendered as text blocks or embedded images.
+pub fn notebook_to_markdown(json: &str, file_path: &str) -> Result<String, AppError> {
+    let notebook: Value = serde_json::from_str(json)
+        .map_err(|e| AppError::BuildError(format!("Invalid notebook '{file_path}': {e}")))?;
+
+    let language = notebook["metadata"]["kernelspec"]["language"].as_str()
+        .or_else(|| notebook["metadata"]["language_info"]["name"].as_str())
+        .unwrap_or("text")
+        .to_string();
+
+    let cells = notebook["cells"].as_array()
+        .ok_or_else(|| AppError::BuildError(format!("Invalid notebook '{file_path}': no cells found")))?;
+
+    let mut markdown = String::new();
+    for cell in cells {
+        let source = cell_text(&cell["source"]);
+        match cell["cell_type"].as_str() {
+            Some("markdown") => push_block(&mut markdown, &source),
+            Some("code") => {
+                if !source.trim().is_empty() {
+                    push_block(&mut markdown, &format!("```{}\n{}\n```", language, source.trim_end()));
+                }
+                for output in cell["outputs"].as_array().into_iter().flatten() {
+                    if let Some(block) = output_to_markdown(output) {
+                        push_block(&mut markdown, &block);
+                    }
+                }
+            }
+            // Raw cells are passed through untouched, like raw HTML.
+            Some("raw") => push_block(&mut markdown, &source),
+            _ => {}
+        }
+    }
+    Ok(markdown)
+}
+
+fn output_to_markdown(output: &Value) -> Option<String> {
+    match output["output_type"].as_str()? {
+        "stream" => Some(text_block(&cell_text(&output["text"]))),
+        "error" => {
+            let name = output["ename"].as_str().unwrap_or("Error");
+            let value = output["evalue"].as_str().unwrap_or("");
+            Some(text_block(&format!("{name}: {value}")))
+        }
+        "execute_result" | "display_data" => {
+            let data = &output["data"];
+            for mime in ["image/png", "image/jpeg"] {
+                if let Some(image) = data[mime].as_str() {
+                    let image: String = image.split_whitespace().collect();
+                    return Some(format!("![output](data:{mime};base64,{image})"));
+                }
+            }
+            if !data["image/svg+xml"].is_null() {
+                return Some(format!("<div class=\"notebook-output\">{}</div>", cell_text(&data["image/svg+xml"]).replace('\n', " ")));
+            }
+            if !data["text/markdown"].is_null() {
+                return Some(cell_text(&data["text/markdown"]));
+            }
+            if !data["text/plain"].is_null() {
+                return Some(text_block(&cell_text(&data["text/plain"])));
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Notebook text fields are either a string or a list of lines.
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn text_block(text: &str) -> String {
+    format!("```text\n{}\n```", text.trim_end())
+}
+
+fn push_block(markdown: &mut String, block: &str) {
+    markdown.push_str(block.trim_end());
+    markdown.push_str("\n\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+        "metadata": { "kernelspec": { "language": "python" } },
+        "nbformat": 4,
+        "cells": [
+            { "cell_type": "markdown", "source": ["# Report\n", "Some text."] },
+            { "cell_type": "code", "source": "print(1 + 1)", "outputs": [
+                { "output_type": "stream", "name": "stdout", "text": ["2\n"] },
+                { "output_type": "display_data", "data": { "image/png": "iVBORw0KGgo=\n", "text/plain": ["<Figure>"] } }
+            ] }
+        ]
+    }"##;
+
+    #[test]
+    fn test_notebook_to_markdown() {
+        let markdown = notebook_to_markdown(NOTEBOOK, "report.ipynb").unwrap();
+        assert!(markdown.starts_with("# Report\nSome text.\n\n"));
+        assert!(markdown.contains("```python\nprint(1 + 1)\n```"));
+        assert!(markdown.contains("```text\n2\n```"));
+        assert!(markdown.contains("![output](data:image/png;base64,iVBORw0KGgo=)"));
+        assert!(!markdown.contains("<Figure>"));
+    }
+
+    #[test]
+    fn test_notebook_invalid_json() {
+        let result = notebook_to_markdown("not json", "broken.ipynb");
+        assert!(matches!(result, Err(AppError::BuildError(_))));
+    }
+}
@@ -0,0 +1,316 @@
+use crate::directives::Directive;
+use crate::error::AppError;
+use crate::jsonschema::{cell, entries, literal, pointer, resolve, text};
+use crate::paths::SourceDir;
+use crate::sidebar;
+use serde_yaml::Value;
+use std::collections::HashSet;
+use std::fs;
+
+const METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+/// Returns the reference of `!openapi(spec.yaml, tag=payments, level=3)` as Markdown: a section
+/// per operation of the spec (only those tagged `tag` with the option) with its parameters,
+/// request body, responses and examples, then a section per schema they use. The spec, YAML or
+/// JSON, is read relative to `source_dir` and must be inside the project.
+pub fn openapi_directive(source_dir: SourceDir, directive: &Directive) -> Result<String, AppError> {
+    directive.expect_positional(1)?;
+    let file = directive.positional(0)
+        .ok_or_else(|| AppError::BuildError("!openapi needs a spec file, e.g. !openapi(spec/openapi.yaml)".to_string()))?;
+    let level = match directive.option("level") {
+        Some(level) => level.parse().ok().filter(|level| (1..=6).contains(level))
+            .ok_or_else(|| AppError::BuildError(format!("Invalid !openapi level '{level}', expected 1 to 6")))?,
+        None => 3,
+    };
+    let path = source_dir.dir.join(file);
+    let content = fs::read_to_string(source_dir.resolve(file)?).map_err(|e| AppError::BuildError(format!("Could not read OpenAPI spec '{}': {e}", path.display())))?;
+    // YAML is a superset of JSON, and its mappings keep the order of the spec.
+    let spec: Value = serde_yaml::from_str(&content).map_err(|e| AppError::BuildError(format!("Invalid OpenAPI spec '{}': {e}", path.display())))?;
+    render_openapi(&spec, directive.option("tag"), level)
+}
+
+fn render_openapi(spec: &Value, tag: Option<&str>, level: usize) -> Result<String, AppError> {
+    let paths = entries(spec.get("paths"));
+    if paths.is_empty() {
+        return Err(AppError::BuildError("The OpenAPI spec has no paths".to_string()));
+    }
+    let hashes = "#".repeat(level);
+    let mut sections = Vec::new();
+    let mut schemas = Vec::new();
+
+    for (path, item) in paths {
+        for method in METHODS {
+            let Some(operation) = item.get(method) else { continue };
+            let tags = operation.get("tags").and_then(Value::as_sequence);
+            if tag.is_some_and(|tag| !tags.is_some_and(|tags| tags.iter().any(|t| t.as_str() == Some(tag)))) {
+                continue;
+            }
+            let deprecated = operation.get("deprecated").and_then(Value::as_bool).unwrap_or(false);
+            let mut section = format!(
+                "<div class=\"openapi-operation{}\">\n\n{hashes} {} `{path}`",
+                if deprecated { " deprecated" } else { "" },
+                method.to_uppercase()
+            );
+            for text in [text(operation, "summary"), text(operation, "description")].into_iter().flatten() {
+                section.push_str(&format!("\n\n{text}"));
+            }
+            if deprecated {
+                section.push_str("\n\n*Deprecated.*");
+            }
+
+            // Parameters of the path item apply to all its operations.
+            let parameters: Vec<Value> = [item.get("parameters"), operation.get("parameters")].into_iter().flatten()
+                .filter_map(Value::as_sequence).flatten()
+                .map(|parameter| resolve(spec, parameter).clone())
+                .collect();
+            if !parameters.is_empty() {
+                section.push_str("\n\n**Parameters**\n\n| Name | In | Type | Required | Description |\n|---|---|---|---|---|");
+                for parameter in &parameters {
+                    let schema = parameter.get("schema").unwrap_or(&Value::Null);
+                    section.push_str(&format!(
+                        "\n| `{}` | {} | {} | {} | {} |",
+                        text(parameter, "name").unwrap_or_default(),
+                        text(parameter, "in").unwrap_or_default(),
+                        type_name(schema, &mut schemas),
+                        if parameter.get("required").and_then(Value::as_bool).unwrap_or(false) { "yes" } else { "no" },
+                        cell(&describe(parameter, schema))
+                    ));
+                }
+            }
+
+            if let Some(body) = operation.get("requestBody").map(|body| resolve(spec, body)) {
+                section.push_str("\n\n**Request body**");
+                if let Some(description) = text(body, "description") {
+                    section.push_str(&format!("\n\n{description}"));
+                }
+                for (media_type, media) in entries(body.get("content")) {
+                    section.push_str(&media_section(spec, &media_type, media, &mut schemas));
+                }
+            }
+
+            let responses = entries(operation.get("responses"));
+            if !responses.is_empty() {
+                section.push_str("\n\n**Responses**\n\n| Status | Type | Description |\n|---|---|---|");
+                let mut examples = String::new();
+                for (status, response) in responses {
+                    let response = resolve(spec, response);
+                    let content = entries(response.get("content"));
+                    let types: Vec<String> = content.iter()
+                        .filter_map(|(_, media)| media.get("schema"))
+                        .map(|schema| type_name(schema, &mut schemas))
+                        .collect();
+                    section.push_str(&format!("\n| `{status}` | {} | {} |", types.join(", "), cell(text(response, "description").unwrap_or_default())));
+                    for (media_type, media) in content {
+                        if let Some(example) = example(media) {
+                            examples.push_str(&format!("\n\nExample response `{status}` (`{media_type}`):\n\n{}", code_block(&media_type, example)));
+                        }
+                    }
+                }
+                section.push_str(&examples);
+            }
+            section.push_str("\n\n</div>");
+            sections.push(section);
+        }
+    }
+    if sections.is_empty() {
+        return Err(AppError::BuildError(match tag {
+            Some(tag) => format!("The OpenAPI spec has no operations tagged '{tag}'"),
+            None => "The OpenAPI spec has no operations".to_string(),
+        }));
+    }
+
+    // Schemas used by the operations, and those they use in turn.
+    let mut rendered = HashSet::new();
+    while let Some(name) = schemas.iter().find(|name| !rendered.contains(*name)).cloned() {
+        rendered.insert(name.clone());
+        let Some(schema) = pointer(spec, &format!("/components/schemas/{name}")) else { continue };
+        let mut section = format!("<div class=\"openapi-schema\">\n\n{hashes} {name} {{#{}}}", schema_anchor(&name));
+        if let Some(description) = text(schema, "description") {
+            section.push_str(&format!("\n\n{description}"));
+        }
+        section.push_str(&properties_table(spec, schema, &mut schemas));
+        section.push_str("\n\n</div>");
+        sections.push(section);
+    }
+    Ok(sections.join("\n\n"))
+}
+
+/// The request body of one media type: its schema as a property table and its example.
+fn media_section(spec: &Value, media_type: &str, media: &Value, schemas: &mut Vec<String>) -> String {
+    let mut section = String::new();
+    if let Some(schema) = media.get("schema") {
+        section.push_str(&format!("\n\n`{media_type}`: {}", type_name(schema, schemas)));
+        if schema.get("$ref").is_none() {
+            section.push_str(&properties_table(spec, schema, schemas));
+        }
+    }
+    if let Some(example) = example(media) {
+        section.push_str(&format!("\n\nExample:\n\n{}", code_block(media_type, example)));
+    }
+    section
+}
+
+/// The properties of an object schema, `allOf` parts included, as a table.
+fn properties_table(spec: &Value, schema: &Value, schemas: &mut Vec<String>) -> String {
+    let mut rows = Vec::new();
+    let parts: Vec<&Value> = match schema.get("allOf").and_then(Value::as_sequence) {
+        Some(parts) => parts.iter().map(|part| resolve(spec, part)).collect(),
+        None => vec![schema],
+    };
+    for part in parts {
+        let required: Vec<&str> = part.get("required").and_then(Value::as_sequence).into_iter().flatten().filter_map(Value::as_str).collect();
+        for (name, property) in entries(part.get("properties")) {
+            rows.push(format!(
+                "\n| `{name}` | {} | {} | {} |",
+                type_name(property, schemas),
+                if required.contains(&name.as_str()) { "yes" } else { "no" },
+                cell(&describe(property, property))
+            ));
+        }
+    }
+    if rows.is_empty() {
+        return String::new();
+    }
+    format!("\n\n| Property | Type | Required | Description |\n|---|---|---|---|{}", rows.concat())
+}
+
+/// A short description of a schema's type. Named schemas link to their section and are
+/// added to `schemas`.
+fn type_name(schema: &Value, schemas: &mut Vec<String>) -> String {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.rsplit('/').next().unwrap_or(reference).to_string();
+        if reference.starts_with("#/components/schemas/") {
+            let link = format!("[{name}](#{})", schema_anchor(&name));
+            if !schemas.contains(&name) {
+                schemas.push(name);
+            }
+            return link;
+        }
+        return name;
+    }
+    for (keyword, separator) in [("oneOf", " or "), ("anyOf", " or "), ("allOf", " and ")] {
+        if let Some(parts) = schema.get(keyword).and_then(Value::as_sequence) {
+            return parts.iter().map(|part| type_name(part, schemas)).collect::<Vec<_>>().join(separator);
+        }
+    }
+    let mut name = match schema.get("type") {
+        Some(Value::String(kind)) if kind == "array" => {
+            format!("array of {}", type_name(schema.get("items").unwrap_or(&Value::Null), schemas))
+        }
+        Some(Value::String(kind)) => kind.clone(),
+        // OpenAPI 3.1 lists nullable types as `[string, "null"]`.
+        Some(Value::Sequence(kinds)) => kinds.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" or "),
+        _ if schema.get("properties").is_some() => "object".to_string(),
+        _ => "any".to_string(),
+    };
+    if let Some(format) = text(schema, "format") {
+        name.push_str(&format!(" ({format})"));
+    }
+    if schema.get("nullable").and_then(Value::as_bool).unwrap_or(false) {
+        name.push_str(" or null");
+    }
+    name
+}
+
+/// The description of a parameter or property, followed by the allowed values and default
+/// of its `schema`.
+fn describe(item: &Value, schema: &Value) -> String {
+    let mut parts: Vec<String> = text(item, "description").into_iter().map(str::to_string).collect();
+    if let Some(values) = schema.get("enum").and_then(Value::as_sequence) {
+        parts.push(format!("One of {}.", values.iter().map(|value| format!("`{}`", literal(value))).collect::<Vec<_>>().join(", ")));
+    }
+    if let Some(default) = schema.get("default") {
+        parts.push(format!("Default: `{}`.", literal(default)));
+    }
+    parts.join(" ")
+}
+
+/// The example of a media type: `example`, the first of `examples` or the schema's example.
+fn example(media: &Value) -> Option<&Value> {
+    media.get("example")
+        .or_else(|| media.get("examples").and_then(Value::as_mapping).and_then(|examples| examples.values().next()).and_then(|example| example.get("value")))
+        .or_else(|| media.get("schema").and_then(|schema| schema.get("example")))
+}
+
+fn code_block(media_type: &str, example: &Value) -> String {
+    let (language, code) = match example {
+        Value::String(text) if !media_type.contains("json") => ("", text.clone()),
+        _ => ("json", serde_json::to_string_pretty(example).unwrap_or_default()),
+    };
+    format!("```{language}\n{code}\n```")
+}
+
+fn schema_anchor(name: &str) -> String {
+    format!("schema-{}", sidebar::slugify(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r##"
+openapi: 3.0.3
+info: { title: Payments, version: "1.0" }
+paths:
+  /payments/{id}:
+    parameters:
+      - { name: id, in: path, required: true, schema: { type: string }, description: Payment identifier }
+    get:
+      tags: [payments]
+      summary: Get a payment
+      parameters:
+        - $ref: "#/components/parameters/Expand"
+      responses:
+        "200":
+          description: The payment
+          content:
+            application/json:
+              schema: { $ref: "#/components/schemas/Payment" }
+              example: { id: pay_1, amount: 1200 }
+        "404": { description: Not found }
+  /users:
+    post:
+      tags: [users]
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: object
+              required: [name]
+              properties:
+                name: { type: string }
+      responses:
+        201: { description: Created }
+components:
+  parameters:
+    Expand: { name: expand, in: query, schema: { type: array, items: { type: string, enum: [customer, refunds] } } }
+  schemas:
+    Payment:
+      type: object
+      required: [id]
+      properties:
+        id: { type: string }
+        amount: { type: integer, format: int64, description: "Amount in cents | minor units" }
+        status: { $ref: "#/components/schemas/Status" }
+    Status: { type: string, enum: [pending, paid], default: pending }
+"##;
+
+    #[test]
+    fn test_render_openapi() {
+        let spec: Value = serde_yaml::from_str(SPEC).unwrap();
+        let markdown = render_openapi(&spec, Some("payments"), 3).unwrap();
+        assert!(markdown.starts_with("<div class=\"openapi-operation\">\n\n### GET `/payments/{id}`\n\nGet a payment\n\n**Parameters**"));
+        assert!(markdown.contains("| `id` | path | string | yes | Payment identifier |\n| `expand` | query | array of string | no |  |"));
+        assert!(markdown.contains("| `200` | [Payment](#schema-payment) | The payment |\n| `404` |  | Not found |"));
+        assert!(markdown.contains("Example response `200` (`application/json`):\n\n```json\n{\n  \"id\": \"pay_1\",\n  \"amount\": 1200\n}\n```"));
+        assert!(markdown.contains("### Payment {#schema-payment}\n\n| Property | Type | Required | Description |\n|---|---|---|---|\n| `id` | string | yes |  |\n\
+            | `amount` | integer (int64) | no | Amount in cents \\| minor units |\n| `status` | [Status](#schema-status) | no |  |"));
+        assert!(markdown.contains("### Status {#schema-status}"));
+        assert!(!markdown.contains("/users"));
+
+        let markdown = render_openapi(&spec, Some("users"), 2).unwrap();
+        assert!(markdown.contains("## POST `/users`\n\n**Request body**\n\n`application/json`: object\n\n| Property | Type | Required | Description |\n|---|---|---|---|\n| `name` | string | yes |  |"));
+        assert!(markdown.contains("| `201` |  | Created |"));
+        assert!(render_openapi(&spec, Some("missing"), 3).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use regex::{Captures, Regex};
+
+/// Converts an Org-mode document into Markdown. Headings, source/example/quote blocks,
+/// tables, links and inline markup are mapped to their Markdown equivalents. Lines that
+/// have no equivalent (keywords, comments, property drawers) become blank lines so the
+/// line numbers of the output stay aligned with the Org file.
+pub fn org_to_markdown(org: &str) -> String {
+    let heading_re = Regex::new(r"^(\*+)\s+(?:(?:TODO|DONE)\s+)?(.*?)(?:\s+:[\w@#%:]+:)?\s*$").unwrap();
+    let begin_re = Regex::new(r"(?i)^\s*#\+begin_(\w+)\s*(\S*)").unwrap();
+    let end_re = Regex::new(r"(?i)^\s*#\+end_(\w+)").unwrap();
+    let keyword_re = Regex::new(r"^\s*#\+(\w+):\s*(.*)$").unwrap();
+    let table_separator_re = Regex::new(r"^\s*\|[-+]+\|?\s*$").unwrap();
+    let list_re = Regex::new(r"^(\s*)(?:\+|(\d+)\))\s+").unwrap();
+
+    let mut markdown = String::with_capacity(org.len());
+    let mut block: Option<String> = None;
+    let mut in_drawer = false;
+    let mut previous_was_table = false;
+    let lines: Vec<&str> = org.lines().collect();
+
+    for (index, line) in lines.iter().copied().enumerate() {
+        let is_table = line.trim_start().starts_with('|');
+
+        let converted = if let Some(kind) = block.clone() {
+            if end_re.is_match(line) {
+                block = None;
+                if kind == "quote" { String::new() } else { "```".to_string() }
+            } else if kind == "quote" {
+                format!("> {}", convert_inline(line.trim()))
+            } else {
+                // Org escapes lines starting with `*` or `#+` inside blocks with a comma.
+                let unescaped = line.strip_prefix(',').filter(|rest| rest.starts_with('*') || rest.starts_with("#+"));
+                unescaped.unwrap_or(line).to_string()
+            }
+        } else if in_drawer {
+            if line.trim().eq_ignore_ascii_case(":END:") {
+                in_drawer = false;
+            }
+            String::new()
+        } else if line.trim().eq_ignore_ascii_case(":PROPERTIES:") || line.trim().eq_ignore_ascii_case(":LOGBOOK:") {
+            in_drawer = true;
+            String::new()
+        } else if let Some(caps) = begin_re.captures(line) {
+            let kind = caps[1].to_lowercase();
+            let fence = match kind.as_str() {
+                "src" => format!("```{}", &caps[2]),
+                "example" => "```text".to_string(),
+                "quote" => String::new(),
+                _ => "```".to_string(),
+            };
+            block = Some(kind);
+            fence
+        } else if let Some(caps) = keyword_re.captures(line) {
+            match caps[1].to_lowercase().as_str() {
+                "title" => format!("# {}", caps[2].trim()),
+                _ => String::new(),
+            }
+        } else if line.starts_with("# ") || line.trim() == "#" {
+            // Org comment line.
+            String::new()
+        } else if let Some(caps) = heading_re.captures(line) {
+            format!("{} {}", "#".repeat(caps[1].len().min(6)), convert_inline(&caps[2]))
+        } else if is_table {
+            if table_separator_re.is_match(line) {
+                table_separator(line)
+            } else {
+                let row = convert_inline(line);
+                let next_is_separator = lines.get(index + 1).is_some_and(|next| table_separator_re.is_match(next));
+                // Markdown tables need a header separator after the first row.
+                if !previous_was_table && !next_is_separator {
+                    format!("{}\n{}", row, table_separator(line))
+                } else {
+                    row
+                }
+            }
+        } else {
+            let line = list_re.replace(line, |caps: &Captures| match caps.get(2) {
+                Some(number) => format!("{}{}. ", &caps[1], number.as_str()),
+                None => format!("{}- ", &caps[1]),
+            });
+            convert_inline(&line)
+        };
+
+        previous_was_table = is_table;
+        markdown.push_str(&converted);
+        markdown.push('\n');
+    }
+    markdown
+}
+
+fn table_separator(row: &str) -> String {
+    let columns = row.trim().trim_matches('|').split(['|', '+']).count().max(1);
+    format!("|{}", " --- |".repeat(columns))
+}
+
+fn convert_inline(text: &str) -> String {
+    let link_re = Regex::new(r"\[\[([^\]]+)\](?:\[([^\]]+)\])?\]").unwrap();
+    let emphasis_re = Regex::new(r"(^|[\s(])([*/=~+])([^\s*/=~+](?:[^*/=~+]*[^\s*/=~+])?)([*/=~+])([\s.,;:!?)]|$)").unwrap();
+
+    let text = link_re.replace_all(text, |caps: &Captures| {
+        let target = caps[1].trim_start_matches("file:");
+        let is_image = [".png", ".jpg", ".jpeg", ".gif", ".svg"].iter().any(|ext| target.to_lowercase().ends_with(ext));
+        match caps.get(2) {
+            Some(description) => format!("[{}]({})", description.as_str(), target),
+            None if is_image => format!("![]({target})"),
+            None => format!("<{target}>"),
+        }
+    });
+
+    emphasis_re.replace_all(&text, |caps: &Captures| {
+        let (open, close) = (&caps[2], &caps[4]);
+        if open != close {
+            return caps[0].to_string();
+        }
+        let content = &caps[3];
+        let converted = match open {
+            "*" => format!("**{content}**"),
+            "/" => format!("*{content}*"),
+            "=" | "~" => format!("`{content}`"),
+            "+" => format!("~~{content}~~"),
+            _ => content.to_string(),
+        };
+        format!("{}{}{}", &caps[1], converted, &caps[5])
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_org_headings_and_markup() {
+        let org = "#+TITLE: Notes\n* TODO Results :draft:\n** Method\nSome *bold*, /italic/ and =code= text.\n# a comment";
+        let markdown = org_to_markdown(org);
+        assert_eq!(markdown, "# Notes\n# Results\n## Method\nSome **bold**, *italic* and `code` text.\n\n");
+    }
+
+    #[test]
+    fn test_org_blocks_and_links() {
+        let org = "#+BEGIN_SRC rust\nfn main() {}\n,* not a heading\n#+END_SRC\nSee [[https://orgmode.org][Org]] and [[file:plot.png]].";
+        let markdown = org_to_markdown(org);
+        assert!(markdown.contains("```rust\nfn main() {}\n* not a heading\n```"));
+        assert!(markdown.contains("See [Org](https://orgmode.org) and ![](plot.png)."));
+    }
+
+    #[test]
+    fn test_org_tables() {
+        let org = "| Name | Age |\n|------+-----|\n| Ada  | 36  |\n\n| a | b |\n| c | d |";
+        let markdown = org_to_markdown(org);
+        assert!(markdown.contains("| Name | Age |\n| --- | --- |\n| Ada  | 36  |"));
+        assert!(markdown.contains("| a | b |\n| --- | --- |\n| c | d |"));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::error::AppError;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Page on which a heading was printed, as written to `build/pagemap.json`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PageMapEntry {
+    pub level: u8,
+    pub title: String,
+    /// 1-based page number, or `None` when the heading could not be found in the PDF.
+    pub page: Option<usize>,
+}
+
+/// A page of the PDF, as written to `build/index.json` for site search or content checks.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexedPage {
+    pub page: usize,
+    /// Titles of the headings enclosing the top of the page, outermost first.
+    pub section: Vec<String>,
+    /// Titles of the headings starting on the page.
+    pub headings: Vec<String>,
+    pub text: String,
+}
+
+/// Before the measuring print, puts an invisible marker at the start of every heading of the
+/// document (the table of contents aside) and returns their level and title as JSON.
+/// The markers are absolutely positioned, so adding them does not change the layout.
+pub const MARK_HEADINGS_SCRIPT: &str = r#"(() => {
+  const headings = [...document.querySelectorAll('h1, h2, h3, h4, h5, h6')]
+    .filter(h => !h.closest('.toc') && h.textContent.trim() !== '');
+  return JSON.stringify(headings.map((h, i) => {
+    const marker = document.createElement('span');
+    marker.className = 'pagemap-marker';
+    marker.style.cssText = 'position: absolute; font-size: 1px; opacity: 0.01;';
+    marker.textContent = '@@pagemap:h' + i + '@@';
+    h.prepend(marker);
+    return [Number(h.tagName.substring(1)), h.textContent.replace(marker.textContent, '').trim()];
+  }));
+})()"#;
+
+/// Removes the markers and writes the page numbers (a JSON array, one per heading listed by
+/// `MARK_HEADINGS_SCRIPT`) into the table of contents, which leaves out `notoc` headings.
+pub fn fill_toc_script(pages: &[Option<usize>]) -> String {
+    let pages = serde_json::to_string(pages).unwrap_or_else(|_| "[]".to_string());
+    format!(
+        r#"(() => {{
+  const headings = [...document.querySelectorAll('.pagemap-marker')]
+    .filter(m => m.textContent.startsWith('@@pagemap:h')).map(m => m.parentElement);
+  document.querySelectorAll('.pagemap-marker').forEach(m => m.remove());
+  const pages = {pages}.filter((_, i) => !headings[i] || !headings[i].classList.contains('notoc'));
+  document.querySelectorAll('.toc .toc-entry-page').forEach((span, i) => {{
+    if (pages[i] !== null && pages[i] !== undefined) span.textContent = pages[i];
+  }});
+}})()"#
+    )
+}
+
+/// Number of pages of a PDF file, if it can be read.
+pub fn page_count(pdf_path: &Path) -> Option<usize> {
+    lopdf::Document::load(pdf_path).ok().map(|document| document.get_pages().len())
+}
+
+/// Text of every page of a PDF, in order.
+pub fn extract_page_texts(pdf: &[u8]) -> Result<Vec<String>, AppError> {
+    let document = lopdf::Document::load_mem(pdf).map_err(|e| AppError::BuildError(format!("Could not read the generated PDF: {e}")))?;
+    let pages: Vec<u32> = document.get_pages().keys().copied().collect();
+    Ok(pages.iter().map(|page| document.extract_text(&[*page]).unwrap_or_default()).collect())
+}
+
+/// Pairs the headings listed by `MARK_HEADINGS_SCRIPT` with the page their marker was printed on.
+pub fn build_page_map(headings: &[(u8, String)], page_texts: &[String]) -> Vec<PageMapEntry> {
+    let pages = marker_pages(page_texts);
+    headings.iter().enumerate()
+        .map(|(index, (level, title))| PageMapEntry { level: *level, title: title.clone(), page: pages.get(&format!("h{index}")).copied() })
+        .collect()
+}
+
+/// Finds the `@@pagemap:<key>@@` markers in the page texts and returns the first page of each key.
+pub fn marker_pages(page_texts: &[String]) -> HashMap<String, usize> {
+    let marker_re = Regex::new(r"@@pagemap:([a-z]\d+(?:\.\d+)?)@@").unwrap();
+    let mut pages = HashMap::new();
+    for (index, text) in page_texts.iter().enumerate() {
+        // Extraction may break the marker with spaces or line breaks.
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        for caps in marker_re.captures_iter(&compact) {
+            pages.entry(caps[1].to_string()).or_insert(index + 1);
+        }
+    }
+    pages
+}
+
+/// Pairs the text of every page with the headings around it.
+pub fn build_search_index(page_map: &[PageMapEntry], page_texts: &[String]) -> Vec<IndexedPage> {
+    let mut trail: Vec<&PageMapEntry> = Vec::new();
+    let mut index = Vec::new();
+    for (page, text) in (1..).zip(page_texts) {
+        let section = trail.iter().map(|entry| entry.title.clone()).collect();
+        let starting: Vec<&PageMapEntry> = page_map.iter().filter(|entry| entry.page == Some(page)).collect();
+        for entry in &starting {
+            while trail.last().is_some_and(|last| last.level >= entry.level) {
+                trail.pop();
+            }
+            trail.push(entry);
+        }
+        let headings = starting.iter().map(|entry| entry.title.clone()).collect();
+        index.push(IndexedPage { page, section, headings, text: normalize_text(text) });
+    }
+    index
+}
+
+/// Trims the lines of a page and drops the empty ones, which depend on the text extraction
+/// rather than on the document.
+pub fn normalize_text(text: &str) -> String {
+    text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+pub fn write_page_map(path: &Path, entries: &[PageMapEntry]) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(entries).map_err(|e| AppError::BuildError(e.to_string()))?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+pub fn write_search_index(path: &Path, pages: &[IndexedPage]) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(pages).map_err(|e| AppError::BuildError(e.to_string()))?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_page_map() {
+        let headings = vec![(1, "Guide".to_string()), (2, "Install".to_string()), (2, "Missing".to_string())];
+        let pages = vec![
+            "@@pagemap:h0@@Guide\nContents Guide Install".to_string(),
+            "Text @@pagemap:b0.3@@".to_string(),
+            "@@page\nmap:h1@@ Install".to_string(),
+        ];
+        let map = build_page_map(&headings, &pages);
+        assert_eq!(map[0], PageMapEntry { level: 1, title: "Guide".to_string(), page: Some(1) });
+        assert_eq!(map[1].page, Some(3));
+        assert_eq!(map[2].page, None);
+        assert_eq!(marker_pages(&pages).get("b0.3"), Some(&2));
+    }
+
+    #[test]
+    fn test_fill_toc_script() {
+        let script = fill_toc_script(&[Some(1), None, Some(4)]);
+        assert!(script.contains("const pages = [1,null,4].filter("));
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        assert_eq!(normalize_text("  Title \n\n\tBody text\n  \n"), "Title\nBody text");
+    }
+
+    #[test]
+    fn test_build_search_index() {
+        let entry = |level, title: &str, page| PageMapEntry { level, title: title.to_string(), page: Some(page) };
+        let page_map = [entry(1, "Guide", 1), entry(2, "Install", 2), entry(3, "Linux", 2), entry(2, "Usage", 3)];
+        let pages = ["Guide\n\n  Welcome ".to_string(), "Install\nLinux".to_string(), "Usage".to_string(), "More usage".to_string()];
+        let index = build_search_index(&page_map, &pages);
+        assert_eq!(index[0], IndexedPage { page: 1, section: vec![], headings: vec!["Guide".to_string()], text: "Guide\nWelcome".to_string() });
+        assert_eq!(index[1].section, ["Guide"]);
+        assert_eq!(index[1].headings, ["Install", "Linux"]);
+        assert_eq!(index[2].section, ["Guide", "Install", "Linux"]);
+        assert_eq!(index[3].section, ["Guide", "Usage"]);
+        assert!(index[3].headings.is_empty());
+    }
+}
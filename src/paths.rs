@@ -0,0 +1,95 @@
+use crate::error::AppError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Resolves `path` and checks that it stays inside `project_root`. Both are canonicalized, so
+/// `..` segments and symlinks are resolved by the file system rather than by string cleaning,
+/// and Windows drive letters, UNC shares (`\\server\share`) and `\\?\` prefixes compare
+/// consistently. Symbolic links are followed as long as their target is in the project, and
+/// refused altogether without `follow_symlinks`. Returns the resolved path.
+pub fn resolve_within(project_root: &Path, path: &Path, follow_symlinks: bool) -> Result<PathBuf, AppError> {
+    let root = dunce::canonicalize(project_root)?;
+    let resolved = dunce::canonicalize(path).map_err(|_| AppError::SourceNotFound(path.display().to_string()))?;
+    if !follow_symlinks {
+        if let Some(link) = symlink_below(project_root, path)? {
+            return Err(AppError::SymlinkRefused(link.display().to_string()));
+        }
+    }
+    if !resolved.starts_with(&root) {
+        return Err(AppError::BuildError(format!("Unauthorized file access attempt: {}", path.display())));
+    }
+    Ok(resolved)
+}
+
+/// The directory of a source file, where the files named by its directives (specs, schemas,
+/// fragments...) are looked up.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceDir<'a> {
+    pub project_root: &'a Path,
+    pub dir: &'a Path,
+    pub follow_symlinks: bool,
+}
+
+impl SourceDir<'_> {
+    /// `file` relative to the directory, checked like an `!include` to stay inside the project.
+    pub fn resolve(&self, file: &str) -> Result<PathBuf, AppError> {
+        resolve_within(self.project_root, &self.dir.join(file), self.follow_symlinks)
+    }
+}
+
+/// The first symbolic link among `path` and its parent directories below `project_root`.
+fn symlink_below(project_root: &Path, path: &Path) -> Result<Option<PathBuf>, AppError> {
+    let current_dir = std::env::current_dir()?;
+    let root = path_clean::clean(current_dir.join(project_root));
+    let path = path_clean::clean(current_dir.join(path));
+    for ancestor in path.ancestors().take_while(|ancestor| ancestor.starts_with(&root) && *ancestor != root) {
+        if fs::symlink_metadata(ancestor).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            return Ok(Some(ancestor.to_path_buf()));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_resolve_within() {
+        let test_dir = TestDir::new("paths_resolve");
+        let dir = test_dir.path();
+        let project = dir.join("project");
+        fs::create_dir_all(project.join("chapters")).unwrap();
+        fs::write(project.join("intro.md"), "Intro").unwrap();
+        fs::write(dir.join("outside.md"), "Outside").unwrap();
+
+        let resolved = resolve_within(&project, &project.join("chapters").join("..").join("intro.md"), true).unwrap();
+        assert_eq!(resolved, dunce::canonicalize(project.join("intro.md")).unwrap());
+        assert!(matches!(resolve_within(&project, &project.join("..").join("outside.md"), true), Err(AppError::BuildError(_))));
+        assert!(matches!(resolve_within(&project, &project.join("missing.md"), true), Err(AppError::SourceNotFound(_))));
+
+        let source_dir = SourceDir { project_root: &project, dir: &project.join("chapters"), follow_symlinks: true };
+        assert_eq!(source_dir.resolve("../intro.md").unwrap(), resolved);
+        assert!(matches!(source_dir.resolve("../../outside.md"), Err(AppError::BuildError(_))));
+        assert!(source_dir.resolve(&dir.join("outside.md").display().to_string()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_within_symlinks() {
+        let test_dir = TestDir::new("paths_symlinks");
+        let dir = test_dir.path();
+        let project = dir.join("project");
+        fs::create_dir_all(project.join("shared")).unwrap();
+        fs::write(project.join("shared").join("chapter.md"), "Shared").unwrap();
+        fs::write(dir.join("secret.md"), "Secret").unwrap();
+        std::os::unix::fs::symlink(project.join("shared"), project.join("chapters")).unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.md"), project.join("escape.md")).unwrap();
+
+        let linked = project.join("chapters").join("chapter.md");
+        assert!(resolve_within(&project, &linked, true).is_ok());
+        assert!(matches!(resolve_within(&project, &linked, false), Err(AppError::SymlinkRefused(link)) if link.ends_with("chapters")));
+        assert!(matches!(resolve_within(&project, &project.join("escape.md"), true), Err(AppError::BuildError(_))));
+    }
+}
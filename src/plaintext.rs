@@ -0,0 +1,288 @@
+use crate::builder;
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::render;
+use crate::{Config, OutputFormat};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Layout of `--format txt` exports.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct TextConfig {
+    /// Column at which paragraphs are wrapped.
+    #[serde(default = "default_width")]
+    pub width: usize,
+}
+
+fn default_width() -> usize {
+    72
+}
+
+impl Default for TextConfig {
+    fn default() -> Self {
+        Self { width: default_width() }
+    }
+}
+
+/// Writes the document as plain text, `<output.dir>/<name>.txt`, and returns its path.
+pub fn write_text(config: &Config, markdown: &str, git_info: &GitInfo) -> Result<PathBuf, AppError> {
+    let markdown = render::apply_text_passes(&config.render_config(), markdown, OutputFormat::Txt)?;
+    fs::create_dir_all(config.build_dir())?;
+    let path = config.build_dir().join(format!("{}.txt", builder::output_filename(config, git_info)));
+    fs::write(&path, to_plain_text(&markdown, config.text.width))?;
+    Ok(path)
+}
+
+/// Converts Markdown into plain text wrapped at `width` columns. Level-1 and level-2 headings
+/// are underlined with `=` and `-`, lists and block quotes keep their markers with a hanging
+/// indent, code blocks are indented without wrapping and tables are aligned in columns.
+/// Links are followed by their address; inline HTML is left out.
+pub fn to_plain_text(markdown: &str, width: usize) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_FOOTNOTES);
+    let mut writer = TextWriter { width, ..TextWriter::default() };
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut links: Vec<String> = Vec::new();
+    let mut table: Option<Vec<Vec<String>>> = None;
+    let mut in_code_block = false;
+
+    for event in parser {
+        if let Some(rows) = table.as_mut() {
+            match event {
+                Event::Start(Tag::TableHead) | Event::Start(Tag::TableRow) => rows.push(Vec::new()),
+                Event::Start(Tag::TableCell) => rows.last_mut().unwrap().push(String::new()),
+                Event::Text(text) | Event::Code(text) => rows.last_mut().unwrap().last_mut().unwrap().push_str(&text),
+                Event::End(TagEnd::Table) => writer.table(&table.take().unwrap()),
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            Event::End(TagEnd::Heading(level)) => {
+                let underline = match level {
+                    HeadingLevel::H1 => Some('='),
+                    HeadingLevel::H2 => Some('-'),
+                    _ => None,
+                };
+                writer.flush(underline);
+                writer.blank_line();
+            }
+            // Only loose lists have paragraphs in their items, so their items are spaced out.
+            Event::End(TagEnd::Paragraph) => {
+                writer.flush(None);
+                writer.blank_line();
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                writer.flush(None);
+                in_code_block = true;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code_block = false;
+                writer.blank_line();
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                writer.flush(None);
+                writer.prefixes.push("> ".to_string());
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                writer.flush(None);
+                // The quote ends with a blank line rather than an empty quoted one.
+                let empty_quote_line = format!("\n{}\n", writer.prefix().trim_end());
+                if writer.out.ends_with(&empty_quote_line) {
+                    writer.out.truncate(writer.out.len() - empty_quote_line.len() + 1);
+                }
+                writer.prefixes.pop();
+                writer.blank_line();
+            }
+            Event::Start(Tag::List(start)) => {
+                writer.flush(None);
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                writer.flush(None);
+                lists.pop();
+                if lists.is_empty() {
+                    writer.blank_line();
+                }
+            }
+            Event::Start(Tag::Item) => {
+                let marker = match lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                writer.marker = Some(marker.clone());
+                writer.prefixes.push(" ".repeat(marker.len()));
+            }
+            Event::End(TagEnd::Item) => {
+                writer.flush(None);
+                writer.prefixes.pop();
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => links.push(dest_url.to_string()),
+            Event::End(TagEnd::Link) => {
+                if let Some(url) = links.pop().filter(|url| !url.starts_with('#') && !writer.buffer.ends_with(url.as_str())) {
+                    writer.buffer.push_str(&format!(" ({url})"));
+                }
+            }
+            Event::Start(Tag::Table(_)) => {
+                writer.flush(None);
+                table = Some(Vec::new());
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                writer.flush(None);
+                writer.buffer.push_str(&format!("[{label}] "));
+            }
+            Event::Text(text) if in_code_block => writer.code(&text),
+            Event::Text(text) | Event::Code(text) => writer.buffer.push_str(&text),
+            Event::SoftBreak => writer.buffer.push(' '),
+            Event::HardBreak => writer.buffer.push('\n'),
+            Event::Rule => {
+                writer.flush(None);
+                writer.line(&"-".repeat(width.min(40)));
+                writer.blank_line();
+            }
+            Event::FootnoteReference(label) => writer.buffer.push_str(&format!("[{label}]")),
+            Event::TaskListMarker(done) => writer.buffer.push_str(if done { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+    writer.flush(None);
+    let mut text = writer.out.trim_end().to_string();
+    text.push('\n');
+    text
+}
+
+#[derive(Default)]
+struct TextWriter {
+    out: String,
+    width: usize,
+    /// Text of the paragraph, heading or item being read.
+    buffer: String,
+    /// Prefixes of the continuation lines: block quote markers and list indents.
+    prefixes: Vec<String>,
+    /// The marker of the list item whose first line comes next.
+    marker: Option<String>,
+}
+
+impl TextWriter {
+    fn prefix(&self) -> String {
+        self.prefixes.concat()
+    }
+
+    /// Writes the buffered text, wrapped, optionally underlined with `underline`.
+    fn flush(&mut self, underline: Option<char>) {
+        let text = std::mem::take(&mut self.buffer);
+        if text.trim().is_empty() {
+            return;
+        }
+        let prefix = self.prefix();
+        // The first line of an item carries its marker instead of the indent.
+        let first_prefix = match self.marker.take() {
+            Some(marker) => format!("{}{marker}", &prefix[..prefix.len() - marker.len()]),
+            None => prefix.clone(),
+        };
+        let lines = wrap(text.trim(), self.width.saturating_sub(prefix.chars().count()).max(1));
+        let longest = lines.iter().map(|line| line.chars().count()).max().unwrap_or_default();
+        for (i, line) in lines.iter().enumerate() {
+            self.out.push_str(if i == 0 { &first_prefix } else { &prefix });
+            self.out.push_str(line);
+            self.out.push('\n');
+        }
+        if let Some(underline) = underline {
+            self.out.push_str(&prefix);
+            self.out.push_str(&underline.to_string().repeat(longest));
+            self.out.push('\n');
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        self.out.push_str(&self.prefix());
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            let prefix = self.prefix();
+            self.out.push_str(prefix.trim_end());
+            self.out.push('\n');
+        }
+    }
+
+    /// Lines of a code block, indented by four spaces and never wrapped.
+    fn code(&mut self, text: &str) {
+        for line in text.lines() {
+            self.line(&format!("    {line}").replace('\t', "    "));
+        }
+    }
+
+    /// Rows of cells aligned in columns, the header separated by dashes.
+    fn table(&mut self, rows: &[Vec<String>]) {
+        let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| rows.iter().filter_map(|row| row.get(column)).map(|cell| cell.trim().chars().count()).max().unwrap_or_default())
+            .collect();
+        for (i, row) in rows.iter().enumerate() {
+            let cells: Vec<String> = widths.iter().enumerate()
+                .map(|(column, width)| {
+                    let cell = row.get(column).map_or("", |cell| cell.trim());
+                    format!("{cell}{}", " ".repeat(width - cell.chars().count()))
+                })
+                .collect();
+            self.line(cells.join("  ").trim_end());
+            if i == 0 {
+                self.line(&widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>().join("  "));
+            }
+        }
+        self.blank_line();
+    }
+}
+
+/// Greedy word wrap; explicit line breaks are kept and longer words stay whole.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_plain_text() {
+        let markdown = "# Release 2.0\n\nThis release makes *everything* faster and fixes `many` bugs, see [the notes](https://example.com/notes).\n\n\
+            ## Changes\n\n- Faster builds with a much longer item that wraps\n- Fixes\n  1. One\n\n> Quoted text\n\n```\nlet x = 1;\n```\n\n\
+            | Version | Date |\n|---|---|\n| 2.0 | 2024-05-01 |\n";
+        assert_eq!(
+            to_plain_text(markdown, 30),
+            "Release 2.0\n===========\n\n\
+             This release makes everything\nfaster and fixes many bugs,\nsee the notes\n(https://example.com/notes).\n\n\
+             Changes\n-------\n\n\
+             - Faster builds with a much\n  longer item that wraps\n- Fixes\n  1. One\n\n\
+             > Quoted text\n\n    let x = 1;\n\n\
+             Version  Date\n-------  ----------\n2.0      2024-05-01\n"
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("a bb ccc\nunbreakable-word x", 5), ["a bb", "ccc", "unbreakable-word", "x"]);
+    }
+}
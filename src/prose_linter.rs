@@ -0,0 +1,95 @@
+use crate::check::{Diagnostic, Sources};
+use crate::error::AppError;
+use crate::ExternalLinterConfig;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Output format shared by `vale --output=line` and most other linters: `file:line[:col][:rule]:message`.
+const DEFAULT_OUTPUT_PATTERN: &str = r"^[^:]+:(?P<line>\d+):(?:\d+:)?(?:(?P<rule>[A-Za-z0-9_.\-]+):)?\s*(?P<message>.+)$";
+
+/// Runs an external prose linter against the fully expanded Markdown and maps its
+/// results back to the original source files.
+pub fn run_external_linter(linter: &ExternalLinterConfig, expanded_markdown: &str, sources: &Sources, build_dir: &Path) -> Result<Vec<Diagnostic>, AppError> {
+    let check_dir = build_dir.join("check");
+    fs::create_dir_all(&check_dir)?;
+    let expanded_path = check_dir.join("expanded.md");
+    fs::write(&expanded_path, expanded_markdown)?;
+
+    let expanded_path = expanded_path.display().to_string();
+    let mut args: Vec<String> = linter.command.split_whitespace().map(|arg| arg.replace("{file}", &expanded_path)).collect();
+    if !linter.command.contains("{file}") {
+        args.push(expanded_path.clone());
+    }
+    if args.is_empty() {
+        return Err(AppError::ExternalLinterError(linter.name.clone(), "empty command".to_string()));
+    }
+
+    let output = Command::new(&args[0])
+        .args(&args[1..])
+        .output()
+        .map_err(|e| AppError::ExternalLinterError(linter.name.clone(), e.to_string()))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Linters usually exit with a non-zero status when they report problems,
+    // so only treat it as a failure when nothing was written to stdout.
+    if !output.status.success() && stdout.trim().is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::ExternalLinterError(linter.name.clone(), stderr.trim().to_string()));
+    }
+
+    let pattern = linter.pattern.as_deref().unwrap_or(DEFAULT_OUTPUT_PATTERN);
+    let output_re = Regex::new(pattern).map_err(|e| AppError::ExternalLinterError(linter.name.clone(), format!("invalid pattern: {e}")))?;
+    Ok(parse_output(&linter.name, &stdout, &output_re, sources, &expanded_path))
+}
+
+/// Lines with no known origin are reported against `expanded_path`.
+fn parse_output(name: &str, output: &str, output_re: &Regex, sources: &Sources, expanded_path: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| output_re.captures(line.trim()))
+        .filter_map(|caps| {
+            let expanded_line: usize = caps.name("line")?.as_str().parse().ok()?;
+            let (file, line) = sources.origin(expanded_line).unwrap_or((expanded_path, expanded_line));
+            let rule = match caps.name("rule") {
+                Some(rule) => format!("{}:{}", name, rule.as_str()),
+                None => name.to_string(),
+            };
+            Some(Diagnostic {
+                file: file.to_string(),
+                line,
+                rule,
+                message: caps.name("message").map(|m| m.as_str().trim().to_string()).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::SourceFile;
+
+    #[test]
+    fn test_parse_vale_line_output() {
+        let sources = Sources {
+            files: vec![
+                SourceFile { path: "main.md".to_string(), content: String::new() },
+                SourceFile { path: "chapters/one.md".to_string(), content: String::new() },
+            ],
+            line_origins: vec![(0, 1), (1, 1), (1, 2), (0, 2)],
+        };
+        let output = "out/check/expanded.md:3:5:Vale.Spelling:Did you really mean 'teh'?\nnoise\nout/check/expanded.md:9:1:Vale.Terms:Use 'PDF'";
+        let output_re = Regex::new(DEFAULT_OUTPUT_PATTERN).unwrap();
+
+        let diagnostics = parse_output("vale", output, &output_re, &sources, "out/check/expanded.md");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file, "chapters/one.md");
+        assert_eq!(diagnostics[0].line, 2);
+        assert_eq!(diagnostics[0].rule, "vale:Vale.Spelling");
+        assert_eq!(diagnostics[0].message, "Did you really mean 'teh'?");
+        assert_eq!(diagnostics[1].file, "out/check/expanded.md");
+        assert_eq!(diagnostics[1].line, 9);
+    }
+}
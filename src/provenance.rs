@@ -0,0 +1,83 @@
+use crate::cache;
+use crate::check;
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::Config;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes `<output>.sha256` and `<output>.provenance.json` next to every output of a build,
+/// so that the files can be verified and traced back to the sources that made them.
+pub fn write_sidecars(config: &Config, outputs: &[PathBuf], git_info: &GitInfo, started: DateTime<Utc>) -> Result<(), AppError> {
+    let inputs = inputs(config)?;
+    let config_json = serde_json::to_string(config).map_err(|e| AppError::BuildError(e.to_string()))?;
+    for output in outputs {
+        let name = output.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let sha256 = cache::content_hash(fs::read(output)?);
+        fs::write(sidecar(output, "sha256"), format!("{sha256}  {name}\n"))?;
+        let provenance = provenance(&name, &sha256, &config_json, &inputs, git_info, started, Utc::now());
+        let content = serde_json::to_string_pretty(&provenance).map_err(|e| AppError::BuildError(e.to_string()))?;
+        fs::write(sidecar(output, "provenance.json"), content + "\n")?;
+    }
+    Ok(())
+}
+
+/// `<output>.<extension>`, keeping the extension of the output.
+fn sidecar(output: &Path, extension: &str) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+/// The files a build reads, with their SHA-256: 'config.yaml', the custom CSS and the
+/// Markdown sources with the files they include.
+fn inputs(config: &Config) -> Result<Vec<(String, String)>, AppError> {
+    let sources = check::config_sources(config)?;
+    let mut inputs: Vec<(String, String)> = sources.files.iter().map(|file| (file.path.clone(), cache::content_hash(&file.content))).collect();
+    for path in std::iter::once("config.yaml").chain(config.custom_css.as_deref()) {
+        if let Ok(content) = fs::read(path) {
+            inputs.push((path.to_string(), cache::content_hash(content)));
+        }
+    }
+    inputs.sort();
+    inputs.dedup();
+    Ok(inputs)
+}
+
+fn provenance(name: &str, sha256: &str, config_json: &str, inputs: &[(String, String)], git_info: &GitInfo, started: DateTime<Utc>, finished: DateTime<Utc>) -> Value {
+    // The lines of a `sha256sum` listing of the inputs, so the digest can be checked by hand.
+    let listing: String = inputs.iter().map(|(path, hash)| format!("{hash}  {path}\n")).collect();
+    json!({
+        "output": { "name": name, "sha256": sha256 },
+        "tool": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+        "config_sha256": cache::content_hash(config_json),
+        "inputs_sha256": cache::content_hash(listing),
+        "inputs": inputs.iter().map(|(path, hash)| json!({ "path": path, "sha256": hash })).collect::<Vec<_>>(),
+        "git": { "commit": git_info.commit, "tag": git_info.tag },
+        "build_started": started.to_rfc3339_opts(SecondsFormat::Secs, true),
+        "build_finished": finished.to_rfc3339_opts(SecondsFormat::Secs, true),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provenance() {
+        let inputs = vec![("chapter.md".to_string(), cache::content_hash("# One\n")), ("main.md".to_string(), cache::content_hash("!include chapter.md\n"))];
+        let git_info = GitInfo { commit: Some("abc1234".to_string()), tag: None, date: None };
+        let started = DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        let value = provenance("doc.pdf", "00ff", "{}", &inputs, &git_info, started, started + chrono::Duration::seconds(5));
+        assert_eq!(value["output"], json!({ "name": "doc.pdf", "sha256": "00ff" }));
+        assert_eq!(value["config_sha256"], cache::content_hash("{}"));
+        assert_eq!(value["inputs_sha256"], cache::content_hash(format!("{}  chapter.md\n{}  main.md\n", inputs[0].1, inputs[1].1)));
+        assert_eq!(value["inputs"][1]["path"], "main.md");
+        assert_eq!(value["git"], json!({ "commit": "abc1234", "tag": null }));
+        assert_eq!(value["build_finished"], "2024-05-01T10:00:05Z");
+        assert_eq!(sidecar(Path::new("build/doc.pdf"), "sha256"), PathBuf::from("build/doc.pdf.sha256"));
+    }
+}
@@ -0,0 +1,364 @@
+use crate::builder;
+use crate::cache;
+use crate::error::AppError;
+use crate::git::{self, GitInfo};
+use crate::i18n;
+use crate::network;
+use crate::Config;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use ureq::typestate::WithoutBody;
+use ureq::RequestBuilder;
+
+/// A destination of `PdfBuilder publish`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PublishTarget {
+    /// Folder receiving the files: `s3://bucket/path/`, `sftp://user@host/path/` or a WebDAV
+    /// `https://` URL, with the `{version}` and `{date}` placeholders and `{{ name }}` variables.
+    pub url: String,
+    /// Server of S3-compatible storage (MinIO, R2...), by default Amazon S3 in `region`.
+    pub endpoint: Option<String>,
+    #[serde(default = "default_region")]
+    pub region: String,
+    /// Extensions of the generated files to upload, e.g. `[pdf, html]`.
+    #[serde(default = "default_formats")]
+    pub formats: Vec<String>,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_formats() -> Vec<String> {
+    vec!["pdf".to_string()]
+}
+
+/// Uploads the files of the last build to every `publish` destination. Credentials come from
+/// the environment: `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` (and `AWS_SESSION_TOKEN`)
+/// for S3, `WEBDAV_USERNAME` and `WEBDAV_PASSWORD` for WebDAV, the SSH agent or keys for SFTP.
+pub fn run_publish(config: &Config) -> Result<(), AppError> {
+    if config.publish.is_empty() {
+        return Err(AppError::BuildError("No destination to publish to: add them under 'publish' in 'config.yaml'".to_string()));
+    }
+    let git_info = GitInfo::collect();
+    let name = builder::output_filename(config, &git_info);
+    for target in &config.publish {
+        let destination = builder::render_output_template(&target.url, config, &git_info);
+        for format in &target.formats {
+            let file = config.build_dir().join(format!("{name}.{format}"));
+            if !file.is_file() {
+                return Err(AppError::BuildError(format!("'{}' does not exist: build the document before publishing it", file.display())));
+            }
+            let url = format!("{}/{name}.{format}", destination.trim_end_matches('/'));
+            #[cfg(not(test))]
+            {
+                use colored::*;
+                println!("{} {} -> {}", "Uploading:".blue(), file.display().to_string().yellow(), url.yellow());
+            }
+            upload(target, &url, &file).map_err(|e| AppError::PublishError(url.clone(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Attaches the built PDFs (one per translation) and their `.sha256` checksum files to the
+/// existing GitHub release `tag`, replacing assets of the same name. The token comes from
+/// `GITHUB_TOKEN` (or `GH_TOKEN`), the repository from `GITHUB_REPOSITORY` or the `origin` remote.
+pub fn publish_github_release(config: &Config, tag: &str) -> Result<(), AppError> {
+    let release_error = |e: String| AppError::PublishError(format!("release {tag}"), e);
+    let token = ["GITHUB_TOKEN", "GH_TOKEN"].iter().find_map(|name| std::env::var(name).ok().filter(|token| !token.is_empty()))
+        .ok_or_else(|| release_error("set GITHUB_TOKEN to a token allowed to write the releases of the repository".to_string()))?;
+    let repository = std::env::var("GITHUB_REPOSITORY").ok().filter(|repository| !repository.is_empty())
+        .or_else(|| git::remote_url("origin").and_then(|url| github_repository(&url)))
+        .ok_or_else(|| release_error("set GITHUB_REPOSITORY to 'owner/repository'".to_string()))?;
+    let api = std::env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string());
+
+    let git_info = GitInfo::collect();
+    let mut files = Vec::new();
+    for config in i18n::language_configs(config, Some(i18n::ALL_LANGUAGES))? {
+        let file = config.build_dir().join(format!("{}.pdf", builder::output_filename(&config, &git_info)));
+        if !file.is_file() {
+            return Err(AppError::BuildError(format!("'{}' does not exist: build the document before publishing it", file.display())));
+        }
+        files.push(file);
+    }
+
+    let github = GitHub { token, api: api.trim_end_matches('/').to_string(), repository };
+    let release_url = format!("{}/repos/{}/releases/tags/{tag}", github.api, github.repository);
+    let release = github.call(network::agent(&release_url).get(&release_url)).map_err(release_error)?;
+    let upload_url = release["upload_url"].as_str().and_then(|url| url.split('{').next()).ok_or_else(|| release_error("no upload URL in the release".to_string()))?;
+    let existing: Vec<(String, u64)> = release["assets"].as_array().into_iter().flatten()
+        .filter_map(|asset| Some((asset["name"].as_str()?.to_string(), asset["id"].as_u64()?)))
+        .collect();
+
+    for file in files {
+        let content = fs::read(&file)?;
+        let name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let checksum = format!("{}  {name}\n", cache::content_hash(&content));
+        for (name, content, mime) in [(name.clone(), content, "application/pdf"), (format!("{name}.sha256"), checksum.into_bytes(), "text/plain")] {
+            #[cfg(not(test))]
+            {
+                use colored::*;
+                println!("{} {} -> {}", "Uploading:".blue(), name.yellow(), format!("release {tag}").yellow());
+            }
+            if let Some((_, id)) = existing.iter().find(|(existing, _)| *existing == name) {
+                let asset_url = format!("{}/repos/{}/releases/assets/{id}", github.api, github.repository);
+                github.call(network::agent(&asset_url).delete(&asset_url)).map_err(release_error)?;
+            }
+            let url = format!("{upload_url}?name={}", uri_encode(&name));
+            let upload = network::agent(&url).post(&url)
+                .header("Authorization", format!("Bearer {}", github.token))
+                .header("Accept", "application/vnd.github+json")
+                .header("Content-Type", mime);
+            upload.send(&content[..]).map_err(|e| AppError::PublishError(name.clone(), e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+struct GitHub {
+    token: String,
+    api: String,
+    repository: String,
+}
+
+impl GitHub {
+    /// Sends an API request without body and returns its JSON answer (`null` when empty).
+    fn call(&self, request: RequestBuilder<WithoutBody>) -> Result<Value, String> {
+        let response = request
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .call()
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body = response.into_body().read_to_string().map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<Value>(&body).ok().and_then(|json| json["message"].as_str().map(str::to_string)).unwrap_or(body);
+            return Err(format!("GitHub answered {status}: {message}"));
+        }
+        Ok(serde_json::from_str(&body).unwrap_or(Value::Null))
+    }
+}
+
+/// `owner/repository` of a GitHub remote URL (https or ssh).
+fn github_repository(url: &str) -> Option<String> {
+    let repository_re = Regex::new(r"github\.com[:/]([\w.-]+/[\w.-]+?)(?:\.git)?/?$").unwrap();
+    repository_re.captures(url.trim()).map(|caps| caps[1].to_string())
+}
+
+fn upload(target: &PublishTarget, url: &str, file: &Path) -> Result<(), String> {
+    if let Some(location) = url.strip_prefix("s3://") {
+        let (bucket, key) = location.split_once('/').ok_or("missing the object path after the bucket")?;
+        upload_s3(target, bucket, key, &fs::read(file).map_err(|e| e.to_string())?)
+    } else if let Some(location) = url.strip_prefix("sftp://") {
+        upload_sftp(location, file)
+    } else if url.starts_with("https://") || url.starts_with("http://") {
+        upload_webdav(url, &fs::read(file).map_err(|e| e.to_string())?)
+    } else {
+        Err("expected an s3://, sftp:// or https:// (WebDAV) destination".to_string())
+    }
+}
+
+fn upload_s3(target: &PublishTarget, bucket: &str, key: &str, content: &[u8]) -> Result<(), String> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let (Some(access_key), Some(secret_key)) = (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY")) else {
+        return Err("set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY".to_string());
+    };
+    let endpoint = target.endpoint.clone().unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", target.region));
+    let endpoint = endpoint.trim_end_matches('/');
+    let host = endpoint.split_once("://").map_or(endpoint, |(_, host)| host);
+    // Path-style addressing, which S3-compatible servers support too.
+    let path = format!("/{bucket}/{}", key.split('/').map(uri_encode).collect::<Vec<_>>().join("/"));
+
+    let now = chrono::Utc::now();
+    let request = SignedRequest {
+        host,
+        path: &path,
+        payload_hash: &hex(&Sha256::digest(content)),
+        amz_date: &now.format("%Y%m%dT%H%M%SZ").to_string(),
+        region: &target.region,
+        session_token: env("AWS_SESSION_TOKEN"),
+    };
+    let url = format!("{endpoint}{path}");
+    let mut put = network::agent(&url).put(&url)
+        .header("Authorization", request.authorization(&access_key, &secret_key))
+        .header("x-amz-content-sha256", request.payload_hash)
+        .header("x-amz-date", request.amz_date);
+    if let Some(token) = &request.session_token {
+        put = put.header("x-amz-security-token", token);
+    }
+    put.send(content).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// An S3 `PUT` signed with AWS Signature Version 4.
+struct SignedRequest<'a> {
+    host: &'a str,
+    path: &'a str,
+    payload_hash: &'a str,
+    amz_date: &'a str,
+    region: &'a str,
+    session_token: Option<String>,
+}
+
+impl SignedRequest<'_> {
+    fn authorization(&self, access_key: &str, secret_key: &str) -> String {
+        let mut headers = vec![("host", self.host), ("x-amz-content-sha256", self.payload_hash), ("x-amz-date", self.amz_date)];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token));
+        }
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{name}:{}\n", value.trim())).collect();
+        let canonical_request = format!("PUT\n{}\n\n{canonical_headers}\n{signed_headers}\n{}", self.path, self.payload_hash);
+
+        let date = &self.amz_date[..8];
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{scope}\n{}", self.amz_date, hex(&Sha256::digest(canonical_request.as_bytes())));
+        let signature = hex(&hmac_sha256(&signing_key(secret_key, date, self.region, "s3"), string_to_sign.as_bytes()));
+        format!("AWS4-HMAC-SHA256 Credential={access_key}/{scope}, SignedHeaders={signed_headers}, Signature={signature}")
+    }
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    [date, region, service, "aws4_request"].iter()
+        .fold(format!("AWS4{secret_key}").into_bytes(), |key, part| hmac_sha256(&key, part.as_bytes()))
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = if key.len() > BLOCK_SIZE { Sha256::digest(key).to_vec() } else { key.to_vec() };
+    block.resize(BLOCK_SIZE, 0);
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<u8>>();
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Percent-encodes a path segment, keeping the unreserved characters of RFC 3986.
+fn uri_encode(segment: &str) -> String {
+    segment.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Creates the missing folders of `url`, then uploads the file with `PUT`.
+fn upload_webdav(url: &str, content: &[u8]) -> Result<(), String> {
+    let authorization = match (std::env::var("WEBDAV_USERNAME"), std::env::var("WEBDAV_PASSWORD")) {
+        (Ok(user), Ok(password)) => Some(format!("Basic {}", STANDARD.encode(format!("{user}:{password}")))),
+        _ => None,
+    };
+    let (origin, path) = url.split_once("://").and_then(|(scheme, rest)| rest.find('/').map(|slash| (format!("{scheme}://{}", &rest[..slash]), &rest[slash..])))
+        .ok_or("missing the folder path")?;
+    let folders: Vec<&str> = path.trim_matches('/').split('/').collect();
+    for depth in 1..folders.len() {
+        let mut mkcol = ureq::http::Request::builder().method("MKCOL").uri(format!("{origin}/{}/", folders[..depth].join("/")));
+        if let Some(authorization) = &authorization {
+            mkcol = mkcol.header("Authorization", authorization);
+        }
+        match network::agent(url).run(mkcol.body(()).map_err(|e| e.to_string())?) {
+            // 405: the folder exists.
+            Ok(_) | Err(ureq::Error::StatusCode(405)) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    let mut put = network::agent(url).put(url);
+    if let Some(authorization) = &authorization {
+        put = put.header("Authorization", authorization);
+    }
+    put.send(content).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Uploads with the `sftp` command in batch mode, creating the missing folders first.
+fn upload_sftp(location: &str, file: &Path) -> Result<(), String> {
+    let (authority, path) = location.split_once('/').ok_or("missing the remote path")?;
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => (host, Some(port)),
+        _ => (authority, None),
+    };
+    let folders: Vec<&str> = path.trim_matches('/').split('/').collect();
+    // `-` ignores the error of folders that already exist.
+    let mut batch: String = (1..folders.len()).map(|depth| format!("-mkdir \"/{}\"\n", folders[..depth].join("/"))).collect();
+    batch.push_str(&format!("put \"{}\" \"/{}\"\n", file.display(), path.trim_start_matches('/')));
+
+    let mut command = Command::new("sftp");
+    command.args(["-b", "-", "-o", "BatchMode=yes"]);
+    if let Some(port) = port {
+        command.args(["-P", port]);
+    }
+    let mut child = command.arg(host)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run 'sftp': {e}"))?;
+    child.stdin.take().ok_or("no stdin")?.write_all(batch.as_bytes()).map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_and_signing_key() {
+        // RFC 4231, test case 2, and the key derivation example of the AWS documentation.
+        assert_eq!(hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        assert_eq!(
+            hex(&signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam")),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+        assert_eq!(uri_encode("guide v1.2+final.pdf"), "guide%20v1.2%2Bfinal.pdf");
+    }
+
+    #[test]
+    fn test_signed_request() {
+        let request = SignedRequest {
+            host: "s3.eu-west-1.amazonaws.com",
+            path: "/docs/guides/v1.2/guide.pdf",
+            payload_hash: "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            amz_date: "20240501T120000Z",
+            region: "eu-west-1",
+            session_token: None,
+        };
+        let authorization = request.authorization("AKIDEXAMPLE", "secret");
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240501/eu-west-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="
+        ));
+        assert_eq!(authorization.rsplit('=').next().unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_github_repository() {
+        assert_eq!(github_repository("git@github.com:htshongany/PdfBuilder.git").as_deref(), Some("htshongany/PdfBuilder"));
+        assert_eq!(github_repository("https://github.com/htshongany/PdfBuilder").as_deref(), Some("htshongany/PdfBuilder"));
+        assert_eq!(github_repository("https://gitlab.com/group/project.git"), None);
+    }
+
+    #[test]
+    fn test_upload_rejects_unknown_destinations() {
+        let target: PublishTarget = serde_yaml::from_str("url: ftp://example.com/docs/").unwrap();
+        assert_eq!(target.formats, ["pdf"]);
+        assert!(upload(&target, "ftp://example.com/docs/guide.pdf", Path::new("guide.pdf")).unwrap_err().contains("s3://"));
+    }
+}
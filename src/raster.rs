@@ -0,0 +1,126 @@
+use crate::error::AppError;
+use crate::imposition;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use headless_chrome::protocol::cdp::{Emulation, Page};
+use headless_chrome::Tab;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// Attempts at capturing a page of the PDF viewer until two captures in a row are the same.
+const VIEWER_CAPTURES: usize = 20;
+
+/// Renders every page of `pdf` to `<out_dir>/page-<n>.png` with Poppler's `pdftoppm` and
+/// returns the images in page order. Earlier images of `out_dir` are removed first.
+pub fn rasterize_pdf(pdf: &Path, out_dir: &Path, dpi: u32) -> Result<Vec<PathBuf>, AppError> {
+    rasterize_pages(pdf, out_dir, dpi, None)
+}
+
+/// `rasterize_pdf` stopping after `last_page`.
+pub fn rasterize_pages(pdf: &Path, out_dir: &Path, dpi: u32, last_page: Option<usize>) -> Result<Vec<PathBuf>, AppError> {
+    if out_dir.exists() {
+        fs::remove_dir_all(out_dir)?;
+    }
+    fs::create_dir_all(out_dir)?;
+    let mut command = Command::new("pdftoppm");
+    command.args(["-png", "-r", &dpi.to_string()]);
+    if let Some(last_page) = last_page {
+        command.args(["-l", &last_page.to_string()]);
+    }
+    let output = command
+        .arg(pdf)
+        .arg(out_dir.join("page"))
+        .output()
+        .map_err(|e| AppError::RasterizerError(format!("could not run 'pdftoppm': {e}")))?;
+    if !output.status.success() {
+        return Err(AppError::RasterizerError(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    // `pdftoppm` pads the page numbers to the width of the last one (page-01.png, page-001.png...).
+    let mut pages: Vec<(usize, PathBuf)> = fs::read_dir(out_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| page_number(&path).map(|number| (number, path)))
+        .collect();
+    pages.sort();
+    Ok(pages.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Renders the pages of `pdf`, served at `url`, to `<out_dir>/page-<n>.png` in the browser of
+/// `tab`: its PDF viewer shows each page alone in a window of the page size at `dpi`. Stops
+/// after `last_page`. Earlier images of `out_dir` are removed first.
+pub fn capture_pages(tab: &Tab, url: &str, pdf: &Path, out_dir: &Path, dpi: u32, last_page: Option<usize>) -> Result<Vec<PathBuf>, AppError> {
+    let error = |e: &dyn std::fmt::Display| AppError::BuildError(format!("Could not render the thumbnails: {e}"));
+    if out_dir.exists() {
+        fs::remove_dir_all(out_dir)?;
+    }
+    fs::create_dir_all(out_dir)?;
+    let sizes = imposition::page_sizes(&fs::read(pdf)?)?;
+    let mut images = Vec::new();
+    for (index, size) in sizes.iter().enumerate().take(last_page.unwrap_or(usize::MAX)) {
+        // Points to CSS pixels, the device pixels giving the resolution.
+        tab.call_method(Emulation::SetDeviceMetricsOverride {
+            width: (size.width * 96.0 / 72.0).round() as u32,
+            height: (size.height * 96.0 / 72.0).round() as u32,
+            device_scale_factor: f64::from(dpi) / 96.0,
+            mobile: false,
+            scale: None,
+            screen_width: None,
+            screen_height: None,
+            position_x: None,
+            position_y: None,
+            dont_set_visible_size: None,
+            screen_orientation: None,
+            viewport: None,
+            display_feature: None,
+            device_posture: None,
+        }).map_err(|e| error(&e))?;
+        // A new document for every page: a changed fragment alone does not reload the viewer.
+        tab.navigate_to("about:blank").and_then(|tab| tab.wait_until_navigated()).map_err(|e| error(&e))?;
+        tab.navigate_to(&format!("{url}#page={}&toolbar=0&view=Fit", index + 1)).and_then(|tab| tab.wait_until_navigated()).map_err(|e| error(&e))?;
+        let path = out_dir.join(format!("page-{}.png", index + 1));
+        fs::write(&path, capture_viewer(tab).map_err(|e| error(&e))?)?;
+        images.push(path);
+    }
+    Ok(images)
+}
+
+/// A PNG of the window once the viewer finished drawing the page, which it does after the
+/// document has loaded.
+fn capture_viewer(tab: &Tab) -> Result<Vec<u8>, String> {
+    let mut previous = None;
+    for _ in 0..VIEWER_CAPTURES {
+        std::thread::sleep(Duration::from_millis(250));
+        let screenshot = tab.call_method(Page::CaptureScreenshot {
+            format: Some(Page::CaptureScreenshotFormatOption::Png),
+            quality: None,
+            clip: None,
+            from_surface: Some(true),
+            capture_beyond_viewport: None,
+            optimize_for_speed: None,
+        }).map_err(|e| e.to_string())?;
+        if previous.as_ref() == Some(&screenshot.data) {
+            break;
+        }
+        previous = Some(screenshot.data);
+    }
+    STANDARD.decode(previous.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+fn page_number(path: &Path) -> Option<usize> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_prefix("page-")?.strip_suffix(".png")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_number() {
+        assert_eq!(page_number(Path::new("out/page-007.png")), Some(7));
+        assert_eq!(page_number(Path::new("out/page-12.png")), Some(12));
+        assert_eq!(page_number(Path::new("out/notes.png")), None);
+    }
+}
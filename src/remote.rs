@@ -0,0 +1,88 @@
+use crate::cache::content_hash;
+use crate::error::AppError;
+use crate::network;
+use crate::warnings;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub use pdfbuilder_core::preprocess::is_remote;
+
+/// How long a downloaded include is used before being downloaded again.
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The cache of downloaded includes, in the build directory.
+pub fn cache_dir(build_dir: &Path) -> PathBuf {
+    build_dir.join(".cache").join("remote")
+}
+
+fn cached_path(cache_dir: &Path, url: &str) -> PathBuf {
+    cache_dir.join(format!("{}.md", content_hash(url)))
+}
+
+/// The last downloaded copy of `url`, if any.
+pub fn cached(cache_dir: &Path, url: &str) -> Option<String> {
+    fs::read_to_string(cached_path(cache_dir, url)).ok()
+}
+
+/// Returns the Markdown at `url`. Copies younger than a day are reused without a request, and
+/// older ones stand in, with a warning, when the download fails, so builds also work offline.
+pub fn fetch(cache_dir: &Path, url: &str) -> Result<String, AppError> {
+    let path = cached_path(cache_dir, url);
+    let age = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()
+        .map(|modified| SystemTime::now().duration_since(modified).unwrap_or_default());
+    if age.is_some_and(|age| age < MAX_AGE) {
+        if let Some(content) = cached(cache_dir, url) {
+            return Ok(content);
+        }
+    }
+
+    match download(url) {
+        Ok(content) => {
+            fs::create_dir_all(cache_dir)?;
+            fs::write(&path, &content)?;
+            Ok(content)
+        }
+        Err(error) => match cached(cache_dir, url) {
+            Some(content) => {
+                warnings::warn("Remote include unavailable", format!("{error}; using the copy downloaded earlier"));
+                Ok(content)
+            }
+            None => Err(error),
+        },
+    }
+}
+
+fn download(url: &str) -> Result<String, AppError> {
+    #[cfg(not(test))]
+    {
+        use colored::*;
+        println!("{} {}", "Downloading:".blue(), url.yellow());
+    }
+    let response = network::agent(url).get(url).call().map_err(|e| AppError::BuildError(format!("Could not download '{url}': {e}")))?;
+    response.into_body().read_to_string().map_err(|e| AppError::BuildError(format!("Could not download '{url}': {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_fetch_uses_cached_copy() {
+        let test_dir = TestDir::new("remote_cache");
+        let dir = test_dir.path();
+        // Nothing listens on the discard port, so only the cache can answer.
+        let url = "http://127.0.0.1:9/boilerplate.md";
+        assert!(fetch(dir, url).is_err());
+
+        fs::write(cached_path(dir, url), "# Shared\n").unwrap();
+        assert_eq!(fetch(dir, url).unwrap(), "# Shared\n");
+
+        // A stale copy is still used when the download fails.
+        let file = fs::File::options().write(true).open(cached_path(dir, url)).unwrap();
+        file.set_modified(SystemTime::now() - 2 * MAX_AGE).unwrap();
+        assert_eq!(fetch(dir, url).unwrap(), "# Shared\n");
+        assert!(is_remote(url) && !is_remote("chapters/http.md"));
+    }
+}
@@ -0,0 +1,349 @@
+use crate::error::AppError;
+use crate::render::escape_html;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Compact styles of resumes, applied after the theme so a typical resume fits on one page.
+pub const RESUME_CSS: &str = r#"/* Resume */
+body { font-size: 10.5pt; line-height: 1.35; }
+.resume-header { text-align: center; margin-bottom: 0.8em; }
+.resume-header h1 { margin: 0; border: none; }
+.resume-header .resume-label { font-size: 1.15em; color: #555; margin: 0.2em 0; }
+.resume-header .resume-contact { font-size: 0.9em; margin: 0.2em 0; }
+.resume-photo { float: right; width: 2.8cm; border-radius: 50%; }
+h2 { font-size: 1.2em; margin: 0.9em 0 0.4em; padding-bottom: 0.1em; border-bottom: 1px solid #ccc; page-break-after: avoid; }
+h3 { font-size: 1em; margin: 0.6em 0 0.1em; page-break-after: avoid; }
+.resume-meta { font-size: 0.9em; color: #555; margin: 0 0 0.3em; }
+h2 + p, h3 + p, ul { margin-top: 0.2em; margin-bottom: 0.3em; }
+li { margin: 0.1em 0; }"#;
+
+/// Whether a source file is a structured resume rather than a document.
+pub fn is_resume(source: &Path) -> bool {
+    matches!(source.extension().and_then(|e| e.to_str()), Some("yaml" | "yml" | "json"))
+}
+
+/// A resume following the JSON Resume schema (https://jsonresume.org/schema), in YAML or JSON.
+/// Fields are optional; unknown ones are ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Resume {
+    basics: Basics,
+    work: Vec<Entry>,
+    volunteer: Vec<Entry>,
+    education: Vec<Education>,
+    projects: Vec<Entry>,
+    awards: Vec<Entry>,
+    certificates: Vec<Entry>,
+    publications: Vec<Entry>,
+    skills: Vec<Keywords>,
+    languages: Vec<Language>,
+    interests: Vec<Keywords>,
+    references: Vec<Reference>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Basics {
+    name: String,
+    label: String,
+    image: String,
+    email: String,
+    phone: String,
+    url: String,
+    summary: String,
+    location: Location,
+    profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Location {
+    city: String,
+    region: String,
+    country_code: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Profile {
+    network: String,
+    username: String,
+    url: String,
+}
+
+/// Work, volunteering, projects, awards, certificates and publications, which share most fields
+/// under different names.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Entry {
+    name: String,
+    position: String,
+    title: String,
+    organization: String,
+    awarder: String,
+    issuer: String,
+    publisher: String,
+    url: String,
+    location: String,
+    start_date: String,
+    end_date: String,
+    #[serde(alias = "releaseDate")]
+    date: String,
+    #[serde(alias = "description")]
+    summary: String,
+    highlights: Vec<String>,
+}
+
+impl Entry {
+    /// What the entry is (a position, an award...) and the organization behind it. `name` is
+    /// the organization of a job but the title of a project or a publication.
+    fn heading(&self) -> (&str, &str) {
+        let organization = first_non_empty(&[&self.organization, &self.awarder, &self.issuer, &self.publisher]);
+        match first_non_empty(&[&self.position, &self.title]) {
+            "" => (&self.name, organization),
+            title => (title, first_non_empty(&[organization, &self.name])),
+        }
+    }
+}
+
+fn first_non_empty<'a>(fields: &[&'a str]) -> &'a str {
+    fields.iter().copied().find(|field| !field.is_empty()).unwrap_or_default()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Education {
+    institution: String,
+    url: String,
+    area: String,
+    study_type: String,
+    start_date: String,
+    end_date: String,
+    score: String,
+    courses: Vec<String>,
+}
+
+/// Skills and interests.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Keywords {
+    name: String,
+    level: String,
+    keywords: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Language {
+    language: String,
+    fluency: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Reference {
+    name: String,
+    reference: String,
+}
+
+/// Converts a JSON Resume document into Markdown: a header with the contact details, then
+/// one section per non-empty part of the resume. Text fields may use Markdown.
+pub fn resume_to_markdown(source: &str, file_path: &str) -> Result<String, AppError> {
+    // JSON is valid YAML, so both forms are read the same way.
+    let resume: Resume = serde_yaml::from_str(source).map_err(|e| AppError::BuildError(format!("Invalid resume '{file_path}': {e}")))?;
+    let basics = &resume.basics;
+
+    let mut markdown = String::from("<header class=\"resume-header\">\n\n");
+    if !basics.image.is_empty() {
+        markdown.push_str(&format!("<img class=\"resume-photo\" src=\"{}\" alt=\"\">\n\n", escape_html(&basics.image)));
+    }
+    markdown.push_str(&format!("# {}\n\n", basics.name));
+    if !basics.label.is_empty() {
+        markdown.push_str(&format!("<p class=\"resume-label\">{}</p>\n\n", escape_html(&basics.label)));
+    }
+    let location = [&basics.location.city, &basics.location.region, &basics.location.country_code]
+        .into_iter().filter(|part| !part.is_empty()).map(String::as_str).collect::<Vec<_>>().join(", ");
+    let mut contact = vec![escape_html(&location)];
+    if !basics.email.is_empty() {
+        contact.push(format!("<a href=\"mailto:{0}\">{0}</a>", escape_html(&basics.email)));
+    }
+    contact.push(escape_html(&basics.phone));
+    contact.push(link(&basics.url, &basics.url));
+    for profile in &basics.profiles {
+        let text = if profile.username.is_empty() { profile.network.clone() } else { format!("{}: {}", profile.network, profile.username) };
+        contact.push(link(&text, &profile.url));
+    }
+    contact.retain(|part| !part.is_empty());
+    if !contact.is_empty() {
+        markdown.push_str(&format!("<p class=\"resume-contact\">{}</p>\n\n", contact.join(" · ")));
+    }
+    markdown.push_str("</header>\n\n");
+    push_paragraph(&mut markdown, &basics.summary);
+
+    push_entries(&mut markdown, "Experience", &resume.work);
+    if !resume.education.is_empty() {
+        markdown.push_str("## Education\n\n");
+        for education in &resume.education {
+            let degree = [&education.study_type, &education.area].into_iter().filter(|part| !part.is_empty()).map(String::as_str).collect::<Vec<_>>().join(", ");
+            push_heading(&mut markdown, &degree, &education.institution, &education.url);
+            let score = if education.score.is_empty() { String::new() } else { format!("Score: {}", escape_html(&education.score)) };
+            push_meta(&mut markdown, &[period(&education.start_date, &education.end_date), score]);
+            push_list(&mut markdown, &education.courses);
+        }
+    }
+    push_entries(&mut markdown, "Projects", &resume.projects);
+    push_entries(&mut markdown, "Volunteering", &resume.volunteer);
+    if !resume.skills.is_empty() {
+        markdown.push_str("## Skills\n\n");
+        push_keywords(&mut markdown, &resume.skills);
+    }
+    push_entries(&mut markdown, "Awards", &resume.awards);
+    push_entries(&mut markdown, "Certificates", &resume.certificates);
+    push_entries(&mut markdown, "Publications", &resume.publications);
+    if !resume.languages.is_empty() {
+        markdown.push_str("## Languages\n\n");
+        let languages: Vec<String> = resume.languages.iter()
+            .map(|language| if language.fluency.is_empty() { language.language.clone() } else { format!("**{}**: {}", language.language, language.fluency) })
+            .collect();
+        push_list(&mut markdown, &languages);
+    }
+    if !resume.interests.is_empty() {
+        markdown.push_str("## Interests\n\n");
+        push_keywords(&mut markdown, &resume.interests);
+    }
+    if !resume.references.is_empty() {
+        markdown.push_str("## References\n\n");
+        for reference in &resume.references {
+            markdown.push_str(&format!("> {}\n>\n> — {}\n\n", reference.reference.trim().replace('\n', "\n> "), reference.name));
+        }
+    }
+    Ok(markdown)
+}
+
+fn push_entries(markdown: &mut String, title: &str, entries: &[Entry]) {
+    if entries.is_empty() {
+        return;
+    }
+    markdown.push_str(&format!("## {title}\n\n"));
+    for entry in entries {
+        let (title, organization) = entry.heading();
+        push_heading(markdown, title, organization, &entry.url);
+        let date = if entry.date.is_empty() { period(&entry.start_date, &entry.end_date) } else { escape_html(&entry.date) };
+        push_meta(markdown, &[date, escape_html(&entry.location)]);
+        push_paragraph(markdown, &entry.summary);
+        push_list(markdown, &entry.highlights);
+    }
+}
+
+/// "Title, Organization" with the organization linked to its site.
+fn push_heading(markdown: &mut String, title: &str, organization: &str, url: &str) {
+    let organization = if url.is_empty() || organization.is_empty() { organization.to_string() } else { format!("[{organization}]({url})") };
+    let heading = [title, organization.as_str()].into_iter().filter(|part| !part.is_empty()).collect::<Vec<_>>().join(", ");
+    if !heading.is_empty() {
+        markdown.push_str(&format!("### {heading}\n\n"));
+    }
+}
+
+fn push_meta(markdown: &mut String, parts: &[String]) {
+    let parts: Vec<&str> = parts.iter().map(String::as_str).filter(|part| !part.is_empty()).collect();
+    if !parts.is_empty() {
+        markdown.push_str(&format!("<p class=\"resume-meta\">{}</p>\n\n", parts.join(" · ")));
+    }
+}
+
+fn push_paragraph(markdown: &mut String, text: &str) {
+    if !text.trim().is_empty() {
+        markdown.push_str(text.trim());
+        markdown.push_str("\n\n");
+    }
+}
+
+fn push_list(markdown: &mut String, items: &[String]) {
+    for item in items {
+        markdown.push_str(&format!("- {}\n", item.trim()));
+    }
+    if !items.is_empty() {
+        markdown.push('\n');
+    }
+}
+
+fn push_keywords(markdown: &mut String, groups: &[Keywords]) {
+    let items: Vec<String> = groups.iter().map(|group| {
+        let level = if group.level.is_empty() { String::new() } else { format!(" ({})", group.level) };
+        let keywords = if group.keywords.is_empty() { String::new() } else { format!(": {}", group.keywords.join(", ")) };
+        format!("**{}**{level}{keywords}", group.name)
+    }).collect();
+    push_list(markdown, &items);
+}
+
+/// "2019-04 – Present", "2019-04 – 2021-01" or a single date.
+fn period(start: &str, end: &str) -> String {
+    match (start.is_empty(), end.is_empty()) {
+        (true, true) => String::new(),
+        (true, false) => escape_html(end),
+        (false, true) => format!("{} – Present", escape_html(start)),
+        (false, false) => format!("{} – {}", escape_html(start), escape_html(end)),
+    }
+}
+
+fn link(text: &str, url: &str) -> String {
+    if url.is_empty() {
+        escape_html(text)
+    } else {
+        format!("<a href=\"{}\">{}</a>", escape_html(url), escape_html(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESUME: &str = r#"
+basics:
+  name: Ada Lovelace
+  label: Analyst & Programmer
+  email: ada@example.com
+  location: { city: London, countryCode: GB }
+  profiles:
+    - { network: GitHub, username: ada, url: "https://github.com/ada" }
+  summary: Writes the *first* programs.
+work:
+  - name: Analytical Engine
+    position: Programmer
+    url: https://example.com/engine
+    startDate: "1842-09"
+    highlights: [Note G, Bernoulli numbers]
+education:
+  - { institution: Home schooling, area: Mathematics, startDate: "1830", endDate: "1835" }
+skills:
+  - { name: Mathematics, level: Expert, keywords: [Calculus, Algebra] }
+projects:
+  - { name: Note G, description: An algorithm for the engine. }
+awards:
+  - { title: Honorary member, date: "1843", awarder: Royal Society }
+certificates:
+  - { name: Engine operator, issuer: Babbage & Co }
+"#;
+
+    #[test]
+    fn test_resume_to_markdown() {
+        let markdown = resume_to_markdown(RESUME, "resume.yaml").unwrap();
+        assert!(markdown.starts_with("<header class=\"resume-header\">\n\n# Ada Lovelace\n\n<p class=\"resume-label\">Analyst &amp; Programmer</p>"));
+        assert!(markdown.contains("<p class=\"resume-contact\">London, GB · <a href=\"mailto:ada@example.com\">ada@example.com</a> · <a href=\"https://github.com/ada\">GitHub: ada</a></p>"));
+        assert!(markdown.contains("</header>\n\nWrites the *first* programs.\n\n## Experience\n\n### Programmer, [Analytical Engine](https://example.com/engine)\n\n<p class=\"resume-meta\">1842-09 – Present</p>\n\n- Note G\n- Bernoulli numbers\n"));
+        assert!(markdown.contains("### Mathematics, Home schooling\n\n<p class=\"resume-meta\">1830 – 1835</p>"));
+        assert!(markdown.contains("## Skills\n\n- **Mathematics** (Expert): Calculus, Algebra\n"));
+        assert!(markdown.contains("### Honorary member, Royal Society\n\n<p class=\"resume-meta\">1843</p>"));
+        assert!(markdown.contains("## Projects\n\n### Note G\n\nAn algorithm for the engine.\n\n"));
+        assert!(markdown.contains("### Engine operator, Babbage & Co\n\n"));
+        assert!(!markdown.contains("## Publications"));
+    }
+
+    #[test]
+    fn test_resume_from_json() {
+        let markdown = resume_to_markdown(r#"{"basics": {"name": "Ada"}, "languages": [{"language": "French", "fluency": "Fluent"}]}"#, "resume.json").unwrap();
+        assert!(markdown.contains("# Ada\n\n</header>"));
+        assert!(markdown.contains("## Languages\n\n- **French**: Fluent\n"));
+        assert!(matches!(resume_to_markdown("basics: [", "resume.yaml"), Err(AppError::BuildError(_))));
+    }
+}
@@ -0,0 +1,392 @@
+use crate::chapter;
+use crate::directives::Directive;
+use crate::error::AppError;
+use crate::paths::SourceDir;
+use regex::Regex;
+use std::fs;
+
+/// An item of a Rust source file with its doc comment.
+#[derive(Debug, Default)]
+struct Item {
+    kind: String,
+    name: String,
+    /// The declaration, without the body.
+    signature: String,
+    docs: Vec<String>,
+    public: bool,
+    /// Fields of a struct, variants of an enum or functions of a trait or `impl` block.
+    members: Vec<Item>,
+    /// The trait of an `impl Trait for Type` block.
+    trait_name: Option<String>,
+}
+
+/// Returns the API docs of `!rustdoc(src/lib.rs, item=Config, level=3)` as Markdown, read from the
+/// `///` comments of the source file (relative to `source_dir`, inside the project): the named
+/// item with its fields, variants and methods, or without `item` the module docs and every
+/// documented public item.
+pub fn rustdoc_directive(source_dir: SourceDir, directive: &Directive) -> Result<String, AppError> {
+    directive.expect_positional(1)?;
+    let file = directive.value("file", 0)
+        .ok_or_else(|| AppError::BuildError("!rustdoc needs a Rust source file, e.g. !rustdoc(src/lib.rs)".to_string()))?;
+    let level = match directive.option("level") {
+        Some(level) => level.parse().ok().filter(|level| (1..=5).contains(level))
+            .ok_or_else(|| AppError::BuildError(format!("Invalid !rustdoc level '{level}', expected 1 to 5")))?,
+        None => 3,
+    };
+    let path = source_dir.dir.join(file);
+    let source = fs::read_to_string(source_dir.resolve(file)?).map_err(|e| AppError::BuildError(format!("Could not read Rust source '{}': {e}", path.display())))?;
+    render_rustdoc(&source, directive.option("item"), level)
+        .map_err(|e| AppError::BuildError(format!("{e} in '{}'", path.display())))
+}
+
+fn render_rustdoc(source: &str, item: Option<&str>, level: usize) -> Result<String, String> {
+    let (module_docs, items) = parse_items(source);
+    let methods = |name: &str| -> Vec<&Item> {
+        items.iter()
+            .filter(|item| item.kind == "impl" && item.name == name && item.trait_name.is_none())
+            .flat_map(|item| item.members.iter().filter(|method| method.public))
+            .collect()
+    };
+    let is_documented_api = |item: &&Item| item.kind != "impl" && item.kind != "mod";
+
+    match item {
+        Some(name) => {
+            let item = items.iter().filter(is_documented_api).find(|item| item.name == name)
+                .ok_or_else(|| format!("No item named '{name}'"))?;
+            Ok(render_item(item, &methods(name), level))
+        }
+        None => {
+            let mut sections = Vec::new();
+            if !module_docs.is_empty() {
+                sections.push(doc_markdown(&module_docs, level as i32 - 1));
+            }
+            for item in items.iter().filter(is_documented_api).filter(|item| item.public && !item.docs.is_empty()) {
+                sections.push(render_item(item, &methods(&item.name), level));
+            }
+            if sections.is_empty() {
+                return Err("No documented public items".to_string());
+            }
+            Ok(sections.join("\n\n"))
+        }
+    }
+}
+
+/// A heading with the kind and name of the item, its signature and docs, then its members.
+fn render_item(item: &Item, methods: &[&Item], level: usize) -> String {
+    let hashes = "#".repeat(level);
+    let kind = match item.kind.as_str() {
+        "fn" => "Function",
+        "struct" => "Struct",
+        "enum" => "Enum",
+        "trait" => "Trait",
+        "type" => "Type",
+        "const" => "Constant",
+        "static" => "Static",
+        "union" => "Union",
+        _ => "Item",
+    };
+    let mut markdown = format!("{hashes} {kind} `{}`\n\n```rust\n{}\n```", item.name, item.signature);
+    if !item.docs.is_empty() {
+        markdown.push_str(&format!("\n\n{}", doc_markdown(&item.docs, level as i32)));
+    }
+
+    let members: Vec<&Item> = item.members.iter().filter(|member| item.kind != "struct" || member.public).collect();
+    let (title, entries) = match item.kind.as_str() {
+        "struct" => ("Fields", members),
+        "enum" => ("Variants", members),
+        _ => ("", Vec::new()),
+    };
+    if !entries.is_empty() {
+        markdown.push_str(&format!("\n\n**{title}**\n"));
+        for entry in entries {
+            let docs = doc_markdown(&entry.docs, 0).split_whitespace().collect::<Vec<_>>().join(" ");
+            markdown.push_str(&format!("\n- `{}`{}", entry.signature, if docs.is_empty() { String::new() } else { format!(": {docs}") }));
+        }
+    }
+
+    // Trait functions and inherent methods each get a heading one level down.
+    let functions: Vec<&Item> = if item.kind == "trait" { item.members.iter().collect() } else { methods.to_vec() };
+    for function in functions {
+        markdown.push_str(&format!("\n\n{hashes}# `{}`\n\n```rust\n{}\n```", function.name, function.signature));
+        if !function.docs.is_empty() {
+            markdown.push_str(&format!("\n\n{}", doc_markdown(&function.docs, level as i32 + 1)));
+        }
+    }
+    markdown
+}
+
+/// The Markdown of a doc comment, its headings moved below the item's. Code blocks are Rust
+/// unless they say otherwise, without their hidden `# ` lines, and intra-doc links become code.
+fn doc_markdown(docs: &[String], shift: i32) -> String {
+    let link_re = Regex::new(r"\[(`[^`\]]+`)\]([^(\[]|$)").unwrap();
+    let mut lines = Vec::new();
+    let mut fence: Option<bool> = None;
+    for line in docs {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            match fence {
+                None => {
+                    let language = trimmed.trim_start_matches('`').trim();
+                    let rust = language.is_empty() || language.split(',').any(|tag| ["rust", "ignore", "no_run", "should_panic", "compile_fail"].contains(&tag.trim()));
+                    fence = Some(rust);
+                    lines.push(if rust { "```rust".to_string() } else { line.clone() });
+                }
+                Some(_) => {
+                    fence = None;
+                    lines.push(line.clone());
+                }
+            }
+            continue;
+        }
+        match fence {
+            Some(true) if trimmed == "#" || trimmed.starts_with("# ") => {}
+            Some(_) => lines.push(line.clone()),
+            None => lines.push(link_re.replace_all(line, "$1$2").into_owned()),
+        }
+    }
+    chapter::shift_headings(&lines.join("\n"), shift)
+}
+
+/// The `//!` module docs and the top-level items of a source file, with the members of
+/// structs, enums, traits and `impl` blocks. Inline modules and function bodies are skipped.
+fn parse_items(source: &str) -> (Vec<String>, Vec<Item>) {
+    let item_re = Regex::new(r#"^(pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|extern\s+"[^"]*")\s+)*(fn|struct|enum|trait|type|const|static|mod|union)\s+(?:mut\s+)?([A-Za-z_]\w*)"#).unwrap();
+    let impl_re = Regex::new(r"^(?:unsafe\s+)?impl\b(?:\s*<[^{]*?>)?\s+(?:([\w:]+)(?:<[^{]*?>)?\s+for\s+)?([\w:]+)").unwrap();
+    let field_re = Regex::new(r"^(pub(?:\([^)]*\))?\s+)?([A-Za-z_]\w*)\s*:\s*(.+?),?$").unwrap();
+    let variant_re = Regex::new(r"^[A-Z]\w*\b").unwrap();
+
+    let lines: Vec<&str> = source.lines().collect();
+    let mut module_docs = Vec::new();
+    let mut items: Vec<Item> = Vec::new();
+    let mut docs: Vec<String> = Vec::new();
+    let mut depth = 0usize;
+    // The body being read: the index of its item and the depth inside it.
+    let mut body: Option<(usize, usize)> = None;
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let trimmed = line.trim();
+        let container = body.filter(|(_, body_depth)| depth == *body_depth).map(|(item, _)| items[item].kind.clone());
+        index += 1;
+
+        if let Some(doc) = trimmed.strip_prefix("///").filter(|_| !trimmed.starts_with("////")) {
+            docs.push(doc.strip_prefix(' ').unwrap_or(doc).to_string());
+            continue;
+        }
+        if let Some(doc) = trimmed.strip_prefix("//!").filter(|_| depth == 0) {
+            module_docs.push(doc.strip_prefix(' ').unwrap_or(doc).to_string());
+            continue;
+        }
+
+        let header = if depth == 0 || matches!(container.as_deref(), Some("impl" | "trait")) {
+            item_re.captures(trimmed).map(|caps| Item {
+                kind: caps[2].to_string(),
+                name: caps[3].to_string(),
+                // Trait functions are as public as the trait.
+                public: caps.get(1).is_some() || container.as_deref() == Some("trait"),
+                ..Item::default()
+            })
+        } else {
+            None
+        };
+        let header = header.or_else(|| impl_re.captures(trimmed).filter(|_| depth == 0).map(|caps| Item {
+            kind: "impl".to_string(),
+            name: caps[2].rsplit("::").next().unwrap_or_default().to_string(),
+            trait_name: caps.get(1).map(|name| name.as_str().to_string()),
+            ..Item::default()
+        }));
+
+        if let Some(mut item) = header {
+            // The signature runs up to the body or the final semicolon, over several lines if needed.
+            let stop: &[char] = if matches!(item.kind.as_str(), "const" | "static" | "type") { &[';'] } else { &['{', ';'] };
+            let indent = line.len() - line.trim_start().len();
+            let mut signature = Vec::new();
+            let mut code = String::new();
+            for part in &lines[index - 1..] {
+                let part_code = code_of(part);
+                let cut = part_code.find(stop);
+                let text = &part[..cut.unwrap_or(part.len())];
+                let dedented = if text.len() >= indent && text[..indent].trim().is_empty() { &text[indent..] } else { text.trim_start() };
+                signature.push(dedented.trim_end());
+                code.push_str(&part_code);
+                if cut.is_some() {
+                    break;
+                }
+                index += 1;
+            }
+            index = index.min(lines.len());
+            item.signature = signature.join("\n").trim().to_string();
+            item.docs = std::mem::take(&mut docs);
+
+            let before = depth;
+            depth = braces(depth, &code);
+            if before == 0 {
+                items.push(item);
+                if depth > 0 {
+                    body = Some((items.len() - 1, depth));
+                }
+            } else if let Some((container, _)) = body {
+                items[container].members.push(item);
+            }
+            continue;
+        }
+
+        if trimmed.starts_with("#[") || trimmed.is_empty() {
+            // Attributes and blank lines keep the doc comment for the next item.
+        } else if container.as_deref() == Some("struct") {
+            if let Some(caps) = field_re.captures(trimmed) {
+                let (item, _) = body.unwrap();
+                items[item].members.push(Item {
+                    name: caps[2].to_string(),
+                    signature: format!("{}: {}", &caps[2], caps[3].trim()),
+                    docs: std::mem::take(&mut docs),
+                    public: caps.get(1).is_some(),
+                    ..Item::default()
+                });
+            }
+        } else if container.as_deref() == Some("enum") && variant_re.is_match(trimmed) {
+            let (item, _) = body.unwrap();
+            items[item].members.push(Item {
+                name: variant_re.find(trimmed).unwrap().as_str().to_string(),
+                signature: trimmed.trim_end_matches(['{', ',']).trim().to_string(),
+                docs: std::mem::take(&mut docs),
+                public: true,
+                ..Item::default()
+            });
+        } else {
+            docs.clear();
+        }
+
+        depth = braces(depth, &code_of(line));
+        if body.is_some_and(|(_, body_depth)| depth < body_depth) {
+            body = None;
+        }
+    }
+    (module_docs, items)
+}
+
+/// The depth after the braces of `code`.
+fn braces(depth: usize, code: &str) -> usize {
+    (depth + code.matches('{').count()).saturating_sub(code.matches('}').count())
+}
+
+/// A line with its comment cut and the content of its string and character literals blanked,
+/// so braces are only those of the code. Positions stay those of the line.
+fn code_of(line: &str) -> String {
+    let mut code = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    let mut in_string = false;
+    let blank = |code: &mut String, c: char| code.push_str(&" ".repeat(c.len_utf8()));
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                blank(&mut code, c);
+                if let Some(escaped) = chars.next() {
+                    blank(&mut code, escaped);
+                }
+            }
+            '"' => {
+                in_string = !in_string;
+                code.push(c);
+            }
+            '/' if !in_string && chars.peek() == Some(&'/') => break,
+            // A character literal such as '{' or '\'', not a lifetime.
+            '\'' if !in_string => {
+                code.push(c);
+                let literal: Vec<char> = chars.clone().take(3).collect();
+                let length = match literal.as_slice() {
+                    ['\\', _, '\'', ..] => 2,
+                    [_, '\'', ..] => 1,
+                    _ => 0,
+                };
+                for _ in 0..length {
+                    let c = chars.next().unwrap();
+                    blank(&mut code, c);
+                }
+            }
+            c if in_string => blank(&mut code, c),
+            c => code.push(c),
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"//! Tools to build documents.
+
+use std::fmt;
+
+/// Settings of a build.
+///
+/// # Examples
+///
+/// ```
+/// # use crate::Config;
+/// let config = Config::new("Guide");
+/// ```
+#[derive(Debug)]
+pub struct Config {
+    /// Title of the document, see [`Config::new`].
+    pub title: String,
+    secret: u32,
+}
+
+impl Config {
+    /// Creates the settings of a document titled `title`.
+    pub fn new(title: &str) -> Self {
+        let braces = "{ not a body }";
+        Config { title: title.to_string(), secret: braces.len() as u32 }
+    }
+
+    fn private(&self) {}
+}
+
+impl fmt::Display for Config {
+    /// Not listed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.title)
+    }
+}
+
+/// Output formats.
+pub enum Format {
+    /// A PDF file.
+    Pdf,
+    Html { inline: bool },
+}
+
+/// Builds the document.
+pub fn build(
+    config: &Config,
+    format: Format,
+) -> Result<(), String> {
+    Ok(())
+}
+
+fn helper() {}
+"#;
+
+    #[test]
+    fn test_render_item() {
+        assert_eq!(
+            render_rustdoc(SOURCE, Some("Config"), 3).unwrap(),
+            "### Struct `Config`\n\n```rust\npub struct Config\n```\n\nSettings of a build.\n\n#### Examples\n\n```rust\nlet config = Config::new(\"Guide\");\n```\n\n\
+             **Fields**\n\n- `title: String`: Title of the document, see `Config::new`.\n\n\
+             #### `new`\n\n```rust\npub fn new(title: &str) -> Self\n```\n\nCreates the settings of a document titled `title`."
+        );
+        assert!(render_rustdoc(SOURCE, Some("Missing"), 3).is_err());
+    }
+
+    #[test]
+    fn test_render_module() {
+        let markdown = render_rustdoc(SOURCE, None, 2).unwrap();
+        assert!(markdown.starts_with("Tools to build documents.\n\n## Struct `Config`"));
+        assert!(markdown.contains("## Enum `Format`\n\n```rust\npub enum Format\n```\n\nOutput formats.\n\n**Variants**\n\n- `Pdf`: A PDF file.\n- `Html { inline: bool }`"));
+        assert!(markdown.ends_with("## Function `build`\n\n```rust\npub fn build(\n    config: &Config,\n    format: Format,\n) -> Result<(), String>\n```\n\nBuilds the document."));
+        assert!(!markdown.contains("helper"));
+        assert!(!markdown.contains("Not listed"));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::browser::BrowserConfig;
+use crate::builder;
+use crate::cache::Cache;
+use crate::directives::Directive;
+use crate::error::AppError;
+use crate::render::escape_html;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+use headless_chrome::types::Bounds;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Pages `!screenshot` may capture, and the default window size.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScreenshotsConfig {
+    /// Hosts that may be captured: `app.example.com`, or `*.example.com` for its subdomains.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+}
+
+fn default_width() -> u32 {
+    1280
+}
+
+fn default_height() -> u32 {
+    800
+}
+
+impl Default for ScreenshotsConfig {
+    fn default() -> Self {
+        Self { allowed_hosts: Vec::new(), width: default_width(), height: default_height() }
+    }
+}
+
+/// Returns the screenshot of `!screenshot(url, width=1280, height=800, selector=#main, alt=...)`
+/// as an embedded PNG image: the window of a page, or one element of it. The URL (which may
+/// contain commas) is everything before the first option. Screenshots are kept in `cache`,
+/// keyed by the URL and the options, so only new or changed directives open the browser.
+pub fn screenshot_directive(directive: &Directive, config: &ScreenshotsConfig, browser: &BrowserConfig, cache: &Cache) -> Result<String, AppError> {
+    let url = directive.data(0).unwrap_or_default();
+    if url.is_empty() {
+        return Err(AppError::BuildError("!screenshot needs the address of a page, e.g. !screenshot(https://app.example.com)".to_string()));
+    }
+    let host = host(&url).ok_or_else(|| AppError::BuildError(format!("Cannot capture '{url}': only http and https pages can be captured")))?;
+    if !is_allowed(config, &host) {
+        return Err(AppError::BuildError(format!("Cannot capture '{url}': add '{host}' to 'screenshots.allowed_hosts' in 'config.yaml'")));
+    }
+    let size = |name: &str, default: u32| -> Result<u32, AppError> {
+        match directive.option(name) {
+            Some(value) => value.trim_end_matches("px").parse().ok().filter(|size| (100..=4000).contains(size))
+                .ok_or_else(|| AppError::BuildError(format!("Invalid !screenshot {name} '{value}', expected 100 to 4000 pixels"))),
+            None => Ok(default),
+        }
+    };
+    let (width, height) = (size("width", config.width)?, size("height", config.height)?);
+    let selector = directive.option("selector");
+
+    let key = format!("{url}\n{width}x{height}\n{}", selector.unwrap_or_default());
+    let png = cache.get_or_insert_with("screenshots", &key, "png", || capture(browser, config, &url, width, height, selector))?;
+    Ok(format!(
+        r#"<img class="screenshot" src="data:image/png;base64,{}" alt="{}">"#,
+        STANDARD.encode(png),
+        escape_html(directive.option("alt").unwrap_or(&url))
+    ))
+}
+
+/// Opens the page in a browser window of `width` by `height` and captures the window, or the
+/// element matching `selector`. The page must still be on an allowed host once loaded, after
+/// any redirect.
+fn capture(browser: &BrowserConfig, config: &ScreenshotsConfig, url: &str, width: u32, height: u32, selector: Option<&str>) -> Result<Vec<u8>, AppError> {
+    #[cfg(not(test))]
+    {
+        use colored::*;
+        println!("{} {}", "Capturing screenshot:".blue(), url.yellow());
+    }
+    let error = |e: &dyn std::fmt::Display| AppError::BuildError(format!("Could not capture '{url}': {e}"));
+    let browser = builder::launch_browser(browser)?;
+    let tab = browser.new_tab().map_err(|e| error(&e))?;
+    tab.set_default_timeout(Duration::from_secs(30));
+    tab.set_bounds(Bounds::Normal { left: Some(0), top: Some(0), width: Some(width as f64), height: Some(height as f64) }).map_err(|e| error(&e))?;
+    tab.navigate_to(url).and_then(|tab| tab.wait_until_navigated()).map_err(|e| error(&e))?;
+    let landed = tab.get_url();
+    if !host(&landed).is_some_and(|host| is_allowed(config, &host)) {
+        return Err(error(&format!("redirected to '{landed}', which is not in 'screenshots.allowed_hosts'")));
+    }
+    let png = match selector {
+        Some(selector) => tab.wait_for_element(selector).and_then(|element| element.capture_screenshot(CaptureScreenshotFormatOption::Png)),
+        None => tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true),
+    };
+    png.map_err(|e| error(&e))
+}
+
+/// The host of an http or https URL, lowercase and without port or credentials.
+fn host(url: &str) -> Option<String> {
+    let host_re = Regex::new(r"(?i)^https?://(?:[^@/?#]*@)?(\[[^\]]*\]|[^:/?#]+)").unwrap();
+    host_re.captures(url).map(|caps| caps[1].to_lowercase())
+}
+
+fn is_allowed(config: &ScreenshotsConfig, host: &str) -> bool {
+    config.allowed_hosts.iter().any(|allowed| host_matches(allowed, host))
+}
+
+fn host_matches(allowed: &str, host: &str) -> bool {
+    let allowed = allowed.trim().to_lowercase();
+    match allowed.strip_prefix("*.") {
+        Some(domain) => host.strip_suffix(domain).is_some_and(|subdomain| subdomain.ends_with('.')),
+        None => allowed == host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use crate::directives;
+
+    #[test]
+    fn test_allowed_hosts() {
+        assert_eq!(host("https://user:pw@App.Example.com:8080/dashboard?a=1").as_deref(), Some("app.example.com"));
+        assert_eq!(host("file:///etc/passwd"), None);
+        assert!(host_matches("app.example.com", "app.example.com"));
+        assert!(host_matches("*.example.com", "app.example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "badexample.com"));
+        let config = ScreenshotsConfig { allowed_hosts: vec!["app.example.com".to_string()], ..ScreenshotsConfig::default() };
+        assert!(is_allowed(&config, "app.example.com"));
+        assert!(!host("http://169.254.169.254/latest/meta-data").is_some_and(|host| is_allowed(&config, &host)));
+    }
+
+    #[test]
+    fn test_screenshot_directive_uses_cache() {
+        let test_dir = TestDir::new("screenshot");
+        let dir = test_dir.path();
+        let cache = Cache::new(dir);
+        let config = ScreenshotsConfig { allowed_hosts: vec!["*.example.com".to_string()], ..ScreenshotsConfig::default() };
+        cache.get_or_insert_with("screenshots", "https://app.example.com/?a=1,b=2\n1024x800\n#main", "png", || Ok(b"png".to_vec())).unwrap();
+
+        let screenshot = |line: &str| screenshot_directive(&directives::parse(line).unwrap().unwrap(), &config, &BrowserConfig::default(), &cache);
+        assert_eq!(
+            screenshot("!screenshot(https://app.example.com/?a=1,b=2, width=1024, selector=#main, alt=\"The dashboard\")").unwrap(),
+            r#"<img class="screenshot" src="data:image/png;base64,cG5n" alt="The dashboard">"#
+        );
+        assert!(screenshot("!screenshot(https://other.org/)").unwrap_err().to_string().contains("screenshots.allowed_hosts"));
+        assert!(screenshot("!screenshot(https://app.example.com/, width=huge)").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File of a series directory, holding the settings its books share.
+pub const SERIES_FILE: &str = "series.yaml";
+
+/// Books built together (the volumes of a course, a collection), each a project in a
+/// subdirectory of the series.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SeriesConfig {
+    /// Directories of the books, in build order. By default every subdirectory holding a
+    /// 'config.yaml', in alphabetical order.
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// Settings of 'config.yaml' inherited by every book, which may override them.
+    #[serde(default)]
+    pub defaults: Mapping,
+}
+
+/// The series directory of a project: the closest parent of `project_root` holding a 'series.yaml'.
+pub fn find_series_dir(project_root: &Path) -> Option<PathBuf> {
+    project_root.ancestors().skip(1).find(|dir| dir.join(SERIES_FILE).is_file()).map(Path::to_path_buf)
+}
+
+pub fn read_series(series_dir: &Path) -> Result<SeriesConfig, AppError> {
+    let path = series_dir.join(SERIES_FILE);
+    let content = fs::read_to_string(&path)?;
+    serde_yaml::from_str(&content).map_err(|e| AppError::BuildError(format!("Invalid '{}': {e}", path.display())))
+}
+
+/// The project directories of the series, in build order.
+pub fn members(series_dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let series = read_series(series_dir)?;
+    if !series.members.is_empty() {
+        return series.members.iter()
+            .map(|member| {
+                let dir = series_dir.join(member);
+                if dir.join("config.yaml").is_file() {
+                    Ok(dir)
+                } else {
+                    Err(AppError::BuildError(format!("Series member '{member}' has no 'config.yaml'")))
+                }
+            })
+            .collect();
+    }
+    let mut members: Vec<PathBuf> = fs::read_dir(series_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|dir| dir.join("config.yaml").is_file())
+        .collect();
+    members.sort();
+    if members.is_empty() {
+        return Err(AppError::BuildError(format!("No book in the series at '{}': its subdirectories have no 'config.yaml'", series_dir.display())));
+    }
+    Ok(members)
+}
+
+/// The 'config.yaml' of a book with the defaults of its series filled in. Nested settings
+/// are merged key by key; lists and other values of the book replace those of the series.
+pub fn with_defaults(config: Value, defaults: &Mapping) -> Value {
+    merge(Value::Mapping(defaults.clone()), config)
+}
+
+fn merge(base: Value, overrides: Value) -> Value {
+    match (base, overrides) {
+        (Value::Mapping(mut base), Value::Mapping(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base_value) => *base_value = merge(std::mem::take(base_value), value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+            Value::Mapping(base)
+        }
+        (_, overrides) => overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_with_defaults() {
+        let series: SeriesConfig = serde_yaml::from_str(
+            "defaults:\n  author: Ada\n  theme: course\n  output:\n    dir: build\n    filename: volume\n  back_matter: [about.md]\n",
+        ).unwrap();
+        let config: Value = serde_yaml::from_str("title: Volume 2\noutput:\n  filename: volume-2\nback_matter: []\n").unwrap();
+        let merged = with_defaults(config, &series.defaults);
+        assert_eq!(
+            serde_yaml::to_string(&merged).unwrap(),
+            "author: Ada\ntheme: course\noutput:\n  dir: build\n  filename: volume-2\nback_matter: []\ntitle: Volume 2\n"
+        );
+    }
+
+    #[test]
+    fn test_members() {
+        let test_dir = TestDir::new("series_members");
+        let dir = test_dir.path();
+        for volume in ["vol-2", "vol-1", "shared"] {
+            fs::create_dir_all(dir.join(volume)).unwrap();
+        }
+        fs::write(dir.join("vol-1/config.yaml"), "").unwrap();
+        fs::write(dir.join("vol-2/config.yaml"), "").unwrap();
+        fs::write(dir.join(SERIES_FILE), "defaults:\n  author: Ada\n").unwrap();
+        assert_eq!(members(dir).unwrap(), [dir.join("vol-1"), dir.join("vol-2")]);
+        assert_eq!(find_series_dir(&dir.join("vol-1")), Some(dir.to_path_buf()));
+
+        fs::write(dir.join(SERIES_FILE), "members: [vol-2, shared]\n").unwrap();
+        assert!(members(dir).unwrap_err().to_string().contains("'shared' has no 'config.yaml'"));
+    }
+}
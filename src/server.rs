@@ -0,0 +1,264 @@
+use crate::browser_pool::{self, BrowserPool, PoolOptions};
+use crate::builder::{self, Includes, ThemeFiles};
+use crate::cancel::CancelToken;
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::render;
+use crate::variables;
+use crate::warnings;
+use crate::{Config, OutputFormat};
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use colored::*;
+use indicatif::ProgressBar;
+use miette::Diagnostic;
+use serde::Deserialize;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Settings used outside a project, where there is no `config.yaml`.
+const DEFAULT_CONFIG: &str = r#"
+title: "Document"
+author: ""
+language: "en"
+theme: "default"
+syntax_theme: "InspiredGitHub"
+output:
+  filename: "document"
+"#;
+
+/// Body of `POST /convert`.
+#[derive(Debug, Deserialize)]
+pub struct ConvertRequest {
+    pub markdown: String,
+    #[serde(default)]
+    pub options: ConvertOptions,
+}
+
+/// Overrides of the server configuration for one document.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct ConvertOptions {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub language: Option<String>,
+    pub theme: Option<String>,
+    pub syntax_theme: Option<String>,
+    pub numbering: Option<bool>,
+    /// Styles appended after the theme.
+    pub css: Option<String>,
+}
+
+struct ServerState {
+    /// `config.yaml` of the project the server runs in, or `DEFAULT_CONFIG`.
+    base_config: Config,
+    /// Browsers kept running between requests.
+    pool: BrowserPool,
+    next_id: AtomicUsize,
+}
+
+/// Serves the conversion API: `POST /convert` turns Markdown into a PDF, `POST /preview` into
+/// its HTML page, and `GET /health` reports that the server is up. `config` is the project
+/// configuration, if the server runs in a project, whose theme and settings apply to every
+/// document. `pool` bounds the number of documents printed at the same time.
+pub async fn run_server(config: Option<Config>, host: &str, port: u16, pool: PoolOptions) -> Result<(), AppError> {
+    let base_config = match config {
+        Some(config) => config,
+        None => serde_yaml::from_str(DEFAULT_CONFIG)?,
+    };
+    let pool = BrowserPool::new(pool, base_config.browser.clone());
+    let state = Arc::new(ServerState { base_config, pool, next_id: AtomicUsize::new(0) });
+    // Launching a browser up front keeps the first request fast.
+    if let Err(e) = state.pool.warm_up() {
+        println!("{} {}", "Warning:".yellow(), e.to_string().yellow());
+    }
+    let pruned = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            pruned.pool.prune_idle();
+        }
+    });
+
+    let app = Router::new()
+        .route("/health", get(|| async { Json(serde_json::json!({ "status": "ok" })) }))
+        .route("/convert", post(convert))
+        .route("/preview", post(preview))
+        .with_state(state);
+    let addr: SocketAddr = format!("{host}:{port}").parse().map_err(|e| AppError::BuildError(format!("Invalid address '{host}:{port}': {e}")))?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("{} {}", "PdfBuilder server listening on".green(), format!("http://{addr}").yellow());
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn convert(State(state): State<Arc<ServerState>>, Json(request): Json<ConvertRequest>) -> Response {
+    let work_dir = work_dir(&state);
+    let result = convert_document(state, work_dir.clone(), request).await;
+    fs::remove_dir_all(&work_dir).ok();
+    respond(result, "application/pdf")
+}
+
+async fn preview(State(state): State<Arc<ServerState>>, Json(request): Json<ConvertRequest>) -> Response {
+    let work_dir = work_dir(&state);
+    let result = preview_document(&state, &work_dir, request);
+    fs::remove_dir_all(&work_dir).ok();
+    respond(result, "text/html; charset=utf-8")
+}
+
+/// A directory of its own for each request.
+fn work_dir(state: &ServerState) -> PathBuf {
+    let id = state.next_id.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join("pdfbuilder-server").join(format!("{}-{id}", std::process::id()))
+}
+
+/// Warnings are not reported by the API: raised outside a build's collection, they are only printed.
+fn respond(result: Result<impl IntoResponse, AppError>, content_type: &'static str) -> Response {
+    match result {
+        Ok(body) => ([(header::CONTENT_TYPE, content_type)], body).into_response(),
+        Err(e) => {
+            let code = e.code().map(|code| code.to_string()).unwrap_or_default();
+            (error_status(&e), Json(serde_json::json!({ "code": code, "error": e.to_string() }))).into_response()
+        }
+    }
+}
+
+/// Writes the submitted Markdown to `work_dir` and expands its directives, returning the
+/// configuration of the document and its Markdown.
+fn expand_request(state: &ServerState, work_dir: &Path, request: &ConvertRequest) -> Result<(Config, String), AppError> {
+    fs::create_dir_all(work_dir)?;
+    let source = work_dir.join("main.md");
+    fs::write(&source, &request.markdown)?;
+    let config = request_config(&state.base_config, &request.options, &source, &work_dir.join("build"));
+    let includes = request_includes(&config)?;
+    let vars = variables::builtin_variables(&config, &GitInfo::default());
+    let markdown = builder::expand_with_includes(work_dir, &source, &config, &vars, includes)?;
+    Ok((config, markdown))
+}
+
+/// The include state of a submitted document. Includes are resolved inside the work directory
+/// only, so a document cannot read server files, and whatever the project allows its own
+/// documents, a request may not download includes or take screenshots, and its `!html`
+/// fragments are sanitized. Images are looked up in the project, whose assets are copied next
+/// to the page.
+fn request_includes(config: &Config) -> Result<Includes, AppError> {
+    Ok(Includes::new(false).with_limits(config.limits).with_image_root(std::env::current_dir()?))
+}
+
+/// Builds the document in `work_dir`, returning the PDF. Rendering and printing block, so they
+/// run on a thread of their own rather than on the threads serving the requests.
+async fn convert_document(state: Arc<ServerState>, work_dir: PathBuf, request: ConvertRequest) -> Result<Vec<u8>, AppError> {
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let (config, markdown) = expand_request(&state, &work_dir, &request)?;
+        let git_info = GitInfo::default();
+        let cancel = CancelToken::default();
+        let html_path = builder::render_html(&config, markdown, &git_info, request.options.css.as_deref().unwrap_or_default(), OutputFormat::Pdf, &cancel)?;
+        let browser = runtime.block_on(state.pool.get())?;
+        // Concurrent requests would draw over each other's progress bars.
+        let printed = runtime.block_on(builder::build_pdf_from_html(&browser, &html_path, &config, &git_info, &cancel, &ProgressBar::hidden()));
+        if printed.is_err() && !browser_pool::is_healthy(&browser) {
+            browser.discard();
+        }
+        printed?;
+        Ok(fs::read(html_path.with_extension("pdf"))?)
+    })
+    .await
+    .map_err(|e| AppError::BuildError(e.to_string()))?
+}
+
+/// The HTML page of the document, rendered in memory without diagrams or copied assets.
+fn preview_document(state: &ServerState, work_dir: &Path, request: ConvertRequest) -> Result<String, AppError> {
+    let (config, markdown) = expand_request(state, work_dir, &request)?;
+    let theme = ThemeFiles::read(&config);
+    let vars = variables::builtin_variables(&config, &GitInfo::default());
+    let (page, page_warnings) = render::render_preview(&config.render_config(), &markdown, &theme.styles(request.options.css.as_deref().unwrap_or_default()), &vars)?;
+    warnings::report(page_warnings);
+    Ok(page)
+}
+
+fn request_config(base: &Config, options: &ConvertOptions, source: &Path, build_dir: &Path) -> Config {
+    let mut config = base.clone();
+    config.source = source.display().to_string();
+    config.book = None;
+    config.translations.clear();
+    config.output.filename = "document".to_string();
+    config.output.dir = build_dir.display().to_string();
+    config.output.archive_dir = None;
+    config.thumbnails.enabled = false;
+    let override_with = |field: &mut String, value: &Option<String>| {
+        if let Some(value) = value {
+            field.clone_from(value);
+        }
+    };
+    override_with(&mut config.title, &options.title);
+    override_with(&mut config.author, &options.author);
+    override_with(&mut config.language, &options.language);
+    override_with(&mut config.theme, &options.theme);
+    override_with(&mut config.syntax_theme, &options.syntax_theme);
+    if let Some(numbering) = options.numbering {
+        config.numbering = numbering;
+    }
+    config
+}
+
+/// Server-side failures are 500s; problems with the submitted document are 422s.
+fn error_status(error: &AppError) -> StatusCode {
+    match error {
+        AppError::BrowserLaunchFailed(_) | AppError::IoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::UNPROCESSABLE_ENTITY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_request_config() {
+        let base: Config = serde_yaml::from_str(DEFAULT_CONFIG).unwrap();
+        let options: ConvertOptions = serde_json::from_str(r#"{"title": "Invoice", "numbering": true}"#).unwrap();
+        let config = request_config(&base, &options, Path::new("/tmp/job/main.md"), Path::new("/tmp/job/build"));
+        assert_eq!(config.title, "Invoice");
+        assert_eq!(config.language, "en");
+        assert!(config.numbering);
+        assert_eq!(config.build_dir(), PathBuf::from("/tmp/job/build"));
+    }
+
+    #[test]
+    fn test_request_includes_are_locked_down() {
+        let test_dir = TestDir::new("server_request_includes");
+        let work_dir = test_dir.path();
+        let mut config: Config = serde_yaml::from_str(DEFAULT_CONFIG).unwrap();
+        config.allow_remote_includes = true;
+        config.sanitize_html_fragments = false;
+        fs::write(work_dir.join("fragment.html"), "<p onclick=\"steal()\">Hi</p><script>steal()</script>").unwrap();
+        let vars = variables::builtin_variables(&config, &GitInfo::default());
+        let expand = |markdown: &str| {
+            let source = work_dir.join("main.md");
+            fs::write(&source, markdown).unwrap();
+            builder::expand_with_includes(work_dir, &source, &config, &vars, request_includes(&config).unwrap())
+        };
+
+        assert!(expand("!include(http://127.0.0.1:9/shared.md)").unwrap_err().to_string().contains("allow_remote_includes"));
+        assert!(expand("!screenshot(http://127.0.0.1:9/)").unwrap_err().to_string().contains("!screenshot"));
+        let fragment = expand("!html(fragment.html)").unwrap();
+        assert!(fragment.contains("<p>Hi</p>"));
+        assert!(!fragment.contains("steal"));
+    }
+
+    #[test]
+    fn test_error_status() {
+        assert_eq!(error_status(&AppError::BrowserLaunchFailed("no Chrome".to_string())), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error_status(&AppError::IncludeCycle(vec!["a.md".to_string(), "a.md".to_string()])), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
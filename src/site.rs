@@ -0,0 +1,222 @@
+use crate::error::AppError;
+use crate::i18n;
+use crate::render::{escape_html, SITE_BODY_END, SITE_BODY_START};
+use crate::sidebar;
+use crate::Config;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Styles of the navigation added to the site pages.
+const SITE_CSS: &str = r#"/* Site navigation */
+.site-nav { display: flex; justify-content: space-between; gap: 1em; margin: 3em 0 1em; padding-top: 1em; border-top: 1px solid #ccc; }
+.site-nav a { text-decoration: none; }
+.site-nav .next { margin-left: auto; text-align: right; }
+.site-contents ol { padding-left: 1.5em; }
+.site-contents li { margin: 0.3em 0; }"#;
+
+/// A page of the site: a chapter, or the content before the first one for the landing page.
+struct Page {
+    file: String,
+    title: String,
+    body: String,
+}
+
+/// Pages written by the last build, in `build/.cache/site.json`.
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct SiteIndex {
+    files: Vec<String>,
+}
+
+/// Splits the page of the document into one page per chapter, next to it in `dir`, with
+/// previous/next links and an `index.html` landing page listing the chapters. Chapters are the
+/// chapter sections of the document (book chapters and included files) or, without any, its
+/// `<h1>` headings. Links to anchors moved to another page are rewritten, and `sitemap.xml` is
+/// written when `html.canonical_url` gives the address of the site.
+pub fn write_site(page: &str, dir: &Path, config: &Config) -> Result<(), AppError> {
+    let (Some(start), Some(end)) = (page.find(SITE_BODY_START), page.rfind(SITE_BODY_END)) else {
+        return Err(AppError::BuildError("The page template has no document body to split into a site".to_string()));
+    };
+    let (shell_start, body, shell_end) = (&page[..start], &page[start + SITE_BODY_START.len()..end], &page[end + SITE_BODY_END.len()..]);
+    let shell_start = shell_start.replacen("</head>", &format!("<style>{SITE_CSS}</style></head>"), 1);
+
+    let chunks = split_chapters(body);
+    let mut used: HashSet<String> = HashSet::from(["index".to_string()]);
+    let mut pages = vec![Page { file: "index.html".to_string(), title: config.title.clone(), body: chunks[0].to_string() }];
+    for (number, chunk) in chunks[1..].iter().enumerate() {
+        let title = first_heading(chunk).unwrap_or_else(|| format!("{} {}", config.title, number + 1));
+        let slug = sidebar::unique_slug(&title, &mut used);
+        used.insert(slug.clone());
+        pages.push(Page { file: format!("{slug}.html"), title, body: chunk.to_string() });
+    }
+
+    // Anchors now live on one of the pages.
+    let id_re = Regex::new(r#"\bid="([^"]+)""#).unwrap();
+    let mut anchors = HashMap::new();
+    for page in &pages {
+        for caps in id_re.captures_iter(&page.body) {
+            anchors.entry(caps[1].to_string()).or_insert_with(|| page.file.clone());
+        }
+    }
+    let link_re = Regex::new(r##"href="#([^"]+)""##).unwrap();
+    let canonical_re = Regex::new(r#"<link rel="canonical" href="[^"]*">"#).unwrap();
+    let base_url = config.html.canonical_url.as_deref().map(|url| &url[..url.rfind('/').map_or(url.len(), |i| i + 1)]);
+    let contents_title = i18n::toc_title(&config.language);
+
+    for (i, page) in pages.iter().enumerate() {
+        let body = link_re.replace_all(&page.body, |caps: &Captures| match anchors.get(&caps[1]) {
+            Some(file) if *file != page.file => format!("href=\"{file}#{}\"", &caps[1]),
+            _ => caps[0].to_string(),
+        });
+        let mut html = shell_start.clone();
+        if i > 0 {
+            html = html.replacen("<title>", &format!("<title>{} - ", escape_html(&page.title)), 1);
+        }
+        if let Some(base_url) = base_url {
+            html = canonical_re.replace(&html, format!("<link rel=\"canonical\" href=\"{}\">", escape_html(&format!("{base_url}{}", page.file)))).into_owned();
+        }
+        html.push_str(&body);
+        if i == 0 {
+            html.push_str(&format!("\n<nav class=\"site-contents\"><h2>{contents_title}</h2><ol>"));
+            for chapter in &pages[1..] {
+                html.push_str(&format!("<li><a href=\"{}\">{}</a></li>", chapter.file, escape_html(&chapter.title)));
+            }
+            html.push_str("</ol></nav>\n");
+        } else {
+            html.push_str("\n<nav class=\"site-nav\">");
+            if i > 1 {
+                html.push_str(&format!("<a class=\"prev\" href=\"{}\">&larr; {}</a>", pages[i - 1].file, escape_html(&pages[i - 1].title)));
+            }
+            html.push_str(&format!("<a class=\"home\" href=\"index.html\">{contents_title}</a>"));
+            if let Some(next) = pages.get(i + 1) {
+                html.push_str(&format!("<a class=\"next\" href=\"{}\">{} &rarr;</a>", next.file, escape_html(&next.title)));
+            }
+            html.push_str("</nav>\n");
+        }
+        html.push_str(shell_end);
+        fs::write(dir.join(&page.file), html)?;
+    }
+
+    let mut files: Vec<String> = pages.iter().map(|page| page.file.clone()).collect();
+    if let Some(base_url) = base_url {
+        let mut sitemap = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        for page in &pages {
+            sitemap.push_str(&format!("  <url><loc>{}</loc></url>\n", escape_html(&format!("{base_url}{}", page.file))));
+        }
+        sitemap.push_str("</urlset>\n");
+        fs::write(dir.join("sitemap.xml"), sitemap)?;
+        files.push("sitemap.xml".to_string());
+    }
+
+    // Pages of chapters since removed or renamed go.
+    let index_path = dir.join(".cache").join("site.json");
+    let previous: SiteIndex = fs::read_to_string(&index_path).ok().and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default();
+    for stale in previous.files.iter().filter(|file| !files.contains(file)) {
+        fs::remove_file(dir.join(stale)).ok();
+    }
+    fs::create_dir_all(dir.join(".cache"))?;
+    let json = serde_json::to_string(&SiteIndex { files }).map_err(|e| AppError::BuildError(e.to_string()))?;
+    fs::write(&index_path, json)?;
+    Ok(())
+}
+
+/// The content before the first chapter, then each chapter. Content between two chapter
+/// sections (e.g. a part divider) goes with the next one.
+fn split_chapters(body: &str) -> Vec<&str> {
+    let section_re = Regex::new(r"<section\b[^>]*>|</section>").unwrap();
+    let mut boundaries = Vec::new();
+    let mut depth = 0usize;
+    let mut in_chapter = false;
+    for tag in section_re.find_iter(body) {
+        if tag.as_str() == "</section>" {
+            depth = depth.saturating_sub(1);
+            if depth == 0 && in_chapter {
+                in_chapter = false;
+                boundaries.push(tag.end());
+            }
+            continue;
+        }
+        if depth == 0 && is_chapter_section(tag.as_str()) {
+            // The first chapter starts the first page; later ones start after the previous chapter.
+            if boundaries.is_empty() {
+                boundaries.push(tag.start());
+            }
+            in_chapter = true;
+        }
+        depth += 1;
+    }
+    // The content after the last chapter stays with it.
+    if boundaries.len() > 1 {
+        boundaries.pop();
+    }
+    if boundaries.is_empty() {
+        boundaries = body.match_indices("<h1").map(|(i, _)| i).collect();
+    }
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for boundary in boundaries {
+        chunks.push(&body[start..boundary]);
+        start = boundary;
+    }
+    chunks.push(&body[start..]);
+    chunks
+}
+
+fn is_chapter_section(tag: &str) -> bool {
+    let class_re = Regex::new(r#"\bclass="([^"]*)""#).unwrap();
+    let chapter = class_re.captures(tag).is_some_and(|classes| classes[1].split_whitespace().any(|class| class == "chapter"));
+    chapter
+}
+
+/// The text of the first heading of a chapter.
+fn first_heading(html: &str) -> Option<String> {
+    let heading_re = Regex::new(r"(?s)<h[1-6][^>]*>(.*?)</h[1-6]>").unwrap();
+    let tag_re = Regex::new(r"<[^>]+>").unwrap();
+    let text = heading_re.captures(html).map(|caps| tag_re.replace_all(&caps[1], "").trim().to_string())?;
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_write_site() {
+        let test_dir = TestDir::new("site");
+        let dir = test_dir.path();
+        fs::write(dir.join("old-chapter.html"), "").unwrap();
+        fs::create_dir_all(dir.join(".cache")).unwrap();
+        fs::write(dir.join(".cache").join("site.json"), r#"{"files":["old-chapter.html"]}"#).unwrap();
+        let config: Config = serde_yaml::from_str("title: Guide\nauthor: Ada\nlanguage: en\ntheme: default\nsyntax_theme: InspiredGitHub\nsource: main.md\n\
+            output:\n  filename: guide\nhtml:\n  site: true\n  canonical_url: https://example.com/guide/guide.html\n").unwrap();
+        let page = format!(
+            "<html><head><link rel=\"canonical\" href=\"https://example.com/guide/guide.html\"><title>Guide</title></head><body>{SITE_BODY_START}<h1>Guide</h1>\
+             <section class=\"chapter\" data-src=\"a.md\"><h1 id=\"intro\">Intro</h1><section class=\"scope-1\"><p><a href=\"#usage\">Usage</a></p></section></section>\n\
+             <div class=\"part\">Part II</div>\n<section class=\"chapter\" data-src=\"b.md\"><h1 id=\"usage\">Usage</h1><p><a href=\"#usage\">here</a></p></section>{SITE_BODY_END}</body></html>"
+        );
+        write_site(&page, dir, &config).unwrap();
+
+        let index = fs::read_to_string(dir.join("index.html")).unwrap();
+        assert!(index.contains("<h1>Guide</h1>\n<nav class=\"site-contents\"><h2>Table of Contents</h2><ol><li><a href=\"intro.html\">Intro</a></li><li><a href=\"usage.html\">Usage</a></li></ol></nav>"));
+        let intro = fs::read_to_string(dir.join("intro.html")).unwrap();
+        assert!(intro.contains("<title>Intro - Guide</title>"));
+        assert!(intro.contains("<link rel=\"canonical\" href=\"https://example.com/guide/intro.html\">"));
+        assert!(intro.contains("<a href=\"usage.html#usage\">Usage</a></p></section></section>\n<nav class=\"site-nav\"><a class=\"home\""));
+        assert!(intro.ends_with("<a class=\"next\" href=\"usage.html\">Usage &rarr;</a></nav>\n</body></html>"));
+        let usage = fs::read_to_string(dir.join("usage.html")).unwrap();
+        assert!(usage.contains("<body>\n<div class=\"part\">Part II</div>\n<section class=\"chapter\" data-src=\"b.md\"><h1 id=\"usage\">Usage</h1><p><a href=\"#usage\">here</a>"));
+        assert!(usage.contains("<a class=\"prev\" href=\"intro.html\">&larr; Intro</a>"));
+        assert!(fs::read_to_string(dir.join("sitemap.xml")).unwrap().contains("<loc>https://example.com/guide/usage.html</loc>"));
+        assert!(!dir.join("old-chapter.html").exists());
+    }
+
+    #[test]
+    fn test_split_chapters_by_headings() {
+        assert_eq!(split_chapters("<p>Cover</p><h1>One</h1><p>a</p><h1>Two</h1>"), ["<p>Cover</p>", "<h1>One</h1><p>a</p>", "<h1>Two</h1>"]);
+        assert_eq!(split_chapters("<p>Only</p>"), ["<p>Only</p>"]);
+    }
+}
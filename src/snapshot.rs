@@ -0,0 +1,128 @@
+use crate::builder;
+use crate::cache::content_hash;
+use crate::cancel::CancelToken;
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::pagemap;
+use crate::raster;
+use crate::{Config, OutputFormat};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the committed snapshots, at the project root.
+const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Resolution of the page images, enough to notice layout changes while keeping them small.
+const RASTER_DPI: u32 = 50;
+
+/// What the built PDF is expected to look like, as recorded by `test --update`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub pages: usize,
+    /// Text of each page, one trimmed line per non-empty line of the page.
+    pub text: Vec<String>,
+    /// SHA-256 of the image of each page, with `--raster`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raster: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct TestOptions {
+    /// Records the current output as the new snapshot instead of comparing.
+    pub update: bool,
+    /// Also compares images of the pages.
+    pub raster: bool,
+}
+
+/// Builds the PDF and compares it with `snapshots/<language>.json`, or records it with `update`.
+pub async fn run_test(config: &Config, options: &TestOptions) -> Result<(), AppError> {
+    builder::run_build(config, OutputFormat::Pdf, &CancelToken::default()).await?;
+    let build_dir = config.build_dir();
+    let pdf_path = build_dir.join(format!("{}.pdf", builder::output_filename(config, &GitInfo::collect())));
+    let pdf = fs::read(&pdf_path)?;
+
+    let snapshot_path = Path::new(SNAPSHOT_DIR).join(format!("{}.json", config.language));
+    // Page images are kept next to the snapshot when recording it, so they can be reviewed.
+    let raster_dir = if options.update { Path::new(SNAPSHOT_DIR).join(&config.language) } else { build_dir.join("snapshot") };
+    let raster = if options.raster { raster::rasterize_pdf(&pdf_path, &raster_dir, RASTER_DPI)? } else { Vec::new() };
+    let actual = take_snapshot(&pdf, &raster)?;
+
+    if options.update {
+        fs::create_dir_all(SNAPSHOT_DIR)?;
+        let json = serde_json::to_string_pretty(&actual).map_err(|e| AppError::BuildError(e.to_string()))?;
+        fs::write(&snapshot_path, json + "\n")?;
+        println!("{} {}", "Snapshot recorded:".green(), snapshot_path.display().to_string().yellow());
+        return Ok(());
+    }
+
+    let differences = match fs::read_to_string(&snapshot_path) {
+        Ok(json) => {
+            let expected: Snapshot = serde_json::from_str(&json)
+                .map_err(|e| AppError::BuildError(format!("Invalid snapshot '{}': {e}", snapshot_path.display())))?;
+            compare(&expected, &actual, &raster)
+        }
+        Err(_) => vec![format!("No snapshot at '{}'.", snapshot_path.display())],
+    };
+    for difference in &differences {
+        println!("{} {}", "✗".red(), difference);
+    }
+    if !differences.is_empty() {
+        return Err(AppError::SnapshotMismatch(differences.len()));
+    }
+    println!("{} {}", "✓".green(), "The document matches its snapshot.".green());
+    Ok(())
+}
+
+fn take_snapshot(pdf: &[u8], raster: &[PathBuf]) -> Result<Snapshot, AppError> {
+    let text: Vec<String> = pagemap::extract_page_texts(pdf)?.iter().map(|page| pagemap::normalize_text(page)).collect();
+    let raster = raster.iter().map(fs::read).map(|image| image.map(content_hash)).collect::<Result<_, _>>()?;
+    Ok(Snapshot { pages: text.len(), text, raster })
+}
+
+/// Describes how `actual` differs from `expected`. `raster` are the page images of `actual`.
+fn compare(expected: &Snapshot, actual: &Snapshot, raster: &[PathBuf]) -> Vec<String> {
+    let mut differences = Vec::new();
+    if expected.pages != actual.pages {
+        differences.push(format!("{} page(s) instead of {}.", actual.pages, expected.pages));
+    }
+    for (index, (old, new)) in expected.text.iter().zip(&actual.text).enumerate() {
+        if old != new {
+            let (old_line, new_line) = old.lines().zip(new.lines()).find(|(old, new)| old != new)
+                .unwrap_or_else(|| (old.lines().last().unwrap_or_default(), new.lines().last().unwrap_or_default()));
+            differences.push(format!("Page {}: text changed from '{old_line}' to '{new_line}'.", index + 1));
+        }
+    }
+    // Images are only compared when both sides have them, i.e. with `--raster` on both runs.
+    for (index, (old, new)) in expected.raster.iter().zip(&actual.raster).enumerate() {
+        if old != new && expected.text.get(index) == actual.text.get(index) {
+            let image = raster.get(index).map(|path| path.display().to_string()).unwrap_or_default();
+            differences.push(format!("Page {}: rendering changed (see '{image}').", index + 1));
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(text: &[&str], raster: &[&str]) -> Snapshot {
+        Snapshot { pages: text.len(), text: text.iter().map(|page| page.to_string()).collect(), raster: raster.iter().map(|hash| hash.to_string()).collect() }
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected = snapshot(&["Title\nIntro", "Chapter"], &["a", "b"]);
+        assert!(compare(&expected, &snapshot(&["Title\nIntro", "Chapter"], &["a", "b"]), &[]).is_empty());
+        assert!(compare(&expected, &snapshot(&["Title\nIntro", "Chapter"], &[]), &[]).is_empty());
+
+        let differences = compare(&expected, &snapshot(&["Title\nIntroduction", "Chapter", "Index"], &["a", "c", "d"]), &[PathBuf::from("a.png"), PathBuf::from("c.png")]);
+        assert_eq!(differences, [
+            "3 page(s) instead of 2.",
+            "Page 1: text changed from 'Intro' to 'Introduction'.",
+            "Page 2: rendering changed (see 'c.png').",
+        ]);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::AppError;
+use crate::render::{escape_html, PageStyles, DEFAULT_THEME_CSS};
+use crate::variables;
+use crate::Config;
+use base64::Engine;
+use headless_chrome::protocol::cdp::Page;
+use headless_chrome::Tab;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Size of the card, as recommended for Open Graph and Twitter images.
+const CARD_WIDTH: f64 = 1200.0;
+const CARD_HEIGHT: f64 = 630.0;
+
+/// Page of the card. The theme styles come first, so the card uses the fonts and colors of the
+/// document. Custom templates receive the same variables.
+const DEFAULT_CARD_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{ language }}">
+<head>
+<meta charset="UTF-8">
+<style>
+{{ css }}
+html, body { margin: 0; padding: 0; }
+.social-card { box-sizing: border-box; width: 1200px; height: 630px; padding: 72px; display: flex; align-items: center; gap: 56px; overflow: hidden; }
+.social-card-text { flex: 1; }
+.social-card h1 { font-size: 64px; line-height: 1.1; margin: 0 0 24px; border: none; }
+.social-card-author { font-size: 32px; opacity: 0.8; margin: 0; }
+.social-card-description { font-size: 28px; opacity: 0.7; margin: 24px 0 0; }
+.social-card-cover { max-width: 420px; max-height: 486px; }
+</style>
+</head>
+<body>
+<div class="social-card">
+<div class="social-card-text">
+<h1>{{ title }}</h1>
+<p class="social-card-author">{{ author }}</p>
+{{ description }}
+</div>
+{{ cover }}
+</div>
+</body>
+</html>"#;
+
+/// HTML page of the social preview card: title, author, description and cover art.
+pub fn card_html(config: &Config, styles: &PageStyles, template: Option<&str>) -> String {
+    let mut css = styles.theme_css.unwrap_or(DEFAULT_THEME_CSS).to_string();
+    if let Some(custom_css) = styles.custom_css {
+        css.push('\n');
+        css.push_str(custom_css);
+    }
+    let mut vars = HashMap::new();
+    vars.insert("css".to_string(), css);
+    vars.insert("language".to_string(), escape_html(&config.language));
+    vars.insert("title".to_string(), escape_html(&config.title));
+    vars.insert("author".to_string(), escape_html(&config.author));
+    let description = config.html.description.as_deref()
+        .map(|description| format!("<p class=\"social-card-description\">{}</p>", escape_html(description)));
+    vars.insert("description".to_string(), description.unwrap_or_default());
+    let cover = config.social_card.cover.as_deref()
+        .map(|cover| format!("<img class=\"social-card-cover\" src=\"{}\" alt=\"\">", escape_html(cover)));
+    vars.insert("cover".to_string(), cover.unwrap_or_default());
+    variables::render_template(template.unwrap_or(DEFAULT_CARD_TEMPLATE), &vars)
+}
+
+/// Loads the card served at `url` and saves a screenshot of it to `png_path`.
+pub fn capture_card(tab: &Tab, url: &str, png_path: &Path) -> Result<(), AppError> {
+    tab.navigate_to(url).map_err(|e| AppError::BuildError(e.to_string()))?;
+    tab.wait_until_navigated().map_err(|e| AppError::BuildError(e.to_string()))?;
+    let screenshot = tab.call_method(Page::CaptureScreenshot {
+        format: Some(Page::CaptureScreenshotFormatOption::Png),
+        quality: None,
+        clip: Some(Page::Viewport { x: 0.0, y: 0.0, width: CARD_WIDTH, height: CARD_HEIGHT, scale: 1.0 }),
+        from_surface: Some(true),
+        // The card is wider than the default window.
+        capture_beyond_viewport: Some(true),
+        optimize_for_speed: None,
+    }).map_err(|e| AppError::BuildError(format!("Could not capture the social card: {e}")))?;
+    let png = base64::engine::general_purpose::STANDARD.decode(screenshot.data)
+        .map_err(|e| AppError::BuildError(format!("Could not capture the social card: {e}")))?;
+    fs::write(png_path, png)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_html() {
+        let mut config: Config = serde_yaml::from_str("title: \"Rust & PDFs\"\nauthor: Ada\nlanguage: en\ntheme: default\nsyntax_theme: InspiredGitHub\nsource: main.md\noutput: { filename: doc }\nsocial_card: { cover: assets/cover.png }").unwrap();
+        let html = card_html(&config, &PageStyles { theme_css: Some("body { color: navy; }"), ..Default::default() }, None);
+        assert!(html.contains("body { color: navy; }\nhtml, body"));
+        assert!(html.contains("<h1>Rust &amp; PDFs</h1>\n<p class=\"social-card-author\">Ada</p>\n\n</div>"));
+        assert!(html.contains("<img class=\"social-card-cover\" src=\"assets/cover.png\" alt=\"\">"));
+
+        config.html.description = Some("A guide".to_string());
+        let html = card_html(&config, &PageStyles::default(), Some("<p>{{ title }}</p>{{ description }}"));
+        assert_eq!(html, "<p>Rust &amp; PDFs</p><p class=\"social-card-description\">A guide</p>");
+    }
+}
@@ -0,0 +1,238 @@
+use crate::check::{Diagnostic, SourceFile};
+use crate::error::AppError;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Project directory holding hunspell dictionaries (`<language>.dic` / `.aff`) and `words.txt`.
+const PROJECT_DICTIONARY_DIR: &str = "dictionaries";
+const PROJECT_WORDS_FILE: &str = "words.txt";
+const SYSTEM_DICTIONARY_DIRS: [&str; 3] = ["/usr/share/hunspell", "/usr/share/myspell", "/usr/local/share/hunspell"];
+
+/// A hunspell-compatible word list. Affix files are supported for simple prefix and
+/// suffix rules, which covers the inflections of most published dictionaries.
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlagFormat {
+    Short,
+    Long,
+    Numeric,
+}
+
+struct AffixRule {
+    strip: String,
+    add: String,
+    condition: Regex,
+}
+
+struct Affixes {
+    flag_format: FlagFormat,
+    prefixes: HashMap<String, Vec<AffixRule>>,
+    suffixes: HashMap<String, Vec<AffixRule>>,
+}
+
+impl Dictionary {
+    /// Loads the dictionary for `language`, looking first in the project's `dictionaries/`
+    /// directory and then in the standard hunspell locations. `words.txt` is always added.
+    pub fn load(language: &str) -> Result<Self, AppError> {
+        let dic_path = find_dictionary(language).ok_or_else(|| AppError::DictionaryNotFound(language.to_string()))?;
+        let mut dictionary = Dictionary::from_files(&dic_path, &dic_path.with_extension("aff"))?;
+
+        let words_path = Path::new(PROJECT_DICTIONARY_DIR).join(PROJECT_WORDS_FILE);
+        if let Ok(words) = fs::read_to_string(&words_path) {
+            dictionary.add_words(&words);
+        }
+        Ok(dictionary)
+    }
+
+    fn from_files(dic_path: &Path, aff_path: &Path) -> Result<Self, AppError> {
+        let dic = fs::read_to_string(dic_path)?;
+        let affixes = match fs::read_to_string(aff_path) {
+            Ok(aff) => parse_affixes(&aff),
+            Err(_) => Affixes { flag_format: FlagFormat::Short, prefixes: HashMap::new(), suffixes: HashMap::new() },
+        };
+        Ok(Dictionary::parse(&dic, &affixes))
+    }
+
+    fn parse(dic: &str, affixes: &Affixes) -> Self {
+        let mut words = HashSet::new();
+        // The first line of a .dic file is the approximate word count.
+        for line in dic.lines().skip(1) {
+            let entry = line.split_whitespace().next().unwrap_or("");
+            if entry.is_empty() {
+                continue;
+            }
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            words.insert(word.to_string());
+
+            for flag in split_flags(flags, affixes.flag_format) {
+                for rule in affixes.suffixes.get(&flag).into_iter().flatten() {
+                    if rule.condition.is_match(word) && word.ends_with(&rule.strip) {
+                        words.insert(format!("{}{}", &word[..word.len() - rule.strip.len()], rule.add));
+                    }
+                }
+                for rule in affixes.prefixes.get(&flag).into_iter().flatten() {
+                    if rule.condition.is_match(word) && word.starts_with(&rule.strip) {
+                        words.insert(format!("{}{}", rule.add, &word[rule.strip.len()..]));
+                    }
+                }
+            }
+        }
+        Dictionary { words }
+    }
+
+    fn add_words(&mut self, list: &str) {
+        for word in list.lines().map(str::trim).filter(|w| !w.is_empty() && !w.starts_with('#')) {
+            self.words.insert(word.to_string());
+        }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        if self.words.contains(word) {
+            return true;
+        }
+        // Accept capitalized forms of lowercase entries (start of sentence, ALL CAPS headings).
+        let lower = word.to_lowercase();
+        if self.words.contains(&lower) {
+            return true;
+        }
+        let mut chars = lower.chars();
+        let capitalized: String = chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default();
+        self.words.contains(&capitalized)
+    }
+}
+
+fn find_dictionary(language: &str) -> Option<PathBuf> {
+    let mut dirs = vec![PathBuf::from(PROJECT_DICTIONARY_DIR)];
+    dirs.extend(SYSTEM_DICTIONARY_DIRS.iter().map(PathBuf::from));
+
+    let language = language.replace('-', "_");
+    for dir in dirs {
+        let exact = dir.join(format!("{language}.dic"));
+        if exact.exists() {
+            return Some(exact);
+        }
+        // "en" also matches regional dictionaries such as "en_US.dic".
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        let mut regional: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension().is_some_and(|ext| ext == "dic")
+                    && p.file_stem().is_some_and(|stem| stem.to_string_lossy().starts_with(&format!("{language}_")))
+            })
+            .collect();
+        regional.sort();
+        if let Some(path) = regional.into_iter().next() {
+            return Some(path);
+        }
+    }
+    None
+}
+
+fn parse_affixes(aff: &str) -> Affixes {
+    let mut affixes = Affixes { flag_format: FlagFormat::Short, prefixes: HashMap::new(), suffixes: HashMap::new() };
+
+    for line in aff.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["FLAG", "long", ..] => affixes.flag_format = FlagFormat::Long,
+            ["FLAG", "num", ..] => affixes.flag_format = FlagFormat::Numeric,
+            // Rule lines have five fields; the header line ("SFX A Y 3") has four.
+            [kind @ ("PFX" | "SFX"), flag, strip, add, condition, ..] => {
+                let strip = if *strip == "0" { "" } else { strip };
+                let add = add.split('/').next().unwrap_or("");
+                let add = if add == "0" { "" } else { add };
+                let pattern = if *kind == "SFX" { format!("{condition}$") } else { format!("^{condition}") };
+                let Ok(condition) = Regex::new(&pattern) else { continue };
+
+                let rule = AffixRule { strip: strip.to_string(), add: add.to_string(), condition };
+                let table = if *kind == "SFX" { &mut affixes.suffixes } else { &mut affixes.prefixes };
+                table.entry(flag.to_string()).or_default().push(rule);
+            }
+            _ => {}
+        }
+    }
+    affixes
+}
+
+fn split_flags(flags: &str, format: FlagFormat) -> Vec<String> {
+    match format {
+        FlagFormat::Short => flags.chars().map(|c| c.to_string()).collect(),
+        FlagFormat::Long => flags.chars().collect::<Vec<_>>().chunks(2).map(|c| c.iter().collect()).collect(),
+        FlagFormat::Numeric => flags.split(',').filter(|f| !f.is_empty()).map(str::to_string).collect(),
+    }
+}
+
+/// Reports every word of the sources missing from the dictionary, skipping code blocks,
+/// inline code, URLs, HTML tags and directives.
+pub fn check_spelling(sources: &[SourceFile], dictionary: &Dictionary) -> Vec<Diagnostic> {
+    let ignore_re = Regex::new(r"`[^`]*`|<[^>]+>|\]\([^)]*\)|https?://\S+|www\.\S+|\S+@\S+\.\S+").unwrap();
+    let word_re = Regex::new(r"\p{L}+(?:['’]\p{L}+)*").unwrap();
+
+    let mut diagnostics = Vec::new();
+    for source in sources {
+        let mut in_code_block = false;
+        for (index, line) in source.content.lines().enumerate() {
+            if line.trim().starts_with("```") {
+                in_code_block = !in_code_block;
+                continue;
+            }
+            if in_code_block || line.trim_start().starts_with('!') {
+                continue;
+            }
+
+            let text = ignore_re.replace_all(line, " ");
+            for word in word_re.find_iter(&text).map(|m| m.as_str()) {
+                let word = word.trim_end_matches("'s").trim_end_matches("’s");
+                if word.chars().count() > 1 && !dictionary.contains(word) {
+                    diagnostics.push(Diagnostic {
+                        file: source.path.clone(),
+                        line: index + 1,
+                        rule: "spelling".to_string(),
+                        message: format!("Unknown word '{word}'"),
+                    });
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8\nSFX S Y 2\nSFX S 0 s [^y]\nSFX S y ies y\nPFX U Y 1\nPFX U 0 un .\n";
+    const DIC: &str = "5\nbook/S\nstory/S\ndo/U\nand\nat\n";
+
+    fn dictionary() -> Dictionary {
+        Dictionary::parse(DIC, &parse_affixes(AFF))
+    }
+
+    #[test]
+    fn test_dictionary_applies_affix_rules() {
+        let dictionary = dictionary();
+        assert!(dictionary.contains("books"));
+        assert!(dictionary.contains("stories"));
+        assert!(dictionary.contains("undo"));
+        assert!(dictionary.contains("Book"));
+        assert!(!dictionary.contains("storys"));
+    }
+
+    #[test]
+    fn test_check_spelling_skips_code_and_urls() {
+        let source = SourceFile {
+            path: "main.md".to_string(),
+            content: "Books and `codez` at https://exemple.org\n```\nmispeled\n```\nStorys".to_string(),
+        };
+        let diagnostics = check_spelling(&[source], &dictionary());
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 5);
+        assert!(diagnostics[0].message.contains("'Storys'"));
+    }
+}
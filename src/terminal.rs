@@ -0,0 +1,235 @@
+use crate::directives::Directive;
+use crate::error::AppError;
+use crate::paths::SourceDir;
+use crate::render::escape_html;
+use serde_json::Value;
+use std::fs;
+
+const DEFAULT_STILLS: usize = 4;
+
+/// Returns the stills of `!asciinema(demo.cast, stills=4)`: the terminal screen of an asciinema
+/// recording (relative to `source_dir`, inside the project) at evenly spaced moments, the last
+/// one at its end, or at the seconds listed by `at="1.5 4 10"`. The HTML stays on one line.
+pub fn asciinema_directive(source_dir: SourceDir, directive: &Directive) -> Result<String, AppError> {
+    directive.expect_positional(1)?;
+    let file = directive.value("file", 0)
+        .ok_or_else(|| AppError::BuildError("!asciinema needs a cast file, e.g. !asciinema(casts/demo.cast)".to_string()))?;
+    let stills = match directive.option("stills") {
+        Some(stills) => stills.parse().ok().filter(|stills| (1..=20).contains(stills))
+            .ok_or_else(|| AppError::BuildError(format!("Invalid !asciinema stills '{stills}', expected 1 to 20")))?,
+        None => DEFAULT_STILLS,
+    };
+    let at = directive.option("at")
+        .map(|at| at.split_whitespace().map(|time| time.parse::<f64>().ok().filter(|time| *time >= 0.0)).collect::<Option<Vec<_>>>()
+            .ok_or_else(|| AppError::BuildError(format!("Invalid !asciinema at '{at}', expected seconds separated by spaces"))))
+        .transpose()?;
+    let path = source_dir.dir.join(file);
+    let content = fs::read_to_string(source_dir.resolve(file)?).map_err(|e| AppError::BuildError(format!("Could not read cast file '{}': {e}", path.display())))?;
+    let cast = Cast::parse(&content).map_err(|e| AppError::BuildError(format!("Invalid cast file '{}': {e}", path.display())))?;
+
+    let times = at.unwrap_or_else(|| (1..=stills).map(|i| cast.duration() * i as f64 / stills as f64).collect());
+    let figures: Vec<String> = cast.screens(&times).into_iter().zip(&times)
+        .map(|(screen, time)| {
+            let seconds = *time as u64;
+            format!(
+                "<figure class=\"terminal-still\"><pre class=\"terminal\">{}</pre><figcaption>{}:{:02}</figcaption></figure>",
+                escape_html(&screen).replace('\n', "&#10;"),
+                seconds / 60,
+                seconds % 60
+            )
+        })
+        .collect();
+    Ok(format!("<div class=\"terminal-stills\">{}</div>", figures.concat()))
+}
+
+/// An asciinema recording: the terminal size and the output written at each moment.
+struct Cast {
+    width: usize,
+    height: usize,
+    /// Seconds from the start and output text.
+    events: Vec<(f64, String)>,
+}
+
+impl Cast {
+    /// Reads version 2 (absolute times) and version 3 (intervals) cast files.
+    fn parse(content: &str) -> Result<Self, String> {
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#'));
+        let header: Value = serde_json::from_str(lines.next().ok_or("empty file")?).map_err(|e| e.to_string())?;
+        let version = header["version"].as_u64().ok_or("no version in the header")?;
+        let (width, height, relative) = match version {
+            2 => (header["width"].as_u64(), header["height"].as_u64(), false),
+            3 => (header["term"]["cols"].as_u64(), header["term"]["rows"].as_u64(), true),
+            version => return Err(format!("unsupported version {version}")),
+        };
+        let mut events = Vec::new();
+        let mut time = 0.0;
+        for line in lines {
+            let event: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+            let (Some(at), Some(kind), Some(data)) = (event[0].as_f64(), event[1].as_str(), event[2].as_str()) else {
+                return Err(format!("invalid event {line}"));
+            };
+            time = if relative { time + at } else { at };
+            if kind == "o" {
+                events.push((time, data.to_string()));
+            }
+        }
+        Ok(Cast { width: width.unwrap_or(80) as usize, height: height.unwrap_or(24) as usize, events })
+    }
+
+    fn duration(&self) -> f64 {
+        self.events.last().map_or(0.0, |(time, _)| *time)
+    }
+
+    /// The text on screen at each of `times`, in the order given.
+    fn screens(&self, times: &[f64]) -> Vec<String> {
+        times.iter()
+            .map(|time| {
+                let mut screen = Screen::new(self.width, self.height);
+                for (_, data) in self.events.iter().take_while(|(at, _)| at <= time) {
+                    screen.feed(data);
+                }
+                screen.text()
+            })
+            .collect()
+    }
+}
+
+/// A character grid following the cursor movements and erasures of a terminal. Colors and
+/// other attributes are ignored.
+struct Screen {
+    cells: Vec<Vec<char>>,
+    width: usize,
+    row: usize,
+    column: usize,
+    /// The escape sequence being read, after its `ESC`.
+    escape: Option<String>,
+}
+
+impl Screen {
+    fn new(width: usize, height: usize) -> Self {
+        let width = width.max(1);
+        Screen { cells: vec![vec![' '; width]; height.max(1)], width, row: 0, column: 0, escape: None }
+    }
+
+    fn feed(&mut self, text: &str) {
+        for c in text.chars() {
+            self.put(c);
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        if let Some(sequence) = self.escape.as_mut() {
+            sequence.push(c);
+            let complete = match sequence.chars().next() {
+                Some('[') => sequence.len() > 1 && ('@'..='~').contains(&c),
+                Some(']') => c == '\x07' || sequence.ends_with("\x1b\\"),
+                Some('(' | ')') => sequence.chars().count() == 2,
+                _ => true,
+            };
+            if complete {
+                let sequence = self.escape.take().unwrap_or_default();
+                if let Some(parameters) = sequence.strip_prefix('[') {
+                    self.control(&parameters[..parameters.len() - c.len_utf8()], c);
+                }
+            }
+            return;
+        }
+        match c {
+            '\x1b' => self.escape = Some(String::new()),
+            '\r' => self.column = 0,
+            '\n' => self.line_feed(),
+            '\x08' => self.column = self.column.saturating_sub(1),
+            '\t' => self.column = ((self.column / 8 + 1) * 8).min(self.width - 1),
+            c if c.is_control() => {}
+            c => {
+                if self.column >= self.width {
+                    self.column = 0;
+                    self.line_feed();
+                }
+                self.cells[self.row][self.column] = c;
+                self.column += 1;
+            }
+        }
+    }
+
+    fn line_feed(&mut self) {
+        if self.row + 1 < self.cells.len() {
+            self.row += 1;
+        } else {
+            self.cells.remove(0);
+            self.cells.push(vec![' '; self.width]);
+        }
+    }
+
+    /// A control sequence `ESC [ parameters final`: cursor movements and erasures.
+    fn control(&mut self, parameters: &str, command: char) {
+        let numbers: Vec<usize> = parameters.trim_start_matches('?').split(';').map(|n| n.parse().unwrap_or(0)).collect();
+        let n = numbers.first().copied().unwrap_or(0);
+        let count = n.max(1);
+        let (last_row, last_column) = (self.cells.len() - 1, self.width - 1);
+        match command {
+            'A' => self.row = self.row.saturating_sub(count),
+            'B' => self.row = (self.row + count).min(last_row),
+            'C' => self.column = (self.column + count).min(last_column),
+            'D' => self.column = self.column.saturating_sub(count),
+            'G' => self.column = (count - 1).min(last_column),
+            'd' => self.row = (count - 1).min(last_row),
+            'H' | 'f' => {
+                self.row = (count - 1).min(last_row);
+                self.column = (numbers.get(1).copied().unwrap_or(1).max(1) - 1).min(last_column);
+            }
+            'J' => {
+                let (row, column) = (self.row, self.column.min(last_column));
+                for (i, line) in self.cells.iter_mut().enumerate() {
+                    let range = match n {
+                        0 if i == row => column..line.len(),
+                        0 if i > row => 0..line.len(),
+                        1 if i == row => 0..column + 1,
+                        1 if i < row => 0..line.len(),
+                        2 | 3 => 0..line.len(),
+                        _ => continue,
+                    };
+                    line[range].fill(' ');
+                }
+            }
+            'K' => {
+                let column = self.column.min(last_column);
+                let line = &mut self.cells[self.row];
+                let range = match n {
+                    0 => column..line.len(),
+                    1 => 0..column + 1,
+                    _ => 0..line.len(),
+                };
+                line[range].fill(' ');
+            }
+            _ => {}
+        }
+    }
+
+    /// The lines on screen without trailing spaces, up to the last one with text.
+    fn text(&self) -> String {
+        let lines: Vec<String> = self.cells.iter().map(|line| line.iter().collect::<String>().trim_end().to_string()).collect();
+        let used = lines.iter().rposition(|line| !line.is_empty()).map_or(0, |last| last + 1);
+        lines[..used].join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cast_screens() {
+        let cast = Cast::parse(
+            "{\"version\": 2, \"width\": 20, \"height\": 3}\n\
+             [0.5, \"o\", \"$ ls\\r\\n\"]\n[1.0, \"i\", \"x\"]\n[1.2, \"o\", \"a.txt  b.txt\\r\\n$ \"]\n\
+             [2.0, \"o\", \"\\u001b[1;31mred\\u001b[0m\\u001b[3D\\u001b[Kclear\\r\\nnext\"]\n"
+        ).unwrap();
+        assert_eq!(cast.duration(), 2.0);
+        assert_eq!(cast.screens(&[0.0, 1.2, 2.0]), ["", "$ ls\na.txt  b.txt\n$", "a.txt  b.txt\n$ clear\nnext"]);
+
+        let cast = Cast::parse("{\"version\": 3, \"term\": {\"cols\": 10, \"rows\": 2}}\n# comment\n[0.5, \"o\", \"ab\"]\n[0.5, \"o\", \"\\bc\"]\n").unwrap();
+        assert_eq!(cast.screens(&[1.0]), ["ac"]);
+        assert!(Cast::parse("{\"version\": 1}").is_err());
+    }
+}
@@ -0,0 +1,420 @@
+use crate::builder::{self, BuildStats};
+use crate::check::{self, Diagnostic};
+use crate::error::AppError;
+use crate::git::GitInfo;
+use crate::lint;
+use crate::pagemap;
+use crate::watch_status::{self, LastBuild, Outcome};
+use crate::{watch_inputs, Config, OutputFormat};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// Lines of build output kept in the log pane.
+const MAX_LOG_LINES: usize = 2000;
+/// Quiet time after a change before rebuilding, as a save often emits several events.
+const SETTLE: Duration = Duration::from_millis(200);
+/// Flags of the watching process that the builds it starts must not get.
+const WATCH_FLAGS: [&str; 4] = ["--watch", "--tui", "--no-clear", "--draft"];
+
+enum Message {
+    Changed(Vec<PathBuf>),
+    /// A line printed by build number `build`, on its standard error when `error` is set.
+    Log { build: u64, line: String, error: bool },
+    /// An output stream of build number `build` was closed.
+    Closed(u64),
+}
+
+/// A build running in a child process, so that its output goes to the log pane and a newer
+/// change can stop it at any point.
+struct Build {
+    id: u64,
+    child: Child,
+    started: Instant,
+    exit: Option<ExitStatus>,
+    open_streams: usize,
+    lines: Vec<String>,
+    errors: Vec<String>,
+}
+
+/// The watch mode dashboard: the watched inputs, the log of the builds, the diagnostics of
+/// the last one and its status line, with keys to rebuild, open the output and switch profile.
+struct Dashboard {
+    configs: Vec<Config>,
+    format: OutputFormat,
+    invocation_dir: PathBuf,
+    project_root: PathBuf,
+    /// The `build` command line of the watching process, without the watch flags.
+    args: Vec<String>,
+    /// Whether builds use the draft profile (`--draft`).
+    draft: bool,
+    inputs: Vec<String>,
+    log: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    pending: Vec<String>,
+    pending_since: Option<Instant>,
+    running: Option<Build>,
+    builds: u64,
+    last_build: Option<LastBuild>,
+}
+
+/// Runs watch mode in a full-screen dashboard until `q` is pressed.
+pub fn run_dashboard(configs: Vec<Config>, format: OutputFormat, invocation_dir: PathBuf) -> Result<(), AppError> {
+    let (tx, rx) = mpsc::channel();
+    let changes = tx.clone();
+    let _watcher = watch_inputs(&configs, move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if event.kind.is_modify() || event.kind.is_create() || event.kind.is_remove() {
+                changes.send(Message::Changed(event.paths)).ok();
+            }
+        }
+    })?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut dashboard = Dashboard {
+        draft: args.iter().any(|arg| arg == "--draft"),
+        args: args.into_iter().filter(|arg| !WATCH_FLAGS.contains(&arg.as_str())).collect(),
+        project_root: std::env::current_dir()?,
+        configs,
+        format,
+        invocation_dir,
+        inputs: Vec::new(),
+        log: Vec::new(),
+        diagnostics: Vec::new(),
+        pending: Vec::new(),
+        pending_since: None,
+        running: None,
+        builds: 0,
+        last_build: None,
+    };
+    dashboard.refresh_sources();
+
+    let mut terminal = ratatui::try_init()?;
+    let result = dashboard.run(&mut terminal, &tx, &rx);
+    ratatui::restore();
+    if let Some(mut build) = dashboard.running.take() {
+        build.child.kill().ok();
+        build.child.wait().ok();
+    }
+    result
+}
+
+impl Dashboard {
+    fn run(&mut self, terminal: &mut DefaultTerminal, tx: &Sender<Message>, rx: &Receiver<Message>) -> Result<(), AppError> {
+        loop {
+            while let Ok(message) = rx.try_recv() {
+                self.handle(message);
+            }
+            self.poll_build(tx)?;
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Char('r') => self.queue("rebuild requested".to_string()),
+                KeyCode::Char('o') => self.open_output(),
+                KeyCode::Char('p') => {
+                    self.draft = !self.draft;
+                    self.queue(format!("{} profile", self.profile()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Changed(paths) => {
+                for path in paths {
+                    let path = path.strip_prefix(&self.project_root).unwrap_or(&path).display().to_string();
+                    self.queue(path);
+                }
+            }
+            Message::Log { build, line, error } => {
+                if let Some(running) = self.running.as_mut().filter(|running| running.id == build) {
+                    running.lines.push(line.clone());
+                    if error {
+                        running.errors.push(line.clone());
+                    }
+                }
+                self.push_log(line);
+            }
+            Message::Closed(build) => {
+                if let Some(running) = self.running.as_mut().filter(|running| running.id == build) {
+                    running.open_streams -= 1;
+                }
+            }
+        }
+    }
+
+    fn queue(&mut self, reason: String) {
+        if !self.pending.contains(&reason) {
+            self.pending.push(reason);
+        }
+        self.pending_since = Some(Instant::now());
+    }
+
+    fn push_log(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.drain(..self.log.len() - MAX_LOG_LINES);
+        }
+    }
+
+    /// Finishes the running build once it has exited and its output is read, and starts a
+    /// new one when changes have settled, stopping the running one first.
+    fn poll_build(&mut self, tx: &Sender<Message>) -> Result<(), AppError> {
+        if let Some(running) = &mut self.running {
+            if running.exit.is_none() {
+                running.exit = running.child.try_wait()?;
+            }
+        }
+        if self.running.as_ref().is_some_and(|running| running.exit.is_some() && running.open_streams == 0) {
+            if let Some(build) = self.running.take() {
+                self.finish(build);
+            }
+        }
+
+        if self.pending_since.is_none_or(|since| since.elapsed() < SETTLE) {
+            return Ok(());
+        }
+        if let Some(mut build) = self.running.take() {
+            build.child.kill().ok();
+            build.child.wait().ok();
+            self.push_log("Build cancelled, starting over with the latest changes.".to_string());
+        }
+        self.push_log(format!("Rebuilding ({}): {}", self.profile(), self.pending.join(", ")));
+        self.pending.clear();
+        self.pending_since = None;
+
+        let mut command = Command::new(std::env::current_exe()?);
+        command.args(&self.args)
+            .args(self.draft.then_some("--draft"))
+            .current_dir(&self.invocation_dir)
+            .env("NO_COLOR", "1")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = command.spawn()?;
+        self.builds += 1;
+        let id = self.builds;
+        let streams: [Option<Box<dyn Read + Send>>; 2] = [
+            child.stdout.take().map(|stdout| Box::new(stdout) as Box<dyn Read + Send>),
+            child.stderr.take().map(|stderr| Box::new(stderr) as Box<dyn Read + Send>),
+        ];
+        for (index, stream) in streams.into_iter().enumerate() {
+            let stream = stream.ok_or_else(|| AppError::BuildError("Could not read the output of the build".to_string()))?;
+            forward(stream, id, index == 1, tx.clone());
+        }
+        self.running = Some(Build { id, child, started: Instant::now(), exit: None, open_streams: 2, lines: Vec::new(), errors: Vec::new() });
+        Ok(())
+    }
+
+    fn finish(&mut self, build: Build) {
+        let outcome = if build.exit.is_some_and(|status| status.success()) {
+            Outcome::Built(self.build_stats(&build.lines))
+        } else {
+            Outcome::Failed(failure_message(&build.errors))
+        };
+        self.refresh_sources();
+        self.diagnostics.extend(build.lines.iter().filter_map(|line| warning_diagnostic(line)));
+        if let Outcome::Failed(error) = &outcome {
+            self.diagnostics.push(Diagnostic { file: String::new(), line: 0, rule: "error".to_string(), message: error.clone() });
+        }
+        let last = LastBuild { finished: chrono::Local::now(), duration: build.started.elapsed(), outcome };
+        self.push_log(watch_status::status_line(Some(&last), &[]));
+        self.last_build = Some(last);
+    }
+
+    /// Statistics of every language built, from the warning summaries of the log and the PDFs.
+    fn build_stats(&self, lines: &[String]) -> Vec<BuildStats> {
+        let mut warnings = lines.iter().filter_map(|line| line.split_once(" warning(s):")?.0.trim().parse::<usize>().ok());
+        self.configs.iter()
+            .map(|config| BuildStats {
+                pages: (self.format == OutputFormat::Pdf).then(|| pagemap::page_count(&self.output_path(config, "pdf"))).flatten(),
+                warnings: warnings.next().unwrap_or(0),
+            })
+            .collect()
+    }
+
+    /// Reads the sources again, for the inputs pane and the lint diagnostics.
+    fn refresh_sources(&mut self) {
+        let config = &self.configs[0];
+        let mut paths = vec!["config.yaml".to_string()];
+        paths.extend(config.custom_css.iter().filter(|css| !css.is_empty()).cloned());
+        self.diagnostics.clear();
+        match check::config_sources(config) {
+            Ok(sources) => {
+                paths.extend(sources.files.iter().map(|file| file.path.clone()));
+                self.diagnostics = lint::lint_sources(&sources.files, &config.lint);
+            }
+            Err(_) => paths.extend(config.source_files()),
+        }
+        self.inputs = file_tree(&paths);
+    }
+
+    fn output_path(&self, config: &Config, extension: &str) -> PathBuf {
+        config.build_dir().join(format!("{}.{extension}", builder::output_filename(config, &GitInfo::collect())))
+    }
+
+    fn open_output(&mut self) {
+        let extension = match self.format {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Html | OutputFormat::HtmlInline => "html",
+            _ => {
+                self.push_log("Only PDF and HTML outputs can be opened from here.".to_string());
+                return;
+            }
+        };
+        let path = self.output_path(&self.configs[0], extension);
+        let message = match open(&path) {
+            Ok(_) => format!("Opened {}", path.display()),
+            Err(e) => format!("Could not open {}: {e}", path.display()),
+        };
+        self.push_log(message);
+    }
+
+    fn profile(&self) -> &'static str {
+        if self.draft { "draft" } else { "default" }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [inputs, right] = Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)]).areas(body);
+        let [log, diagnostics] = Layout::vertical([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(right);
+
+        let state = if self.running.is_some() { "building..." } else { "watching" };
+        let status = watch_status::status_line(self.last_build.as_ref(), &self.pending);
+        let failed = matches!(self.last_build.as_ref().map(|last| &last.outcome), Some(Outcome::Failed(_)));
+        frame.render_widget(
+            Line::from(vec![
+                format!(" {} ", self.configs[0].title).bold(),
+                format!("[{}] [{state}] ", self.profile()).cyan(),
+                if failed { status.red() } else { status.green() },
+            ]),
+            header,
+        );
+
+        frame.render_widget(List::new(self.inputs.iter().map(String::as_str)).block(Block::bordered().title(" Inputs ")), inputs);
+
+        let height = log.height.saturating_sub(2) as usize;
+        let visible = self.log[self.log.len().saturating_sub(height)..].iter().map(|line| Line::from(line.as_str()));
+        frame.render_widget(Paragraph::new(visible.collect::<Vec<_>>()).block(Block::bordered().title(" Log ")), log);
+
+        let items: Vec<Line> = self.diagnostics.iter()
+            .map(|diagnostic| {
+                let location = if diagnostic.file.is_empty() { String::new() } else { format!("{}:{} ", diagnostic.file, diagnostic.line) };
+                let style = if diagnostic.rule == "error" { Style::new().fg(Color::Red) } else { Style::new().fg(Color::Yellow) };
+                Line::from(vec![location.into(), format!("[{}] ", diagnostic.rule).cyan(), Span::styled(diagnostic.message.clone(), style)])
+            })
+            .collect();
+        let title = format!(" Diagnostics ({}) ", items.len());
+        frame.render_widget(List::new(items).block(Block::bordered().title(title)), diagnostics);
+
+        frame.render_widget(Line::from(" r rebuild · o open output · p toggle draft profile · q quit").dark_gray(), footer);
+    }
+}
+
+/// Sends the lines of a build output to the dashboard, then reports the stream closed.
+fn forward(stream: Box<dyn Read + Send>, build: u64, error: bool, tx: Sender<Message>) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            let line = line.trim_end_matches('\r').to_string();
+            if tx.send(Message::Log { build, line, error }).is_err() {
+                return;
+            }
+        }
+        tx.send(Message::Closed(build)).ok();
+    });
+}
+
+/// Opens a file in the default application of the desktop.
+fn open(path: &Path) -> std::io::Result<Child> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = Command::new("xdg-open");
+    command.arg(path).stdout(Stdio::null()).stderr(Stdio::null()).spawn()
+}
+
+/// The paths as an indented tree, each directory listed once above its files.
+fn file_tree(paths: &[String]) -> Vec<String> {
+    let mut paths: Vec<Vec<&str>> = paths.iter().map(|path| path.trim_start_matches("./").split(['/', '\\']).collect()).collect();
+    paths.sort();
+    paths.dedup();
+    let mut tree = Vec::new();
+    let mut previous: Vec<&str> = Vec::new();
+    for components in paths {
+        let (file, dirs) = components.split_last().unwrap_or((&"", &[]));
+        let shared = dirs.iter().zip(&previous).take_while(|(dir, previous)| dir == previous).count();
+        for (depth, dir) in dirs.iter().enumerate().skip(shared) {
+            tree.push(format!("{}{dir}/", "  ".repeat(depth)));
+        }
+        tree.push(format!("{}{file}", "  ".repeat(dirs.len())));
+        previous = dirs.to_vec();
+    }
+    tree
+}
+
+/// A `Warning: <category>: <detail>` line of the build output as a diagnostic.
+fn warning_diagnostic(line: &str) -> Option<Diagnostic> {
+    let (category, detail) = line.strip_prefix("Warning: ")?.split_once(": ")?;
+    Some(Diagnostic { file: String::new(), line: 0, rule: category.to_string(), message: detail.to_string() })
+}
+
+/// The message of a failed build: the `× message` line of its report, or its last line.
+fn failure_message(errors: &[String]) -> String {
+    let lines = || errors.iter().map(|line| line.trim()).filter(|line| !line.is_empty());
+    lines().find_map(|line| line.strip_prefix('×').map(str::trim))
+        .or_else(|| lines().next_back())
+        .unwrap_or("the build stopped without an error message")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_tree() {
+        let paths = ["main.md", "chapters/part-1/intro.md", "config.yaml", "chapters/part-1/setup.md", "chapters/outro.md", "./main.md"].map(String::from);
+        assert_eq!(
+            file_tree(&paths),
+            ["chapters/", "  outro.md", "  part-1/", "    intro.md", "    setup.md", "config.yaml", "main.md"]
+        );
+    }
+
+    #[test]
+    fn test_build_output_parsing() {
+        let warning = warning_diagnostic("Warning: Unknown code language: 'foo'").unwrap();
+        assert_eq!((warning.rule.as_str(), warning.message.as_str()), ("Unknown code language", "'foo'"));
+        assert!(warning_diagnostic("Generated PDF file: build/guide.pdf").is_none());
+
+        let report = ["pdfbuilder::include::missing", "", "  × Source file not found: 'chapter.md'", "  help: check the path"].map(String::from);
+        assert_eq!(failure_message(&report), "Source file not found: 'chapter.md'");
+        assert_eq!(failure_message(&["Error: boom".to_string(), "".to_string()]), "Error: boom");
+    }
+}
@@ -0,0 +1,41 @@
+use crate::git::GitInfo;
+use crate::i18n;
+use crate::Config;
+use chrono::Local;
+use std::collections::HashMap;
+
+pub use pdfbuilder_core::variables::*;
+
+const UNKNOWN_VALUE: &str = "unknown";
+
+/// Environment variables holding the number of a CI build (Jenkins, GitHub Actions, GitLab, Azure Pipelines).
+const BUILD_NUMBER_VARIABLES: [&str; 4] = ["BUILD_NUMBER", "GITHUB_RUN_NUMBER", "CI_PIPELINE_IID", "BUILD_BUILDNUMBER"];
+
+/// Builds the set of built-in template variables available in the Markdown sources.
+pub fn builtin_variables(config: &Config, git: &GitInfo) -> HashMap<String, String> {
+    let mut vars = HashMap::new();
+    vars.insert("title".to_string(), config.title.clone());
+    vars.insert("author".to_string(), config.author.clone());
+    vars.insert("language".to_string(), config.language.clone());
+
+    let unknown = || UNKNOWN_VALUE.to_string();
+    vars.insert("git_commit".to_string(), git.commit.clone().unwrap_or_else(unknown));
+    vars.insert("git_tag".to_string(), git.tag.clone().unwrap_or_else(unknown));
+    vars.insert("git_date".to_string(), git.date.clone().unwrap_or_else(unknown));
+    vars.insert("version".to_string(), document_version(config, git));
+    let now = Local::now();
+    vars.insert("year".to_string(), now.format("%Y").to_string());
+    vars.insert("today".to_string(), format_date(&now, i18n::long_date_format(&config.language), &config.language).unwrap_or_default());
+    let build_number = BUILD_NUMBER_VARIABLES.iter().find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()));
+    vars.insert("build_number".to_string(), build_number.unwrap_or_else(unknown));
+    vars.extend(config.boilerplate.variables.iter().map(|(name, value)| (name.clone(), value.clone())));
+    vars
+}
+
+/// The `version` of the configuration, or else the latest git tag or commit.
+pub fn document_version(config: &Config, git: &GitInfo) -> String {
+    config.version.clone()
+        .or_else(|| git.tag.clone())
+        .or_else(|| git.commit.clone())
+        .unwrap_or_else(|| "dev".to_string())
+}
@@ -0,0 +1,104 @@
+use colored::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+
+pub use pdfbuilder_core::warnings::Warning;
+
+tokio::task_local! {
+    /// Non-fatal issues of the build running in the current task, summarized at its end so they
+    /// are not lost among the progress messages. Each build has its own list, so builds running
+    /// side by side (the requests of the server) do not collect each other's warnings.
+    static WARNINGS: RefCell<Vec<Warning>>;
+}
+
+/// Prints a warning and records it for the summary of the build, when raised by one. Repeated
+/// warnings are recorded once.
+pub fn warn(category: &'static str, detail: impl Into<String>) {
+    let detail = detail.into();
+    #[cfg(not(test))]
+    println!("{} {}: {}", "Warning:".yellow(), category.yellow(), detail.yellow());
+    let _ = WARNINGS.try_with(|warnings| {
+        let mut warnings = warnings.borrow_mut();
+        if !warnings.iter().any(|warning| warning.category == category && warning.detail == detail) {
+            warnings.push(Warning { category, detail });
+        }
+    });
+}
+
+/// Raises the warnings returned by the conversion, as `warn` does.
+pub fn report(warnings: Vec<Warning>) {
+    for warning in warnings {
+        warn(warning.category, warning.detail);
+    }
+}
+
+/// Runs a build, returning its result with the warnings it raised.
+pub async fn collect<T>(build: impl Future<Output = T>) -> (T, Vec<Warning>) {
+    WARNINGS.scope(RefCell::default(), async {
+        let output = build.await;
+        (output, WARNINGS.with(RefCell::take))
+    }).await
+}
+
+/// Prints the summary of the warnings of a build, if any. Returns their number.
+pub fn print_summary(warnings: &[Warning]) -> usize {
+    if let Some(summary) = summary(warnings) {
+        println!("\n{}", summary.yellow());
+    }
+    warnings.len()
+}
+
+/// Warnings grouped by category, with counts.
+pub fn summary(warnings: &[Warning]) -> Option<String> {
+    if warnings.is_empty() {
+        return None;
+    }
+    let mut by_category: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for warning in warnings {
+        by_category.entry(warning.category).or_default().push(&warning.detail);
+    }
+    let mut summary = format!("{} warning(s):", warnings.len());
+    for (category, details) in by_category {
+        summary.push_str(&format!("\n  {} ({}): {}", category, details.len(), details.join(", ")));
+    }
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let warning = |category, detail: &str| Warning { category, detail: detail.to_string() };
+        let warnings = [
+            warning("Unknown code language", "'foo'"),
+            warning("Custom CSS not found", "'extra.css'"),
+            warning("Unknown code language", "'bar'"),
+        ];
+        assert_eq!(
+            summary(&warnings).unwrap(),
+            "3 warning(s):\n  Custom CSS not found (1): 'extra.css'\n  Unknown code language (2): 'foo', 'bar'"
+        );
+        assert_eq!(summary(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_collect() {
+        let (_, outside) = collect(async { warn("Unknown code language", "'foo'") }).await;
+        let ((_, inside), outer) = collect(async {
+            warn("Unresolved link", "'#intro'");
+            let inner = collect(async {
+                warn("Unknown code language", "'bar'");
+                warn("Unknown code language", "'bar'");
+            }).await;
+            warn("Unresolved link", "'#intro'");
+            inner
+        }).await;
+        warn("Unknown code language", "'baz'");
+        assert_eq!(outside, [Warning { category: "Unknown code language", detail: "'foo'".to_string() }]);
+        assert_eq!(inside, [Warning { category: "Unknown code language", detail: "'bar'".to_string() }]);
+        assert_eq!(outer, [Warning { category: "Unresolved link", detail: "'#intro'".to_string() }]);
+    }
+}
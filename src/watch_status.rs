@@ -0,0 +1,69 @@
+use crate::builder::BuildStats;
+use chrono::{DateTime, Local};
+use std::time::Duration;
+
+/// How the last build of watch mode ended.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// Statistics of every language built.
+    Built(Vec<BuildStats>),
+    /// First line of the error.
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct LastBuild {
+    pub finished: DateTime<Local>,
+    pub duration: Duration,
+    pub outcome: Outcome,
+}
+
+/// The line shown after every build of watch mode, and again above the next one when the
+/// screen is cleared: when the last build ended, how long it took, its pages and warnings
+/// (or its error), and the changed files waiting for a rebuild.
+pub fn status_line(last: Option<&LastBuild>, pending: &[String]) -> String {
+    let mut parts = Vec::new();
+    if let Some(last) = last {
+        let mut part = format!("Last build {} ({:.1}s)", last.finished.format("%H:%M:%S"), last.duration.as_secs_f64());
+        match &last.outcome {
+            Outcome::Built(stats) => {
+                let pages: Vec<String> = stats.iter().filter_map(|stats| stats.pages).map(|pages| pages.to_string()).collect();
+                if !pages.is_empty() {
+                    part.push_str(&format!(": {} page(s)", pages.join(" + ")));
+                }
+                let warnings: usize = stats.iter().map(|stats| stats.warnings).sum();
+                part.push_str(&format!("{} {warnings} warning(s)", if pages.is_empty() { ":" } else { "," }));
+            }
+            Outcome::Failed(error) => part.push_str(&format!(" failed: {error}")),
+        }
+        parts.push(part);
+    }
+    if !pending.is_empty() {
+        parts.push(format!("Pending rebuild: {}", pending.join(", ")));
+    }
+    parts.join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_status_line() {
+        let finished = Local.with_ymd_and_hms(2024, 5, 1, 14, 2, 31).unwrap();
+        let built = LastBuild {
+            finished,
+            duration: Duration::from_millis(3240),
+            outcome: Outcome::Built(vec![BuildStats { pages: Some(42), warnings: 2 }, BuildStats { pages: Some(40), warnings: 0 }]),
+        };
+        assert_eq!(status_line(Some(&built), &[]), "Last build 14:02:31 (3.2s): 42 + 40 page(s), 2 warning(s)");
+
+        let failed = LastBuild { outcome: Outcome::Failed("Source file not found: 'main.md'".to_string()), ..built };
+        assert_eq!(
+            status_line(Some(&failed), &["main.md".to_string(), "assets/logo.png".to_string()]),
+            "Last build 14:02:31 (3.2s) failed: Source file not found: 'main.md' | Pending rebuild: main.md, assets/logo.png"
+        );
+        assert_eq!(status_line(None, &[]), "");
+    }
+}